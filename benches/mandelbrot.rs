@@ -0,0 +1,44 @@
+//! Loop hoisting against stepping every instruction, on `samples/mandelbrot.bf` or the program given:
+//!
+//! ```text
+//! cargo bench --bench mandelbrot [-- PATH [RUNS]]
+//! ```
+//!
+//! Both builds are folded like `--single-pass`, the stepped runs set `ExecutionConfig::step_loops`.
+
+use std::time::{Duration, Instant};
+
+use brainfck::{compile, execute_code, ExecutionConfig, Interpreter, ParseOptions, Program};
+
+fn time_runs(program: &Program, config: &ExecutionConfig, runs: usize) -> (Duration, Vec<u8>, u64) {
+    let mut best: Duration = Duration::MAX;
+    let mut output: Vec<u8> = vec![];
+    let mut steps: u64 = 0;
+    for _ in 0..runs {
+        let mut interpreter: Interpreter = Interpreter::default();
+        output.clear();
+        let start: Instant = Instant::now();
+        steps = execute_code(program, &mut interpreter, &mut std::io::empty(), &mut output, config).expect("the benchmark program failed").steps;
+        best = best.min(start.elapsed());
+    }
+    (best, output, steps)
+}
+
+fn main() {
+    // `cargo bench` passes `--bench` along
+    let args: Vec<String> = std::env::args().skip(1).filter(|arg| !arg.starts_with("--")).collect();
+    let path: &str = args.first().map_or(concat!(env!("CARGO_MANIFEST_DIR"), "/samples/mandelbrot.bf"), String::as_str);
+    let runs: usize = args.get(1).map_or(1, |runs| runs.parse().expect("RUNS has to be a number"));
+
+    let source: Vec<u8> = std::fs::read(path).unwrap_or_else(|err| panic!("{}: {}", path, err));
+    let program: Program = compile(&source, &ParseOptions { fold: true, ..ParseOptions::default() }, true).expect("the benchmark program doesn't parse");
+
+    let (hoisted, hoisted_output, steps) = time_runs(&program, &ExecutionConfig::default(), runs);
+    let (stepped, stepped_output, stepped_steps) = time_runs(&program, &ExecutionConfig { step_loops: true, ..ExecutionConfig::default() }, runs);
+    assert!(hoisted_output == stepped_output && steps == stepped_steps, "hoisting changed the run");
+
+    println!("{}: {} steps, best of {} run(s)", path, steps, runs);
+    println!("  stepped  {:>10.3?}  {:>6.2} ns/step", stepped, stepped.as_nanos() as f64 / steps as f64);
+    println!("  hoisted  {:>10.3?}  {:>6.2} ns/step", hoisted, hoisted.as_nanos() as f64 / steps as f64);
+    println!("  speedup  {:>10.2}x", stepped.as_secs_f64() / hoisted.as_secs_f64());
+}
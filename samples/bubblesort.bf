@@ -0,0 +1,11 @@
+Bubble sort by Daniel B Cristofani
+Sorts the bytes of its input up to the end of the input or a zero byte
+
+>>,[>>,]<<[
+[<<]>>>>[
+<<[>+<<+>-]
+>>[>+<<<<[->]>[<]>>-]
+<<<[[-]>>[>+<-]>>[<<<+>>>-]]
+>>[[<+>-]>>]<
+]<<[>>+<<-]<<
+]>>>>[.>>]
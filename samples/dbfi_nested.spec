@@ -0,0 +1,8 @@
+# dbfi interpreting itself interpreting hello world: the input is dbfi's source, `!`, the hello
+# world program and another `!` ending its (empty) input. About 14 billion steps.
+program = "dbfi.bf"
+input_file = "dbfi_nested.in"
+dynamic_tape = "right"
+single_pass = true
+max_steps = 50000000000
+expect_output_string = "Hello World!\n"
@@ -0,0 +1,65 @@
+A small Mandelbrot set viewer in brainfuck
+21 by 9 characters with 3 fractional bits and at most 12 iterations a point
++++++++++>++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++<[->>+++++++++++++++++++++>[-]
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++<[->>+++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
++++++>++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++>++++++++++++[-<<[->>>>>>>
+>>>>>>>>>>>+>>>>>+<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<
+<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>]<<<<+++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]<<<<<<+>>>>>>>>[-<<<<<<<<->>>>>>>[-<<<
+<<<<<<<<<<<<+>>>>>>>>>>>>>>>]>]<<<<<<<<[-<<<<<<<<+++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
++++++++++++++++++>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<->>>>>>>>>>>>>>>]<<<<<<<<<<<<<<
++>>>>>>>]<<<<<<<<<<<<[->>>>>>>>>>>>>>>>>+>>>>>+<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>
+>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>]<<<<++++++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]<<<<<<+>>>>>>
+>>[-<<<<<<<<->>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]>]<<<<<<<<[-<<<<<<++++++++++++
+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+++++++++++++++++++++++++++++++++++++>>>>>>>>>>>>>[-<<<<<<<<<<<<<->>>>>>>>>>>>>]<
+<<<<<<<<<<<+>>>>>]<<<<<<<<[->>>>>>>>>>>>>+>>>>>+<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>
+>>>>>[-<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>]<<<<++++++++++++++++<[->-[>+>>]>[+[
+-<+>]>+>>]<<<<<]>[-]>[-]>[-<<<<<<<<<<<+>>>>>>>>>>>]<<<<<<<<<<<<<<[->>>>>>>>>>>+>
+>>>>+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>[-<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<<<<++
+++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]>[-]>[-<<<<<<<<<<<+>>>>>>>>>>>]
+<<<<<<<<<<<<+>[[-]<<<<<<[-]+<<[-]>>>>>>>[-]>]<[-<<<<[->>>>>>>+<+<<<<<<]>>>>>>[-<
+<<<<<+>>>>>>]>[-<<<<<<<[->>>>>>>>>+<<<+<<<<<<]>>>>>>[-<<<<<<+>>>>>>]>]>>[->>>>+>
+>>>>+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>>>>>>>]<<<<++++++++<[->-[>+>>]>[+[-<+>]>+
+>>]<<<<<]>[-]<<<<<[-]>>>>>>[-]>[-<<<<<<<+>>>>>>>]<<<<<<<<<<<<<<[->>>>>+<+<<<<]>>
+>>[-<<<<+>>>>]>[-<<<<<[->>>>>>>>+<<<<+<<<<]>>>>[-<<<<+>>>>]>]>>>[->>>+>>>>>+<<<<
+<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<<<<++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]
+<<<<[-]>>>>>[-]>[-<<<<<<+>>>>>>]<<<<<<<<<<<<<<<<[->>>>>>>+<+<<<<<<]>>>>>>[-<<<<<
+<+>>>>>>]>[-<<<<<[->>>>>>>>>+<<<<<+<<<<]>>>>[-<<<<+>>>>]>]>>>>[->>+>>>>>+<<<<<<<
+]>>>>>>>[-<<<<<<<+>>>>>>>]<<<<++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]<<<[-]>>>>[
+-]>[-<<<<<+>>>>>]<<<<<<<[-<<<<+>+>>>]<<<[->>>+<<<]>>>>[-<<<<<+>+>>>>]<<<<[->>>>+
+<<<<]<[->>>>>>>>+>>>>>+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]
+<<<<+++++++++++++++++++++++++++++++++<[->-[>+>>]>[+[-<+>]>+>>]<<<<<]>[-]<<<<<<<<
+<[-]>>>>>>>>>>[-]>[-<<<<<<<<<<<+>>>>>>>>>>>]<<<<<<<<<<<<+>[[-]<<<<<<[-]+<<[-]>>>
+>>>>[-]>]<[-<<<<<<<<<[-]<[->+>>>>>>>>>>>+<<<<<<<<<<<<]>>>>>>>>>>>>[-<<<<<<<<<<<<
++>>>>>>>>>>>>]>>>[-<<<<<<<<<<<<<<+>>>>>>>>>>>>>>]>[-<<<<<<<<<<<<<<<->>>>>>>>>>>>
+>>>]<<<<<<<<<[->>>>>>+<<<<<<]>>>>>>>+<<<<<[->>>>>-<<+>[-<->]<[->+<]<<<]>>>>>[-]<
+<<<<<<<<<<<[-]<<<<[->>>>+>>>>>>>>>>+<<<<<<<<<<<<<<]>>>>>>>>>>>>>>[-<<<<<<<<<<<<<
+<+>>>>>>>>>>>>>>]>>+<[->->>>[-<<<<<<<<<<<<<<<->>>>>>>>>>>>>>>]<<<<]>[->>>[-<<<<<
+<<<<<<<<<<+>>>>>>>>>>>>>>>]<<<]<<<<<<<<<<+>>>>>>]>>>>>[-]>[-]>[-]<<<<<<<]<<<<[-]
+>[-]>[-]>[-]<<<<<<]<<[-]>[-]>>>>>>>>>>>>+<<<<<<<<<[->>>>>>>>>->>>>++++++++++++++
+++++++++++++++++++<<<<<<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>
+>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>++++++++++++++<<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<
+<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>--<<<<<]<<<<<<<<<[-
+>>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>+<
+<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<-
+>>>>>>>>>>>>>>++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
++++++++++++++++<<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>
+]>[[-]<<<<<<<<<->>>>>>>>>>>>>>--------------------------------------------------
+---------------------------------<<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[
+-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>++++++++++++++++++<<<<<]<<<<<<<<
+<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>
+>-------------------<<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>
+>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>-----<<<<<]<<<<<<<<<[->>>>>>>>>+<+<<<<<<<<]>>
+>>>>>>[-<<<<<<<<+>>>>>>>>]>[[-]<<<<<<<<<->>>>>>>>>>>>>>+<<<<<]<<<<<<<<<[-]>>>>>>
+>>>>>>>>.[-]<<<<<<<<<<<<<]>>>>>>>>>[->>>>+++++++++++++++++++++++++++++++++++.[-]
+<<<<]<<<<<<<<<<[-]<<<<+<]>>>>>>>>>>>>>>>>>>>++++++++++.[-]<<<<<<<<<<<<<<<<<<<<++
+<]
@@ -0,0 +1,29 @@
+ROT13 filter from the Brainfuck article on Wikipedia
+Letters are rotated by thirteen places and everything else is copied as is
+
+-,+[
+    -[
+        >>++++[>++++++++<-]
+        <+<-[
+            >+>+>-[>>>]
+            <[[>+<-]>>+>]
+            <<<<<-
+        ]
+    ]>>>[-]+
+    >--[-[<->+++[-]]]<[
+        ++++++++++++<[
+            >-[>+>>]
+            >[+[<+>-]>+>>]
+            <<<<<-
+        ]
+        >>[<+>-]
+        >[
+            -[
+                -<<[-]>>
+            ]<<[<<->>-]>>
+        ]<<[<<+>>-]
+    ]
+    <[-]
+    <.[-]
+    <-,+
+]
@@ -0,0 +1,99 @@
+use crate::parser::{Instruction, Program};
+
+/// Static shape of a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub instructions: usize, // `Halt` not included, for an unfolded parse this is the minified source length
+    pub loops: usize,
+    pub max_depth: usize
+}
+
+pub fn analyze(program: &Program) -> Stats {
+    let mut stats: Stats = Stats::default();
+    let mut depth: usize = 0;
+
+    for instruction in program.instructions() {
+        match instruction {
+            Instruction::Halt => { continue; },
+            Instruction::Begin => {
+                stats.loops += 1;
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+            },
+            Instruction::End => { depth -= 1; },
+            _ => {}
+        }
+        stats.instructions += 1;
+    }
+
+    stats
+}
+
+/// Pointer range one iteration of a loop touches, relative to the pointer at the top of the body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Excursion {
+    pub left: usize, // furthest cell left of the entry cell the body reads or writes
+    pub right: usize, // furthest cell right of it, including the one the closing `]` tests
+    pub shift: isize, // where the pointer ends up after one iteration
+    pub body_len: usize // instructions between the brackets
+}
+
+/// Excursions of the innermost loops whose bodies only move the pointer and change cells, indexed by
+/// the position of their `Begin`. Everything else (nested loops, I/O) is `None`: those loops keep the
+/// per-instruction checks.
+pub fn loop_excursions(program: &Program) -> Vec<Option<Excursion>> {
+    let instructions: &[Instruction] = program.instructions();
+    let mut excursions: Vec<Option<Excursion>> = vec![None; instructions.len()];
+
+    for (begin, instruction) in instructions.iter().enumerate() {
+        if *instruction != Instruction::Begin {
+            continue;
+        }
+
+        let end: usize = program.jumps()[begin] - 1;
+        let (mut offset, mut min, mut max): (isize, isize, isize) = (0, 0, 0);
+        let simple: bool = instructions[begin + 1..end].iter().all(|instruction| {
+            match instruction {
+                Instruction::IncrementPointer => { offset += 1; },
+                Instruction::DecrementPointer => { offset -= 1; },
+                Instruction::Move(distance) => { offset += distance; },
+                Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) | Instruction::SetZero => {},
+                _ => return false
+            }
+            min = min.min(offset);
+            max = max.max(offset);
+            true
+        });
+
+        if simple {
+            excursions[begin] = Some(Excursion { left: min.unsigned_abs(), right: max as usize, shift: offset, body_len: end - begin - 1 });
+        }
+    }
+
+    excursions
+}
+
+/// Marks, by instruction index, the `,` of every innermost loop reading exactly one byte per iteration
+/// (`,[.,]` and other scanners), whose input can be fetched ahead a batch at a time.
+pub fn reader_loop_inputs(program: &Program) -> Vec<bool> {
+    let instructions: &[Instruction] = program.instructions();
+    let mut readers: Vec<bool> = vec![false; instructions.len()];
+
+    for (begin, instruction) in instructions.iter().enumerate() {
+        if *instruction != Instruction::Begin {
+            continue;
+        }
+
+        let end: usize = program.jumps()[begin] - 1;
+        let body: &[Instruction] = &instructions[begin + 1..end];
+        if body.contains(&Instruction::Begin) {
+            continue;
+        }
+        let mut inputs = body.iter().enumerate().filter(|(_, instruction)| **instruction == Instruction::InputValue);
+        if let (Some((offset, _)), None) = (inputs.next(), inputs.next()) {
+            readers[begin + 1 + offset] = true;
+        }
+    }
+
+    readers
+}
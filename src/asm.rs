@@ -0,0 +1,210 @@
+//! "Brainfuck assembly", a front end with mnemonics and repeat counts compiling to the same
+//! instructions as brainfuck source, and the matching disassembler.
+//!
+//! ```text
+//! # comments run to the end of the line
+//! add 72        # `+` 72 times, `add -3` or `sub 3` subtract (wrapping)
+//! ptr +2        # `>>`, `ptr -1` is `<`
+//! out           # `.`, `out 3` writes the cell three times, `in` / `in N` read
+//! outdec        # `:` (the extension instruction)
+//! clear         # `[-]`
+//! loop {        # `[` ... `]`
+//!     sub 1
+//! }
+//! ```
+//!
+//! Mnemonics and counts are separated by whitespace, braces need none (`loop{` works too).
+
+use std::fmt;
+
+use crate::parser::{Instruction, Program};
+
+/// Syntax error in an assembly source, `position` is the byte offset of the offending token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub position: usize,
+    pub message: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Whitespace separated tokens of `code` with their byte offsets, comments and `{` `}` split off.
+fn tokens(code: &str) -> Vec<(usize, &str)> {
+    let mut tokens: Vec<(usize, &str)> = vec![];
+    let mut line_start: usize = 0;
+    for line in code.split_inclusive('\n') {
+        let text: &str = line.split('#').next().unwrap_or_default();
+        let mut start: Option<usize> = None;
+        for (offset, c) in text.char_indices().chain([(text.len(), ' ')]) {
+            let brace: bool = c == '{' || c == '}';
+            if c.is_whitespace() || brace {
+                if let Some(begin) = start.take() {
+                    tokens.push((line_start + begin, &text[begin..offset]));
+                }
+                if brace {
+                    tokens.push((line_start + offset, &text[offset..offset + 1]));
+                }
+            } else if start.is_none() {
+                start = Some(offset);
+            }
+        }
+        line_start += line.len();
+    }
+    tokens
+}
+
+/// Highest count of `out`, `in` and `outdec`, each repetition is an instruction of its own.
+pub const MAX_REPEAT: usize = 1 << 20;
+
+/// Assembles `code`, `auto_halt` appends the closing `Instruction::Halt` like [`crate::compile`].
+/// Positions of the instructions point at their mnemonics.
+pub fn assemble(code: &str, auto_halt: bool) -> Result<Program, AsmError> {
+    let tokens: Vec<(usize, &str)> = tokens(code);
+    let mut instructions: Vec<(Instruction, usize)> = vec![];
+    let mut open: Vec<usize> = vec![]; // positions of the `loop`s waiting for their `}`
+
+    let mut index: usize = 0;
+    while index < tokens.len() {
+        let (position, mnemonic) = tokens[index];
+        let error = |message: String| AsmError { position, message };
+        index += 1;
+
+        // optional count after the mnemonic
+        let argument: Option<(usize, &str)> = tokens.get(index).copied().filter(|(_, token)| {
+            token.starts_with(|c: char| c.is_ascii_digit() || c == '+' || c == '-')
+        });
+        let count = |default: Option<i64>| -> Result<i64, AsmError> {
+            match argument {
+                Some((at, token)) => token.parse::<i64>().map_err(|_| AsmError { position: at, message: format!("Invalid count `{}`", token) }),
+                None => default.ok_or_else(|| error(format!("`{}` expects a count", mnemonic)))
+            }
+        };
+        let repeat = |count: i64| -> Result<usize, AsmError> {
+            usize::try_from(count).ok().filter(|&times| times <= MAX_REPEAT).ok_or_else(|| {
+                error(format!("`{}` can't repeat {} times, at most {}", mnemonic, count, MAX_REPEAT))
+            })
+        };
+
+        match mnemonic {
+            "add" | "sub" => {
+                let delta: i64 = count(None)?;
+                let delta: u8 = delta.rem_euclid(256) as u8;
+                let delta: u8 = if mnemonic == "sub" { delta.wrapping_neg() } else { delta };
+                if delta != 0 {
+                    instructions.push((Instruction::Add(delta), position));
+                }
+            },
+            "ptr" => {
+                let offset: isize = isize::try_from(count(None)?).map_err(|_| error("Pointer move out of range".to_string()))?;
+                if offset != 0 {
+                    instructions.push((Instruction::Move(offset), position));
+                }
+            },
+            "out" | "in" | "outdec" => {
+                let instruction: Instruction = match mnemonic {
+                    "out" => Instruction::OutputValue,
+                    "in" => Instruction::InputValue,
+                    _ => Instruction::OutputDecimal
+                };
+                let times: usize = repeat(count(Some(1))?)?;
+                instructions.extend(std::iter::repeat_n((instruction, position), times));
+            },
+            "clear" => { instructions.push((Instruction::SetZero, position)); },
+            "loop" => {
+                match tokens.get(index) {
+                    Some(&(_, "{")) => { index += 1; },
+                    _ => return Err(error("`loop` expects `{`".to_string()))
+                }
+                open.push(position);
+                instructions.push((Instruction::Begin, position));
+                continue;
+            },
+            "}" => {
+                open.pop().ok_or_else(|| error("Unmatched `}`".to_string()))?;
+                instructions.push((Instruction::End, position));
+                continue;
+            },
+            _ => return Err(error(format!("Unknown mnemonic `{}`", mnemonic)))
+        }
+        if argument.is_some() {
+            index += 1;
+        }
+    }
+
+    if let Some(&position) = open.last() {
+        return Err(AsmError { position, message: "`loop` without its closing `}`".to_string() });
+    }
+    let mut program: Program = Program::from_parts(&instructions, code.len(), instructions.iter().any(|&(instruction, _)| matches!(instruction, Instruction::Add(_))));
+    if auto_halt {
+        program.push_halt();
+    }
+    Ok(program)
+}
+
+/// Loop depth past which [`disassemble`] stops indenting, deeper nests would make the listing grow
+/// with the square of their depth.
+const MAX_INDENT: usize = 32;
+
+/// `program` written back as assembly, one instruction per line. Folded programs (`--single-pass`)
+/// disassemble into the most compact form.
+pub fn disassemble(program: &Program) -> String {
+    let mut listing: String = String::new();
+    let mut depth: usize = 0;
+    for instruction in program.instructions() {
+        if *instruction == Instruction::End {
+            depth = depth.saturating_sub(1);
+        }
+        let line: String = match instruction {
+            Instruction::IncrementPointer => "ptr +1".to_string(),
+            Instruction::DecrementPointer => "ptr -1".to_string(),
+            Instruction::Move(offset) => format!("ptr {:+}", offset),
+            Instruction::IncrementValue => "add 1".to_string(),
+            Instruction::DecrementValue => "sub 1".to_string(),
+            Instruction::Add(delta) if *delta > 128 => format!("sub {}", delta.wrapping_neg()),
+            Instruction::Add(delta) => format!("add {}", delta),
+            Instruction::SetZero => "clear".to_string(),
+            Instruction::OutputValue => "out".to_string(),
+            Instruction::InputValue => "in".to_string(),
+            Instruction::OutputDecimal => "outdec".to_string(),
+            Instruction::Begin => "loop {".to_string(),
+            Instruction::End => "}".to_string(),
+            Instruction::Halt => continue
+        };
+        listing.push_str(&"    ".repeat(depth.min(MAX_INDENT)));
+        listing.push_str(&line);
+        listing.push('\n');
+        if *instruction == Instruction::Begin {
+            depth += 1;
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(code: &str) -> String {
+        assemble(code, true).unwrap_err().message
+    }
+
+    #[test]
+    fn counts_wrap_to_a_byte_whatever_their_size() {
+        let program: Program = assemble("add -9223372036854775808\nsub -9223372036854775808\nsub 9223372036854775807\nsub 1", false).unwrap();
+        // i64::MIN and its negation are both 0 modulo 256, so the first two lines vanish
+        assert_eq!(program.instructions(), [Instruction::Add(1), Instruction::Add(255)]);
+    }
+
+    #[test]
+    fn repeat_counts_are_capped() {
+        assert_eq!(assemble(&format!("out {}", MAX_REPEAT), false).unwrap().len(), MAX_REPEAT);
+        assert_eq!(message("out 99999999999999"), format!("`out` can't repeat 99999999999999 times, at most {}", MAX_REPEAT));
+        assert_eq!(message("in -1"), format!("`in` can't repeat -1 times, at most {}", MAX_REPEAT));
+    }
+}
@@ -0,0 +1,145 @@
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::interpreter::{execute_code, ExecutionConfig, Interpreter, RunControl, RunSummary, RuntimeError};
+use crate::parser::Program;
+
+/// Handle to a program running on a worker thread, created by [`spawn_run`].
+#[derive(Debug)]
+pub struct RunHandle {
+    control: Arc<RunControl>,
+    thread: JoinHandle<Result<RunSummary, RuntimeError>>
+}
+
+impl RunHandle {
+    /// `(steps, instruction index)`, refreshed every [`crate::POLL_INTERVAL`] steps.
+    pub fn progress(&self) -> (u64, usize) {
+        self.control.progress()
+    }
+
+    /// Asks the run to stop at its next poll point, the summary then reports `HaltReason::Cancelled`.
+    pub fn cancel(&self) {
+        self.control.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the run to end, a panic on the worker thread is propagated to the caller.
+    pub fn join(self) -> Result<RunSummary, RuntimeError> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic)
+        }
+    }
+}
+
+/// Runs `program` on a new thread, any `control` already present in `config` is replaced.
+pub fn spawn_run<R, W>(
+    program: Program,
+    mut interpreter: Interpreter,
+    mut input: R,
+    mut output: W,
+    mut config: ExecutionConfig
+) -> RunHandle
+where
+    R: Read + Send + 'static,
+    W: Write + Send + 'static
+{
+    let control: Arc<RunControl> = Arc::new(RunControl::default());
+    config.control = Some(Arc::clone(&control));
+
+    let thread = thread::spawn(move || {
+        execute_code(&program, &mut interpreter, &mut input, &mut output, &config)
+    });
+
+    RunHandle { control, thread }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::interpreter::{HaltReason, POLL_INTERVAL};
+    use crate::parser::{compile, ParseOptions};
+
+    fn program(code: &str) -> Program {
+        compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, true).unwrap()
+    }
+
+    /// Waits until the run has published progress past `steps`.
+    fn wait_for_progress(handle: &RunHandle, steps: u64) -> (u64, usize) {
+        let deadline: Instant = Instant::now() + Duration::from_secs(10);
+        loop {
+            let progress: (u64, usize) = handle.progress();
+            if progress.0 > steps {
+                return progress;
+            }
+            assert!(Instant::now() < deadline, "no progress past {} steps", steps);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn cancel_and_join(handle: RunHandle) -> Result<RunSummary, RuntimeError> {
+        handle.cancel();
+        let deadline: Instant = Instant::now() + Duration::from_secs(10);
+        while !handle.is_finished() {
+            assert!(Instant::now() < deadline, "the run ignored the cancellation");
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.join()
+    }
+
+    #[test]
+    fn progress_advances_and_cancel_stops_a_stepped_run() {
+        let handle: RunHandle = spawn_run(program("+[,.]"), Interpreter::default(), io::repeat(7), io::sink(), ExecutionConfig::default());
+        let (first, _) = wait_for_progress(&handle, 0);
+        wait_for_progress(&handle, first);
+
+        let summary: RunSummary = cancel_and_join(handle).unwrap();
+        assert_eq!(summary.halt, HaltReason::Cancelled);
+    }
+
+    #[test]
+    fn progress_advances_and_cancel_stops_a_hoisted_loop() {
+        // a loop whose every iteration runs in one go and never ends
+        let handle: RunHandle = spawn_run(program("+[>+<]"), Interpreter::default(), io::empty(), io::sink(), ExecutionConfig::default());
+        let (first, index) = wait_for_progress(&handle, 0);
+        assert_eq!(index, 2, "paused at the start of the loop body");
+        wait_for_progress(&handle, first);
+
+        let summary: RunSummary = cancel_and_join(handle).unwrap();
+        assert_eq!(summary.halt, HaltReason::Cancelled);
+        assert!(summary.steps >= first);
+    }
+
+    #[test]
+    fn a_run_ending_before_the_first_poll_ignores_a_late_cancel() {
+        let handle: RunHandle = spawn_run(program("++[->+<]>."), Interpreter::default(), io::empty(), Vec::new(), ExecutionConfig::default());
+        while !handle.is_finished() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.cancel();
+        let summary: RunSummary = handle.join().unwrap();
+        assert_eq!(summary.halt, HaltReason::EndOfProgram);
+        assert!(summary.steps < POLL_INTERVAL);
+    }
+
+    #[test]
+    fn errors_and_cancellation_stay_apart() {
+        // fails right away, a cancel arriving afterwards doesn't turn the error into a cancellation
+        let failing: RunHandle = spawn_run(program("<"), Interpreter::fixed(1), io::empty(), io::sink(), ExecutionConfig::default());
+        assert!(matches!(cancel_and_join(failing), Err(RuntimeError::PointerUnderflow)));
+
+        // would fail once it runs out of tape, cancelled long before
+        let config: ExecutionConfig = ExecutionConfig { max_steps: Some(u64::MAX), ..ExecutionConfig::default() };
+        let slow: RunHandle = spawn_run(program("+[,.>+<]>[>+]"), Interpreter::growable(1 << 20), io::repeat(1), io::sink(), config);
+        wait_for_progress(&slow, POLL_INTERVAL);
+        let summary: RunSummary = cancel_and_join(slow).unwrap();
+        assert_eq!(summary.halt, HaltReason::Cancelled);
+    }
+}
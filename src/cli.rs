@@ -0,0 +1,1118 @@
+use std::path::{Path, PathBuf};
+
+use brainfck::console::ConsoleEncoding;
+use brainfck::manifest::Data;
+use brainfck::reduce::Failure;
+use brainfck::{Compat, FilterMode, GasCosts, TapeMode};
+
+pub const USAGE: &str = "\
+usage: bf_interpreter [OPTIONS] FILENAME
+       bf_interpreter golf [--apply] [OPTIONS] FILENAME
+       bf_interpreter size FILENAME
+       bf_interpreter slice --output-index N [--emit-sliced PATH] [OPTIONS] FILENAME
+       bf_interpreter reduce [--oracle ORACLE [--max-tests N]] [OPTIONS] FILENAME
+       bf_interpreter run-manifest [--parallel | --jobs N] MANIFEST
+       bf_interpreter examples list | show NAME | run NAME [OPTIONS]
+       bf_interpreter pipe [OPTIONS] FILENAME...
+       bf_interpreter multi [--slice N] [--input-to NAME] [OPTIONS] FILENAME...
+       bf_interpreter completions bash|zsh|fish
+       bf_interpreter --spec FILE
+
+commands:
+    golf              suggest behavior-preserving rewrites that shorten the source, those moving
+                      to a neighbouring cell only where the tape options leave room for it;
+                      --apply writes them back after re-verifying by differential execution on
+                      the configured tape, with empty input and with the input it is given
+    size              print the minified length, loop count and maximum loop nesting
+    slice             run the program once and list the instructions output byte N (counted from 0)
+                      depends on through the cells they wrote and the loops they ran in, then show
+                      them under the source; --emit-sliced writes just those instructions to PATH, an
+                      approximation that may not run on its own
+    reduce            shrink a program showing a failure to a small one that still shows it and
+                      print that: `diverges` (the optimized build ends differently than the
+                      unoptimized one), `panics` or `exitcode N` (the status a run would exit with);
+                      at most --max-tests checks (default 10000) of at most --max-steps steps each
+                      (default 1000000), a run reaching that limit shows no failure; without
+                      --oracle, print the program rebuilt with every optimization pass as plain
+                      brainfuck instead, after checking it still behaves the same on the input
+    pipe              run the programs concurrently as a chain of filters, each one reading what
+                      the previous one outputs, the first reading the input and the last writing
+                      to stdout (parse, tape and limit options apply to every stage)
+    multi             run the programs in one thread by turns of --slice steps (default 10000), each
+                      on its own tape, every output line tagged `[FILENAME]`; only the program named
+                      by --input-to (default the first) reads the input, --max-steps limits all of
+                      them together
+    examples          list the built-in example programs, show the source of one or run it like a
+                      file (with its sample input unless --input-file/--input-string give another)
+    run-manifest      run every `[[job]]` of a manifest, write its artifacts, check its
+                      expectations and print a summary table, --parallel runs the jobs on
+                      their own threads, --jobs N runs up to N of them at a time
+    completions       print a completion script for bash, zsh or fish covering the commands and
+                      options listed here, e.g. `source <(bf_interpreter completions bash)`
+
+options:
+    --alt-brackets    treat `(` `)` as loop delimiters too (each must close its own kind)
+    --extensions      enable non-standard instructions: `:` outputs the current cell as a decimal
+                      number (a comment otherwise)
+    --max-nesting N   reject a program nesting loops more than N deep (no limit by default)
+    --input-file PATH read `,` input from PATH instead of stdin
+    --input-string S  read `,` input from S instead of stdin
+    --random-input SEED
+                      read `,` input from endless pseudo-random bytes generated from SEED (a number),
+                      the same bytes for every run with that seed; stderr tells the seed and the
+                      bytes read afterwards, limit the run with --max-steps
+    --single-pass     fold runs of `+-`/`<>` and `[-]` clear loops into single instructions
+                      while parsing
+    --constprop       remove loops and clears that constant propagation proves dead (a loop right
+                      after a loop, doubled `[-]`, a leading comment loop), see --emit-ir for the result
+    --warn            report suspicious loops to stderr before running: empty loops, a scan right
+                      after a scan the other way (`[<][>]`) and bodies clearing the cell `]` tests
+    --explain-errors  on a parse error, also show the source line around it and explain in plain words
+                      what is wrong and how it is usually fixed
+    --bisect-passes   build the program with longer and longer prefixes of the pass pipeline (fold,
+                      constprop), report the first pass changing the output, final tape or ending
+                      against the unoptimized build and list the IR of the code it changed before
+                      and after it (budget defaults to 100000000 steps per run)
+    --no-auto-halt    don't append the closing `halt` instruction, the run ends by running off the
+                      end of the program instead (visible in --emit-ir)
+    --tape-size N     number of tape cells (default 30000), or the growth limit of a dynamic
+                      tape (default 67108864)
+    --dynamic-tape[=right|bidirectional]
+                      start with a single cell and grow the tape on demand, to the right only
+                      or in both directions
+    --sandbox-region LO:HI
+                      confine the pointer to cells LO..HI (HI excluded) of a fixed tape, it starts
+                      at LO unless the usual start cell is inside
+    --cell-modulus M  make `+` and `-` wrap modulo M (2 to 256, cells stay 8 bits wide) instead of at
+                      256, a value read by `,` past M is reduced by the next `+` or `-`; with
+                      --single-pass, --constprop, --bisect-passes or an optimizing `reduce` M has
+                      to divide 256
+    --loop-cell ADDR  make `[` and `]` test cell ADDR (counted from the starting cell, negative to its
+                      left) instead of the current cell, a non-standard control flow experiment; rules
+                      out --single-pass, --constprop and --bisect-passes
+    --max-steps N     abort once N instructions have been executed
+    --max-pointer-range N
+                      abort before a move would widen the span of cells the pointer has reached
+                      past N cells, wherever on the tape that span lies
+    --max-output N    abort with `Output limit exceeded` before the program writes more than N bytes
+    --gas-limit N     abort before the instruction that would take the gas burnt past N and print the
+                      gas used and left to stderr after the run
+    --gas-cost KIND=N what an instruction kind costs, can be repeated: pointer (default 1, per cell
+                      moved), arithmetic (1, per unit added), clear (2, a folded `[-]`), input (10),
+                      output (10), branch (1, `[` and `]`)
+    --compat=dbfi|bff preset the conventions of Daniel Cristofani's dbfi (a tape growing to the right)
+                      or of Oleg Mazonka's bff (growing both ways), and treat a `!` in the source as
+                      the end of the program, the rest is input read before the usual one; 8-bit
+                      wrapping cells and `,` leaving the cell unchanged at EOF are the defaults anyway,
+                      --dynamic-tape and --sandbox-region override the preset's tape
+    --filter[=stream|byte]
+                      act as a Unix filter: `stream` (the default) reads the whole input and runs the
+                      program once over it, `byte` runs it once per input byte on a fresh tape with
+                      just that byte as its input; the outputs go to stdout in order
+    --truth-table     run the program once for every input byte 0..=255 on a fresh tape and
+                      print the input -> output mapping (budget defaults to 1000000 steps per byte)
+    --asm             read FILENAME as brainfuck assembly (`add 5`, `sub 1`, `ptr +3`, `out`, `in`,
+                      `outdec`, `clear`, `loop { ... }`, `#` comments) instead of brainfuck
+    --emit-asm        print the program as brainfuck assembly instead of running it (combine with
+                      --single-pass for the compact form)
+    --emit-ir         print the program lowered to a three-address listing (registers `p` and `v`,
+                      opcodes load, store, add, sub, in, out, outdec, brz, brnz, halt) instead of
+                      running it
+    --post-mortem     on a runtime error, dump the last executed instructions, I/O events
+                      and the tape around the pointer to stderr
+    --post-mortem-size N
+                      instructions kept for --post-mortem (default 32)
+    --post-mortem-io N
+                      I/O events kept for --post-mortem (default 16)
+    --explain         print a sentence describing every executed instruction to stderr
+    --explain-limit N stop explaining after N steps, the run then continues silently
+    --explain-delay MS
+                      pause between explained steps (default 250)
+    --explain-step    wait for Enter after every explained step instead of pausing
+                      (needs --input-file or --input-string)
+    --layout PATH     name tape cells with a layout file (`name = offset`, `name = [start, end]`
+                      or `name = { offset = N, width = N, endian = \"little\" }`, offsets relative
+                      to the starting pointer)
+    --watch CELL      print the value of CELL (layout name or absolute index) whenever it changes,
+                      can be repeated
+    --count-output    discard the output and print how many bytes it had once the run ends
+    -o, --output PATH also write the output to PATH, can be repeated; a file that fails is dropped
+                      with a warning while the run goes on
+    --console-encoding utf8|cp437|raw
+                      on a Windows console, `utf8` switches the console to UTF-8 for the run, `cp437`
+                      draws the output bytes as their CP437 characters, `raw` (the default) leaves
+                      them to the console's code page; ignored elsewhere and when stdout is redirected
+    --on-broken-pipe exit|error|ignore
+                      what to do when the reader of stdout goes away (`| head`): `exit` (the
+                      default) ends the run quietly with status 141 like a Unix tool killed by
+                      SIGPIPE, `error` reports it on stderr and exits with 1, `ignore` runs on and
+                      drops the rest of the output
+    --output-image PATH
+                      write the output as a grayscale image to PATH instead of stdout, one byte per
+                      pixel and --width pixels a row (the last one padded with black), PGM or with
+                      the `png` feature PNG, chosen by the extension
+    --record-diffs PATH
+                      write every change to the tape to PATH as it happens, one `STEP CELL VALUE` line
+                      per cell write and one `STEP @CELL` line per pointer move, for animations
+    --report PATH     profile the run and write a self-contained HTML report to PATH: the source
+                      shaded by how often each instruction ran (never run code greyed out), the
+                      busiest loops, memory use, the output and the settings of the run
+    --width W         pixels per row of --output-image
+    --expect S        compare the output with S once the run ends and print PASS or FAIL to stderr,
+                      with the first differing line of both on a mismatch (exit status 1 then)
+    --expect-file PATH
+                      the same with the contents of PATH
+    --dump-tape       print the non-zero cells (and layout fields) to stderr after the run
+    --dump-csv PATH   write the tape to PATH as `index,value` rows after the run, cells counted
+                      from the starting cell, by default those between the leftmost and rightmost
+                      cell the pointer reached
+    --csv-range LO:HI the cells LO..HI (HI excluded) --dump-csv writes instead
+    --trace-source    print the source line of every executed instruction with a caret under it
+                      to stderr
+    --trace-limit N   stop tracing after N steps, the run then continues silently
+    --trace-delay MS
+                      pause between traced steps (default 250)
+    --trace-step      wait for Enter after every traced step instead of pausing
+                      (needs --input-file or --input-string)
+    --break-on-output BYTE
+                      stop the run right after `.` outputs BYTE (0-255, a character or an escape
+                      like \\n) and report the step and pointer, with --explain-step or
+                      --trace-step pause until Enter instead
+    --halt-on-output BYTE
+                      end the run cleanly the first time `.` outputs BYTE, without writing it
+    --emit-sentinel   write the --halt-on-output byte before halting
+    --sentinel-exit-code N
+                      exit status of a run ended by --halt-on-output (default 0)
+    --stop-at LINE:COL
+                      end the run cleanly the first time execution reaches the instruction at (or
+                      the first one after) LINE:COL
+    --stop-at-instruction N
+                      same, for the N-th parsed instruction (counted from 0)
+    --start-at N      begin the run at the N-th parsed instruction (counted from 0) instead of the
+                      first one, it has to be outside every loop (rules out --constprop and
+                      --bisect-passes)
+    --save-snapshot PATH
+                      write the tape, pointer and execution position to PATH once the run halts
+    --start-snapshot PATH
+                      resume a run saved with --save-snapshot (same program, parse options and input)
+    --tape-checksum   print the final tape checksum to stderr
+    --pointer-range   print the leftmost and rightmost cell the pointer reached to stderr after the
+                      run, counted from the starting cell, and how many cells that spans
+    --print-result    once the run ends without an error, print exactly one line `pointer=P cell=V`
+                      to stderr: the final cell (counted from the starting cell) and its value
+    --taint           track cell ownership from `@tag:NAME` / `@owner:NAME` ... `@end` comments
+                      and report writes to cells tagged by another owner
+    --cost-model classic|optimized|FILE
+                      print the run's weighted operation count to stderr: `classic` prices folded
+                      instructions by the primitive operations they stand for, `optimized` counts
+                      one per instruction, FILE holds `key = weight` lines (see the README)
+    --cycle-profile   print to stderr which share of the run's time each instruction kind took
+                      (pointer, arithmetic, clear, input, output, branch), timing a random 1 in 64
+                      steps; the run steps every instruction on its own, so it is slower
+    --progress N      print the step count and pointer to stderr every N million steps
+    --spec FILE       run the test case described by FILE (program, input, expected output, tape
+                      size, EOF mode, ...) and report whether it passed
+    --verify-determinism N
+                      run the program N (at least 2) times on the same (non-interactive) input
+                      and report any run whose output, final tape checksum or step count differs
+    --no-config       ignore .brainfck.toml and BRAINFCK_OPTS
+    --print-config    print the defaults taken from .brainfck.toml and BRAINFCK_OPTS and the flags
+                      overriding them, with where each one came from, and exit
+    -h, --help        print this message
+
+defaults:
+    .brainfck.toml in the program's directory or the nearest parent holding one sets defaults as
+    `key = value` lines (`tape_size = 100000`, `single_pass = true`, `dynamic_tape = \"right\"`, ...),
+    BRAINFCK_OPTS (`--max-steps=1000000 --constprop`) overrides them and the command line overrides
+    both. Keys: alt_brackets, extensions, max_nesting, single_pass, no_auto_halt, constprop, warn,
+    explain_errors, tape_size, dynamic_tape, sandbox_region, compat, cell_modulus, max_steps,
+    max_output, gas_limit, post_mortem, post_mortem_size, post_mortem_io, explain_delay, trace_delay,
+    layout, dump_tape, tape_checksum, print_result, count_output, progress, filter, console_encoding,
+    on_broken_pipe, sentinel_exit_code";
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    #[default]
+    Run,
+    Golf { apply: bool },
+    Size,
+    Slice { output_index: u64, emit_sliced: Option<String> },
+    Reduce { oracle: Option<Failure>, max_tests: usize }, // no oracle: the optimized equivalent
+    RunManifest { parallel: bool, jobs: Option<usize> }, // `jobs` threads at most
+    Examples { action: ExampleAction },
+    Pipe { programs: Vec<String> },
+    Multi { programs: Vec<String>, slice: u64, input_to: Option<String> },
+    Completions { shell: Shell }
+}
+
+/// Oracle checks `reduce` runs unless `--max-tests` says otherwise.
+pub const REDUCE_TESTS: usize = 10_000;
+
+/// Steps a `multi` program runs per turn unless `--slice` says otherwise.
+pub const MULTI_SLICE: u64 = 10_000;
+
+/// What `examples` does, FILENAME names the example for `show` and `run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExampleAction {
+    List,
+    Show,
+    Run
+}
+
+/// Shell `completions` prints a script for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Input {
+    #[default]
+    Stdin,
+    File(String),
+    String(String),
+    Random(u64) // seed of a `RandomInput`
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub filepath: String,
+    pub alt_brackets: bool,
+    pub single_pass: bool,
+    pub no_auto_halt: bool,
+    pub constprop: bool,
+    pub bisect_passes: bool,
+    pub warn: bool,
+    pub explain_errors: bool,
+    pub extensions: bool,
+    pub max_nesting: Option<usize>,
+    pub input: Input,
+    pub tape_size: Option<usize>,
+    pub tape_mode: TapeMode,
+    pub sandbox: Option<std::ops::Range<usize>>,
+    pub compat: Option<Compat>,
+    pub cell_modulus: Option<u16>,
+    pub loop_cell: Option<isize>, // relative to the starting cell
+    pub max_steps: Option<u64>,
+    pub gas_limit: Option<u64>,
+    pub gas_costs: GasCosts,
+    pub filter: Option<FilterMode>,
+    pub truth_table: bool,
+    pub emit_ir: bool,
+    pub asm: bool,
+    pub emit_asm: bool,
+    pub post_mortem: Option<(usize, usize)>, // (instructions, I/O events) kept in the rings
+    pub verify_determinism: Option<usize>,
+    pub explain: Option<Pacing>,
+    pub trace_source: Option<Pacing>,
+    pub layout: Option<String>,
+    pub watches: Vec<String>,
+    pub dump_tape: bool,
+    pub dump_csv: Option<String>,
+    pub csv_range: Option<std::ops::Range<isize>>, // relative to the starting cell, all the pointer reached if `None`
+    pub break_on_output: Option<u8>,
+    pub halt_on_output: Option<u8>,
+    pub emit_sentinel: bool,
+    pub sentinel_exit_code: i32,
+    pub stop_at: Option<StopAt>,
+    pub start_at: Option<usize>,
+    pub save_snapshot: Option<String>,
+    pub start_snapshot: Option<String>,
+    pub tape_checksum: bool,
+    pub pointer_range: bool,
+    pub max_pointer_range: Option<usize>,
+    pub max_output: Option<u64>,
+    pub print_result: bool, // `pointer=P cell=V` on stderr after a clean run
+    pub progress: Option<u64>, // in steps
+    pub cost_model: Option<String>,
+    pub cycle_profile: bool,
+    pub spec: Option<String>,
+    pub taint: bool,
+    pub count_output: bool,
+    pub no_config: bool,
+    pub print_config: bool,
+    pub outputs: Vec<String>,
+    pub output_image: Option<(String, usize)>, // path and width
+    pub record_diffs: Option<String>,
+    pub report: Option<String>,
+    pub expect: Option<Data>, // output `--expect`/`--expect-file` compare with
+    pub console_encoding: ConsoleEncoding,
+    pub on_broken_pipe: BrokenPipe
+}
+
+/// What `--on-broken-pipe` does once stdout's reader is gone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokenPipe {
+    Exit, // stop quietly with the status of a process killed by SIGPIPE
+    Error, // report it like any other output error
+    Ignore // keep running, discarding the output
+}
+
+/// Where `--stop-at`/`--stop-at-instruction` end the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopAt {
+    Source { line: usize, column: usize },
+    Instruction(usize)
+}
+
+/// How fast per-step reports (`--explain`, `--trace-source`) go by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pacing {
+    pub limit: Option<u64>,
+    pub delay_ms: u64,
+    pub wait_for_enter: bool
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            command: Command::default(),
+            filepath: String::new(),
+            alt_brackets: false,
+            single_pass: false,
+            no_auto_halt: false,
+            constprop: false,
+            bisect_passes: false,
+            warn: false,
+            explain_errors: false,
+            extensions: false,
+            max_nesting: None,
+            input: Input::default(),
+            tape_size: None,
+            tape_mode: TapeMode::Fixed,
+            sandbox: None,
+            compat: None,
+            cell_modulus: None,
+            loop_cell: None,
+            max_steps: None,
+            gas_limit: None,
+            gas_costs: GasCosts::default(),
+            filter: None,
+            truth_table: false,
+            emit_ir: false,
+            asm: false,
+            emit_asm: false,
+            post_mortem: None,
+            verify_determinism: None,
+            explain: None,
+            trace_source: None,
+            layout: None,
+            watches: vec![],
+            dump_tape: false,
+            dump_csv: None,
+            csv_range: None,
+            break_on_output: None,
+            halt_on_output: None,
+            emit_sentinel: false,
+            sentinel_exit_code: 0,
+            stop_at: None,
+            start_at: None,
+            save_snapshot: None,
+            start_snapshot: None,
+            tape_checksum: false,
+            pointer_range: false,
+            max_pointer_range: None,
+            max_output: None,
+            print_result: false,
+            progress: None,
+            cost_model: None,
+            cycle_profile: false,
+            spec: None,
+            taint: false,
+            count_output: false,
+            no_config: false,
+            print_config: false,
+            outputs: vec![],
+            output_image: None,
+            record_diffs: None,
+            report: None,
+            expect: None,
+            console_encoding: ConsoleEncoding::Raw,
+            on_broken_pipe: BrokenPipe::Exit
+        }
+    }
+}
+
+impl Options {
+    /// Whether some per-step report waits for Enter, breakpoints then pause instead of aborting.
+    pub fn single_stepping(&self) -> bool {
+        [self.explain, self.trace_source].iter().flatten().any(|pacing| pacing.wait_for_enter)
+    }
+
+    /// Whether something reports on stderr while the program runs, the output then has to go out
+    /// as soon as it is written so the two stay in order.
+    pub fn reports_steps(&self) -> bool {
+        self.explain.is_some() || self.trace_source.is_some() || !self.watches.is_empty()
+    }
+
+    /// Whether a run needs nothing beyond the settings of `brainfck::Run`: no per-step observer,
+    /// snapshot, start or stop point, assembler and no output going anywhere but stdout.
+    pub fn plain_run(&self) -> bool {
+        self.post_mortem.is_none()
+            && self.explain.is_none()
+            && self.trace_source.is_none()
+            && self.watches.is_empty()
+            && self.progress.is_none()
+            && !self.taint
+            && self.cost_model.is_none()
+            && self.record_diffs.is_none()
+            && self.report.is_none()
+            && !self.cycle_profile
+            && self.start_snapshot.is_none()
+            && self.stop_at.is_none()
+            && self.start_at.is_none()
+            && !self.asm
+            && !self.truth_table
+            && self.verify_determinism.is_none()
+            && self.outputs.is_empty()
+            && self.output_image.is_none()
+            && !self.count_output
+    }
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self { limit: None, delay_ms: 250, wait_for_enter: false }
+    }
+}
+
+const POST_MORTEM_DEFAULT: (usize, usize) = (32, 16);
+
+/// Value of `--flag VALUE` or `--flag=VALUE`, `inline` is the part after `=` if there was one.
+fn flag_value<'a>(flag: &str, inline: Option<&'a str>, rest: &mut impl Iterator<Item = &'a String>) -> Result<&'a str, String> {
+    match inline {
+        Some(value) => Ok(value),
+        None => rest.next().map(|value| value.as_str()).ok_or(format!("Option `{}` expects a value", flag))
+    }
+}
+
+/// A byte given as a number, a single ASCII character or one of the escapes `\n` `\r` `\t` `\0`.
+fn parse_byte(flag: &str, value: &str) -> Result<u8, String> {
+    match value {
+        "\\n" => Ok(b'\n'),
+        "\\r" => Ok(b'\r'),
+        "\\t" => Ok(b'\t'),
+        "\\0" => Ok(0),
+        _ if value.len() == 1 && !value.as_bytes()[0].is_ascii_digit() => Ok(value.as_bytes()[0]),
+        _ => parse_number(flag, value)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value `{}` for `{}`", value, flag))
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options: Options = Options::default();
+    let mut filepath: Option<String> = None;
+    let (mut output_image, mut width): (Option<String>, Option<usize>) = (None, None);
+    let (mut output_index, mut emit_sliced): (Option<u64>, Option<String>) = (None, None);
+    let mut oracle: Option<Failure> = None;
+
+    let mut args = args.iter().peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("golf") => { options.command = Command::Golf { apply: false }; },
+        Some("size") => { options.command = Command::Size; },
+        Some("slice") => { options.command = Command::Slice { output_index: 0, emit_sliced: None }; },
+        Some("reduce") => { options.command = Command::Reduce { oracle: None, max_tests: REDUCE_TESTS }; },
+        Some("pipe") => { options.command = Command::Pipe { programs: vec![] }; },
+        Some("multi") => { options.command = Command::Multi { programs: vec![], slice: MULTI_SLICE, input_to: None }; },
+        Some("run-manifest") => { options.command = Command::RunManifest { parallel: false, jobs: None }; },
+        Some("examples") => { options.command = Command::Examples { action: ExampleAction::List }; },
+        Some("completions") => { options.command = Command::Completions { shell: Shell::Bash }; },
+        _ => {}
+    }
+    if options.command != Command::Run {
+        args.next();
+    }
+    if let Command::Examples { action } = &mut options.command {
+        *action = match args.next().map(|arg| arg.as_str()) {
+            Some("list") => ExampleAction::List,
+            Some("show") => ExampleAction::Show,
+            Some("run") => ExampleAction::Run,
+            _ => return Err("`examples` expects list, show NAME or run NAME".to_string())
+        };
+    }
+    if let Command::Completions { shell } = &mut options.command {
+        *shell = match args.next().map(|arg| arg.as_str()) {
+            Some("bash") => Shell::Bash,
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            _ => return Err("`completions` expects bash, zsh or fish".to_string())
+        };
+    }
+
+    while let Some(arg) = args.next() {
+        let (flag, inline): (&str, Option<&str>) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value)),
+            _ => (arg.as_str(), None)
+        };
+
+        match flag {
+            "--alt-brackets" => { options.alt_brackets = true; },
+            "--single-pass" => { options.single_pass = true; },
+            "--no-auto-halt" => { options.no_auto_halt = true; },
+            "--constprop" => { options.constprop = true; },
+            "--bisect-passes" => { options.bisect_passes = true; },
+            "--warn" => { options.warn = true; },
+            "--explain-errors" => { options.explain_errors = true; },
+            "--extensions" => { options.extensions = true; },
+            "--max-nesting" => { options.max_nesting = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--input-file" => { options.input = Input::File(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--input-string" => { options.input = Input::String(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--random-input" => { options.input = Input::Random(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--tape-size" => { options.tape_size = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--dynamic-tape" => {
+                options.tape_mode = match inline {
+                    None | Some("right") => TapeMode::Growable,
+                    Some("bidirectional") => TapeMode::Bidirectional,
+                    Some(other) => return Err(format!("Invalid value `{}` for `{}`", other, flag))
+                };
+            },
+            "--sandbox-region" => {
+                let value: &str = flag_value(flag, inline, &mut args)?;
+                let (low, high) = value.split_once(':').ok_or(format!("Invalid value `{}` for `{}`, expected LO:HI", value, flag))?;
+                options.sandbox = Some(parse_number(flag, low)?..parse_number(flag, high)?);
+            },
+            "--compat" => {
+                options.compat = match flag_value(flag, inline, &mut args)? {
+                    "dbfi" => Some(Compat::Dbfi),
+                    "bff" => Some(Compat::Bff),
+                    other => return Err(format!("Invalid value `{}` for `{}`, expected dbfi or bff", other, flag))
+                };
+            },
+            "--cell-modulus" => { options.cell_modulus = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--loop-cell" => { options.loop_cell = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--max-steps" => { options.max_steps = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--gas-limit" => { options.gas_limit = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--gas-cost" => {
+                let value: &str = flag_value(flag, inline, &mut args)?;
+                let (kind, cost) = value.split_once('=').ok_or(format!("Invalid value `{}` for `{}`, expected KIND=N", value, flag))?;
+                if !options.gas_costs.set(kind, parse_number(flag, cost)?) {
+                    return Err(format!("Unknown instruction kind `{}` for `{}`, expected one of {}", kind, flag, GasCosts::KINDS.join(", ")));
+                }
+            },
+            "--filter" => {
+                options.filter = match inline {
+                    None | Some("stream") => Some(FilterMode::Stream),
+                    Some("byte") => Some(FilterMode::Byte),
+                    Some(other) => return Err(format!("Invalid value `{}` for `{}`", other, flag))
+                };
+            },
+            "--truth-table" => { options.truth_table = true; },
+            "--emit-ir" => { options.emit_ir = true; },
+            "--asm" => { options.asm = true; },
+            "--emit-asm" => { options.emit_asm = true; },
+            "--post-mortem" => { options.post_mortem.get_or_insert(POST_MORTEM_DEFAULT); },
+            "--post-mortem-size" => {
+                options.post_mortem.get_or_insert(POST_MORTEM_DEFAULT).0 = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+            },
+            "--post-mortem-io" => {
+                options.post_mortem.get_or_insert(POST_MORTEM_DEFAULT).1 = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+            },
+            "--apply" if matches!(options.command, Command::Golf { .. }) => { options.command = Command::Golf { apply: true }; },
+            "--output-index" if matches!(options.command, Command::Slice { .. }) => {
+                output_index = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?);
+            },
+            "--emit-sliced" if matches!(options.command, Command::Slice { .. }) => {
+                emit_sliced = Some(flag_value(flag, inline, &mut args)?.to_string());
+            },
+            "--oracle" if matches!(options.command, Command::Reduce { .. }) => {
+                oracle = Some(Failure::parse(flag_value(flag, inline, &mut args)?).map_err(|err| format!("`--oracle`: {}", err))?);
+            },
+            "--max-tests" if matches!(options.command, Command::Reduce { .. }) => {
+                let tests: usize = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+                if tests == 0 {
+                    return Err("`--max-tests` has to be at least 1".to_string());
+                }
+                if let Command::Reduce { max_tests, .. } = &mut options.command { *max_tests = tests; }
+            },
+            "--parallel" if matches!(options.command, Command::RunManifest { .. }) => {
+                if let Command::RunManifest { parallel, .. } = &mut options.command { *parallel = true; }
+            },
+            "--slice" if matches!(options.command, Command::Multi { .. }) => {
+                let steps: u64 = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+                if steps == 0 {
+                    return Err("`--slice` has to be at least 1".to_string());
+                }
+                if let Command::Multi { slice, .. } = &mut options.command { *slice = steps; }
+            },
+            "--input-to" if matches!(options.command, Command::Multi { .. }) => {
+                let name: String = flag_value(flag, inline, &mut args)?.to_string();
+                if let Command::Multi { input_to, .. } = &mut options.command { *input_to = Some(name); }
+            },
+            "--jobs" if matches!(options.command, Command::RunManifest { .. }) => {
+                let threads: usize = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+                if threads == 0 {
+                    return Err("`--jobs` has to be at least 1".to_string());
+                }
+                if let Command::RunManifest { jobs, .. } = &mut options.command { *jobs = Some(threads); }
+            },
+            "--explain" => { options.explain.get_or_insert_with(Pacing::default); },
+            "--explain-limit" => {
+                options.explain.get_or_insert_with(Pacing::default).limit = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?);
+            },
+            "--explain-delay" => {
+                options.explain.get_or_insert_with(Pacing::default).delay_ms = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+            },
+            "--explain-step" => { options.explain.get_or_insert_with(Pacing::default).wait_for_enter = true; },
+            "--trace-source" => { options.trace_source.get_or_insert_with(Pacing::default); },
+            "--trace-limit" => {
+                options.trace_source.get_or_insert_with(Pacing::default).limit = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?);
+            },
+            "--trace-delay" => {
+                options.trace_source.get_or_insert_with(Pacing::default).delay_ms = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+            },
+            "--trace-step" => { options.trace_source.get_or_insert_with(Pacing::default).wait_for_enter = true; },
+            "--layout" => { options.layout = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--watch" => { options.watches.push(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--dump-tape" => { options.dump_tape = true; },
+            "--dump-csv" => { options.dump_csv = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--csv-range" => {
+                let value: &str = flag_value(flag, inline, &mut args)?;
+                let (low, high) = value.split_once(':').ok_or(format!("Invalid value `{}` for `{}`, expected LO:HI", value, flag))?;
+                options.csv_range = Some(parse_number(flag, low)?..parse_number(flag, high)?);
+            },
+            "--halt-on-output" => { options.halt_on_output = Some(parse_byte(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--emit-sentinel" => { options.emit_sentinel = true; },
+            "--sentinel-exit-code" => { options.sentinel_exit_code = parse_number(flag, flag_value(flag, inline, &mut args)?)?; },
+            "--stop-at" => {
+                let value: &str = flag_value(flag, inline, &mut args)?;
+                let (line, column) = value.split_once(':').ok_or(format!("Invalid value `{}` for `{}`, expected LINE:COL", value, flag))?;
+                options.stop_at = Some(StopAt::Source { line: parse_number(flag, line)?, column: parse_number(flag, column)? });
+            },
+            "--stop-at-instruction" => { options.stop_at = Some(StopAt::Instruction(parse_number(flag, flag_value(flag, inline, &mut args)?)?)); },
+            "--start-at" => { options.start_at = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--save-snapshot" => { options.save_snapshot = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--start-snapshot" => { options.start_snapshot = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--count-output" => { options.count_output = true; },
+            "--output-image" => { output_image = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--record-diffs" => { options.record_diffs = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--report" => { options.report = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--expect" => { options.expect = Some(Data::Inline(flag_value(flag, inline, &mut args)?.as_bytes().to_vec())); },
+            "--expect-file" => { options.expect = Some(Data::File(flag_value(flag, inline, &mut args)?.into())); },
+            "--width" => { width = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "-o" | "--output" => { options.outputs.push(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--console-encoding" => {
+                options.console_encoding = match flag_value(flag, inline, &mut args)? {
+                    "utf8" => ConsoleEncoding::Utf8,
+                    "cp437" => ConsoleEncoding::Cp437,
+                    "raw" => ConsoleEncoding::Raw,
+                    other => return Err(format!("Invalid value `{}` for `{}`", other, flag))
+                };
+            },
+            "--on-broken-pipe" => {
+                options.on_broken_pipe = match flag_value(flag, inline, &mut args)? {
+                    "exit" => BrokenPipe::Exit,
+                    "error" => BrokenPipe::Error,
+                    "ignore" => BrokenPipe::Ignore,
+                    other => return Err(format!("Invalid value `{}` for `{}`, expected exit, error or ignore", other, flag))
+                };
+            },
+            "--cost-model" => { options.cost_model = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--cycle-profile" => { options.cycle_profile = true; },
+            "--taint" => { options.taint = true; },
+            "--spec" => { options.spec = Some(flag_value(flag, inline, &mut args)?.to_string()); },
+            "--tape-checksum" => { options.tape_checksum = true; },
+            "--print-result" => { options.print_result = true; },
+            "--pointer-range" => { options.pointer_range = true; },
+            "--max-pointer-range" => { options.max_pointer_range = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--max-output" => { options.max_output = Some(parse_number(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--progress" => {
+                let millions: u64 = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+                if millions == 0 { return Err(format!("`{}` expects a positive number", flag)); }
+                options.progress = Some(millions.saturating_mul(1_000_000));
+            },
+            "--break-on-output" => { options.break_on_output = Some(parse_byte(flag, flag_value(flag, inline, &mut args)?)?); },
+            "--verify-determinism" => {
+                let runs: usize = parse_number(flag, flag_value(flag, inline, &mut args)?)?;
+                if runs < 2 {
+                    return Err("`--verify-determinism` has to be at least 2, a single run has nothing to compare with".to_string());
+                }
+                options.verify_determinism = Some(runs);
+            },
+            "--no-config" => { options.no_config = true; },
+            "--print-config" => { options.print_config = true; },
+            "-h" | "--help" => { return Err(String::new()); },
+            flag if flag.starts_with('-') && flag.len() > 1 => { return Err(format!("Unknown option `{}`", flag)); },
+            path if matches!(options.command, Command::Pipe { .. } | Command::Multi { .. }) => {
+                if let Command::Pipe { programs } | Command::Multi { programs, .. } = &mut options.command { programs.push(path.to_string()); }
+                filepath.get_or_insert_with(|| path.to_string());
+            },
+            path => {
+                if filepath.is_some() { return Err(format!("Unexpected argument `{}`", path)); }
+                filepath = Some(path.to_string());
+            }
+        }
+    }
+
+    let listing: bool = options.command == (Command::Examples { action: ExampleAction::List });
+    let completions: bool = matches!(options.command, Command::Completions { .. });
+    options.filepath = match (filepath, &options.spec) {
+        (Some(_), None) if listing => return Err("`examples list` takes no NAME".to_string()),
+        (Some(_), None) if completions => return Err("`completions` takes no FILENAME".to_string()),
+        (Some(filepath), None) => filepath,
+        (None, Some(_)) => String::new(),
+        (Some(_), Some(_)) => return Err("`--spec` describes the program itself, no FILENAME expected".to_string()),
+        (None, None) if options.print_config || listing || completions => String::new(),
+        (None, None) => return Err("Input filepath expected".to_string())
+    };
+
+    if let Command::Slice { .. } = options.command {
+        let output_index: u64 = output_index.ok_or("`slice` needs `--output-index N`")?;
+        options.command = Command::Slice { output_index, emit_sliced };
+    }
+    if let Command::Reduce { max_tests, .. } = options.command {
+        if oracle.is_none() && max_tests != REDUCE_TESTS {
+            return Err("`--max-tests` only applies to `reduce --oracle`".to_string());
+        }
+        options.command = Command::Reduce { oracle, max_tests };
+    }
+
+    options.output_image = match (output_image, width) {
+        (Some(_), Some(0)) => return Err("`--width` has to be at least 1".to_string()),
+        (Some(path), _) if path.ends_with(".png") && !cfg!(feature = "png") => {
+            return Err("PNG output needs the `png` feature, write a .pgm instead".to_string());
+        },
+        (Some(path), Some(width)) => Some((path, width)),
+        (Some(_), None) => return Err("`--output-image` needs `--width`".to_string()),
+        (None, Some(_)) => return Err("`--width` only applies to `--output-image`".to_string()),
+        (None, None) => None
+    };
+    if options.output_image.is_some() && options.count_output {
+        return Err("`--output-image` and `--count-output` both replace the output, give only one".to_string());
+    }
+    if options.cell_modulus.is_some_and(|modulus| !(2..=256).contains(&modulus)) {
+        return Err("`--cell-modulus` has to be between 2 and 256, cells are 8 bits wide".to_string());
+    }
+    if options.single_pass && options.cell_modulus.is_some_and(|modulus| 256 % modulus != 0) {
+        return Err("`--single-pass` folds runs of `+`/`-` modulo 256, `--cell-modulus` has to divide 256 with it".to_string());
+    }
+    if (options.constprop || options.bisect_passes) && options.cell_modulus.is_some_and(|modulus| 256 % modulus != 0) {
+        return Err("the optimizations count modulo 256, `--cell-modulus` has to divide 256 with them".to_string());
+    }
+    if matches!(options.command, Command::Reduce { oracle: None | Some(Failure::Diverges), .. }) && options.cell_modulus.is_some_and(|modulus| 256 % modulus != 0) {
+        return Err("`reduce` optimizes modulo 256 to minimize or look for divergences, `--cell-modulus` has to divide 256 with it".to_string());
+    }
+    if options.start_at.is_some() && (options.constprop || options.bisect_passes) {
+        return Err("constant propagation assumes the run starts at the first instruction, `--start-at` can't be used with `--constprop` or `--bisect-passes`".to_string());
+    }
+    if options.loop_cell.is_some() && (options.single_pass || options.constprop || options.bisect_passes) {
+        return Err("`--loop-cell` changes what loops like `[-]` do, the optimizations can't be used with it".to_string());
+    }
+    if options.loop_cell.is_some() && matches!(options.command, Command::Reduce { oracle: None | Some(Failure::Diverges), .. }) {
+        return Err("`--loop-cell` changes what loops like `[-]` do, `reduce` can't optimize or look for divergences with it".to_string());
+    }
+    if matches!(options.command, Command::Golf { .. }) && (options.alt_brackets || options.loop_cell.is_some() || options.cell_modulus.is_some_and(|modulus| modulus != 256)) {
+        return Err("`golf` rewrites plain brainfuck on 256-value cells, it can't be used with `--alt-brackets`, `--loop-cell` or `--cell-modulus`".to_string());
+    }
+    match &options.csv_range {
+        Some(_) if options.dump_csv.is_none() => return Err("`--csv-range` only applies to `--dump-csv`".to_string()),
+        Some(range) if range.is_empty() => return Err(format!("`--csv-range` {}:{} is an empty range", range.start, range.end)),
+        _ => {}
+    }
+    if options.sandbox.is_some() && options.tape_mode != TapeMode::Fixed {
+        return Err("`--sandbox-region` needs a fixed tape, it can't be combined with `--dynamic-tape`".to_string());
+    }
+    if matches!(options.input, Input::Random(_))
+        && (options.filter == Some(FilterMode::Stream) || options.bisect_passes || options.verify_determinism.is_some() || matches!(options.command, Command::Reduce { .. }))
+    {
+        return Err("`--random-input` never ends, it can't be read up front by `--filter`, `--bisect-passes`, `--verify-determinism` or `reduce`".to_string());
+    }
+    if options.expect.is_some()
+        && (!matches!(options.command, Command::Run | Command::Examples { .. }) || options.filter.is_some() || options.truth_table || options.verify_determinism.is_some())
+    {
+        return Err("`--expect`/`--expect-file` check the output of a single run".to_string());
+    }
+    if options.single_stepping() && options.input == Input::Stdin {
+        return Err("`--explain-step`/`--trace-step` read Enter from stdin, give the program its input with --input-file or --input-string".to_string());
+    }
+    Ok(options)
+}
+
+/// Name of the file holding per-project defaults, looked up from the program's directory upwards.
+pub const CONFIG_FILE: &str = ".brainfck.toml";
+/// Environment variable holding default flags, overriding the config file.
+pub const CONFIG_VARIABLE: &str = "BRAINFCK_OPTS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arity {
+    Switch, // `--flag`, `key = true`
+    Value, // `--flag VALUE`, `key = VALUE`
+    Optional // `--flag` or `--flag=VALUE`, `key = true` or `key = "VALUE"`
+}
+
+/// Flags which may have a default, in the order `--print-config` lists them.
+const CONFIG_FLAGS: &[(&str, Arity)] = &[
+    ("alt-brackets", Arity::Switch),
+    ("extensions", Arity::Switch),
+    ("max-nesting", Arity::Value),
+    ("single-pass", Arity::Switch),
+    ("no-auto-halt", Arity::Switch),
+    ("constprop", Arity::Switch),
+    ("warn", Arity::Switch),
+    ("explain-errors", Arity::Switch),
+    ("tape-size", Arity::Value),
+    ("dynamic-tape", Arity::Optional),
+    ("sandbox-region", Arity::Value),
+    ("compat", Arity::Value),
+    ("cell-modulus", Arity::Value),
+    ("max-steps", Arity::Value),
+    ("max-output", Arity::Value),
+    ("gas-limit", Arity::Value),
+    ("post-mortem", Arity::Switch),
+    ("post-mortem-size", Arity::Value),
+    ("post-mortem-io", Arity::Value),
+    ("explain-delay", Arity::Value),
+    ("trace-delay", Arity::Value),
+    ("layout", Arity::Value),
+    ("dump-tape", Arity::Switch),
+    ("tape-checksum", Arity::Switch),
+    ("print-result", Arity::Switch),
+    ("count-output", Arity::Switch),
+    ("progress", Arity::Value),
+    ("filter", Arity::Optional),
+    ("console-encoding", Arity::Value),
+    ("on-broken-pipe", Arity::Value),
+    ("sentinel-exit-code", Arity::Value)
+];
+
+/// Where a setting came from, later ones override earlier ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File(PathBuf),
+    Environment,
+    CommandLine
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Environment => write!(f, "{}", CONFIG_VARIABLE),
+            Source::CommandLine => write!(f, "command line")
+        }
+    }
+}
+
+/// One flag with a default, `value` is `None` for a switch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Setting {
+    pub flag: &'static str,
+    pub value: Option<String>,
+    pub source: Source
+}
+
+impl Setting {
+    fn to_arg(&self) -> String {
+        match &self.value {
+            Some(value) => format!("--{}={}", self.flag, value),
+            None => format!("--{}", self.flag)
+        }
+    }
+}
+
+/// Options after merging the defaults in, plus what went into them.
+#[derive(Debug)]
+pub struct Loaded {
+    pub options: Options,
+    pub settings: Vec<Setting>, // in precedence order, the last one of a flag wins
+    pub warnings: Vec<String> // unknown keys and malformed lines, which are skipped
+}
+
+fn config_flag(name: &str) -> Option<(&'static str, Arity)> {
+    CONFIG_FLAGS.iter().copied().find(|&(flag, _)| flag == name)
+}
+
+/// Part of a config file line before its `#` comment, `#` inside quotes doesn't count.
+fn strip_config_comment(line: &str) -> &str {
+    let mut quoted: bool = false;
+    for (offset, c) in line.char_indices() {
+        match c {
+            '"' => { quoted = !quoted; },
+            '#' if !quoted => return &line[..offset],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Settings of a config file, relative `layout` paths are taken relative to the file.
+fn parse_config(text: &str, path: &Path) -> (Vec<Setting>, Vec<String>) {
+    let mut settings: Vec<Setting> = vec![];
+    let mut warnings: Vec<String> = vec![];
+    let warn = |warnings: &mut Vec<String>, number: usize, message: String| {
+        warnings.push(format!("{}, line {}: {}, ignored", path.display(), number + 1, message));
+    };
+
+    for (number, line) in text.lines().enumerate() {
+        let line: &str = strip_config_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            warn(&mut warnings, number, "expected `key = value`".to_string());
+            continue;
+        };
+        let (key, value): (&str, &str) = (key.trim(), value.trim());
+        let Some((flag, arity)) = config_flag(&key.replace('_', "-")) else {
+            warn(&mut warnings, number, format!("unknown key `{}`", key));
+            continue;
+        };
+
+        let text: Option<&str> = value.strip_prefix('"').and_then(|value| value.strip_suffix('"'));
+        let value: Option<String> = match (arity, value, text) {
+            (Arity::Switch | Arity::Optional, "false", _) => continue,
+            (Arity::Switch | Arity::Optional, "true", _) => None,
+            (Arity::Value | Arity::Optional, _, Some(text)) if flag == "layout" && Path::new(text).is_relative() => {
+                Some(path.parent().unwrap_or(Path::new("")).join(text).display().to_string())
+            },
+            (Arity::Value | Arity::Optional, _, Some(text)) => Some(text.to_string()),
+            (Arity::Value, number, None) if !number.is_empty() && number.bytes().all(|byte| byte.is_ascii_digit()) => Some(number.to_string()),
+            (Arity::Switch, _, _) => {
+                warn(&mut warnings, number, format!("`{}` expects true or false", key));
+                continue;
+            },
+            _ => {
+                warn(&mut warnings, number, format!("`{}` expects a number or a quoted string", key));
+                continue;
+            }
+        };
+        settings.push(Setting { flag, value, source: Source::File(path.to_path_buf()) });
+    }
+
+    (settings, warnings)
+}
+
+/// Settings among command line style `args`, anything which can't have a default is skipped and
+/// reported through `unknown`.
+fn parse_flags(args: &[String], source: Source, unknown: &mut dyn FnMut(&str)) -> Vec<Setting> {
+    let mut settings: Vec<Setting> = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(name) = arg.strip_prefix("--") else {
+            unknown(arg);
+            continue;
+        };
+        let (name, inline): (&str, Option<&str>) = match name.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (name, None)
+        };
+        let value: Option<String> = match config_flag(name) {
+            Some((_, Arity::Value)) => inline.map(str::to_string).or_else(|| args.next().cloned()),
+            Some(_) => inline.map(str::to_string),
+            None => {
+                unknown(arg);
+                continue;
+            }
+        };
+        if let Some((flag, _)) = config_flag(name) {
+            settings.push(Setting { flag, value, source: source.clone() });
+        }
+    }
+    settings
+}
+
+/// Nearest `.brainfck.toml` in `directory` or one of its parents.
+fn find_config(directory: &Path) -> Option<PathBuf> {
+    let directory: PathBuf = directory.canonicalize().ok()?;
+    directory.ancestors().map(|directory| directory.join(CONFIG_FILE)).find(|path| path.is_file())
+}
+
+/// [`parse_args`] with the defaults of `.brainfck.toml` and `BRAINFCK_OPTS` merged in underneath
+/// `args`, unless `args` has `--no-config`.
+pub fn load(args: &[String]) -> Result<Loaded, String> {
+    let direct: Options = parse_args(args)?;
+    let mut warnings: Vec<String> = vec![];
+    let mut settings: Vec<Setting> = vec![];
+
+    if !direct.no_config {
+        let program: &Path = Path::new(&direct.filepath);
+        let directory: &Path = match program.parent() {
+            Some(parent) if !direct.filepath.is_empty() && !parent.as_os_str().is_empty() => parent,
+            _ => Path::new(".")
+        };
+        if let Some(path) = find_config(directory) {
+            let text: String = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            let (file_settings, file_warnings) = parse_config(&text, &path);
+            settings.extend(file_settings);
+            warnings.extend(file_warnings);
+        }
+        if let Ok(variable) = std::env::var(CONFIG_VARIABLE) {
+            let tokens: Vec<String> = variable.split_whitespace().map(str::to_string).collect();
+            settings.extend(parse_flags(&tokens, Source::Environment, &mut |arg: &str| {
+                warnings.push(format!("{}: `{}` can't be given a default, ignored", CONFIG_VARIABLE, arg));
+            }));
+        }
+    }
+
+    let mut options: Options = if settings.is_empty() {
+        direct
+    } else {
+        // defaults go between the command word and the rest, so the command line overrides them
+        let command_words: usize = match direct.command {
+            Command::Run => 0,
+            Command::Examples { .. } | Command::Completions { .. } => 2,
+            _ => 1
+        };
+        let mut merged: Vec<String> = args[..command_words].to_vec();
+        merged.extend(settings.iter().map(Setting::to_arg));
+        merged.extend_from_slice(&args[command_words..]);
+        parse_args(&merged).map_err(|err| if err.is_empty() { err } else { format!("{} (with the defaults from {})", err, settings_sources(&settings)) })?
+    };
+    settings.extend(parse_flags(args, Source::CommandLine, &mut |_| {}));
+    warnings.extend(apply_compat(&mut options));
+
+    Ok(Loaded { options, settings, warnings })
+}
+
+/// Sets the tape of the `--compat` preset unless the options already chose one, which then wins
+/// with a notice. The cells and EOF behavior every preset wants are the defaults.
+fn apply_compat(options: &mut Options) -> Vec<String> {
+    let Some(compat) = options.compat else {
+        return vec![];
+    };
+    let overridden: Option<&str> = if options.sandbox.is_some() {
+        Some("--sandbox-region")
+    } else if options.tape_mode != TapeMode::Fixed && options.tape_mode != compat.tape_mode() {
+        Some("--dynamic-tape")
+    } else {
+        None
+    };
+
+    match overridden {
+        Some(flag) => vec![format!("`{}` overrides the tape of `--compat={}`", flag, compat)],
+        None => {
+            options.tape_mode = compat.tape_mode();
+            vec![]
+        }
+    }
+}
+
+fn settings_sources(settings: &[Setting]) -> String {
+    let mut sources: Vec<String> = settings.iter().map(|setting| setting.source.to_string()).collect();
+    sources.dedup();
+    sources.join(" and ")
+}
+
+/// What `--print-config` prints: the winning setting of every flag which has one, with its source.
+pub fn describe_config(settings: &[Setting]) -> String {
+    let mut description: String = String::new();
+    for &(flag, _) in CONFIG_FLAGS {
+        if let Some(setting) = settings.iter().rev().find(|setting| setting.flag == flag) {
+            let assignment: String = match &setting.value {
+                Some(value) => format!("{} = {}", flag, value),
+                None => flag.to_string()
+            };
+            description.push_str(&format!("{:<32} # {}\n", assignment, setting.source));
+        }
+    }
+    if description.is_empty() {
+        description.push_str("built-in defaults only\n");
+    }
+    description
+}
+
@@ -0,0 +1,298 @@
+//! Shell completion scripts, see the `completions` command.
+//!
+//! The commands and flags are read from [`USAGE`], so the scripts stay in step with the help text:
+//! every flag the synopsis or the options list mentions, its value (a file for PATH-like values, the
+//! listed choices for `a|b|c`, anything otherwise) and the first line of its description.
+
+use crate::cli::{Shell, USAGE};
+
+/// Value placeholders completed with file names.
+const FILE_VALUES: [&str; 4] = ["PATH", "FILE", "FILENAME", "MANIFEST"];
+
+/// Words following a command word, fixed ones only.
+const COMMAND_WORDS: [(&str, &str); 2] = [("examples", "list show run"), ("completions", "bash zsh fish")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Switch,
+    File,
+    Choices(Vec<String>),
+    Any
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Flag {
+    short: Option<char>,
+    long: String, // without the dashes
+    value: Value,
+    description: String
+}
+
+fn value_of(placeholder: Option<&str>) -> Value {
+    match placeholder {
+        None => Value::Switch,
+        Some(placeholder) if FILE_VALUES.contains(&placeholder) => Value::File,
+        Some(placeholder) if placeholder.contains('|') => {
+            let choices: Vec<String> = placeholder.split('|').filter(|choice| !FILE_VALUES.contains(choice)).map(str::to_string).collect();
+            if placeholder.split('|').any(|choice| FILE_VALUES.contains(&choice)) { Value::File } else { Value::Choices(choices) }
+        },
+        Some(_) => Value::Any
+    }
+}
+
+/// A flag spec like `-o, --output PATH`, `--compat=dbfi|bff` or `--filter[=stream|byte]`. An
+/// optional value only goes with `=`, so such a flag completes like a switch.
+fn parse_spec(spec: &str, description: &str) -> Option<Flag> {
+    let mut short: Option<char> = None;
+    let mut long: Option<String> = None;
+    let mut placeholder: Option<&str> = None;
+    for word in spec.split_whitespace() {
+        let word: &str = word.trim_end_matches(',');
+        if let Some(name) = word.strip_prefix("--") {
+            let (name, inline) = match (name.split_once("[="), name.split_once('=')) {
+                (Some((name, _)), _) => (name, None),
+                (None, Some((name, value))) => (name, Some(value)),
+                (None, None) => (name, None)
+            };
+            long = Some(name.to_string());
+            placeholder = inline;
+        } else if let Some(name) = word.strip_prefix('-') {
+            short = name.chars().next();
+        } else {
+            placeholder = Some(word);
+        }
+    }
+    Some(Flag { short, long: long?, value: value_of(placeholder), description: description.to_string() })
+}
+
+/// Every flag of [`USAGE`], in the order it lists them, and the commands.
+fn usage_entries() -> (Vec<String>, Vec<Flag>) {
+    let mut commands: Vec<String> = vec![];
+    let mut flags: Vec<Flag> = vec![];
+    let mut section: &str = "synopsis";
+    let lines: Vec<&str> = USAGE.lines().collect();
+
+    for (index, line) in lines.iter().enumerate() {
+        if line.ends_with(':') && !line.starts_with(' ') {
+            section = line;
+            continue;
+        }
+        match section {
+            "synopsis" => {
+                // `[--parallel | --jobs N]`, `--output-index N`, ...
+                let words: Vec<&str> = line.split_whitespace().collect();
+                for (at, word) in words.iter().enumerate() {
+                    let Some(flag) = word.strip_prefix('[').or(Some(word)).filter(|flag| flag.starts_with("--")) else {
+                        continue;
+                    };
+                    // a value belongs to the flag unless a `]` closes it first
+                    let next: Option<&str> = words.get(at + 1).filter(|_| !flag.ends_with(']')).map(|next| next.trim_end_matches(']'));
+                    let value: Option<&str> = next.filter(|next| !next.is_empty() && next.chars().all(|c| c.is_ascii_uppercase()));
+                    let spec: String = format!("{} {}", flag.trim_end_matches(']'), value.unwrap_or(""));
+                    flags.extend(parse_spec(&spec, ""));
+                }
+            },
+            "commands:" if line.len() > 4 && !line[4..].starts_with(' ') => {
+                commands.extend(line.split_whitespace().next().map(str::to_string));
+            },
+            "options:" if line.trim_start().starts_with('-') && line.starts_with("    -") => {
+                // the description starts in column 22, or on the next line after a long spec (a
+                // single word there is the value of the flag)
+                let (spec, description): (&str, &str) = match line.get(21..23) {
+                    Some(gap) if gap.starts_with(' ') && !gap.ends_with(' ') && line[22..].contains(' ') => (&line[4..21], &line[22..]),
+                    _ => (&line[4..], lines.get(index + 1).map_or("", |next| next.trim()))
+                };
+                flags.extend(parse_spec(spec.trim(), description));
+            },
+            _ => {}
+        }
+    }
+
+    // the options list describes a flag better than the synopsis
+    let mut unique: Vec<Flag> = vec![];
+    for flag in flags.into_iter().rev() {
+        if let Some(known) = unique.iter_mut().find(|known| known.long == flag.long) {
+            if known.description.is_empty() { *known = flag; }
+        } else {
+            unique.push(flag);
+        }
+    }
+    unique.reverse();
+    (commands, unique)
+}
+
+/// The completion script for `shell`.
+pub fn completion_script(shell: Shell) -> String {
+    let (commands, flags) = usage_entries();
+    match shell {
+        Shell::Bash => bash(&commands, &flags),
+        Shell::Zsh => zsh(&commands, &flags),
+        Shell::Fish => fish(&commands, &flags)
+    }
+}
+
+fn names(flag: &Flag) -> Vec<String> {
+    flag.short.map(|short| format!("-{}", short)).into_iter().chain([format!("--{}", flag.long)]).collect()
+}
+
+fn bash(commands: &[String], flags: &[Flag]) -> String {
+    let matching = |wanted: fn(&Value) -> bool| -> String {
+        flags.iter().filter(|flag| wanted(&flag.value)).flat_map(names).collect::<Vec<String>>().join("|")
+    };
+    let mut cases: String = String::new();
+    cases.push_str(&format!("        {})\n            COMPREPLY=($(compgen -f -- \"$cur\")); return ;;\n", matching(|value| *value == Value::File)));
+    for flag in flags {
+        if let Value::Choices(choices) = &flag.value {
+            cases.push_str(&format!("        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;\n", names(flag).join("|"), choices.join(" ")));
+        }
+    }
+    cases.push_str(&format!("        {})\n            return ;;\n", matching(|value| *value == Value::Any)));
+    for (command, words) in COMMAND_WORDS {
+        cases.push_str(&format!("        {})\n            [[ $COMP_CWORD -eq 2 ]] && COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) && return ;;\n", command, words));
+    }
+    let all: String = flags.iter().flat_map(names).collect::<Vec<String>>().join(" ");
+
+    format!(
+        "# bash completion for bf_interpreter, generated by `bf_interpreter completions bash`
+_bf_interpreter() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    case \"$prev\" in
+{}    esac
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))
+    elif [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+}}
+complete -o filenames -F _bf_interpreter bf_interpreter
+",
+        cases, all, commands.join(" ")
+    )
+}
+
+fn zsh(commands: &[String], flags: &[Flag]) -> String {
+    // `_arguments` specs can't hold a bare `[`, `]` or `:` in a description, nor `'` inside the quotes
+    let describe = |text: &str| -> String { text.replace('\'', "'\\''").replace('[', "(").replace(']', ")").replace(':', "\\:") };
+    let mut specs: String = String::new();
+    for flag in flags {
+        let action: String = match &flag.value {
+            Value::Switch => String::new(),
+            Value::File => ":file:_files".to_string(),
+            Value::Choices(choices) => format!(":value:({})", choices.join(" ")),
+            Value::Any => ":value: ".to_string()
+        };
+        let description: String = if flag.description.is_empty() { String::new() } else { format!("[{}]", describe(&flag.description)) };
+        match flag.short {
+            Some(short) => specs.push_str(&format!(
+                "    {{-{0},--{1}}}'{2}{3}' \\\n", short, flag.long, description, action
+            )),
+            None => specs.push_str(&format!("    '--{}{}{}' \\\n", flag.long, description, action))
+        }
+    }
+    let words: String = COMMAND_WORDS.iter().map(|(command, words)| format!("        {}) _values word {} ;;\n", command, words)).collect();
+
+    format!(
+        "#compdef bf_interpreter
+# zsh completion for bf_interpreter, generated by `bf_interpreter completions zsh`
+_bf_interpreter() {{
+  local state
+  _arguments -s \\
+{}    '1: :->first' \\
+    '*:file:_files'
+  case $state in
+    first) _alternative 'commands:command:({})' 'files:file:_files' ;;
+  esac
+  if (( CURRENT == 3 )); then
+    case $words[2] in
+{}    esac
+  fi
+}}
+_bf_interpreter \"$@\"
+",
+        specs, commands.join(" "), words
+    )
+}
+
+fn fish(commands: &[String], flags: &[Flag]) -> String {
+    let quote = |text: &str| -> String { format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'")) };
+    let mut script: String = String::from("# fish completion for bf_interpreter, generated by `bf_interpreter completions fish`\n");
+    script.push_str(&format!("complete -c bf_interpreter -n __fish_use_subcommand -a {}\n", quote(&commands.join(" "))));
+    for (command, words) in COMMAND_WORDS {
+        script.push_str(&format!("complete -c bf_interpreter -n '__fish_seen_subcommand_from {}' -x -a {}\n", command, quote(words)));
+    }
+    for flag in flags {
+        let mut line: String = String::from("complete -c bf_interpreter");
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", flag.long));
+        match &flag.value {
+            Value::Switch => {},
+            Value::File => line.push_str(" -r -F"),
+            Value::Choices(choices) => line.push_str(&format!(" -x -a {}", quote(&choices.join(" ")))),
+            Value::Any => line.push_str(" -x")
+        }
+        if !flag.description.is_empty() {
+            line.push_str(&format!(" -d {}", quote(&flag.description)));
+        }
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every flag `parse_args` matches on, read from its source.
+    fn accepted_flags() -> Vec<String> {
+        let mut flags: Vec<String> = vec![];
+        for line in include_str!("cli.rs").lines().map(str::trim_start).filter(|line| line.starts_with("\"-")) {
+            let Some(arm) = line.split(" =>").next().map(|arm| arm.split(" if ").next().unwrap_or(arm)) else { continue };
+            let names: Vec<&str> = arm.split(" | ").map(|name| name.trim_matches('"')).collect();
+            if names.iter().all(|name| name.starts_with('-') && name.chars().skip(1).all(|c| c == '-' || c.is_ascii_lowercase())) {
+                flags.extend(names.iter().map(|name| name.to_string()));
+            }
+        }
+        flags
+    }
+
+    #[test]
+    fn every_accepted_flag_is_completed() {
+        let accepted: Vec<String> = accepted_flags();
+        assert!(accepted.len() > 80 && accepted.contains(&"-o".to_string()) && accepted.contains(&"--trace-step".to_string()), "{:?}", accepted);
+
+        let (_, flags) = usage_entries();
+        let listed: Vec<String> = flags.iter().flat_map(names).collect();
+        let missing: Vec<&String> = accepted.iter().filter(|flag| !listed.contains(flag)).collect();
+        assert!(missing.is_empty(), "not in USAGE: {:?}", missing);
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script: String = completion_script(shell);
+            let words: Vec<&str> = script.split(|c: char| !(c == '-' || c.is_ascii_alphanumeric())).collect();
+            let missing: Vec<&String> = accepted.iter().filter(|flag| match (shell, flag.strip_prefix("--")) {
+                // fish takes the names without their dashes, after `-l` and `-s`
+                (Shell::Fish, Some(long)) => !script.lines().any(|line| line.split(' ').skip_while(|word| *word != "-l").nth(1) == Some(long)),
+                (Shell::Fish, None) => !script.lines().any(|line| line.split(' ').skip_while(|word| *word != "-s").nth(1) == Some(&flag[1..])),
+                _ => !words.contains(&flag.as_str())
+            }).collect();
+            assert!(missing.is_empty(), "not in the {:?} script: {:?}", shell, missing);
+        }
+    }
+
+    #[test]
+    fn flags_from_the_options_list_keep_their_value_and_description() {
+        let (commands, flags) = usage_entries();
+        assert!(commands.contains(&"golf".to_string()) && commands.contains(&"completions".to_string()), "{:?}", commands);
+        let flag = |long: &str| flags.iter().find(|flag| flag.long == long).unwrap_or_else(|| panic!("no --{}", long)).clone();
+        assert_eq!(flag("trace-limit"), Flag { short: None, long: "trace-limit".to_string(), value: Value::Any, description: "stop tracing after N steps, the run then continues silently".to_string() });
+        assert_eq!((flag("trace-delay").value, flag("trace-delay").description), (Value::Any, "pause between traced steps (default 250)".to_string()));
+        assert_eq!(flag("trace-step").value, Value::Switch);
+        assert_eq!(flag("output").short, Some('o'));
+        assert_eq!(flag("layout").value, Value::File);
+        assert_eq!(flag("on-broken-pipe").value, Value::Choices(vec!["exit".to_string(), "error".to_string(), "ignore".to_string()]));
+    }
+}
@@ -0,0 +1,310 @@
+//! Getting output bytes onto a Windows console unchanged, see `--console-encoding`.
+//!
+//! A Windows console reinterprets the bytes written to it through its output code page, so output meant
+//! for a CP437 or UTF-8 terminal comes out garbled even though the same bytes are fine in a file. The
+//! console calls sit behind [`Console`], on other systems [`prepare_stdout`] does nothing.
+
+use std::io::{self, Write};
+
+/// The Windows code page number of UTF-8.
+pub const UTF8_CODE_PAGE: u32 = 65001;
+
+/// How output reaches a Windows console.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsoleEncoding {
+    Utf8, // switch the console to UTF-8 for the run
+    Cp437, // decode the output as CP437 and write it as wide characters
+    #[default]
+    Raw // leave it to the console's code page
+}
+
+/// The characters of the CP437 bytes 0x80..=0xFF, the lower half is ASCII.
+const CP437_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å', // 0x80
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ', // 0x90
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»', // 0xA0
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐', // 0xB0
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧', // 0xC0
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀', // 0xD0
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩', // 0xE0
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}', // 0xF0
+];
+
+/// The character CP437 draws for `byte`.
+pub fn cp437_char(byte: u8) -> char {
+    match byte {
+        0..=0x7F => byte as char,
+        _ => CP437_HIGH[byte as usize - 0x80]
+    }
+}
+
+/// The console calls `--console-encoding` needs.
+pub trait Console {
+    fn output_code_page(&self) -> u32;
+    fn set_output_code_page(&mut self, code_page: u32) -> io::Result<()>;
+    /// Writes all of `text` (UTF-16) to the console.
+    fn write_wide(&mut self, text: &[u16]) -> io::Result<()>;
+}
+
+/// Switches a console to another output code page and back to the old one when dropped.
+pub struct CodePageGuard<C: Console> {
+    console: C,
+    saved: u32
+}
+
+impl<C: Console> CodePageGuard<C> {
+    pub fn set(mut console: C, code_page: u32) -> io::Result<Self> {
+        let saved: u32 = console.output_code_page();
+        console.set_output_code_page(code_page)?;
+        Ok(Self { console, saved })
+    }
+
+    /// The code page the console goes back to.
+    pub fn saved(&self) -> u32 {
+        self.saved
+    }
+}
+
+impl<C: Console> Drop for CodePageGuard<C> {
+    fn drop(&mut self) {
+        let _ = self.console.set_output_code_page(self.saved);
+    }
+}
+
+/// Writes bytes to a console as the CP437 characters they stand for.
+pub struct Cp437Writer<C: Console> {
+    console: C,
+    wide: Vec<u16>
+}
+
+impl<C: Console> Cp437Writer<C> {
+    pub fn new(console: C) -> Self {
+        Self { console, wide: vec![] }
+    }
+}
+
+impl<C: Console> Write for Cp437Writer<C> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        // every CP437 character is in the BMP, one byte makes one UTF-16 unit
+        self.wide.clear();
+        self.wide.extend(bytes.iter().map(|&byte| cp437_char(byte) as u16));
+        self.console.write_wide(&self.wide)?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+pub use self::windows::WindowsConsole as SystemConsole;
+
+/// Stands in for the Windows console elsewhere, there never is one.
+#[cfg(not(windows))]
+pub enum SystemConsole {}
+
+#[cfg(not(windows))]
+impl Console for SystemConsole {
+    fn output_code_page(&self) -> u32 {
+        match *self {}
+    }
+
+    fn set_output_code_page(&mut self, _: u32) -> io::Result<()> {
+        match *self {}
+    }
+
+    fn write_wide(&mut self, _: &[u16]) -> io::Result<()> {
+        match *self {}
+    }
+}
+
+/// Sets up stdout for `encoding`: `Utf8` returns the guard restoring the code page once the run is
+/// over, `Cp437` the writer the output has to go through instead of stdout. Both are `None` when stdout
+/// isn't a Windows console or the console refuses the code page.
+#[cfg_attr(not(windows), allow(unused_variables))]
+pub fn prepare_stdout(encoding: ConsoleEncoding) -> (Option<CodePageGuard<SystemConsole>>, Option<Cp437Writer<SystemConsole>>) {
+    #[cfg(windows)]
+    if let Some(console) = SystemConsole::stdout() {
+        return match encoding {
+            ConsoleEncoding::Utf8 => (CodePageGuard::set(console, UTF8_CODE_PAGE).ok(), None),
+            ConsoleEncoding::Cp437 => (None, Some(Cp437Writer::new(console))),
+            ConsoleEncoding::Raw => (None, None)
+        };
+    }
+    (None, None)
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::io;
+
+    use super::Console;
+
+    type Handle = *mut c_void;
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(which: u32) -> Handle;
+        fn GetConsoleMode(console: Handle, mode: *mut u32) -> i32;
+        fn GetConsoleOutputCP() -> u32;
+        fn SetConsoleOutputCP(code_page: u32) -> i32;
+        fn WriteConsoleW(console: Handle, buffer: *const u16, length: u32, written: *mut u32, reserved: *mut c_void) -> i32;
+    }
+
+    /// The console stdout is attached to.
+    #[derive(Debug, Clone, Copy)]
+    pub struct WindowsConsole {
+        handle: Handle
+    }
+
+    // the standard handle belongs to the process, any thread may write to it
+    unsafe impl Send for WindowsConsole {}
+
+    impl WindowsConsole {
+        /// `None` when stdout is redirected away from a console.
+        pub fn stdout() -> Option<Self> {
+            let mut mode: u32 = 0;
+            // SAFETY: plain Win32 calls, `mode` outlives them
+            let handle: Handle = unsafe { GetStdHandle(STD_OUTPUT_HANDLE) };
+            (unsafe { GetConsoleMode(handle, &mut mode) } != 0).then_some(Self { handle })
+        }
+    }
+
+    impl Console for WindowsConsole {
+        fn output_code_page(&self) -> u32 {
+            // SAFETY: no arguments, no preconditions
+            unsafe { GetConsoleOutputCP() }
+        }
+
+        fn set_output_code_page(&mut self, code_page: u32) -> io::Result<()> {
+            // SAFETY: no preconditions, a bad code page only makes the call fail
+            match unsafe { SetConsoleOutputCP(code_page) } {
+                0 => Err(io::Error::last_os_error()),
+                _ => Ok(())
+            }
+        }
+
+        fn write_wide(&mut self, mut text: &[u16]) -> io::Result<()> {
+            while !text.is_empty() {
+                let length: u32 = text.len().min(u32::MAX as usize) as u32;
+                let mut written: u32 = 0;
+                // SAFETY: `buffer` is valid for `length` units, `written` outlives the call
+                if unsafe { WriteConsoleW(self.handle, text.as_ptr(), length, &mut written, std::ptr::null_mut()) } == 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if written == 0 {
+                    return Err(io::Error::from(io::ErrorKind::WriteZero));
+                }
+                text = &text[written as usize..];
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// What a [`MockConsole`] was asked to do.
+    #[derive(Debug, Default)]
+    struct State {
+        code_page: u32,
+        switches: Vec<u32>, // every code page set, in order
+        refuse: bool, // fail every call changing something
+        text: String // written so far
+    }
+
+    #[derive(Clone, Default)]
+    struct MockConsole(Rc<RefCell<State>>);
+
+    impl Console for MockConsole {
+        fn output_code_page(&self) -> u32 {
+            self.0.borrow().code_page
+        }
+
+        fn set_output_code_page(&mut self, code_page: u32) -> io::Result<()> {
+            let mut state = self.0.borrow_mut();
+            if state.refuse {
+                return Err(io::Error::other("refused"));
+            }
+            state.code_page = code_page;
+            state.switches.push(code_page);
+            Ok(())
+        }
+
+        fn write_wide(&mut self, text: &[u16]) -> io::Result<()> {
+            let mut state = self.0.borrow_mut();
+            if state.refuse {
+                return Err(io::Error::other("refused"));
+            }
+            state.text.push_str(&String::from_utf16(text).unwrap());
+            Ok(())
+        }
+    }
+
+    fn console(code_page: u32) -> MockConsole {
+        MockConsole(Rc::new(RefCell::new(State { code_page, ..State::default() })))
+    }
+
+    #[test]
+    fn cp437_keeps_ascii_and_draws_the_rest() {
+        assert!((0..=0x7F).all(|byte: u8| cp437_char(byte) == byte as char));
+        let known: [(u8, char); 8] = [(0x80, 'Ç'), (0x9B, '¢'), (0xB0, '░'), (0xC9, '╔'), (0xDB, '█'), (0xE1, 'ß'), (0xFE, '■'), (0xFF, '\u{a0}')];
+        for (byte, c) in known {
+            assert_eq!(cp437_char(byte), c, "{:#04x}", byte);
+        }
+
+        let mut high: Vec<char> = (0x80..=0xFF).map(cp437_char).collect();
+        assert!(high.iter().all(|&c| !c.is_ascii() && (c as u32) < 0x10000), "one UTF-16 unit each");
+        high.sort_unstable();
+        high.dedup();
+        assert_eq!(high.len(), 128, "no two bytes draw the same character");
+    }
+
+    #[test]
+    fn the_writer_sends_the_characters_as_utf16() {
+        let mock: MockConsole = console(437);
+        let mut writer: Cp437Writer<MockConsole> = Cp437Writer::new(mock.clone());
+        assert_eq!(writer.write(b"\xc9\xcd\xbb ok\n").unwrap(), 7);
+        writer.write_all(b"\xc8\xcd\xbc").unwrap();
+        assert_eq!(mock.0.borrow().text, "╔═╗ ok\n╚═╝");
+
+        mock.0.borrow_mut().refuse = true;
+        assert!(writer.write(b"x").is_err());
+    }
+
+    #[test]
+    fn the_guard_restores_the_code_page() {
+        let mock: MockConsole = console(850);
+        let guard: CodePageGuard<MockConsole> = CodePageGuard::set(mock.clone(), UTF8_CODE_PAGE).unwrap();
+        assert_eq!((guard.saved(), mock.output_code_page()), (850, UTF8_CODE_PAGE));
+        drop(guard);
+        assert_eq!(mock.0.borrow().switches, [UTF8_CODE_PAGE, 850]);
+
+        // unwinding out of the run drops it all the same
+        let mock: MockConsole = console(437);
+        let run = std::panic::AssertUnwindSafe(|| {
+            let _guard: CodePageGuard<MockConsole> = CodePageGuard::set(mock.clone(), UTF8_CODE_PAGE).unwrap();
+            panic!("the run failed");
+        });
+        assert!(std::panic::catch_unwind(run).is_err());
+        assert_eq!((mock.output_code_page(), &mock.0.borrow().switches[..]), (437, &[UTF8_CODE_PAGE, 437][..]));
+    }
+
+    #[test]
+    fn a_refused_code_page_leaves_the_console_alone() {
+        let mock: MockConsole = console(437);
+        mock.0.borrow_mut().refuse = true;
+        assert!(CodePageGuard::set(mock.clone(), UTF8_CODE_PAGE).is_err());
+        assert_eq!(mock.output_code_page(), 437);
+        assert!(mock.0.borrow().switches.is_empty());
+    }
+}
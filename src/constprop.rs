@@ -0,0 +1,143 @@
+//! Constant propagation through straight-line code.
+//!
+//! Tracks which cells hold a known value, keyed by their offset from the pointer at the start of the
+//! program. The domain is deliberately tiny, a cell is either a known constant or unknown:
+//!
+//! - the run starts on a zeroed tape, so every cell is known to be 0
+//! - `+`/`-`/`Add` and `[-]` update a known cell, `,` makes the current cell unknown
+//! - the body of a loop may run any number of times, so entering one forgets everything
+//! - leaving a loop forgets everything but the current cell, which is 0
+//!
+//! With that, a loop whose guard is known to be 0 is never entered and is removed, and so is a clear
+//! of a cell which is already 0. The classic cases are the comment loop at the start of a program,
+//! a loop right after another loop (`[..][..]`) and doubled clears.
+
+use std::collections::HashMap;
+
+use crate::parser::{Instruction, Program};
+
+/// Known cell values, by offset relative to where the pointer started.
+struct State {
+    offset: isize,
+    cells: HashMap<isize, Option<u8>>, // `None` is unknown
+    rest_zero: bool // cells missing from `cells` are 0 (true) or unknown (false)
+}
+
+impl State {
+    fn current(&self) -> Option<u8> {
+        match self.cells.get(&self.offset) {
+            Some(&value) => value,
+            None => self.rest_zero.then_some(0)
+        }
+    }
+
+    fn set_current(&mut self, value: Option<u8>) {
+        self.cells.insert(self.offset, value);
+    }
+
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.rest_zero = false;
+    }
+}
+
+/// `program` without the loops and clears constant propagation proves dead. Only valid for runs which
+/// start at the first instruction on a zeroed tape.
+pub fn propagate(program: &Program) -> Program {
+    let instructions: &[Instruction] = program.instructions();
+    let mut kept: Vec<(Instruction, usize)> = Vec::with_capacity(instructions.len());
+    let mut state: State = State { offset: 0, cells: HashMap::new(), rest_zero: true };
+
+    let mut index: usize = 0;
+    while index < instructions.len() {
+        let instruction: Instruction = instructions[index];
+        match instruction {
+            Instruction::Begin if state.current() == Some(0) => {
+                // never entered, skip past the matching `End`
+                index = program.jumps()[index];
+                continue;
+            },
+            Instruction::SetZero if state.current() == Some(0) => {
+                index += 1;
+                continue;
+            },
+
+            Instruction::IncrementPointer => { state.offset += 1; },
+            Instruction::DecrementPointer => { state.offset -= 1; },
+            Instruction::Move(offset) => { state.offset += offset; },
+
+            Instruction::IncrementValue => { state.set_current(state.current().map(|value| value.wrapping_add(1))); },
+            Instruction::DecrementValue => { state.set_current(state.current().map(|value| value.wrapping_sub(1))); },
+            Instruction::Add(delta) => { state.set_current(state.current().map(|value| value.wrapping_add(delta))); },
+            Instruction::SetZero => { state.set_current(Some(0)); },
+            Instruction::InputValue => { state.set_current(None); },
+
+            Instruction::Begin => { state.forget(); },
+            Instruction::End => {
+                state.forget();
+                state.set_current(Some(0));
+            },
+
+            Instruction::OutputValue | Instruction::OutputDecimal | Instruction::Halt => {}
+        }
+        kept.push((instruction, program.positions()[index]));
+        index += 1;
+    }
+
+    debug!("constant propagation removed {} of {} instructions", instructions.len() - kept.len(), instructions.len());
+    Program::from_parts(&kept, program.source_len(), program.is_folded())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, ParseOptions};
+
+    fn propagated(code: &str) -> String {
+        let program: Program = compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, false).unwrap();
+        propagate(&program).to_source()
+    }
+
+    #[test]
+    fn removes_the_loops_and_clears_that_never_run() {
+        assert_eq!(propagated("[comment, with a , and a .]+."), "+.");
+        assert_eq!(propagated(",[>+<-][>.<-]"), ",[>+<-]");
+        assert_eq!(propagated(",[-][-]"), ",[-]");
+        assert_eq!(propagated("+>[-]<."), "+><.", "the moves are left as they are");
+    }
+
+    #[test]
+    fn keeps_what_input_or_an_entered_loop_may_have_changed() {
+        assert_eq!(propagated(",[.,]"), ",[.,]");
+        assert_eq!(propagated("+[>+<-]>[<+>-]"), "+[>+<-]>[<+>-]");
+        assert_eq!(propagated("+[-]"), "+[-]");
+    }
+
+    #[test]
+    fn keeps_the_moves_around_a_loop_whose_body_ends_where_it_started() {
+        // the moves choose the cell the loop tests and the ones its body works on, even with a net
+        // pointer movement of zero in the body they can't be cancelled across it
+        assert_eq!(propagated("++>+[<+>-]<."), "++>+[<+>-]<.");
+        assert_eq!(propagated(",>,[<->-]<."), ",>,[<->-]<.");
+    }
+
+    #[test]
+    fn every_instruction_updates_the_cell_it_acts_on() {
+        assert_eq!(propagated(">+<[-]>[-]"), ">+<>[-]", "cells are tracked by their offset");
+        assert_eq!(propagated(">>>[-]<<<"), ">>><<<");
+        assert_eq!(propagated(&format!("{}[.]+", "+".repeat(256))), "+", "counts wrap at 256");
+        assert_eq!(propagated("-+[.]"), "");
+        assert_eq!(propagated("+.-[.]"), "+.-", "output leaves the cell alone");
+        assert_eq!(propagated("+,-[.]"), "+,-[.]", "input makes it unknown");
+        assert_eq!(propagated(">,<[.]>[.]"), ">,<>[.]", "and only it");
+    }
+
+    #[test]
+    fn loops_forget_everything_but_their_zero() {
+        assert_eq!(propagated("+[->+<][.]>[.]"), "+[->+<]>[.]", "after the loop only the current cell is known");
+        assert_eq!(propagated("+[[-]>]"), "+[[-]>]", "inside it nothing is");
+        assert_eq!(propagated("+[[-][.]]"), "+[[-]]", "until the body learns something itself");
+        assert_eq!(propagated("+[-[.]]"), "+[-[.]]");
+        assert_eq!(propagated("[+[.]]+[>]"), "+[>]", "a dead loop takes what is nested in it along");
+    }
+}
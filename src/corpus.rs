@@ -0,0 +1,49 @@
+//! Classic programs built into the binary, see the `examples` command. Tests, benchmarks and
+//! differential runs can refer to them by name instead of by a path into `samples/`.
+
+/// An embedded program with the input it is meant to be tried with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Example {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+    pub input: &'static str // empty if the program reads nothing
+}
+
+const EXAMPLES: &[Example] = &[
+    Example { name: "hello", description: "prints Hello World!", source: include_str!("../samples/helloworld.bf"), input: "" },
+    Example {
+        name: "rot13",
+        description: "rotates the letters of its input by 13 places",
+        source: include_str!("../samples/rot13.bf"),
+        input: "Hello, World!\n"
+    },
+    Example {
+        name: "bubblesort",
+        description: "sorts the bytes of its input",
+        source: include_str!("../samples/bubblesort.bf"),
+        input: "brainfuck\n"
+    },
+    Example { name: "squares", description: "prints the square numbers up to 10000", source: include_str!("../samples/squares.bf"), input: "" },
+    Example {
+        name: "bitwidth",
+        description: "tells the cell size and checks for common interpreter bugs",
+        source: include_str!("../samples/bitwidth.bf"),
+        input: ""
+    },
+    Example {
+        name: "mandelbrot",
+        description: "draws the Mandelbrot set as 21 by 9 characters of ASCII art",
+        source: include_str!("../samples/mandelbrot_small.bf"),
+        input: ""
+    }
+];
+
+/// Every example, in the order `examples list` shows them.
+pub fn all() -> &'static [Example] {
+    EXAMPLES
+}
+
+pub fn find(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name == name)
+}
@@ -0,0 +1,192 @@
+//! Weighted operation counts for comparing implementations of the same algorithm, see `--cost-model`.
+//!
+//! Raw step counts undercount folded programs, where one `Move(5)` or `SetZero` stands for many
+//! primitive operations. A [`CostModel`] prices every instruction kind, optionally per unit of work
+//! actually done at runtime (cells moved, units added, units cleared), and a [`CostMeter`] adds the
+//! prices up during a run. A model file holds `key = weight` lines:
+//!
+//! ```toml
+//! move = 0            # per folded or plain pointer move
+//! move_per_cell = 1   # times the distance
+//! add = 0
+//! add_per_unit = 1
+//! clear = 1           # a folded `[-]`
+//! clear_per_unit = 2  # times the value it cleared
+//! input = 1
+//! output = 1          # `.` and `:`
+//! branch = 1          # `[` and `]`
+//! ```
+//!
+//! Missing keys keep their [`CostModel::classic`] weight.
+
+use std::path::Path;
+
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+use crate::layout::strip_comment;
+use crate::parser::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostModel {
+    pub move_base: u64,
+    pub move_per_cell: u64,
+    pub add_base: u64,
+    pub add_per_unit: u64, // an `Add(d)` is `min(d, 256 - d)` units, the shorter way around
+    pub clear_base: u64,
+    pub clear_per_unit: u64, // the value under the pointer before the clear
+    pub input: u64,
+    pub output: u64,
+    pub branch: u64
+}
+
+impl CostModel {
+    /// What the unfolded program would take in steps: a folded run reports the step count of the
+    /// plain one, less whatever folding genuinely removed (`+-` pairs). A `[-]` clearing `v` costs
+    /// `1 + 2v` (`[`, then `-` and `]` per unit), a folded `[+]` is priced the same way.
+    pub fn classic() -> Self {
+        Self { move_base: 0, move_per_cell: 1, add_base: 0, add_per_unit: 1, clear_base: 1, clear_per_unit: 2, input: 1, output: 1, branch: 1 }
+    }
+
+    /// One per executed instruction, folded or not, the same as the step count.
+    pub fn optimized() -> Self {
+        Self { move_base: 1, move_per_cell: 0, add_base: 1, add_per_unit: 0, clear_base: 1, clear_per_unit: 0, input: 1, output: 1, branch: 1 }
+    }
+
+    /// `classic`, `optimized` or the path of a model file (relative to `base`).
+    pub fn load(spec: &str, base: &Path) -> Result<Self, String> {
+        match spec {
+            "classic" => Ok(Self::classic()),
+            "optimized" => Ok(Self::optimized()),
+            path => {
+                let path = base.join(path);
+                let text: String = std::fs::read_to_string(&path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                Self::parse(&text).map_err(|err| format!("{}: {}", path.display(), err))
+            }
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut model: CostModel = Self::classic();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let content: &str = strip_comment(raw_line).trim();
+            if content.is_empty() {
+                continue;
+            }
+            let (key, value) = content.split_once('=').ok_or(format!("line {}: expected `key = weight`, found `{}`", index + 1, content))?;
+            let weight: u64 = value.trim().parse().map_err(|_| format!("line {}: expected a weight, found `{}`", index + 1, value.trim()))?;
+            let field: &mut u64 = match key.trim() {
+                "move" => &mut model.move_base,
+                "move_per_cell" => &mut model.move_per_cell,
+                "add" => &mut model.add_base,
+                "add_per_unit" => &mut model.add_per_unit,
+                "clear" => &mut model.clear_base,
+                "clear_per_unit" => &mut model.clear_per_unit,
+                "input" => &mut model.input,
+                "output" => &mut model.output,
+                "branch" => &mut model.branch,
+                other => return Err(format!("line {}: unknown key `{}`", index + 1, other))
+            };
+            *field = weight;
+        }
+
+        Ok(model)
+    }
+
+    /// Price of `instruction` executed on a cell holding `before`.
+    pub fn cost(&self, instruction: Instruction, before: u8) -> u64 {
+        let units = |base: u64, per_unit: u64, units: u64| base.saturating_add(per_unit.saturating_mul(units));
+        match instruction {
+            Instruction::IncrementPointer | Instruction::DecrementPointer => units(self.move_base, self.move_per_cell, 1),
+            Instruction::Move(offset) => units(self.move_base, self.move_per_cell, offset.unsigned_abs() as u64),
+            Instruction::IncrementValue | Instruction::DecrementValue => units(self.add_base, self.add_per_unit, 1),
+            Instruction::Add(delta) => units(self.add_base, self.add_per_unit, delta.min(delta.wrapping_neg()) as u64),
+            Instruction::SetZero => units(self.clear_base, self.clear_per_unit, before as u64),
+            Instruction::InputValue => self.input,
+            Instruction::OutputValue | Instruction::OutputDecimal => self.output,
+            Instruction::Begin | Instruction::End => self.branch,
+            Instruction::Halt => 0
+        }
+    }
+}
+
+/// Adds up the [`CostModel`] price of every step of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostMeter {
+    pub model: CostModel,
+    pub total: u64 // effective primitive operations so far
+}
+
+impl CostMeter {
+    pub fn new(model: CostModel) -> Self {
+        Self { model, total: 0 }
+    }
+}
+
+impl Observer for CostMeter {
+    fn on_step(&mut self, event: &StepEvent, _interpreter: &Interpreter) {
+        self.total = self.total.saturating_add(self.model.cost(event.instruction, event.before));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_observed, ExecutionConfig};
+    use crate::parser::{compile, ParseOptions, Program};
+    use std::path::PathBuf;
+
+    /// Total cost under `model` and step count of a run of `code`, folded with `fold`.
+    fn metered(code: &str, fold: bool, model: CostModel) -> (u64, u64) {
+        let program: Program = compile(code.as_bytes(), &ParseOptions { fold, ..ParseOptions::default() }, true).unwrap();
+        let mut interpreter: Interpreter = Interpreter::default();
+        let mut meter: CostMeter = CostMeter::new(model);
+        execute_observed(&program, &mut interpreter, &mut &b""[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut meter).unwrap();
+        (meter.total, interpreter.steps())
+    }
+
+    #[test]
+    fn classic_prices_a_folded_run_like_the_plain_one_and_optimized_counts_steps() {
+        let code: &str = "++++++++[>+++<-]>>><<-+.[-]";
+        let (plain_classic, plain_steps) = metered(code, false, CostModel::classic());
+        let (folded_classic, folded_steps) = metered(code, true, CostModel::classic());
+        assert!(folded_steps < plain_steps);
+        assert_eq!(plain_classic, plain_steps);
+        // folding dropped the pairs cancelling out, two in `>>><<` and the `-+`, nothing else
+        assert_eq!(folded_classic, plain_classic - 6);
+
+        assert_eq!(metered(code, false, CostModel::optimized()).0, plain_steps);
+        assert_eq!(metered(code, true, CostModel::optimized()).0, folded_steps);
+    }
+
+    #[test]
+    fn a_clear_costs_per_unit_it_clears() {
+        let classic: CostModel = CostModel::classic();
+        assert_eq!(classic.cost(Instruction::SetZero, 0), 1);
+        assert_eq!(classic.cost(Instruction::SetZero, 5), 11);
+        assert_eq!(classic.cost(Instruction::SetZero, 255), 511);
+        assert_eq!(CostModel::optimized().cost(Instruction::SetZero, 255), 1);
+        // adds go the shorter way around 256, moves either way count their distance
+        assert_eq!(classic.cost(Instruction::Add(200), 0), 56);
+        assert_eq!(classic.cost(Instruction::Move(-7), 0), 7);
+        let saturating: CostModel = CostModel { clear_per_unit: u64::MAX, ..classic };
+        assert_eq!(saturating.cost(Instruction::SetZero, 2), u64::MAX);
+    }
+
+    #[test]
+    fn model_files_override_the_classic_weights() {
+        let base: PathBuf = std::env::temp_dir().join(format!("brainfck-cost-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("model.toml"), "# cheap clears\nclear = 3\nclear_per_unit = 0 # folded or not\n\nbranch=4\n").unwrap();
+        std::fs::write(base.join("bad.toml"), "move = 1\nmove_per_cel = 2\n").unwrap();
+
+        let model: CostModel = CostModel::load("model.toml", &base).unwrap();
+        assert_eq!(model, CostModel { clear_base: 3, clear_per_unit: 0, branch: 4, ..CostModel::classic() });
+        assert_eq!(CostModel::load("bad.toml", &base).unwrap_err(), format!("{}: line 2: unknown key `move_per_cel`", base.join("bad.toml").display()));
+        assert!(CostModel::load("missing.toml", &base).unwrap_err().starts_with(&base.join("missing.toml").display().to_string()));
+        assert_eq!(CostModel::load("classic", &base), Ok(CostModel::classic()));
+        assert_eq!(CostModel::load("optimized", &base), Ok(CostModel::optimized()));
+        assert_eq!(CostModel::parse("input = lots").unwrap_err(), "line 1: expected a weight, found `lots`");
+        assert_eq!(CostModel::parse("output 3").unwrap_err(), "line 1: expected `key = weight`, found `output 3`");
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+//! Wall-clock time by instruction kind, see `--cycle-profile`.
+//!
+//! Timing every instruction would mostly measure the timer: reading it takes about as long as a
+//! plain step. A [`CycleProfiler`] times one step in [`SAMPLE_INTERVAL`] on average instead, picking
+//! the gaps pseudo-randomly so a loop body whose length divides the interval doesn't always show the
+//! same instruction. A sample is the time from the end of one [`Observer::on_step`] call to the start
+//! of the next, which is the dispatch of the run loop, the instruction itself and building its event,
+//! less the cost of reading the timer measured once up front. Every step is counted by kind, and a
+//! kind's share of the run is its mean sample times its count.
+//!
+//! The numbers describe the stepping interpreter: an observed run executes simple loops one
+//! instruction at a time instead of in one go, and pays a counter per step on top. Other observers
+//! of the same run are timed along with the instruction.
+
+use std::time::{Duration, Instant};
+
+use crate::gas::GasCosts;
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+use crate::parser::Instruction;
+
+/// Steps between two samples on average.
+pub const SAMPLE_INTERVAL: u64 = 64;
+
+/// Timer reads to find the cost of one.
+const CALIBRATION_READS: usize = 1000;
+
+/// Index into [`GasCosts::KINDS`] of the kind `instruction` belongs to.
+fn kind(instruction: Instruction) -> usize {
+    match instruction {
+        Instruction::IncrementPointer | Instruction::DecrementPointer | Instruction::Move(_) => 0,
+        Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) => 1,
+        Instruction::SetZero => 2,
+        Instruction::InputValue => 3,
+        Instruction::OutputValue | Instruction::OutputDecimal => 4,
+        Instruction::Begin | Instruction::End | Instruction::Halt => 5
+    }
+}
+
+/// One kind of [`CycleProfiler::kinds`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KindTime {
+    pub name: &'static str, // one of `GasCosts::KINDS`
+    pub count: u64, // steps of the kind
+    pub samples: u64,
+    pub mean: Option<Duration>, // `None` without samples
+    pub estimated: Duration // the kind's time in the run, `mean` times `count`
+}
+
+#[derive(Debug, Clone)]
+pub struct CycleProfiler {
+    counts: [u64; 6],
+    sampled: [Duration; 6],
+    samples: [u64; 6],
+    overhead: Duration, // of one timer read, taken off every sample
+    armed: Option<Instant>, // the next step is timed from here
+    countdown: u64, // steps until the next one armed
+    state: u64 // xorshift state picking the gaps
+}
+
+impl Default for CycleProfiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CycleProfiler {
+    /// Measures the timer before the first step.
+    pub fn new() -> Self {
+        let mut overhead: Duration = Duration::MAX;
+        for _ in 0..CALIBRATION_READS {
+            let start: Instant = Instant::now();
+            overhead = overhead.min(start.elapsed());
+        }
+        let mut profiler: CycleProfiler = Self {
+            counts: [0; 6],
+            sampled: [Duration::ZERO; 6],
+            samples: [0; 6],
+            overhead,
+            armed: None,
+            countdown: 0,
+            state: 0x2545_f491_4f6c_dd1d
+        };
+        profiler.countdown = profiler.next_gap();
+        profiler
+    }
+
+    /// 1 to `2 * SAMPLE_INTERVAL - 1` steps, `SAMPLE_INTERVAL` on average.
+    fn next_gap(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        1 + self.state % (2 * SAMPLE_INTERVAL - 1)
+    }
+
+    /// What a timer read costs, as subtracted from the samples.
+    pub fn overhead(&self) -> Duration {
+        self.overhead
+    }
+
+    /// Every kind in [`GasCosts::KINDS`] order.
+    pub fn kinds(&self) -> Vec<KindTime> {
+        GasCosts::KINDS.iter().enumerate().map(|(index, &name)| {
+            let (sampled, samples, count): (u128, u128, u128) = (self.sampled[index].as_nanos(), self.samples[index] as u128, self.counts[index] as u128);
+            let nanos = |total: u128| Duration::from_nanos(total.min(u64::MAX as u128) as u64);
+            KindTime {
+                name,
+                count: self.counts[index],
+                samples: self.samples[index],
+                mean: sampled.checked_div(samples).map(nanos),
+                estimated: (sampled * count).checked_div(samples).map_or(Duration::ZERO, nanos)
+            }
+        }).collect()
+    }
+}
+
+impl Observer for CycleProfiler {
+    fn on_step(&mut self, event: &StepEvent, _interpreter: &Interpreter) {
+        if let Some(start) = self.armed.take() {
+            let elapsed: Duration = start.elapsed();
+            let kind: usize = kind(event.instruction);
+            self.sampled[kind] += elapsed.saturating_sub(self.overhead);
+            self.samples[kind] += 1;
+        }
+        self.counts[kind(event.instruction)] += 1;
+
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = self.next_gap();
+            self.armed = Some(Instant::now());
+        }
+    }
+}
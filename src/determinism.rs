@@ -0,0 +1,155 @@
+use std::fmt;
+
+use crate::interpreter::{execute_code, ExecutionConfig, Interpreter};
+use crate::parser::Program;
+
+/// Everything that is compared between runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RunRecord {
+    output: Vec<u8>,
+    tape_checksum: u64,
+    steps: u64,
+    error: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    Output { run: usize, position: usize }, // first differing byte, or where one output ends
+    TapeChecksum { run: usize, expected: u64, found: u64 },
+    Steps { run: usize, expected: u64, found: u64 },
+    Error { run: usize, expected: Option<String>, found: Option<String> }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Output { run, position } => write!(f, "run {} output differs from run 0 at byte {}", run, position),
+            Divergence::TapeChecksum { run, expected, found } => {
+                write!(f, "run {} final tape checksum {:016x} differs from run 0 ({:016x})", run, found, expected)
+            },
+            Divergence::Steps { run, expected, found } => write!(f, "run {} took {} steps, run 0 took {}", run, found, expected),
+            Divergence::Error { run, expected, found } => write!(
+                f,
+                "run {} ended with {}, run 0 with {}",
+                run,
+                found.as_deref().unwrap_or("success"),
+                expected.as_deref().unwrap_or("success")
+            )
+        }
+    }
+}
+
+fn record(program: &Program, interpreter: &mut Interpreter, input: &[u8], config: &ExecutionConfig) -> RunRecord {
+    interpreter.reset();
+
+    let mut output: Vec<u8> = vec![];
+    let result = execute_code(program, interpreter, &mut &input[..], &mut output, config);
+
+    RunRecord {
+        output,
+        tape_checksum: interpreter.tape_checksum(),
+        steps: interpreter.steps(),
+        error: result.err().map(|err| err.to_string())
+    }
+}
+
+fn compare(run: usize, expected: &RunRecord, found: &RunRecord) -> Option<Divergence> {
+    if expected.output != found.output {
+        let position: usize = expected.output.iter().zip(&found.output).take_while(|(a, b)| a == b).count();
+        return Some(Divergence::Output { run, position });
+    }
+    if expected.tape_checksum != found.tape_checksum {
+        return Some(Divergence::TapeChecksum { run, expected: expected.tape_checksum, found: found.tape_checksum });
+    }
+    if expected.steps != found.steps {
+        return Some(Divergence::Steps { run, expected: expected.steps, found: found.steps });
+    }
+    if expected.error != found.error {
+        return Some(Divergence::Error { run, expected: expected.error.clone(), found: found.error.clone() });
+    }
+
+    None
+}
+
+/// Runs `program` `runs` times on the same `input`, resetting `interpreter` before each run, and
+/// returns every run that doesn't match the first one. A run ending in an error is fine as long as
+/// all runs end in the same error.
+pub fn verify_determinism(
+    program: &Program,
+    interpreter: &mut Interpreter,
+    input: &[u8],
+    runs: usize,
+    config: &ExecutionConfig
+) -> Vec<Divergence> {
+    verify_runs(runs, || record(program, interpreter, input, config))
+}
+
+/// Compares `runs` records made by `run` with the first one.
+fn verify_runs(runs: usize, mut run: impl FnMut() -> RunRecord) -> Vec<Divergence> {
+    if runs == 0 {
+        return vec![];
+    }
+
+    let first: RunRecord = run();
+
+    (1..runs).filter_map(|index| compare(index, &first, &run())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{compile, ParseOptions};
+
+    fn program(code: &str) -> Program {
+        compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, true).unwrap()
+    }
+
+    #[test]
+    fn identical_runs_have_no_divergence() {
+        let mut interpreter: Interpreter = Interpreter::default();
+        // leave a dirty tape behind, every run has to start from a clean one anyway
+        execute_code(&program("+>++>+++"), &mut interpreter, &mut &b""[..], &mut vec![], &ExecutionConfig::default()).unwrap();
+
+        let echo: Program = program(",[.>+<[-],]");
+        assert_eq!(verify_determinism(&echo, &mut interpreter, b"abc", 5, &ExecutionConfig::default()), vec![]);
+
+        // the same error every time is no divergence either
+        let config: ExecutionConfig = ExecutionConfig { max_steps: Some(100), ..ExecutionConfig::default() };
+        assert_eq!(verify_determinism(&program("+[>+]"), &mut interpreter, b"", 3, &config), vec![]);
+        assert_eq!(verify_determinism(&echo, &mut interpreter, b"", 0, &config), vec![]);
+    }
+
+    /// Stands in for a backend that isn't deterministic: the records of a good run, with the run
+    /// `flaky` changed by `change`.
+    fn flaky_runs(runs: usize, flaky: usize, change: impl Fn(&mut RunRecord)) -> Vec<Divergence> {
+        let mut index: usize = 0;
+        verify_runs(runs, || {
+            let mut record: RunRecord = RunRecord { output: b"hello".to_vec(), tape_checksum: 7, steps: 40, error: None };
+            if index == flaky {
+                change(&mut record);
+            }
+            index += 1;
+            record
+        })
+    }
+
+    #[test]
+    fn a_diverging_run_is_reported_with_its_index() {
+        assert_eq!(flaky_runs(4, 2, |record| record.output[3] = b'p'), vec![Divergence::Output { run: 2, position: 3 }]);
+        assert_eq!(flaky_runs(4, 1, |record| record.output.truncate(2)), vec![Divergence::Output { run: 1, position: 2 }]);
+        assert_eq!(flaky_runs(3, 2, |record| record.tape_checksum = 8), vec![Divergence::TapeChecksum { run: 2, expected: 7, found: 8 }]);
+        assert_eq!(flaky_runs(3, 1, |record| record.steps = 41), vec![Divergence::Steps { run: 1, expected: 40, found: 41 }]);
+        assert_eq!(
+            flaky_runs(2, 1, |record| record.error = Some("Step limit exceeded".to_string())),
+            vec![Divergence::Error { run: 1, expected: None, found: Some("Step limit exceeded".to_string()) }]
+        );
+    }
+
+    #[test]
+    fn every_run_is_compared_with_the_first() {
+        // a diverging first run makes every other run diverge from it
+        let divergences: Vec<Divergence> = flaky_runs(3, 0, |record| record.steps = 1);
+        assert_eq!(divergences.len(), 2);
+        assert_eq!(divergences[0].to_string(), "run 1 took 40 steps, run 0 took 1");
+    }
+}
@@ -0,0 +1,139 @@
+use crate::interpreter::StepEvent;
+use crate::parser::{Instruction, Program};
+use crate::source::line_col;
+
+fn describe_byte(byte: u8) -> String {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        format!("{} ('{}')", byte, byte as char)
+    } else {
+        format!("{}", byte)
+    }
+}
+
+/// Beginner-friendly sentence describing `event`. `next_index` is the instruction that executes next,
+/// `source` the text `program` was parsed from (used for line numbers).
+pub fn explain_step(event: &StepEvent, next_index: usize, program: &Program, source: &[u8]) -> String {
+    let cell: usize = event.pointer;
+    // without the closing `Halt` a jump may go to the end of the program, past the last instruction
+    let target = |index: usize| -> String {
+        match program.positions().get(index) {
+            Some(&position) => format!("instruction {} (line {})", index, line_col(source, position).0),
+            None => "the end of the program".to_string()
+        }
+    };
+
+    let sentence: String = match event.instruction {
+        Instruction::IncrementPointer => format!("'>' moves the pointer from cell {} to cell {}", cell, cell + 1),
+        Instruction::DecrementPointer => format!("'<' moves the pointer from cell {} to cell {}", cell, cell.wrapping_sub(1)),
+        Instruction::Move(offset) => {
+            format!("'{}' moves the pointer from cell {} to cell {}", event.instruction, cell, cell.wrapping_add_signed(offset))
+        },
+
+        Instruction::IncrementValue => format!("'+' increments cell {} from {} to {}", cell, event.before, event.after),
+        Instruction::DecrementValue => format!("'-' decrements cell {} from {} to {}", cell, event.before, event.after),
+        Instruction::Add(_) => format!("'{}' changes cell {} from {} to {}", event.instruction, cell, event.before, event.after),
+        Instruction::SetZero => format!("'[-]' clears cell {} (was {})", cell, event.before),
+
+        Instruction::OutputValue => format!("'.' outputs cell {}: {}", cell, describe_byte(event.after)),
+        Instruction::OutputDecimal => format!("':' outputs cell {} as the number {}", cell, event.after),
+        Instruction::InputValue if event.before == event.after => {
+            format!("',' reads into cell {}, which stays {} (end of input or the same byte)", cell, event.after)
+        },
+        Instruction::InputValue => format!("',' reads {} into cell {}", describe_byte(event.after), cell),
+
+        Instruction::Begin if event.before == 0 => format!(
+            "'[' — cell {} is 0, jumping past the loop to {}",
+            cell, target(next_index)
+        ),
+        Instruction::Begin => format!("'[' — cell {} is {}, entering the loop", cell, event.before),
+        Instruction::End if event.before != 0 => format!(
+            "']' — cell {} is {}, jumping back to {}",
+            cell, event.before, target(next_index)
+        ),
+        Instruction::End => format!("']' — cell {} is 0, leaving the loop", cell),
+
+        Instruction::Halt => "the program ends".to_string()
+    };
+
+    format!("step {}: {}", event.step, sentence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_observed, ExecutionConfig, Interpreter, Observer};
+    use crate::parser::{compile, ParseOptions};
+
+    struct Explained<'a> {
+        program: &'a Program,
+        source: &'a [u8],
+        sentences: Vec<String>
+    }
+
+    impl Observer for Explained<'_> {
+        fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+            self.sentences.push(explain_step(event, interpreter.instruction_index(), self.program, self.source));
+        }
+    }
+
+    /// Every sentence of a run of `source` on `input`, on a tape of 8 cells.
+    fn explained(source: &str, options: &ParseOptions, input: &[u8]) -> Vec<String> {
+        let program: Program = compile(source.as_bytes(), options, true).unwrap();
+        let mut explained: Explained = Explained { program: &program, source: source.as_bytes(), sentences: vec![] };
+        execute_observed(&program, &mut Interpreter::fixed(8), &mut &input[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut explained).unwrap();
+        explained.sentences
+    }
+
+    #[test]
+    fn every_instruction_gets_its_sentence() {
+        let source: &str = ",+.\n>[-]<\n,:\n>++[-<+>]<";
+        let options: ParseOptions = ParseOptions { extensions: true, ..ParseOptions::default() };
+        let golden: &[&str] = &[
+            "step 1: ',' reads 65 ('A') into cell 4",
+            "step 2: '+' increments cell 4 from 65 to 66",
+            "step 3: '.' outputs cell 4: 66 ('B')",
+            "step 4: '>' moves the pointer from cell 4 to cell 5",
+            "step 5: '[' — cell 5 is 0, jumping past the loop to instruction 7 (line 2)",
+            "step 6: '<' moves the pointer from cell 5 to cell 4",
+            "step 7: ',' reads into cell 4, which stays 66 (end of input or the same byte)",
+            "step 8: ':' outputs cell 4 as the number 66",
+            "step 9: '>' moves the pointer from cell 4 to cell 5",
+            "step 10: '+' increments cell 5 from 0 to 1",
+            "step 11: '+' increments cell 5 from 1 to 2",
+            "step 12: '[' — cell 5 is 2, entering the loop",
+            "step 13: '-' decrements cell 5 from 2 to 1",
+            "step 14: '<' moves the pointer from cell 5 to cell 4",
+            "step 15: '+' increments cell 4 from 66 to 67",
+            "step 16: '>' moves the pointer from cell 4 to cell 5",
+            "step 17: ']' — cell 5 is 1, jumping back to instruction 14 (line 4)",
+            "step 18: '-' decrements cell 5 from 1 to 0",
+            "step 19: '<' moves the pointer from cell 5 to cell 4",
+            "step 20: '+' increments cell 4 from 67 to 68",
+            "step 21: '>' moves the pointer from cell 4 to cell 5",
+            "step 22: ']' — cell 5 is 0, leaving the loop",
+            "step 23: '<' moves the pointer from cell 5 to cell 4"
+        ];
+        assert_eq!(explained(source, &options, b"A"), golden);
+    }
+
+    #[test]
+    fn folded_instructions_get_theirs() {
+        let options: ParseOptions = ParseOptions { fold: true, ..ParseOptions::default() };
+        let golden: &[&str] = &[
+            "step 1: '>3' moves the pointer from cell 4 to cell 7",
+            "step 2: '+4' changes cell 7 from 0 to 4",
+            "step 3: '.' outputs cell 7: 4",
+            "step 4: '[-]' clears cell 7 (was 4)",
+            "step 5: '<2' moves the pointer from cell 7 to cell 5",
+            "step 6: '-3' changes cell 5 from 0 to 253"
+        ];
+        assert_eq!(explained(">>>++++.[-]<<---", &options, b""), golden);
+    }
+
+    #[test]
+    fn the_closing_halt_ends_the_program() {
+        let program: Program = compile(b"+", &ParseOptions::default(), true).unwrap();
+        let event: StepEvent = StepEvent { step: 2, index: 1, instruction: Instruction::Halt, pointer: 4, before: 1, after: 1 };
+        assert_eq!(explain_step(&event, 2, &program, b"+"), "step 2: the program ends");
+    }
+}
@@ -0,0 +1,61 @@
+use crate::parser::Instruction;
+
+/// Price of every instruction kind for [`Gas`] metering. Folded instructions cost what the run they
+/// replace would (`Move(-3)` three pointer moves, `Add(254)` two decrements), a `[-]` folded into
+/// `SetZero` costs `clear` however many iterations it saves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasCosts {
+    pub pointer: u64, // `>`, `<`
+    pub arithmetic: u64, // `+`, `-`
+    pub clear: u64, // folded `[-]`
+    pub input: u64, // `,`
+    pub output: u64, // `.`, `:`
+    pub branch: u64 // `[`, `]`
+}
+
+impl Default for GasCosts {
+    fn default() -> Self {
+        Self { pointer: 1, arithmetic: 1, clear: 2, input: 10, output: 10, branch: 1 }
+    }
+}
+
+impl GasCosts {
+    /// Names accepted by [`GasCosts::set`], in field order.
+    pub const KINDS: [&'static str; 6] = ["pointer", "arithmetic", "clear", "input", "output", "branch"];
+
+    /// Overrides the cost of the kind called `name`, `false` for an unknown name.
+    pub fn set(&mut self, name: &str, cost: u64) -> bool {
+        let field: &mut u64 = match name {
+            "pointer" => &mut self.pointer,
+            "arithmetic" => &mut self.arithmetic,
+            "clear" => &mut self.clear,
+            "input" => &mut self.input,
+            "output" => &mut self.output,
+            "branch" => &mut self.branch,
+            _ => return false
+        };
+        *field = cost;
+        true
+    }
+
+    pub fn cost(&self, instruction: Instruction) -> u64 {
+        match instruction {
+            Instruction::IncrementPointer | Instruction::DecrementPointer => self.pointer,
+            Instruction::Move(offset) => self.pointer.saturating_mul(offset.unsigned_abs() as u64),
+            Instruction::IncrementValue | Instruction::DecrementValue => self.arithmetic,
+            Instruction::Add(delta) => self.arithmetic.saturating_mul(delta.min(delta.wrapping_neg()) as u64),
+            Instruction::SetZero => self.clear,
+            Instruction::InputValue => self.input,
+            Instruction::OutputValue | Instruction::OutputDecimal => self.output,
+            Instruction::Begin | Instruction::End => self.branch,
+            Instruction::Halt => 0
+        }
+    }
+}
+
+/// Budget of a metered run, see [`crate::ExecutionConfig::gas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Gas {
+    pub limit: u64,
+    pub costs: GasCosts
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::interpreter::{execute_code, ExecutionConfig, Interpreter};
+use crate::parser::{compile, ParseOptions, Program};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    CancelledPair, // `+-`, `-+`, `<>`, `><`
+    ShorterRun, // run of `+`/`-` written the short way around 256, or as a multiply loop over a zero scratch cell
+    RedundantClear, // `[-]` on a cell that is already known to be zero
+    ClearThenSet // `[-]` followed by a run on a cell whose value is known, reached directly instead
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::CancelledPair => write!(f, "cancelled pair"),
+            Rule::ShorterRun => write!(f, "shorter run"),
+            Rule::RedundantClear => write!(f, "redundant clear"),
+            Rule::ClearThenSet => write!(f, "clear then set")
+        }
+    }
+}
+
+/// Replace the source bytes in `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub span: Range<usize>,
+    pub replacement: String,
+    pub rule: Rule
+}
+
+impl Suggestion {
+    pub fn savings(&self) -> usize {
+        self.span.len() - self.replacement.len()
+    }
+}
+
+/// Cells a run may move the pointer to without failing or growing the tape, relative to where it
+/// starts. A rewrite dropping or adding a move to a cell outside of them could take away or add an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bounds {
+    pub cells: Range<isize>,
+    pub max_range: Option<usize> // `ExecutionConfig::max_pointer_range`
+}
+
+impl Bounds {
+    /// The allocated tape of `interpreter` (or its sandbox) around its pointer, and the pointer range cap of `config`.
+    pub fn of(interpreter: &Interpreter, config: &ExecutionConfig) -> Self {
+        let start: isize = interpreter.pointer() as isize;
+        let cells: Range<usize> = interpreter.sandbox().unwrap_or(0..interpreter.tape().len());
+        Self { cells: cells.start as isize - start..cells.end as isize - start, max_range: config.max_pointer_range }
+    }
+}
+
+/// What is statically known about the tape, relative to the pointer position at the start of the
+/// current straight-line stretch. Everything is forgotten whenever a loop body starts or ends, except
+/// that the current cell is zero right after a `]`.
+struct KnownTape {
+    cells: HashMap<isize, Option<u8>>,
+    offset: isize,
+    untouched_are_zero: bool, // only true before the first loop, when the tape is still fresh
+    position: Option<isize>, // pointer relative to the start of the run, known while every loop entered is balanced
+    extremes: Option<(isize, isize)> // leftmost and rightmost cells visited, known until the first loop
+}
+
+impl KnownTape {
+    fn fresh() -> Self {
+        Self { cells: HashMap::new(), offset: 0, untouched_are_zero: true, position: Some(0), extremes: Some((0, 0)) }
+    }
+
+    fn shift(&mut self, step: isize) {
+        self.offset += step;
+        self.position = self.position.map(|position| position + step);
+        if let (Some(position), Some((lowest, highest))) = (self.position, self.extremes.as_mut()) {
+            *lowest = (*lowest).min(position);
+            *highest = (*highest).max(position);
+        }
+    }
+
+    /// Whether the pointer is known to reach the cell `step` away without failing, growing the tape or
+    /// widening a capped pointer range, so a rewrite may add or drop a visit to it.
+    fn can_visit(&self, step: isize, bounds: &Bounds) -> bool {
+        self.position.map(|position| position + step).is_some_and(|cell| {
+            bounds.cells.contains(&cell)
+                && (bounds.max_range.is_none() || self.extremes.is_some_and(|(lowest, highest)| lowest <= cell && cell <= highest))
+        })
+    }
+
+    fn forget(&mut self) {
+        self.cells.clear();
+        self.offset = 0;
+        self.untouched_are_zero = false;
+    }
+
+    fn get(&self, offset: isize) -> Option<u8> {
+        match self.cells.get(&offset) {
+            Some(&value) => value,
+            None if self.untouched_are_zero => Some(0),
+            None => None
+        }
+    }
+
+    fn set(&mut self, value: Option<u8>) {
+        self.cells.insert(self.offset, value);
+    }
+
+    fn add(&mut self, delta: u8) {
+        let value: Option<u8> = self.get(self.offset).map(|value| value.wrapping_add(delta));
+        self.set(value);
+    }
+}
+
+/// Shortest `+`/`-` text changing a cell by `delta` (mod 256).
+fn plain_delta(delta: u8) -> String {
+    if delta <= 128 { "+".repeat(delta as usize) } else { "-".repeat(256 - delta as usize) }
+}
+
+/// Shortest text changing the current cell by `delta`, optionally through a multiply loop that borrows
+/// the zero cell to the right (and leaves it zero again).
+fn shortest_delta(delta: u8, scratch_is_zero: bool) -> String {
+    let mut best: String = plain_delta(delta);
+    if !scratch_is_zero {
+        return best;
+    }
+
+    for (step, sign) in [('+', 1i32), ('-', -1i32)] {
+        for times in 2..=16 {
+            for per_loop in 2..=32 {
+                let product: i32 = sign * times * per_loop;
+                let rest: u8 = (delta as i32 - product).rem_euclid(256) as u8;
+                let rest_text: String = plain_delta(rest);
+
+                let length: usize = (times + per_loop) as usize + 7 + rest_text.len();
+                if length < best.len() {
+                    best = format!(
+                        ">{}[<{}>-]<{}",
+                        "+".repeat(times as usize),
+                        step.to_string().repeat(per_loop as usize),
+                        rest_text
+                    );
+                }
+            }
+        }
+    }
+
+    best
+}
+
+/// For every `[` and `]` token, whether its loop ends where it started: no net pointer movement in
+/// its body, and every loop nested in it balanced too.
+fn balanced_loops(tokens: &[(usize, u8)]) -> Vec<bool> {
+    let mut balanced: Vec<bool> = vec![false; tokens.len()];
+    let mut open: Vec<(usize, isize, bool)> = vec![]; // `[` token, net movement so far, nested loops all balanced
+
+    for (i, &(_, byte)) in tokens.iter().enumerate() {
+        match byte {
+            b'>' | b'<' => {
+                if let Some((_, net, _)) = open.last_mut() { *net += if byte == b'>' { 1 } else { -1 }; }
+            },
+            b'[' => { open.push((i, 0, true)); },
+            b']' => {
+                if let Some((begin, net, nested)) = open.pop() {
+                    balanced[begin] = net == 0 && nested;
+                    balanced[i] = balanced[begin];
+                    if let Some((_, _, outer)) = open.last_mut() { *outer &= balanced[begin]; }
+                }
+            },
+            _ => {}
+        }
+    }
+
+    balanced
+}
+
+/// [`suggest_within`] the bounds of a default interpreter.
+pub fn suggest(source: &[u8]) -> Vec<Suggestion> {
+    suggest_within(source, &Bounds::of(&Interpreter::default(), &ExecutionConfig::default()))
+}
+
+/// Conservative, behavior-preserving rewrites that make `source` shorter. Suggestions never overlap
+/// and are sorted by position. `source` is read as plain brainfuck: `[`/`]` loops testing the current
+/// cell, which holds 256 values.
+///
+/// Only contiguous instruction bytes are rewritten so comments are never touched. Knowledge about
+/// cell values never crosses a loop boundary, so rewrites relying on it only fire in straight-line code.
+/// Rewrites dropping or adding a visit to a neighbouring cell (a `<>` pair, a multiply loop over the
+/// scratch cell) only fire where the pointer position is known, inside and after balanced loops, and
+/// the cell lies within `bounds`: they would otherwise drop or add an error at the edge of the tape.
+pub fn suggest_within(source: &[u8], bounds: &Bounds) -> Vec<Suggestion> {
+    let tokens: Vec<(usize, u8)> = source.iter().copied().enumerate().filter(|(_, byte)| b"><+-.,[]".contains(byte)).collect();
+    let balanced: Vec<bool> = balanced_loops(&tokens);
+    let contiguous = |from: usize, length: usize| -> bool {
+        from + length <= tokens.len() && (from..from + length).all(|i| tokens[i].0 == tokens[from].0 + (i - from))
+    };
+    let text = |from: usize, length: usize| -> Vec<u8> { tokens[from..from + length].iter().map(|&(_, byte)| byte).collect() };
+
+    let mut suggestions: Vec<Suggestion> = vec![];
+    let mut tape: KnownTape = KnownTape::fresh();
+
+    let mut i: usize = 0;
+    while i < tokens.len() {
+        let (position, byte) = tokens[i];
+
+        if contiguous(i, 3) && (text(i, 3) == b"[-]" || text(i, 3) == b"[+]") {
+            let run: usize = (i + 3..tokens.len()).take_while(|&j| tokens[j].1 == b'+' || tokens[j].1 == b'-').count();
+            let run: usize = if contiguous(i, 3 + run) { run } else { 0 };
+            let net: u8 = tokens[i + 3..i + 3 + run].iter().fold(0u8, |net, &(_, op)| if op == b'+' { net.wrapping_add(1) } else { net.wrapping_sub(1) });
+
+            match tape.get(tape.offset) {
+                Some(0) => {
+                    suggestions.push(Suggestion { span: position..position + 3, replacement: String::new(), rule: Rule::RedundantClear });
+                },
+                Some(value) => {
+                    let replacement: String = plain_delta(net.wrapping_sub(value));
+                    if replacement.len() < 3 + run {
+                        suggestions.push(Suggestion { span: position..position + 3 + run, replacement, rule: Rule::ClearThenSet });
+                        tape.set(Some(net));
+                        i += 3 + run;
+                        continue;
+                    }
+                },
+                None => {}
+            }
+
+            tape.set(Some(0));
+            i += 3;
+            continue;
+        }
+
+        if contiguous(i, 2) {
+            let pair: Vec<u8> = text(i, 2);
+            let safe: bool = match pair.as_slice() {
+                b"+-" | b"-+" => true,
+                b"<>" => tape.can_visit(-1, bounds),
+                b"><" => tape.can_visit(1, bounds),
+                _ => false
+            };
+            if safe {
+                suggestions.push(Suggestion { span: position..position + 2, replacement: String::new(), rule: Rule::CancelledPair });
+                i += 2;
+                continue;
+            }
+        }
+
+        match byte {
+            b'+' | b'-' => {
+                let mut run: usize = 1;
+                while i + run < tokens.len() && tokens[i + run] == (position + run, byte) { run += 1; }
+
+                let delta: u8 = if byte == b'+' { (run % 256) as u8 } else { 0u8.wrapping_sub((run % 256) as u8) };
+                let replacement: String = shortest_delta(delta, tape.get(tape.offset + 1) == Some(0) && tape.can_visit(1, bounds));
+                if replacement.len() < run {
+                    suggestions.push(Suggestion { span: position..position + run, replacement, rule: Rule::ShorterRun });
+                }
+
+                tape.add(delta);
+                i += run;
+                continue;
+            },
+            b'>' => { tape.shift(1); },
+            b'<' => { tape.shift(-1); },
+            b',' => { tape.set(None); },
+            b'[' => {
+                tape.forget();
+                if !balanced[i] { tape.position = None; }
+                tape.extremes = None;
+            },
+            b']' => {
+                tape.forget();
+                tape.set(Some(0));
+                if !balanced[i] { tape.position = None; }
+            },
+            _ => {}
+        }
+
+        i += 1;
+    }
+
+    suggestions
+}
+
+/// `source` with every suggestion applied, `suggestions` must be sorted and non-overlapping as returned by [`suggest`].
+pub fn apply(source: &[u8], suggestions: &[Suggestion]) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::with_capacity(source.len());
+    let mut copied_up_to: usize = 0;
+
+    for suggestion in suggestions {
+        result.extend_from_slice(&source[copied_up_to..suggestion.span.start]);
+        result.extend_from_slice(suggestion.replacement.as_bytes());
+        copied_up_to = suggestion.span.end;
+    }
+    result.extend_from_slice(&source[copied_up_to..]);
+
+    result
+}
+
+/// Differential check of a rewrite: both programs have to finish within `max_steps` on empty input with
+/// identical output, final tape and pointer.
+pub fn verify(original: &[u8], rewritten: &[u8], max_steps: u64) -> bool {
+    let config: ExecutionConfig = ExecutionConfig { max_steps: Some(max_steps), ..ExecutionConfig::default() };
+    verify_on(original, rewritten, &ParseOptions::default(), &Interpreter::default(), &config, &[])
+}
+
+/// [`verify`] with both programs parsed with `options` (unfolded) and run on a copy of `interpreter`
+/// under `config` (which should have a step limit), reading `input`.
+pub fn verify_on(original: &[u8], rewritten: &[u8], options: &ParseOptions, interpreter: &Interpreter, config: &ExecutionConfig, input: &[u8]) -> bool {
+    let options: ParseOptions = ParseOptions { fold: false, ..*options };
+    let run = |source: &[u8]| -> Option<(Vec<u8>, Interpreter)> {
+        let program: Program = compile(source, &options, true).ok()?;
+
+        let mut interpreter: Interpreter = interpreter.clone();
+        let mut output: Vec<u8> = vec![];
+        execute_code(&program, &mut interpreter, &mut &input[..], &mut output, config).ok()?;
+
+        Some((output, interpreter))
+    };
+
+    match (run(original), run(rewritten)) {
+        (Some((original_output, original_state)), Some((rewritten_output, rewritten_state))) => {
+            original_output == rewritten_output
+                && original_state.pointer() == rewritten_state.pointer()
+                && original_state.tape() == rewritten_state.tape()
+        },
+        _ => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(source: &str, bounds: &Bounds) -> Vec<(Range<usize>, Rule)> {
+        suggest_within(source.as_bytes(), bounds).into_iter().map(|suggestion| (suggestion.span, suggestion.rule)).collect()
+    }
+
+    fn unbounded() -> Bounds {
+        Bounds { cells: -1000..1000, max_range: None }
+    }
+
+    #[test]
+    fn every_rule_fires_where_it_is_safe() {
+        assert_eq!(rules("+-<>><", &unbounded()), [(0..2, Rule::CancelledPair), (2..4, Rule::CancelledPair), (4..6, Rule::CancelledPair)]);
+        assert_eq!(rules(&"+".repeat(200), &unbounded()), [(0..200, Rule::ShorterRun)]);
+        assert_eq!(rules("[-].", &unbounded()), [(0..3, Rule::RedundantClear)]);
+        assert_eq!(rules("+++[-]++.", &unbounded()), [(3..8, Rule::ClearThenSet)]);
+
+        let suggestions: Vec<Suggestion> = suggest_within("+".repeat(60).as_bytes(), &unbounded());
+        assert!(suggestions[0].replacement.starts_with(">+"), "multiply loop over the scratch cell: {}", suggestions[0].replacement);
+    }
+
+    #[test]
+    fn rules_needing_a_known_cell_skip_after_a_loop() {
+        assert_eq!(rules(",[.,][-].", &unbounded()), [(5..8, Rule::RedundantClear)], "a loop ends on a 0");
+        assert_eq!(rules(",[.,]>[-].", &unbounded()), []);
+        assert_eq!(rules(",[.,]>+++[-]++.", &unbounded()), []);
+        let suggestions: Vec<Suggestion> = suggest_within(format!(",[.,]{}", "+".repeat(60)).as_bytes(), &unbounded());
+        assert!(suggestions.iter().all(|suggestion| !suggestion.replacement.contains('[')), "the scratch cell isn't known to be 0");
+    }
+
+    #[test]
+    fn pointer_pairs_and_the_scratch_cell_stay_inside_the_tape() {
+        let edge: Bounds = Bounds::of(&Interpreter::fixed(1), &ExecutionConfig::default());
+        assert_eq!(edge.cells, 0..1);
+        assert_eq!(rules("+-<>><", &edge), [(0..2, Rule::CancelledPair)]);
+        assert_eq!(rules(&"+".repeat(60), &edge), [], "60 `+` are the shortest without the scratch cell");
+
+        // the dropped `<` is what makes the original fail
+        let rewritten: Vec<u8> = apply(b"<>+.", &suggest(b"<>+."));
+        let config: ExecutionConfig = ExecutionConfig { max_steps: Some(100), ..ExecutionConfig::default() };
+        assert!(verify(b"<>+.", &rewritten, 100));
+        assert!(!verify_on(b"<>+.", b"+.", &ParseOptions::default(), &Interpreter::fixed(1), &config, &[]));
+    }
+
+    #[test]
+    fn pointer_pairs_need_a_known_position() {
+        assert_eq!(rules("+[>]<>", &unbounded()), [], "where `[>]` stops is unknown");
+        assert_eq!(rules("+[>+<-]<>", &unbounded()), [(7..9, Rule::CancelledPair)]);
+        assert_eq!(rules("+[>+<-<>]", &unbounded()), [(6..8, Rule::CancelledPair)]);
+        assert_eq!(rules("+[[>]<<>]", &unbounded()), [], "a balanced body around an unbalanced loop");
+    }
+
+    #[test]
+    fn pointer_pairs_never_widen_a_capped_range() {
+        let capped: Bounds = Bounds { cells: -1000..1000, max_range: Some(3) };
+        assert_eq!(rules("><", &capped), []);
+        assert_eq!(rules(">><>", &capped), [(2..4, Rule::CancelledPair)]);
+        assert_eq!(rules("+[>+<-]><", &capped), [], "the loop may not have run");
+    }
+
+    #[test]
+    fn verification_runs_on_the_given_machine_and_input() {
+        let source: String = format!("+-<>{}.[-]+++.", "+".repeat(60));
+        let rewritten: Vec<u8> = apply(source.as_bytes(), &suggest(source.as_bytes()));
+        assert!(rewritten.len() < source.len());
+        assert!(verify(source.as_bytes(), &rewritten, 10_000));
+
+        let modulo: ExecutionConfig = ExecutionConfig { max_steps: Some(10_000), cell_modulus: Some(100), ..ExecutionConfig::default() };
+        let original: String = format!("{}.", "-".repeat(200));
+        let rewritten: Vec<u8> = apply(original.as_bytes(), &suggest(original.as_bytes()));
+        assert!(verify(original.as_bytes(), &rewritten, 10_000));
+        assert!(!verify_on(original.as_bytes(), &rewritten, &ParseOptions::default(), &Interpreter::default(), &modulo, &[]), "runs are shortened modulo 256");
+
+        let config: ExecutionConfig = ExecutionConfig { max_steps: Some(10_000), ..ExecutionConfig::default() };
+        assert!(verify_on(b",.", b",.", &ParseOptions::default(), &Interpreter::default(), &config, b"x"));
+        assert!(!verify_on(b",.", b",[-]+.", &ParseOptions::default(), &Interpreter::default(), &config, b"x"));
+    }
+}
@@ -0,0 +1,77 @@
+//! Output bytes as a grayscale image, one byte per pixel, see `--output-image`.
+//!
+//! Rows are `width` pixels wide, a last row the output doesn't fill is padded with black (0) pixels,
+//! and an image always has at least one row. PGM needs nothing, PNG (uncompressed, written without
+//! any dependency) wants the `png` feature.
+
+/// Number of rows `pixels` fill at `width` pixels a row.
+pub fn height(pixels: &[u8], width: usize) -> usize {
+    pixels.len().div_ceil(width).max(1)
+}
+
+fn rows(pixels: &[u8], width: usize) -> impl Iterator<Item = Vec<u8>> + '_ {
+    (0..height(pixels, width)).map(move |row| {
+        let mut bytes: Vec<u8> = pixels.get(row * width..).unwrap_or_default().iter().take(width).copied().collect();
+        bytes.resize(width, 0);
+        bytes
+    })
+}
+
+/// Binary (`P5`) PGM with a maximum value of 255. `width` must not be 0.
+pub fn encode_pgm(pixels: &[u8], width: usize) -> Vec<u8> {
+    let mut image: Vec<u8> = format!("P5\n{} {}\n255\n", width, height(pixels, width)).into_bytes();
+    rows(pixels, width).for_each(|row| image.extend_from_slice(&row));
+    image
+}
+
+/// 8-bit grayscale PNG, its zlib stream made of stored blocks. `width` must not be 0.
+#[cfg(feature = "png")]
+pub fn encode_png(pixels: &[u8], width: usize) -> Vec<u8> {
+    let mut raw: Vec<u8> = vec![];
+    for row in rows(pixels, width) {
+        raw.push(0); // filter type None
+        raw.extend_from_slice(&row);
+    }
+
+    let mut zlib: Vec<u8> = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable(); // never empty, there is at least one row
+    while let Some(block) = blocks.next() {
+        let len: u16 = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8); // BFINAL, BTYPE 00
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header: Vec<u8> = vec![];
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height(pixels, width) as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 0, 0, 0, 0]); // bit depth, grayscale, deflate, adaptive filtering, no interlace
+
+    let mut image: Vec<u8> = vec![0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+    push_chunk(&mut image, b"IHDR", &header);
+    push_chunk(&mut image, b"IDAT", &zlib);
+    push_chunk(&mut image, b"IEND", &[]);
+    image
+}
+
+#[cfg(feature = "png")]
+fn push_chunk(image: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    image.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    image.extend_from_slice(kind);
+    image.extend_from_slice(data);
+    let crc: u32 = !kind.iter().chain(data).fold(!0u32, |crc: u32, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc: u32, _| if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 })
+    });
+    image.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(feature = "png")]
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b): (u32, u32) = data.iter().fold((1, 0), |(a, b), &byte| {
+        let a: u32 = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
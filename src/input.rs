@@ -0,0 +1,40 @@
+//! Input sources for [`crate::execute_code`] and friends.
+
+use std::io::{self, Read};
+
+/// Endless pseudo-random bytes from a seed, the same seed always giving the same bytes (SplitMix64).
+/// A program reading until end of input never stops on it, limit the steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomInput {
+    state: u64,
+    pending: [u8; 8], // bytes of the latest number not read yet, from `pending[used]` on
+    used: usize
+}
+
+impl RandomInput {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed, pending: [0; 8], used: 8 }
+    }
+
+    fn next_number(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut mixed: u64 = self.state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        mixed ^ (mixed >> 31)
+    }
+}
+
+impl Read for RandomInput {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        for byte in buffer.iter_mut() {
+            if self.used == self.pending.len() {
+                self.pending = self.next_number().to_le_bytes();
+                self.used = 0;
+            }
+            *byte = self.pending[self.used];
+            self.used += 1;
+        }
+        Ok(buffer.len())
+    }
+}
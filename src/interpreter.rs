@@ -0,0 +1,1329 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::analysis::{loop_excursions, reader_loop_inputs, Excursion};
+use crate::gas::Gas;
+use crate::parser::{Instruction, Program};
+use crate::snapshot::Snapshot;
+
+pub const TAPE_SIZE: usize = 30000;
+
+/// Number of steps between two looks at the [`RunControl`] of a run.
+pub const POLL_INTERVAL: u64 = 4096;
+
+/// What `,` stores in the current cell once the input is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofBehavior {
+    #[default]
+    Unchanged,
+    Zero,
+    Max
+}
+
+/// Shared between a run and whoever watches it, see [`crate::spawn_run`].
+///
+/// Progress is published and the cancellation flag is checked every [`POLL_INTERVAL`] steps.
+#[derive(Debug, Default)]
+pub struct RunControl {
+    cancelled: AtomicBool,
+    steps: AtomicU64,
+    instruction_index: AtomicUsize
+}
+
+impl RunControl {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Last published `(steps, instruction index)` pair.
+    pub fn progress(&self) -> (u64, usize) {
+        (self.steps.load(Ordering::Relaxed), self.instruction_index.load(Ordering::Relaxed))
+    }
+
+    fn publish(&self, interpreter: &Interpreter) {
+        self.steps.store(interpreter.steps, Ordering::Relaxed);
+        self.instruction_index.store(interpreter.instruction_index, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionConfig {
+    pub max_steps: Option<u64>, // fuel, every executed instruction except `Halt` burns one step
+    pub gas: Option<Gas>, // weighted fuel, an instruction whose cost would overdraw it fails with `GasExhausted`
+    pub max_output: Option<u64>, // maximum number of bytes `.` may write
+    pub eof: EofBehavior,
+    pub cell_modulus: Option<u16>, // 2 to 256, `+`/`-` wrap modulo it instead of at 256, see `add_cell`
+    pub loop_cell: Option<isize>, // `[` and `]` test this cell (relative to the start pointer) instead of the current one
+    pub max_pointer_range: Option<usize>, // cells `Interpreter::pointer_range` may span, a move widening it further fails
+    pub control: Option<Arc<RunControl>>,
+    pub break_on_output: Option<u8>, // `.` writing this byte fails the run with `OutputBreakpoint` right after
+    pub halt_on_output: Option<u8>, // `.` of this byte ends the run cleanly with `HaltReason::Sentinel`
+    pub emit_sentinel: bool, // whether the byte ending the run is written too
+    pub input_batch: Option<usize>, // bytes a `,` reading once per loop iteration fetches ahead, `None` for interactive input
+    pub step_loops: bool // step through every loop instruction by instruction, never hoisting one (a baseline for benchmarks)
+}
+
+/// `value + delta`, wrapping at 256 or modulo `modulus`. A value the cell got from `,` may be past
+/// the modulus, it is reduced too. A folded `Add` passes its whole delta, which is only known modulo
+/// 256 and so exact for a modulus dividing 256 (see [`cell_modulus_fits`]).
+#[inline]
+fn add_cell(value: u8, delta: i16, modulus: Option<u16>) -> u8 {
+    match modulus {
+        Some(modulus) => (value as i16 + delta).rem_euclid(modulus as i16) as u8,
+        None => value.wrapping_add(delta as u8)
+    }
+}
+
+/// Whether `program` may run with [`ExecutionConfig::cell_modulus`] set to `modulus`: it has to be 2 to
+/// 256, and divide 256 for a program whose runs of `+`/`-` only survive modulo 256 ([`Program::is_folded`]).
+pub fn cell_modulus_fits(modulus: u16, program: &Program) -> bool {
+    (2..=256).contains(&modulus) && (256 % modulus == 0 || !program.is_folded())
+}
+
+/// `InvalidCellModulus` unless the configured modulus fits `program`.
+fn check_cell_modulus(program: &Program, config: &ExecutionConfig) -> Result<(), RuntimeError> {
+    match config.cell_modulus {
+        Some(modulus) if !cell_modulus_fits(modulus, program) => Err(RuntimeError::InvalidCellModulus(modulus)),
+        _ => Ok(())
+    }
+}
+
+/// Why a run stopped without an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    EndOfProgram, // the closing `Halt` or the end of the instructions
+    Stopped, // a `Halt` placed by `Program::halt_at`
+    Cancelled,
+    Sentinel // `.` emitted `ExecutionConfig::halt_on_output`
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HaltReason::EndOfProgram => write!(f, "end of program"),
+            HaltReason::Stopped => write!(f, "stop point"),
+            HaltReason::Cancelled => write!(f, "cancelled"),
+            HaltReason::Sentinel => write!(f, "sentinel byte")
+        }
+    }
+}
+
+/// Why [`Interpreter::start_at`] refused an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryError {
+    OutOfRange { index: usize, len: usize },
+    InsideLoop { index: usize, begin: usize } // `begin` is the innermost enclosing `Begin`
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryError::OutOfRange { index, len } => write!(f, "Instruction {} is past the end of the program ({} instructions)", index, len),
+            EntryError::InsideLoop { index, begin } => {
+                write!(f, "Instruction {} is inside the loop opened by instruction {}, runs can only start outside loops", index, begin)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EntryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSummary {
+    pub steps: u64,
+    pub gas_used: u64,
+    pub output_bytes: u64,
+    pub halt: HaltReason
+}
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    PointerOverflow,
+    PointerUnderflow,
+    TapeLimitExceeded, // growable tape would need more cells than it is allowed to have
+    StepLimitExceeded,
+    GasExhausted, // see `ExecutionConfig::gas`, the instruction index stays at the unaffordable instruction
+    OutputLimitExceeded,
+    SandboxViolation, // pointer left the region given to `Interpreter::set_sandbox`
+    PointerRangeExceeded, // see `ExecutionConfig::max_pointer_range`, the pointer stays where it was
+    OutputBreakpoint(u8), // see `ExecutionConfig::break_on_output`, the instruction index stays at the `.`
+    InvalidCellModulus(u16), // see `cell_modulus_fits`, the run fails before its first step
+    Io(io::Error)
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::PointerOverflow => write!(f, "Pointer out of bounds, overflow"),
+            RuntimeError::PointerUnderflow => write!(f, "Pointer out of bounds, underflow"),
+            RuntimeError::TapeLimitExceeded => write!(f, "Tape cell limit exceeded"),
+            RuntimeError::StepLimitExceeded => write!(f, "Step limit exceeded"),
+            RuntimeError::GasExhausted => write!(f, "Out of gas"),
+            RuntimeError::OutputLimitExceeded => write!(f, "Output limit exceeded"),
+            RuntimeError::SandboxViolation => write!(f, "Pointer left the sandbox region"),
+            RuntimeError::PointerRangeExceeded => write!(f, "Pointer range limit exceeded"),
+            RuntimeError::OutputBreakpoint(byte) => write!(f, "Output breakpoint hit, byte {} ({:?})", byte, *byte as char),
+            RuntimeError::InvalidCellModulus(modulus) => write!(f, "Cell modulus {} has to be 2 to 256, and divide 256 for a folded program", modulus),
+            RuntimeError::Io(err) => write!(f, "I/O error: {}", err)
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RuntimeError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        RuntimeError::Io(err)
+    }
+}
+
+/// How the tape reacts to the pointer leaving the allocated cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeMode {
+    Fixed, // running off either end is an error
+    Growable, // grows to the right, running off the left end is an error
+    Bidirectional // grows in both directions
+}
+
+/// How [`Interpreter::overlay`] combines a cell of the other tape into the same cell of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeMode {
+    Add, // wrapping sum
+    Or, // bitwise
+    Max,
+    CopyNonZero // take the other cell unless it is 0
+}
+
+/// Tape, pointer and execution position of a (possibly paused) run.
+///
+/// The tape grows on demand until it holds `max_cells` cells, a fixed tape is simply one which is
+/// fully allocated up front. Growing to the left prepends cells, which shifts every physical index
+/// (including [`Interpreter::start_pointer`]) to the right, so anything remembering positions across
+/// steps should keep them relative to the start pointer.
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    buffer: Vec<u8>,
+    pointer: usize,
+    start_pointer: usize,
+    max_cells: usize,
+    mode: TapeMode,
+    sandbox: Option<std::ops::Range<usize>>, // cells the pointer may visit, see `Interpreter::set_sandbox`
+    lowest: isize, // leftmost cell the pointer reached, relative to the start pointer
+    highest: isize, // rightmost one
+    untracked: bool, // cells outside `lowest..=highest` may be set too, by `restore` or `overlay`
+
+    instruction_index: usize,
+    steps: u64,
+    gas_used: u64,
+    input_bytes: u64,
+    output_bytes: u64
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self {
+            buffer: vec![0; TAPE_SIZE],
+            pointer: TAPE_SIZE / 2,
+            start_pointer: TAPE_SIZE / 2,
+            max_cells: TAPE_SIZE,
+            mode: TapeMode::Fixed,
+            sandbox: None,
+            lowest: 0,
+            highest: 0,
+            untracked: false,
+
+            instruction_index: 0,
+            steps: 0,
+            gas_used: 0,
+            input_bytes: 0,
+            output_bytes: 0
+        }
+    }
+}
+
+impl Interpreter {
+    /// Fixed tape of `size` cells with the pointer starting in the middle, like the default one.
+    pub fn fixed(size: usize) -> Self {
+        let size: usize = size.max(1);
+        Self { buffer: vec![0; size], pointer: size / 2, start_pointer: size / 2, max_cells: size, ..Self::default() }
+    }
+
+    /// Tape that starts as a single cell with the pointer at 0 and grows rightwards up to `max_cells` cells.
+    pub fn growable(max_cells: usize) -> Self {
+        Self::dynamic(max_cells, TapeMode::Growable)
+    }
+
+    /// Tape that starts as a single cell and grows in whichever direction the pointer leaves it,
+    /// up to `max_cells` cells in total.
+    pub fn bidirectional(max_cells: usize) -> Self {
+        Self::dynamic(max_cells, TapeMode::Bidirectional)
+    }
+
+    fn dynamic(max_cells: usize, mode: TapeMode) -> Self {
+        Self { buffer: vec![0; 1], pointer: 0, start_pointer: 0, max_cells: max_cells.max(1), mode, ..Self::default() }
+    }
+
+    /// Confines the pointer of a fixed tape to `region`, leaving it fails the run with
+    /// `RuntimeError::SandboxViolation`. The pointer moves to the start of the region unless it already
+    /// starts inside. Returns `false` (changing nothing) for a dynamic tape or a region which is empty or
+    /// not on the tape.
+    pub fn set_sandbox(&mut self, region: std::ops::Range<usize>) -> bool {
+        if self.mode != TapeMode::Fixed || region.is_empty() || region.end > self.buffer.len() {
+            return false;
+        }
+        if !region.contains(&self.start_pointer) {
+            // the extremes stay on the same cells, relative to the new start
+            let shift: isize = self.start_pointer as isize - region.start as isize;
+            (self.lowest, self.highest) = (self.lowest + shift, self.highest + shift);
+            self.start_pointer = region.start;
+            self.pointer = region.start;
+        }
+        self.sandbox = Some(region);
+        true
+    }
+
+    pub fn sandbox(&self) -> Option<std::ops::Range<usize>> {
+        self.sandbox.clone()
+    }
+
+    pub fn tape_mode(&self) -> TapeMode {
+        self.mode
+    }
+
+    /// Cells the tape may hold, its size for a fixed tape.
+    pub fn max_cells(&self) -> usize {
+        self.max_cells
+    }
+
+    /// Makes the run continue at instruction `index`, which has to be one of [`Program::entry_points`]
+    /// (or the end of the program) so every loop it runs into is entered through its `Begin`.
+    pub fn start_at(&mut self, program: &Program, index: usize) -> Result<(), EntryError> {
+        if index > program.len() {
+            return Err(EntryError::OutOfRange { index, len: program.len() });
+        }
+        if let Some(begin) = program.enclosing_loop(index) {
+            return Err(EntryError::InsideLoop { index, begin });
+        }
+        self.instruction_index = index;
+        Ok(())
+    }
+
+    /// Puts the interpreter back into the state it was created in, keeping the tape allocation. Only
+    /// the cells between the extremes of [`Interpreter::pointer_range`] are cleared, the rest of a
+    /// fixed tape was never written (unless it was restored or overlaid, then all of it is cleared).
+    pub fn reset(&mut self) {
+        if self.mode != TapeMode::Fixed {
+            self.buffer.truncate(1);
+            self.start_pointer = 0;
+        }
+        if self.untracked {
+            self.buffer.fill(0);
+        } else {
+            let first: usize = (self.start_pointer as isize + self.lowest).max(0) as usize;
+            let last: usize = ((self.start_pointer as isize + self.highest).max(0) as usize).min(self.buffer.len() - 1);
+            self.buffer[first.min(last)..=last].fill(0);
+        }
+        self.pointer = self.start_pointer;
+        (self.lowest, self.highest) = (0, 0);
+        self.untracked = false;
+
+        self.instruction_index = 0;
+        self.steps = 0;
+        self.gas_used = 0;
+        self.input_bytes = 0;
+        self.output_bytes = 0;
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Combines the tape of `other` into this one cell by cell, for experiments running several
+    /// programs on one memory layout. Cells are paired relative to the start pointers, so tapes of
+    /// different sizes or grown to the left line up; only cells both tapes hold are touched, the
+    /// pointer and everything else stay as they are.
+    pub fn overlay(&mut self, other: &Interpreter, mode: MergeMode) {
+        // the overlap in this tape's indices, `other` is shifted by the difference of the start pointers
+        let start: usize = self.start_pointer.saturating_sub(other.start_pointer);
+        let end: usize = self.buffer.len().min((self.start_pointer + other.buffer.len()).saturating_sub(other.start_pointer));
+        self.untracked = true;
+
+        for index in start..end {
+            let theirs: u8 = other.buffer[index + other.start_pointer - self.start_pointer];
+            let cell: &mut u8 = &mut self.buffer[index];
+            *cell = match mode {
+                MergeMode::Add => cell.wrapping_add(theirs),
+                MergeMode::Or => *cell | theirs,
+                MergeMode::Max => (*cell).max(theirs),
+                MergeMode::CopyNonZero if theirs != 0 => theirs,
+                MergeMode::CopyNonZero => *cell
+            };
+        }
+    }
+
+    /// FNV-1a hash of the tape contents, cheap way to compare final states of two runs.
+    pub fn tape_checksum(&self) -> u64 {
+        self.buffer.iter().fold(0xcbf29ce484222325, |hash: u64, &cell| (hash ^ cell as u64).wrapping_mul(0x100000001b3))
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// Where the pointer was when the interpreter was created (and goes back to on `reset`).
+    pub fn start_pointer(&self) -> usize {
+        self.start_pointer
+    }
+
+    pub fn current(&self) -> u8 {
+        self.buffer[self.pointer]
+    }
+
+    pub fn instruction_index(&self) -> usize {
+        self.instruction_index
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Gas burnt so far by runs with [`ExecutionConfig::gas`], not kept in snapshots.
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    /// Number of bytes `,` has read so far.
+    pub fn input_bytes(&self) -> u64 {
+        self.input_bytes
+    }
+
+    /// Leftmost and rightmost cell the pointer has reached, relative to the start pointer (negative
+    /// to its left). A resumed run starts counting where it was resumed, this isn't kept in snapshots.
+    pub fn pointer_range(&self) -> (isize, isize) {
+        (self.lowest, self.highest)
+    }
+
+    /// Number of bytes `.` has written so far.
+    pub fn output_bytes(&self) -> u64 {
+        self.output_bytes
+    }
+
+    /// Complete state of the run, see [`Interpreter::restore`].
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            tape_mode: self.mode,
+            max_cells: self.max_cells,
+            tape: self.buffer.clone(),
+            pointer: self.pointer,
+            start_pointer: self.start_pointer,
+            sandbox: self.sandbox.clone(),
+            instruction_index: self.instruction_index,
+            steps: self.steps,
+            input_bytes: self.input_bytes,
+            output_bytes: self.output_bytes
+        }
+    }
+
+    /// Interpreter continuing where the run of `snapshot` was, the caller has to supply the same
+    /// program and skip the `input_bytes` already consumed.
+    pub fn restore(snapshot: &Snapshot) -> Self {
+        let offset: isize = snapshot.pointer as isize - snapshot.start_pointer as isize;
+        Self {
+            buffer: snapshot.tape.clone(),
+            pointer: snapshot.pointer,
+            start_pointer: snapshot.start_pointer,
+            max_cells: snapshot.max_cells,
+            mode: snapshot.tape_mode,
+            sandbox: snapshot.sandbox.clone(),
+            lowest: offset,
+            highest: offset,
+            untracked: true,
+
+            instruction_index: snapshot.instruction_index,
+            steps: snapshot.steps,
+            gas_used: 0,
+            input_bytes: snapshot.input_bytes,
+            output_bytes: snapshot.output_bytes
+        }
+    }
+
+    pub fn summary(&self, halt: HaltReason) -> RunSummary {
+        RunSummary { steps: self.steps, gas_used: self.gas_used, output_bytes: self.output_bytes, halt }
+    }
+
+    #[inline]
+    fn move_right(&mut self, distance: usize, config: &ExecutionConfig) -> Result<(), RuntimeError> {
+        let target: usize = self.pointer + distance;
+        if let Some(sandbox) = &self.sandbox {
+            if target >= sandbox.end { return Err(RuntimeError::SandboxViolation); }
+        }
+        let offset: isize = target as isize - self.start_pointer as isize;
+        if offset > self.highest && config.max_pointer_range.is_some_and(|cells| (offset - self.lowest) as usize >= cells) {
+            return Err(RuntimeError::PointerRangeExceeded);
+        }
+        if target >= self.buffer.len() {
+            if target >= self.max_cells {
+                return Err(if self.mode == TapeMode::Fixed { RuntimeError::PointerOverflow } else { RuntimeError::TapeLimitExceeded });
+            }
+            self.buffer.resize(target + 1, 0);
+        }
+        self.pointer = target;
+        self.highest = self.highest.max(offset); // only once the move succeeded
+
+        Ok(())
+    }
+
+    #[inline]
+    fn move_left(&mut self, distance: usize, config: &ExecutionConfig) -> Result<(), RuntimeError> {
+        if let Some(sandbox) = &self.sandbox {
+            if distance > self.pointer - sandbox.start { return Err(RuntimeError::SandboxViolation); }
+        }
+        let offset: isize = self.pointer as isize - distance as isize - self.start_pointer as isize;
+        if offset < self.lowest && config.max_pointer_range.is_some_and(|cells| (self.highest - offset) as usize >= cells) {
+            return Err(RuntimeError::PointerRangeExceeded);
+        }
+        if distance > self.pointer {
+            if self.mode != TapeMode::Bidirectional { return Err(RuntimeError::PointerUnderflow); }
+            self.grow_left(distance - self.pointer)?;
+        }
+        self.pointer -= distance;
+        self.lowest = self.lowest.min(offset); // only once the move succeeded
+
+        Ok(())
+    }
+
+    /// Prepends at least `needed` cells, doubling the tape when possible so repeated `<` past the
+    /// start stays amortized O(1).
+    #[cold]
+    fn grow_left(&mut self, needed: usize) -> Result<(), RuntimeError> {
+        let room: usize = self.max_cells - self.buffer.len();
+        if needed > room {
+            return Err(RuntimeError::TapeLimitExceeded);
+        }
+
+        let grown: usize = needed.max(self.buffer.len()).min(room);
+        self.buffer.splice(0..0, std::iter::repeat_n(0, grown));
+        self.pointer += grown;
+        self.start_pointer += grown;
+
+        Ok(())
+    }
+
+    /// Runs the loop starting at the `Begin` under the instruction index to completion, checking the
+    /// tape bounds once per iteration instead of on every move. Gives up (returning `false`, with the
+    /// state exactly as after the iterations done so far) as soon as an iteration could leave the
+    /// allocated tape or exhaust the step budget, normal stepping then takes over. A loop running past
+    /// `next_poll` publishes its progress to `config.control` like the run loop, and on cancellation
+    /// returns `true` between two iterations for the run loop to see the flag at its own poll.
+    fn run_hoisted(&mut self, hoisted: &HoistedLoop, config: &ExecutionConfig, next_poll: &mut u64) -> bool {
+        let excursion: Excursion = hoisted.excursion;
+        let iteration_steps: u64 = excursion.body_len as u64 + 1; // the body and the closing `]`
+
+        if self.buffer[self.pointer] == 0 || !self.hoisting_fits(excursion, 1, config) {
+            return false;
+        }
+        let begin: usize = self.instruction_index;
+        self.steps += 1; // the `[` itself
+        self.instruction_index = begin + 1;
+
+        loop {
+            if !self.hoisting_fits(excursion, iteration_steps, config) {
+                return false;
+            }
+
+            let offset: isize = self.pointer as isize - self.start_pointer as isize;
+            self.lowest = self.lowest.min(offset - excursion.left as isize);
+            self.highest = self.highest.max(offset + excursion.right as isize);
+            let window: &mut [u8] = &mut self.buffer[self.pointer - excursion.left..=self.pointer + excursion.right];
+            for &operation in &hoisted.operations {
+                match operation {
+                    CellOperation::Add(cell, delta) => { window[cell] = window[cell].wrapping_add(delta); },
+                    CellOperation::Clear(cell) => { window[cell] = 0; }
+                }
+            }
+            self.pointer = self.pointer.wrapping_add_signed(excursion.shift);
+            self.steps += iteration_steps;
+
+            if self.buffer[self.pointer] == 0 {
+                self.instruction_index = begin + excursion.body_len + 2;
+                return true;
+            }
+            if let Some(control) = config.control.as_ref().filter(|_| self.steps >= *next_poll) {
+                if control.is_cancelled() {
+                    return true;
+                }
+                *next_poll = self.steps + POLL_INTERVAL;
+                control.publish(self);
+            }
+        }
+    }
+
+    /// Whether `steps` more steps stay within the budget and the excursion around the pointer within the
+    /// tape. Metered runs never hoist, gas is charged instruction by instruction.
+    #[inline]
+    fn hoisting_fits(&self, excursion: Excursion, steps: u64, config: &ExecutionConfig) -> bool {
+        config.gas.is_none()
+            && config.cell_modulus.is_none()
+            && config.loop_cell.is_none()
+            && config.max_pointer_range.is_none()
+            && config.max_steps.is_none_or(|max_steps| self.steps + steps <= max_steps)
+            && self.pointer >= excursion.left
+            && self.pointer + excursion.right < self.buffer.len()
+            && self.sandbox.as_ref().is_none_or(|sandbox| {
+                self.pointer - excursion.left >= sandbox.start && self.pointer + excursion.right < sandbox.end
+            })
+    }
+
+    /// Executes up to `steps` instructions one by one, `Some` once the run has halted (possibly before
+    /// the call), `None` if it is still going and can be resumed by another call.
+    pub fn run_for(
+        &mut self,
+        program: &Program,
+        steps: u64,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        config: &ExecutionConfig
+    ) -> Result<Option<HaltReason>, RuntimeError> {
+        check_cell_modulus(program, config)?;
+        for _ in 0..steps {
+            let index: usize = self.instruction_index;
+            if !self.step(program, input, output, config)? {
+                return Ok(Some(halt_reason(program, index).unwrap_or(HaltReason::Sentinel)));
+            }
+        }
+        Ok(halt_reason(program, self.instruction_index))
+    }
+
+    /// The cell `[` and `]` test, see [`ExecutionConfig::loop_cell`]. A cell the tape doesn't hold
+    /// (yet) reads as zero.
+    #[inline]
+    fn loop_test(&self, config: &ExecutionConfig) -> u8 {
+        match config.loop_cell {
+            Some(offset) => self.start_pointer.checked_add_signed(offset).and_then(|index| self.buffer.get(index)).copied().unwrap_or(0),
+            None => self.buffer[self.pointer]
+        }
+    }
+
+    /// Executes a single instruction, returns `false` once `Halt` or the end of the program is reached or
+    /// the sentinel byte was output (the instruction index is then past the `.`).
+    #[inline]
+    pub fn step(
+        &mut self,
+        program: &Program,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+        config: &ExecutionConfig
+    ) -> Result<bool, RuntimeError> {
+        let instruction: Instruction = program.instructions().get(self.instruction_index).copied().unwrap_or(Instruction::Halt);
+        if instruction == Instruction::Halt {
+            return Ok(false);
+        }
+
+        self.burn(instruction, config)?;
+
+        match instruction {
+            Instruction::IncrementPointer => {
+                self.move_right(1, config)?;
+                self.instruction_index += 1;
+            },
+            Instruction::DecrementPointer => {
+                self.move_left(1, config)?;
+                self.instruction_index += 1;
+            },
+            Instruction::Move(offset) => {
+                if offset < 0 { self.move_left(offset.unsigned_abs(), config)?; } else { self.move_right(offset as usize, config)?; }
+                self.instruction_index += 1;
+            },
+
+            Instruction::IncrementValue => {
+                self.buffer[self.pointer] = add_cell(self.buffer[self.pointer], 1, config.cell_modulus);
+                self.instruction_index += 1;
+            },
+            Instruction::DecrementValue => {
+                self.buffer[self.pointer] = add_cell(self.buffer[self.pointer], -1, config.cell_modulus);
+                self.instruction_index += 1;
+            },
+            Instruction::Add(delta) => {
+                self.buffer[self.pointer] = add_cell(self.buffer[self.pointer], delta as i16, config.cell_modulus);
+                self.instruction_index += 1;
+            },
+            Instruction::SetZero => {
+                self.buffer[self.pointer] = 0;
+                self.instruction_index += 1;
+            },
+
+            Instruction::InputValue => {
+                output.flush()?; // a prompt written before `,` has to be visible while it waits
+                let mut input_buffer: [u8; 1] = [0; 1];
+                let read: usize = read_retrying(input, &mut input_buffer)?;
+                self.store_input((read == 1).then_some(input_buffer[0]), config);
+                self.instruction_index += 1;
+            },
+            Instruction::OutputValue => {
+                if let Some(max_output) = config.max_output {
+                    if self.output_bytes >= max_output { return Err(RuntimeError::OutputLimitExceeded); }
+                }
+                if config.halt_on_output == Some(self.buffer[self.pointer]) {
+                    if config.emit_sentinel {
+                        output.write_all(&[self.buffer[self.pointer]])?;
+                        self.output_bytes += 1;
+                    }
+                    self.instruction_index += 1;
+                    return Ok(false);
+                }
+                output.write_all(&[self.buffer[self.pointer]])?;
+                self.output_bytes += 1;
+
+                if config.break_on_output == Some(self.buffer[self.pointer]) {
+                    return Err(RuntimeError::OutputBreakpoint(self.buffer[self.pointer]));
+                }
+
+                self.instruction_index += 1;
+            },
+            Instruction::OutputDecimal => {
+                let digits: String = self.buffer[self.pointer].to_string();
+                if let Some(max_output) = config.max_output {
+                    if self.output_bytes + digits.len() as u64 > max_output { return Err(RuntimeError::OutputLimitExceeded); }
+                }
+                output.write_all(digits.as_bytes())?;
+                self.output_bytes += digits.len() as u64;
+
+                self.instruction_index += 1;
+            },
+
+            Instruction::Begin => {
+                if self.loop_test(config) == 0 {
+                    self.instruction_index = program.jumps()[self.instruction_index];
+                } else {
+                    self.instruction_index += 1;
+                }
+            },
+
+            Instruction::End => {
+                if self.loop_test(config) != 0 {
+                    self.instruction_index = program.jumps()[self.instruction_index];
+                } else {
+                    self.instruction_index += 1;
+                }
+            },
+
+            Instruction::Halt => unreachable!()
+        }
+
+        Ok(true)
+    }
+
+    /// [`Interpreter::step`] taking the byte of a reader loop's `,` from the queue of `input`.
+    fn step_prefetching(
+        &mut self,
+        program: &Program,
+        input: &mut Prefetch,
+        output: &mut dyn Write,
+        config: &ExecutionConfig
+    ) -> Result<bool, RuntimeError> {
+        if !input.serves(self.instruction_index) {
+            return self.step(program, input, output, config);
+        }
+
+        self.burn(Instruction::InputValue, config)?;
+        let byte: Option<u8> = input.next_byte(output)?;
+        self.store_input(byte, config);
+        self.instruction_index += 1;
+        Ok(true)
+    }
+
+    /// Takes the step and the gas `instruction` costs out of the budgets, leaving them untouched if
+    /// either falls short.
+    #[inline]
+    fn burn(&mut self, instruction: Instruction, config: &ExecutionConfig) -> Result<(), RuntimeError> {
+        if let Some(max_steps) = config.max_steps {
+            if self.steps >= max_steps { return Err(RuntimeError::StepLimitExceeded); }
+        }
+        if let Some(gas) = &config.gas {
+            let gas_used: u64 = self.gas_used.saturating_add(gas.costs.cost(instruction));
+            if gas_used > gas.limit { return Err(RuntimeError::GasExhausted); }
+            self.gas_used = gas_used;
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// What `,` does with the byte it read, `None` being the end of the input.
+    fn store_input(&mut self, byte: Option<u8>, config: &ExecutionConfig) {
+        match (byte, config.eof) {
+            (Some(byte), _) => {
+                self.buffer[self.pointer] = byte;
+                self.input_bytes += 1;
+            },
+            (None, EofBehavior::Unchanged) => {},
+            (None, EofBehavior::Zero) => { self.buffer[self.pointer] = 0; },
+            (None, EofBehavior::Max) => { self.buffer[self.pointer] = u8::MAX; }
+        }
+    }
+}
+
+fn read_retrying(input: &mut dyn Read, buffer: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match input.read(buffer) {
+            Ok(read) => return Ok(read),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        }
+    }
+}
+
+/// Input of a run, with [`ExecutionConfig::input_batch`] the `,` of reader loops (see
+/// [`reader_loop_inputs`]) take their bytes from a queue refilled a batch at a time.
+///
+/// Every other read drains the queue before going back to the source, so the program sees the bytes in
+/// order; bytes fetched but not read by the end of the run are lost to the source.
+struct Prefetch<'a> {
+    source: &'a mut dyn Read,
+    readers: Vec<bool>, // by instruction index, empty without a batch size
+    queue: Vec<u8>,
+    next: usize, // first byte of `queue` not handed out yet
+    batch: usize
+}
+
+impl<'a> Prefetch<'a> {
+    fn new(source: &'a mut dyn Read, program: &Program, batch: Option<usize>) -> Self {
+        let readers: Vec<bool> = if batch.is_some() { reader_loop_inputs(program) } else { vec![] };
+        Self { source, readers, queue: vec![], next: 0, batch: batch.unwrap_or(1).max(1) }
+    }
+
+    fn serves(&self, index: usize) -> bool {
+        self.readers.get(index).copied().unwrap_or(false)
+    }
+
+    /// `None` only at the end of the input, never at the end of a batch.
+    fn next_byte(&mut self, output: &mut dyn Write) -> io::Result<Option<u8>> {
+        if self.next == self.queue.len() {
+            output.flush()?; // the refill may block like any other `,`
+            self.queue.resize(self.batch, 0);
+            self.next = 0;
+            let read: usize = read_retrying(self.source, &mut self.queue).inspect_err(|_| self.queue.clear())?;
+            self.queue.truncate(read);
+            if read == 0 {
+                return Ok(None);
+            }
+        }
+
+        self.next += 1;
+        Ok(Some(self.queue[self.next - 1]))
+    }
+}
+
+impl Read for Prefetch<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.next == self.queue.len() {
+            return self.source.read(buffer);
+        }
+        let read: usize = (&self.queue[self.next..]).read(buffer)?;
+        self.next += read;
+        Ok(read)
+    }
+}
+
+/// Body of a loop admitted by [`loop_excursions`] flattened to cell changes, indices are relative to
+/// `pointer - excursion.left` so the pointer moves vanish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellOperation {
+    Add(usize, u8),
+    Clear(usize)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HoistedLoop {
+    excursion: Excursion,
+    operations: Vec<CellOperation>
+}
+
+impl HoistedLoop {
+    fn lower(program: &Program, begin: usize, excursion: Excursion) -> Self {
+        let mut operations: Vec<CellOperation> = vec![];
+        let mut cell: usize = excursion.left;
+
+        for instruction in &program.instructions()[begin + 1..begin + 1 + excursion.body_len] {
+            let operation: CellOperation = match *instruction {
+                Instruction::IncrementPointer => { cell += 1; continue; },
+                Instruction::DecrementPointer => { cell -= 1; continue; },
+                Instruction::Move(offset) => { cell = cell.wrapping_add_signed(offset); continue; },
+                Instruction::IncrementValue => CellOperation::Add(cell, 1),
+                Instruction::DecrementValue => CellOperation::Add(cell, u8::MAX),
+                Instruction::Add(delta) => CellOperation::Add(cell, delta),
+                Instruction::SetZero => CellOperation::Clear(cell),
+                _ => unreachable!("`loop_excursions` only admits moves and cell changes")
+            };
+
+            match (operations.last_mut(), operation) {
+                (Some(CellOperation::Add(last, total)), CellOperation::Add(_, delta)) if *last == cell => { *total = total.wrapping_add(delta); },
+                _ => operations.push(operation)
+            }
+        }
+
+        Self { excursion, operations }
+    }
+}
+
+/// One executed instruction as seen by an [`Observer`]. `before`/`after` are the values of the cell at
+/// `pointer` (the pointer position before the instruction ran) around the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepEvent {
+    pub step: u64,
+    pub index: usize,
+    pub instruction: Instruction,
+    pub pointer: usize,
+    pub before: u8,
+    pub after: u8
+}
+
+/// Instrumentation hook for [`execute_observed`], called after every successfully executed instruction.
+pub trait Observer {
+    /// `false` skips building the events altogether, so an inactive observer costs nothing.
+    const ACTIVE: bool = true;
+
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter);
+}
+
+impl Observer for () {
+    const ACTIVE: bool = false;
+
+    fn on_step(&mut self, _event: &StepEvent, _interpreter: &Interpreter) {}
+}
+
+impl<O: Observer> Observer for Option<O> {
+    const ACTIVE: bool = O::ACTIVE;
+
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if let Some(observer) = self {
+            observer.on_step(event, interpreter);
+        }
+    }
+}
+
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    const ACTIVE: bool = A::ACTIVE || B::ACTIVE;
+
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        self.0.on_step(event, interpreter);
+        self.1.on_step(event, interpreter);
+    }
+}
+
+/// Runs `program` until it reaches `Instruction::Halt` or its end, or fails.
+pub fn execute_code(
+    program: &Program,
+    interpreter: &mut Interpreter,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    config: &ExecutionConfig
+) -> Result<RunSummary, RuntimeError> {
+    execute_observed(program, interpreter, input, output, config, &mut ())
+}
+
+/// [`execute_code`] reporting every step to `observer`.
+pub fn execute_observed<O: Observer>(
+    program: &Program,
+    interpreter: &mut Interpreter,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    config: &ExecutionConfig,
+    observer: &mut O
+) -> Result<RunSummary, RuntimeError> {
+    check_cell_modulus(program, config)?;
+    // observers need every step, only unobserved runs execute simple loops in one go
+    let hoisted_loops: Vec<Option<HoistedLoop>> = if O::ACTIVE || config.step_loops {
+        vec![]
+    } else {
+        let excursions = loop_excursions(program).into_iter().enumerate();
+        excursions.map(|(begin, excursion)| excursion.map(|excursion| HoistedLoop::lower(program, begin, excursion))).collect()
+    };
+
+    debug!("run starts at instruction {} after {} steps, {} loops hoisted", interpreter.instruction_index, interpreter.steps, hoisted_loops.iter().flatten().count());
+    let mut input: Prefetch = Prefetch::new(input, program, config.input_batch);
+    let result: Result<HaltReason, RuntimeError> = run_until_halt(program, interpreter, &mut input, output, config, observer, &hoisted_loops);
+
+    if let Some(control) = &config.control {
+        control.publish(interpreter);
+    }
+
+    // flushed on errors too, so everything written up to the failure reaches the sink
+    let flushed: io::Result<()> = output.flush();
+    match &result {
+        Ok(halt) => debug!("run halted ({}) after {} steps at instruction {}", halt, interpreter.steps, interpreter.instruction_index),
+        Err(err) => debug!("run failed after {} steps at instruction {}: {}", interpreter.steps, interpreter.instruction_index, err)
+    }
+    let halt: HaltReason = result?;
+    flushed?;
+    Ok(interpreter.summary(halt))
+}
+
+/// Why execution ends at `index`, `None` unless it is a `Halt` or past the last instruction.
+fn halt_reason(program: &Program, index: usize) -> Option<HaltReason> {
+    match program.instructions().get(index) {
+        None => Some(HaltReason::EndOfProgram),
+        Some(Instruction::Halt) if program.is_stop(index) => Some(HaltReason::Stopped),
+        Some(Instruction::Halt) => Some(HaltReason::EndOfProgram),
+        Some(_) => None
+    }
+}
+
+/// The loop of [`execute_observed`], stopping at the first error without flushing.
+#[allow(clippy::too_many_arguments)]
+fn run_until_halt<O: Observer>(
+    program: &Program,
+    interpreter: &mut Interpreter,
+    input: &mut Prefetch,
+    output: &mut dyn Write,
+    config: &ExecutionConfig,
+    observer: &mut O,
+    hoisted_loops: &[Option<HoistedLoop>]
+) -> Result<HaltReason, RuntimeError> {
+    let mut next_poll: u64 = interpreter.steps + POLL_INTERVAL;
+
+    Ok(loop {
+        if O::ACTIVE {
+            let index: usize = interpreter.instruction_index;
+            let pointer: usize = interpreter.pointer;
+            let before: u8 = interpreter.buffer[pointer];
+
+            let running: bool = interpreter.step_prefetching(program, input, output, config)?;
+            if let Some(reason) = halt_reason(program, index).filter(|_| !running) {
+                break reason;
+            }
+
+            let event: StepEvent = StepEvent {
+                step: interpreter.steps,
+                index,
+                instruction: program.instructions()[index],
+                pointer,
+                before,
+                after: interpreter.buffer[pointer]
+            };
+            observer.on_step(&event, interpreter);
+            if !running {
+                break HaltReason::Sentinel;
+            }
+        } else {
+            let index: usize = interpreter.instruction_index;
+            let hoisted: bool = match hoisted_loops.get(index) {
+                Some(Some(hoisted_loop)) => interpreter.run_hoisted(hoisted_loop, config, &mut next_poll),
+                _ => false
+            };
+            if !hoisted && !interpreter.step_prefetching(program, input, output, config)? {
+                // `step` stops either at `Halt` or after the `.` which emitted the sentinel
+                break halt_reason(program, index).unwrap_or(HaltReason::Sentinel);
+            }
+        }
+
+        if let Some(control) = &config.control {
+            if interpreter.steps >= next_poll {
+                next_poll = interpreter.steps + POLL_INTERVAL;
+                control.publish(interpreter);
+                if control.is_cancelled() { break HaltReason::Cancelled; }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_code;
+
+    fn run(code: &str, interpreter: &mut Interpreter, config: &ExecutionConfig) -> Result<RunSummary, RuntimeError> {
+        let program: Program = parse_code(code).unwrap();
+        execute_code(&program, interpreter, &mut io::empty(), &mut io::sink(), config)
+    }
+
+    /// How a run ended (errors compared by message), the tape, pointer, steps, instruction index and pointer range.
+    type Outcome = (Result<RunSummary, String>, Vec<u8>, usize, u64, usize, (isize, isize));
+
+    fn outcome(result: Result<RunSummary, RuntimeError>, interpreter: &Interpreter) -> Outcome {
+        let result: Result<RunSummary, String> = result.map_err(|err| err.to_string());
+        (result, interpreter.tape().to_vec(), interpreter.pointer(), interpreter.steps(), interpreter.instruction_index(), interpreter.pointer_range())
+    }
+
+    fn folded(code: &str) -> Program {
+        crate::parser::compile(code.as_bytes(), &crate::parser::ParseOptions { fold: true, ..Default::default() }, true).unwrap()
+    }
+
+    /// [`assert_same_as_stepping`] for a program which does have a loop to hoist.
+    fn assert_hoisting_exact(code: &str, interpreter: &Interpreter, config: &ExecutionConfig) {
+        assert!(loop_excursions(&folded(code)).iter().any(Option::is_some), "`{}` has no loop to hoist", code);
+        assert_same_as_stepping(code, interpreter, config);
+    }
+
+    /// Runs `code` with loop hoisting and stepping every instruction, both have to end the same way.
+    fn assert_same_as_stepping(code: &str, interpreter: &Interpreter, config: &ExecutionConfig) {
+        let program: Program = folded(code);
+
+        let mut hoisted: Interpreter = interpreter.clone();
+        let result = execute_code(&program, &mut hoisted, &mut io::empty(), &mut io::sink(), config);
+        let mut stepped: Interpreter = interpreter.clone();
+        let stepping: ExecutionConfig = ExecutionConfig { step_loops: true, ..config.clone() };
+        let reference = execute_code(&program, &mut stepped, &mut io::empty(), &mut io::sink(), &stepping);
+        assert_eq!(outcome(result, &hoisted), outcome(reference, &stepped), "`{}`", code);
+    }
+
+    #[test]
+    fn hoisted_loops_stop_at_the_tape_edges_like_stepping() {
+        let small: Interpreter = Interpreter::fixed(8); // starts at cell 4
+        for code in ["+[>+]", "+[<+]", "+[>>+]", "+[<<<+]", "+[->+>+<<]", "+[-<<<<+>>>>]", "+[->>>+<<<]", "+[-<<<<<+>>>>>]", ">>>+[<]", "<<<<+[>]"] {
+            assert_hoisting_exact(code, &small, &ExecutionConfig::default());
+        }
+        // the pointer on the first and the last cell
+        assert_hoisting_exact("<<<<+[-<+>]", &small, &ExecutionConfig::default());
+        assert_hoisting_exact(">>>+[->+<]", &small, &ExecutionConfig::default());
+        assert_hoisting_exact(">>>+[-<+>]>>", &small, &ExecutionConfig::default());
+    }
+
+    #[test]
+    fn hoisted_loops_grow_dynamic_tapes_like_stepping() {
+        for code in ["+[>+]", "+[-<+>]", "++++[->>+<<]>>[<]", "+[<+]"] {
+            assert_hoisting_exact(code, &Interpreter::growable(6), &ExecutionConfig::default());
+            assert_hoisting_exact(code, &Interpreter::bidirectional(6), &ExecutionConfig::default());
+        }
+    }
+
+    #[test]
+    fn hoisted_loops_respect_sandboxes_and_step_limits() {
+        let mut sandboxed: Interpreter = Interpreter::fixed(16);
+        assert!(sandboxed.set_sandbox(6..11));
+        for code in ["+[>+]", "+[<+]", "+[->>+<<]", "++[->>>>+<<<<]"] {
+            assert_hoisting_exact(code, &sandboxed, &ExecutionConfig::default());
+        }
+        for max_steps in [0, 1, 2, 3, 10, 11, 12, 13, 100] {
+            let config: ExecutionConfig = ExecutionConfig { max_steps: Some(max_steps), ..ExecutionConfig::default() };
+            assert_hoisting_exact("+++[->++<]>", &Interpreter::default(), &config);
+            assert_hoisting_exact("+[>+]", &Interpreter::fixed(8), &config);
+        }
+    }
+
+    #[test]
+    fn hoisted_loops_match_stepping_on_generated_loops() {
+        // bodies of moves and cell changes starting from cells near either end of a small tape
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: u64| -> u64 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+        for _ in 0..500 {
+            let mut code: String = "+".repeat(next(4) as usize + 1);
+            code.push_str(&if next(2) == 0 { ">".repeat(next(5) as usize) } else { "<".repeat(next(5) as usize) });
+            code.push('[');
+            for _ in 0..next(8) + 1 {
+                code.push(['+', '-', '>', '<', '>', '<'][next(6) as usize]);
+            }
+            code.push_str("-]");
+            let config: ExecutionConfig = ExecutionConfig { max_steps: Some(10_000), ..ExecutionConfig::default() };
+            assert_same_as_stepping(&code, &Interpreter::fixed(9), &config);
+            assert_same_as_stepping(&code, &Interpreter::growable(9), &config);
+        }
+    }
+
+    #[test]
+    fn pointer_range_tracks_both_extremes() {
+        let mut interpreter: Interpreter = Interpreter::default();
+        run(">>><<<<<", &mut interpreter, &ExecutionConfig::default()).unwrap();
+        assert_eq!(interpreter.pointer_range(), (-2, 3));
+    }
+
+    #[test]
+    fn failed_moves_leave_the_pointer_range_alone() {
+        let mut interpreter: Interpreter = Interpreter::fixed(4); // cells 0..4, starting at 2
+        assert!(matches!(run(">>", &mut interpreter, &ExecutionConfig::default()), Err(RuntimeError::PointerOverflow)));
+        assert_eq!(interpreter.pointer_range(), (0, 1));
+
+        let mut interpreter: Interpreter = Interpreter::fixed(4);
+        assert!(matches!(run("<<<", &mut interpreter, &ExecutionConfig::default()), Err(RuntimeError::PointerUnderflow)));
+        assert_eq!(interpreter.pointer_range(), (-2, 0));
+
+        let mut interpreter: Interpreter = Interpreter::growable(3);
+        assert!(matches!(run(">>>", &mut interpreter, &ExecutionConfig::default()), Err(RuntimeError::TapeLimitExceeded)));
+        assert_eq!(interpreter.pointer_range(), (0, 2));
+    }
+
+    #[test]
+    fn pointer_range_cap_fails_before_the_move() {
+        let config: ExecutionConfig = ExecutionConfig { max_pointer_range: Some(3), ..ExecutionConfig::default() };
+        let mut interpreter: Interpreter = Interpreter::default();
+        assert!(matches!(run("><<>>>", &mut interpreter, &config), Err(RuntimeError::PointerRangeExceeded)));
+        assert_eq!(interpreter.pointer_range(), (-1, 1));
+        assert_eq!(interpreter.pointer() - interpreter.start_pointer(), 1);
+    }
+
+    #[test]
+    fn pointer_range_is_relative_to_a_start_that_moved() {
+        let mut interpreter: Interpreter = Interpreter::bidirectional(100);
+        run("<<<>", &mut interpreter, &ExecutionConfig::default()).unwrap();
+        assert_eq!(interpreter.pointer_range(), (-3, 0));
+    }
+
+    #[test]
+    fn cell_modulus_out_of_range_fails_before_the_first_step() {
+        for modulus in [0, 1, 257, u16::MAX] {
+            let config: ExecutionConfig = ExecutionConfig { cell_modulus: Some(modulus), ..ExecutionConfig::default() };
+            let mut interpreter: Interpreter = Interpreter::default();
+            assert!(matches!(run("+", &mut interpreter, &config), Err(RuntimeError::InvalidCellModulus(value)) if value == modulus));
+            assert_eq!(interpreter.steps(), 0);
+            let result = interpreter.run_for(&parse_code("+").unwrap(), 1, &mut io::empty(), &mut io::sink(), &config);
+            assert!(matches!(result, Err(RuntimeError::InvalidCellModulus(_))));
+        }
+    }
+
+    #[test]
+    fn folded_runs_wrap_like_unfolded_ones_for_a_dividing_modulus() {
+        for modulus in [2, 4, 16, 64, 128, 256] {
+            let config: ExecutionConfig = ExecutionConfig { cell_modulus: Some(modulus), ..ExecutionConfig::default() };
+            for code in ["+".repeat(200), "-".repeat(56), "+".repeat(300), format!("{}>{}", "-".repeat(129), "+".repeat(127))] {
+                let mut unfolded: Interpreter = Interpreter::default();
+                run(&code, &mut unfolded, &config).unwrap();
+                let mut folding: Interpreter = Interpreter::default();
+                execute_code(&folded(&code), &mut folding, &mut io::empty(), &mut io::sink(), &config).unwrap();
+                assert_eq!(folding.tape(), unfolded.tape(), "{} with modulus {}", code, modulus);
+            }
+        }
+
+        let config: ExecutionConfig = ExecutionConfig { cell_modulus: Some(128), ..ExecutionConfig::default() };
+        let mut interpreter: Interpreter = Interpreter::default();
+        execute_code(&folded(&"+".repeat(200)), &mut interpreter, &mut io::empty(), &mut io::sink(), &config).unwrap();
+        assert_eq!(interpreter.tape()[interpreter.pointer()], 72);
+    }
+
+    #[test]
+    fn folded_programs_refuse_a_modulus_not_dividing_256() {
+        let config: ExecutionConfig = ExecutionConfig { cell_modulus: Some(100), ..ExecutionConfig::default() };
+        let code: String = "+".repeat(200);
+
+        let mut interpreter: Interpreter = Interpreter::default();
+        run(&code, &mut interpreter, &config).unwrap();
+        assert_eq!(interpreter.tape()[interpreter.pointer()], 0);
+
+        let mut interpreter: Interpreter = Interpreter::default();
+        assert!(matches!(
+            execute_code(&folded(&code), &mut interpreter, &mut io::empty(), &mut io::sink(), &config),
+            Err(RuntimeError::InvalidCellModulus(100))
+        ));
+        let mut interpreter: Interpreter = Interpreter::default();
+        assert!(matches!(
+            execute_code(&folded(&"+".repeat(256)), &mut interpreter, &mut io::empty(), &mut io::sink(), &config),
+            Err(RuntimeError::InvalidCellModulus(100))
+        ), "a run dropped for adding up to 256");
+        assert!(cell_modulus_fits(100, &parse_code(&code).unwrap()));
+    }
+
+    /// Hands out at most `chunk` bytes of `bytes` per read, like a pipe does.
+    struct ShortReads<'a> {
+        bytes: &'a [u8],
+        chunk: usize
+    }
+
+    impl Read for ShortReads<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let read: usize = buffer.len().min(self.chunk).min(self.bytes.len());
+            buffer[..read].copy_from_slice(&self.bytes[..read]);
+            self.bytes = &self.bytes[read..];
+            Ok(read)
+        }
+    }
+
+    /// Output, tape and bytes read of `code` on `input` read `chunk` bytes at a time.
+    fn batched(code: &str, input: &[u8], chunk: usize, config: &ExecutionConfig) -> (Vec<u8>, Vec<u8>, u64) {
+        let mut interpreter: Interpreter = Interpreter::fixed(16);
+        let mut output: Vec<u8> = vec![];
+        execute_code(&folded(code), &mut interpreter, &mut ShortReads { bytes: input, chunk }, &mut output, config).unwrap();
+        (output, interpreter.tape().to_vec(), interpreter.input_bytes())
+    }
+
+    #[test]
+    fn batched_input_ends_where_the_input_does() {
+        // every EOF policy with a reader loop it ends, the input ending on and between batch boundaries
+        let programs: [(&str, EofBehavior); 3] = [(",[.[-],]", EofBehavior::Unchanged), (",[.,]", EofBehavior::Zero), (",+[-.,+]", EofBehavior::Max)];
+        let input: Vec<u8> = (1..=24).collect();
+        for (code, eof) in programs {
+            for length in 0..=input.len() {
+                let unbatched: ExecutionConfig = ExecutionConfig { eof, max_steps: Some(10_000), ..ExecutionConfig::default() };
+                let reference = batched(code, &input[..length], 1, &unbatched);
+                for (batch, chunk) in [(1, 1), (4, 4), (4, 3), (8, 1), (8, 64), (64, 5)] {
+                    let config: ExecutionConfig = ExecutionConfig { input_batch: Some(batch), ..unbatched.clone() };
+                    assert_eq!(batched(code, &input[..length], chunk, &config), reference, "`{}` on {} bytes, batches of {} in reads of {}", code, length, batch, chunk);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reads_outside_a_reader_loop_drain_its_batch_first() {
+        let config: ExecutionConfig = ExecutionConfig { input_batch: Some(64), ..ExecutionConfig::default() };
+        let (output, _, read) = batched(",[.,],.,.,[.,]", b"ab\0cdef\0", 64, &config);
+        assert_eq!((&output[..], read), (&b"abcdef"[..], 8));
+
+        // after the end of the input the next reader loop sees the end too, not a stale batch
+        let zero: ExecutionConfig = ExecutionConfig { eof: EofBehavior::Zero, ..config };
+        let (output, tape, read) = batched(",[.,]>+,[.,]>+,", b"xy", 2, &zero);
+        assert_eq!((&output[..], read), (&b"xy"[..], 2));
+        assert_eq!(&tape[8..11], [0, 0, 0]);
+    }
+
+    /// Output, halt reason and steps of an echo of `input` up to its first zero byte, run with `config`
+    /// by `execute_code`, `execute_observed` and `run_for`, which all have to agree.
+    fn echoed(input: &[u8], config: &ExecutionConfig) -> (Vec<u8>, HaltReason, u64) {
+        struct Stepping;
+        impl Observer for Stepping {
+            fn on_step(&mut self, _event: &StepEvent, _interpreter: &Interpreter) {}
+        }
+
+        let program: Program = folded(",[.,]");
+        let mut endings: Vec<(Vec<u8>, HaltReason, u64)> = vec![];
+        for way in 0..3 {
+            let (mut interpreter, mut output): (Interpreter, Vec<u8>) = (Interpreter::default(), vec![]);
+            let halt: HaltReason = match way {
+                0 => execute_code(&program, &mut interpreter, &mut &input[..], &mut output, config).unwrap().halt,
+                1 => execute_observed(&program, &mut interpreter, &mut &input[..], &mut output, config, &mut Stepping).unwrap().halt,
+                _ => interpreter.run_for(&program, 1000, &mut &input[..], &mut output, config).unwrap().unwrap()
+            };
+            endings.push((output, halt, interpreter.steps()));
+        }
+        assert!(endings.windows(2).all(|pair| pair[0] == pair[1]), "{:?}", endings);
+        endings.remove(0)
+    }
+
+    #[test]
+    fn the_sentinel_ends_the_run_where_it_is_output() {
+        let sentinel = |byte: u8, emit_sentinel: bool| ExecutionConfig { halt_on_output: Some(byte), emit_sentinel, ..ExecutionConfig::default() };
+        // `,` then `[` and three steps per byte echoed: the newline is output in step 9
+        assert_eq!(echoed(b"ab\ncd\0", &sentinel(b'\n', false)), (b"ab".to_vec(), HaltReason::Sentinel, 9));
+        assert_eq!(echoed(b"ab\ncd\0", &sentinel(b'\n', true)), (b"ab\n".to_vec(), HaltReason::Sentinel, 9));
+        assert_eq!(echoed(b"ab\ncd\0", &sentinel(b'a', false)), (vec![], HaltReason::Sentinel, 3));
+        assert_eq!(echoed(b"ab\ncd\0", &sentinel(b'a', true)), (b"a".to_vec(), HaltReason::Sentinel, 3));
+    }
+
+    #[test]
+    fn a_sentinel_never_output_lets_the_run_end() {
+        let whole: (Vec<u8>, HaltReason, u64) = echoed(b"ab\ncd\0", &ExecutionConfig::default());
+        assert_eq!(whole, (b"ab\ncd".to_vec(), HaltReason::EndOfProgram, 17));
+        for emit_sentinel in [false, true] {
+            let config: ExecutionConfig = ExecutionConfig { halt_on_output: Some(b'z'), emit_sentinel, ..ExecutionConfig::default() };
+            assert_eq!(echoed(b"ab\ncd\0", &config), whole);
+        }
+        // read but never output, the zero byte ends the echo
+        let config: ExecutionConfig = ExecutionConfig { halt_on_output: Some(0), ..ExecutionConfig::default() };
+        assert_eq!(echoed(b"ab\0cd", &config), (b"ab".to_vec(), HaltReason::EndOfProgram, 8));
+    }
+}
@@ -0,0 +1,131 @@
+//! Lowering of a [`Program`] to a small three-address listing, to show what brainfuck boils down to
+//! on a register machine.
+//!
+//! The machine has two registers, `p` (the cell pointer) and `v` (a scratch byte), and these opcodes:
+//!
+//! | opcode            | effect                                                               |
+//! |-------------------|----------------------------------------------------------------------|
+//! | `p = add p, K`    | moves the pointer by K cells (negative to the left)                  |
+//! | `v = load p`      | reads the cell under the pointer                                     |
+//! | `store p, v`      | writes `v` to the cell under the pointer, `store p, 0` clears it     |
+//! | `v = add v, K`    | adds K to `v`, wrapping around at 256 (`sub` subtracts)              |
+//! | `out v`           | writes `v` as a byte, `outdec v` as a decimal number                 |
+//! | `v = in v`        | reads a byte into `v`, leaving it as it was at the end of the input  |
+//! | `brz v, L`        | continues at label `L` if `v` is zero                                |
+//! | `brnz v, L`       | continues at label `L` if `v` is not zero                            |
+//! | `halt`            | ends the program                                                     |
+//!
+//! Label `Ln` marks the lowering of instruction `n`, only jump targets get one. Every instruction is
+//! lowered on its own, so `v` never carries a value from one instruction to the next. Without a
+//! closing `halt` the program ends by running off the end of the listing.
+
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::parser::{Instruction, Program};
+
+fn lower(instruction: Instruction, target: usize) -> Vec<String> {
+    let add = |delta: u8| if delta > 128 { format!("v = sub v, {}", delta.wrapping_neg()) } else { format!("v = add v, {}", delta) };
+
+    match instruction {
+        Instruction::IncrementPointer => vec!["p = add p, 1".to_string()],
+        Instruction::DecrementPointer => vec!["p = add p, -1".to_string()],
+        Instruction::Move(offset) => vec![format!("p = add p, {}", offset)],
+
+        Instruction::IncrementValue => vec!["v = load p".to_string(), add(1), "store p, v".to_string()],
+        Instruction::DecrementValue => vec!["v = load p".to_string(), add(255), "store p, v".to_string()],
+        Instruction::Add(delta) => vec!["v = load p".to_string(), add(delta), "store p, v".to_string()],
+        Instruction::SetZero => vec!["store p, 0".to_string()],
+
+        Instruction::OutputValue => vec!["v = load p".to_string(), "out v".to_string()],
+        Instruction::OutputDecimal => vec!["v = load p".to_string(), "outdec v".to_string()],
+        Instruction::InputValue => vec!["v = load p".to_string(), "v = in v".to_string(), "store p, v".to_string()],
+
+        Instruction::Begin => vec!["v = load p".to_string(), format!("brz v, L{}", target)],
+        Instruction::End => vec!["v = load p".to_string(), format!("brnz v, L{}", target)],
+
+        Instruction::Halt => vec!["halt".to_string()]
+    }
+}
+
+/// [`line_col`](crate::line_col) of positions in ascending order, each scan picking up where the last one stopped so a
+/// listing takes time linear in the source.
+struct Lines<'a> {
+    source: &'a [u8],
+    scanned: usize, // bytes counted so far
+    line: usize, // of `scanned`
+    line_start: usize
+}
+
+impl<'a> Lines<'a> {
+    fn new(source: &'a [u8]) -> Self {
+        Self { source, scanned: 0, line: 1, line_start: 0 }
+    }
+
+    fn at(&mut self, position: usize) -> (usize, usize) {
+        let end: usize = position.min(self.source.len());
+        if end < self.scanned {
+            *self = Self::new(self.source);
+        }
+        for (offset, &byte) in self.source[self.scanned..end].iter().enumerate() {
+            if byte == b'\n' {
+                self.line += 1;
+                self.line_start = self.scanned + offset + 1;
+            }
+        }
+        self.scanned = end;
+        (self.line, position - self.line_start + 1)
+    }
+}
+
+/// Textual listing of `program` in the register machine described above, each instruction
+/// annotated with its brainfuck form and where it is in `source`.
+pub fn emit_ir(program: &Program, source: &[u8]) -> String {
+    emit_ir_range(program, source, 0..program.len())
+}
+
+/// [`emit_ir`] of the instructions in `range` only, keeping their labels and numbering.
+pub fn emit_ir_range(program: &Program, source: &[u8], range: Range<usize>) -> String {
+    let instructions: &[Instruction] = program.instructions();
+    let mut targets: Vec<bool> = vec![false; instructions.len() + 1]; // a loop may jump to the very end
+    for (index, instruction) in instructions.iter().enumerate() {
+        if matches!(instruction, Instruction::Begin | Instruction::End) {
+            targets[program.jumps()[index]] = true;
+        }
+    }
+
+    let mut lines: Lines = Lines::new(source);
+    let mut listing: String = String::new();
+    for (index, &instruction) in instructions.iter().enumerate().take(range.end).skip(range.start) {
+        if targets[index] {
+            let _ = writeln!(listing, "L{}:", index);
+        }
+        let (line, column) = lines.at(program.positions()[index]);
+        for (number, op) in lower(instruction, program.jumps()[index]).iter().enumerate() {
+            if number == 0 {
+                let _ = writeln!(listing, "    {:<20} ; {:<5} line {}:{}", op, instruction.to_string(), line, column);
+            } else {
+                let _ = writeln!(listing, "    {}", op);
+            }
+        }
+    }
+    if range.end == instructions.len() && targets[instructions.len()] {
+        let _ = writeln!(listing, "L{}:", instructions.len());
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::line_col;
+
+    #[test]
+    fn lines_count_like_line_col() {
+        let source: &[u8] = b"+[\n>\n\n-]\r\n.";
+        let mut lines: Lines = Lines::new(source);
+        for position in (0..=source.len() + 2).chain([4, 0, 9, 3]) {
+            assert_eq!(lines.at(position), line_col(source, position), "position {}", position);
+        }
+    }
+}
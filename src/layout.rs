@@ -0,0 +1,300 @@
+//! Named tape layouts: a tiny TOML subset mapping names to cells relative to the starting pointer.
+//!
+//! ```toml
+//! counter = 0                                      # single cell
+//! buffer = [1, 5]                                  # cells 1, 2, 3 and 4
+//! total = { offset = 5, width = 2, endian = "big" } # 16-bit integer in cells 5 and 6
+//! ```
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Cells, // independent cells, rendered one by one
+    Integer(Endian) // one number spread over `width` cells
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub name: String,
+    pub offset: isize, // relative to the pointer position the run starts at
+    pub width: usize,
+    pub kind: FieldKind
+}
+
+impl Field {
+    /// Absolute cell indices of the field for a run starting at `origin`, `None` if they fall off the tape.
+    pub fn cells(&self, origin: usize) -> Option<std::ops::Range<usize>> {
+        let start: usize = origin.checked_add_signed(self.offset)?;
+        Some(start..start.checked_add(self.width)?)
+    }
+
+    /// Field value as text, cells that fall off `tape` read as 0.
+    pub fn render(&self, tape: &[u8], origin: usize) -> String {
+        let values: Vec<u8> = match self.cells(origin) {
+            Some(cells) => cells.map(|index| tape.get(index).copied().unwrap_or(0)).collect(),
+            None => vec![0; self.width]
+        };
+
+        match self.kind {
+            FieldKind::Cells if self.width == 1 => format!("{}", values[0]),
+            FieldKind::Cells => format!("{:?}", values),
+            FieldKind::Integer(endian) => {
+                let ordered: Vec<u8> = match endian {
+                    Endian::Little => values.into_iter().rev().collect(),
+                    Endian::Big => values
+                };
+                format!("{}", ordered.iter().fold(0u128, |value, &byte| (value << 8) | byte as u128))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "layout line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Layout {
+    fields: Vec<Field>
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal: usize = row[0];
+        row[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let above: usize = row[j + 1];
+            row[j + 1] = (above + 1).min(row[j] + 1).min(diagonal + usize::from(a_char != b_char));
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+fn parse_integer(text: &str, line: usize) -> Result<isize, LayoutError> {
+    text.trim().parse().map_err(|_| LayoutError { line, message: format!("expected an integer, found `{}`", text.trim()) })
+}
+
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let mut in_string: bool = false;
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => { in_string = !in_string; },
+            '#' if !in_string => { return &line[..index]; },
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Widest `[start, end]` range, every cell of it is rendered whenever the field is.
+pub const MAX_RANGE_WIDTH: usize = 65536;
+
+fn parse_field(name: &str, value: &str, line: usize) -> Result<Field, LayoutError> {
+    let error = |message: String| LayoutError { line, message };
+
+    if let Some(inner) = value.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let bounds: Vec<&str> = inner.split(',').collect();
+        if bounds.len() != 2 {
+            return Err(error(format!("range of `{}` must be `[start, end]`", name)));
+        }
+        let (start, end) = (parse_integer(bounds[0], line)?, parse_integer(bounds[1], line)?);
+        if end <= start {
+            return Err(error(format!("range of `{}` is empty", name)));
+        }
+        let width: usize = end.checked_sub(start).map_or(usize::MAX, |width| width as usize);
+        if width > MAX_RANGE_WIDTH {
+            return Err(error(format!("range of `{}` is {} cells wide, at most {}", name, end as i128 - start as i128, MAX_RANGE_WIDTH)));
+        }
+        return Ok(Field { name: name.to_string(), offset: start, width, kind: FieldKind::Cells });
+    }
+
+    if let Some(inner) = value.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+        let mut field: Field = Field { name: name.to_string(), offset: 0, width: 1, kind: FieldKind::Integer(Endian::Little) };
+        let mut has_offset: bool = false;
+
+        for entry in inner.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, entry_value) = entry.split_once('=').ok_or_else(|| error(format!("expected `key = value`, found `{}`", entry)))?;
+            match key.trim() {
+                "offset" => {
+                    field.offset = parse_integer(entry_value, line)?;
+                    has_offset = true;
+                },
+                "width" => {
+                    let width: isize = parse_integer(entry_value, line)?;
+                    if !(1..=16).contains(&width) {
+                        return Err(error(format!("width of `{}` must be between 1 and 16", name)));
+                    }
+                    field.width = width as usize;
+                },
+                "endian" => {
+                    field.kind = match entry_value.trim().trim_matches('"') {
+                        "little" => FieldKind::Integer(Endian::Little),
+                        "big" => FieldKind::Integer(Endian::Big),
+                        other => return Err(error(format!("endian must be \"little\" or \"big\", found `{}`", other)))
+                    };
+                },
+                other => return Err(error(format!("unknown key `{}` in `{}`", other, name)))
+            }
+        }
+
+        if !has_offset {
+            return Err(error(format!("`{}` is missing its offset", name)));
+        }
+        return Ok(field);
+    }
+
+    Ok(Field { name: name.to_string(), offset: parse_integer(value, line)?, width: 1, kind: FieldKind::Cells })
+}
+
+impl Layout {
+    pub fn parse(text: &str) -> Result<Self, LayoutError> {
+        let mut layout: Layout = Layout::default();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line: usize = index + 1;
+            let content: &str = strip_comment(raw_line).trim();
+            if content.is_empty() {
+                continue;
+            }
+
+            let (name, value) = content
+                .split_once('=')
+                .ok_or_else(|| LayoutError { line, message: format!("expected `name = offset`, found `{}`", content) })?;
+            let name: &str = name.trim().trim_matches('"');
+            if name.is_empty() || name.parse::<usize>().is_ok() {
+                return Err(LayoutError { line, message: format!("`{}` is not a valid field name", name) });
+            }
+            if layout.field(name).is_some() {
+                return Err(LayoutError { line, message: format!("`{}` is defined twice", name) });
+            }
+
+            layout.fields.push(parse_field(name, value.trim(), line)?);
+        }
+
+        Ok(layout)
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Field named `name`, or an error suggesting the closest known name.
+    pub fn resolve(&self, name: &str) -> Result<&Field, String> {
+        if let Some(field) = self.field(name) {
+            return Ok(field);
+        }
+
+        let closest: Option<&Field> = self.fields.iter().min_by_key(|field| edit_distance(&field.name, name));
+        match closest {
+            Some(field) if edit_distance(&field.name, name) <= field.name.len().max(name.len()) / 2 + 1 => {
+                Err(format!("unknown field `{}`, did you mean `{}`?", name, field.name))
+            },
+            _ => Err(format!("unknown field `{}`", name))
+        }
+    }
+
+    /// Name of the cell at absolute index `index` for a run starting at `origin`, with the position
+    /// inside the field for multi-cell fields (`buffer[2]`).
+    pub fn label(&self, index: usize, origin: usize) -> Option<String> {
+        self.fields.iter().find_map(|field| {
+            let cells = field.cells(origin)?;
+            if !cells.contains(&index) {
+                return None;
+            }
+            Some(if field.width == 1 { field.name.clone() } else { format!("{}[{}]", field.name, index - cells.start) })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = "counter = 0\nbuffer = [1, 4] # three cells\nlittle = { offset = -2, width = 2 }\nbig = { offset = 4, width = 2, endian = \"big\" }\n";
+
+    fn message(text: &str) -> String {
+        Layout::parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn names_resolve_or_suggest_the_closest_one() {
+        let layout: Layout = Layout::parse(LAYOUT).unwrap();
+        assert_eq!(layout.resolve("buffer").unwrap().offset, 1);
+        assert_eq!(layout.resolve("bufer").unwrap_err(), "unknown field `bufer`, did you mean `buffer`?");
+        assert_eq!(layout.resolve("countr").unwrap_err(), "unknown field `countr`, did you mean `counter`?");
+        assert_eq!(layout.resolve("pointer_to_nothing").unwrap_err(), "unknown field `pointer_to_nothing`");
+        assert_eq!(Layout::default().resolve("counter").unwrap_err(), "unknown field `counter`");
+    }
+
+    #[test]
+    fn multi_cell_fields_render_as_lists_or_numbers() {
+        let layout: Layout = Layout::parse(LAYOUT).unwrap();
+        // origin 2: little in cells 0 and 1, counter in 2, buffer in 3..6, big in 6 and 7
+        let tape: [u8; 8] = [0x34, 0x12, 9, 1, 2, 3, 0x56, 0x78];
+        let rendered: Vec<String> = layout.fields().iter().map(|field| field.render(&tape, 2)).collect();
+        assert_eq!(rendered, ["9", "[1, 2, 3]", "4660", "22136"]);
+        // cells off the tape read as 0
+        assert_eq!(layout.field("buffer").unwrap().render(&tape, 6), "[120, 0, 0]");
+        assert_eq!(layout.field("little").unwrap().render(&tape, 0), "0");
+    }
+
+    #[test]
+    fn dump_labels_name_the_cell_inside_its_field() {
+        let layout: Layout = Layout::parse(LAYOUT).unwrap();
+        let labels: Vec<Option<String>> = (0..9).map(|index| layout.label(index, 2)).collect();
+        assert_eq!(labels, [
+            Some("little[0]".to_string()),
+            Some("little[1]".to_string()),
+            Some("counter".to_string()),
+            Some("buffer[0]".to_string()),
+            Some("buffer[1]".to_string()),
+            Some("buffer[2]".to_string()),
+            Some("big[0]".to_string()),
+            Some("big[1]".to_string()),
+            None
+        ]);
+        // from cell 0, `little` falls off the left end and cell 0 is `counter`
+        assert_eq!(layout.label(0, 0), Some("counter".to_string()));
+    }
+
+    #[test]
+    fn ranges_are_checked_without_overflowing() {
+        assert_eq!(
+            message("far = [-9223372036854775807, 9223372036854775807]"),
+            "layout line 1: range of `far` is 18446744073709551614 cells wide, at most 65536"
+        );
+        assert_eq!(message("huge = [0, 4000000000]"), "layout line 1: range of `huge` is 4000000000 cells wide, at most 65536");
+        assert_eq!(message("empty = [3, 3]"), "layout line 1: range of `empty` is empty");
+        assert_eq!(Layout::parse("widest = [0, 65536]").unwrap().fields()[0].width, MAX_RANGE_WIDTH);
+
+        let layout: Layout = Layout::parse("end = [9223372036854775806, 9223372036854775807]").unwrap();
+        assert_eq!(layout.fields()[0].cells(usize::MAX - 1), None);
+        assert_eq!(layout.label(0, usize::MAX - 1), None);
+    }
+}
@@ -0,0 +1,66 @@
+//! Very basic brainfuck interpreter, usable both from the `bf_interpreter` binary and as a library.
+
+/// Debug event for [`logging`], compiled out without the `logging` feature.
+#[cfg(feature = "logging")]
+macro_rules! debug {
+    ($($arg:tt)+) => { $crate::logging::emit(module_path!(), format_args!($($arg)+)) };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! debug {
+    ($($arg:tt)+) => { if false { let _ = format_args!($($arg)+); } };
+}
+
+pub mod analysis;
+pub mod asm;
+mod background;
+pub mod console;
+pub mod constprop;
+pub mod corpus;
+pub mod cost;
+pub mod cycles;
+mod determinism;
+mod explain;
+mod gas;
+pub mod golf;
+pub mod image;
+pub mod input;
+mod interpreter;
+pub mod ir;
+pub mod layout;
+pub mod lint;
+#[cfg(feature = "logging")]
+pub mod logging;
+pub mod manifest;
+pub mod multi;
+pub mod output;
+mod parser;
+pub mod passes;
+mod pipeline;
+mod pool;
+pub mod post_mortem;
+pub mod reduce;
+pub mod report;
+mod run;
+pub mod slice;
+mod snapshot;
+mod source;
+pub mod taint;
+pub mod tape_diff;
+mod untrusted;
+
+pub use background::{spawn_run, RunHandle};
+pub use determinism::{verify_determinism, Divergence};
+pub use explain::explain_step;
+pub use gas::{Gas, GasCosts};
+pub use interpreter::{
+    cell_modulus_fits, execute_code, execute_observed, EntryError, EofBehavior, ExecutionConfig, HaltReason, Interpreter, MergeMode, Observer, RunControl, RunSummary,
+    RuntimeError, StepEvent, TapeMode, POLL_INTERVAL, TAPE_SIZE
+};
+pub use parser::{bracket_pairs, compile, parse_bytes, parse_bytes_with, parse_code, Instruction, ParseError, ParseOptions, Program};
+pub use pipeline::{run_pipeline, Stage};
+pub use pool::{InterpreterPool, PoolStats, PooledInterpreter, TapeConfig};
+pub use run::{run_full, Compat, FilterMode, InputSpec, OutputSpec, Run, RunError, RunReport, Source, DYNAMIC_TAPE_LIMIT};
+pub use snapshot::{Snapshot, SnapshotError};
+pub use source::{highlight_position, line_col, position_at};
+pub use untrusted::{run_untrusted, Limit, Limits, Outcome};
@@ -0,0 +1,126 @@
+//! Balanced but suspicious loops, reported by `--warn`. None of them is necessarily a bug, but most
+//! of the time one is.
+
+use std::fmt;
+
+use crate::analysis::loop_excursions;
+use crate::parser::{Instruction, Program};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    EmptyLoop, // `[]`
+    OpposingScans, // `[<][>]`, a scan right after a scan the other way
+    RunsOnce // `[...[-]]`, the body clears the cell `]` tests
+}
+
+impl Rule {
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            Rule::EmptyLoop => "empty loop, it never ends once entered on a non-zero cell",
+            Rule::OpposingScans => "this scan never runs, the scan before it stops on a zero cell and nothing moves off it",
+            Rule::RunsOnce => "loop body runs at most once, it clears the cell `]` tests"
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::EmptyLoop => write!(f, "empty loop"),
+            Rule::OpposingScans => write!(f, "opposing scans"),
+            Rule::RunsOnce => write!(f, "runs once")
+        }
+    }
+}
+
+/// `rule` matched the loop whose `[` is at source `position`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Warning {
+    pub position: usize,
+    pub rule: Rule
+}
+
+/// Warnings for the loops of `program` in source order, folded or not.
+pub fn lint(program: &Program) -> Vec<Warning> {
+    let instructions: &[Instruction] = program.instructions();
+    let balanced: Vec<bool> = loop_excursions(program).iter().map(|excursion| excursion.is_some_and(|excursion| excursion.shift == 0)).collect();
+    let mut warnings: Vec<Warning> = vec![];
+
+    for (begin, instruction) in instructions.iter().enumerate() {
+        if *instruction != Instruction::Begin {
+            continue;
+        }
+        let end: usize = program.jumps()[begin] - 1;
+        // `]` jumps to just after its `[`
+        let previous_loop: Option<usize> = (begin > 0 && instructions[begin - 1] == Instruction::End).then(|| program.jumps()[begin - 1] - 1);
+
+        let rule: Option<Rule> = if end == begin + 1 {
+            Some(Rule::EmptyLoop)
+        } else if previous_loop.is_some_and(|previous| opposing(scan_shift(program, previous), scan_shift(program, begin))) {
+            Some(Rule::OpposingScans)
+        } else if clears_tested_cell(program, begin, &balanced) {
+            Some(Rule::RunsOnce)
+        } else {
+            None
+        };
+
+        if let Some(rule) = rule {
+            warnings.push(Warning { position: program.positions()[begin], rule });
+        }
+    }
+
+    warnings
+}
+
+fn opposing(first: Option<isize>, second: Option<isize>) -> bool {
+    matches!((first, second), (Some(first), Some(second)) if first.signum() == -second.signum())
+}
+
+/// Net shift of the loop at `begin` if its body only moves the pointer.
+fn scan_shift(program: &Program, begin: usize) -> Option<isize> {
+    let end: usize = program.jumps()[begin] - 1;
+    let mut shift: isize = 0;
+    for instruction in &program.instructions()[begin + 1..end] {
+        match instruction {
+            Instruction::IncrementPointer => { shift += 1; },
+            Instruction::DecrementPointer => { shift -= 1; },
+            Instruction::Move(distance) => { shift += distance; },
+            _ => return None
+        }
+    }
+    (shift != 0).then_some(shift)
+}
+
+/// Whether the body of the loop at `begin` ends on the cell it started on and its last change to that
+/// cell is a clear (`[-]`, `[->+<]`, a folded one, ...). `balanced` tells which nested loops leave the
+/// pointer where they found it, any other one ends the search.
+fn clears_tested_cell(program: &Program, begin: usize, balanced: &[bool]) -> bool {
+    let instructions: &[Instruction] = program.instructions();
+    let end: usize = program.jumps()[begin] - 1;
+    let (mut offset, mut cleared): (isize, bool) = (0, false);
+
+    let mut index: usize = begin + 1;
+    while index < end {
+        match instructions[index] {
+            Instruction::IncrementPointer => { offset += 1; },
+            Instruction::DecrementPointer => { offset -= 1; },
+            Instruction::Move(distance) => { offset += distance; },
+            Instruction::SetZero => { cleared |= offset == 0; },
+            Instruction::Begin => {
+                // a loop keeping the pointer in place ends on a zero cell, wherever else it writes
+                if !balanced[index] {
+                    return false;
+                }
+                cleared = offset == 0;
+                index = program.jumps()[index] - 1;
+            },
+            Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) | Instruction::InputValue => {
+                cleared &= offset != 0;
+            },
+            _ => {}
+        }
+        index += 1;
+    }
+
+    offset == 0 && cleared
+}
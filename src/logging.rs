@@ -0,0 +1,42 @@
+//! Debug events for embedders, compiled in with the `logging` feature.
+//!
+//! The crate stays dependency-free, so instead of talking to the `log` crate directly it hands its
+//! events to whatever [`Log`] the host installs with [`set_logger`]. Forwarding them to `log` takes a
+//! few lines:
+//!
+//! ```ignore
+//! struct Forward;
+//!
+//! impl brainfck::logging::Log for Forward {
+//!     fn log(&self, target: &str, message: std::fmt::Arguments) {
+//!         log::debug!(target: target, "{}", message);
+//!     }
+//! }
+//!
+//! brainfck::logging::set_logger(Box::new(Forward)).ok();
+//! ```
+//!
+//! Events are debug level: parse start and end, passes run over a program, and the start and end
+//! of every run. Nothing is logged per step.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Receiver of the crate's debug events, `target` is the module emitting them.
+pub trait Log: Send + Sync {
+    fn log(&self, target: &str, message: fmt::Arguments);
+}
+
+static LOGGER: OnceLock<Box<dyn Log>> = OnceLock::new();
+
+/// Installs the process-wide logger, hands `logger` back if one is installed already.
+pub fn set_logger(logger: Box<dyn Log>) -> Result<(), Box<dyn Log>> {
+    LOGGER.set(logger)
+}
+
+#[doc(hidden)]
+pub fn emit(target: &str, message: fmt::Arguments) {
+    if let Some(logger) = LOGGER.get() {
+        logger.log(target, message);
+    }
+}
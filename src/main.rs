@@ -1,160 +1,1216 @@
+mod cli;
+mod completions;
+mod observers;
+
 use std::fs::File;
-use std::vec;
-use std::io::{prelude::*, BufReader};
+use std::io::{prelude::*, BufReader, BufWriter, IsTerminal};
+
+use brainfck::analysis::{analyze, Stats};
+use brainfck::asm;
+use brainfck::console::{self, CodePageGuard, SystemConsole};
+use brainfck::constprop;
+use brainfck::corpus::{self, Example};
+use brainfck::cost::{CostMeter, CostModel};
+use brainfck::cycles::{CycleProfiler, KindTime, SAMPLE_INTERVAL};
+use brainfck::golf::{self, Suggestion};
+use brainfck::image;
+use brainfck::input::RandomInput;
+use brainfck::ir;
+use brainfck::passes;
+use brainfck::layout::Layout;
+use brainfck::lint::{self, Warning};
+use brainfck::manifest::{parse_manifest, parse_spec, run_jobs, Data, Job, JobReport};
+use brainfck::multi::{run_round_robin, Ending};
+use brainfck::output::{CountingWriter, DiscardOnBrokenPipe, TeeWriter};
+use brainfck::post_mortem::PostMortem;
+use brainfck::reduce::{reduce, Failure, Oracle, Reduction};
+use brainfck::report::{render_html, Profiler, RunReport};
+use brainfck::slice::{render_slice, sliced_source, Slicer};
+use brainfck::taint::TaintTracker;
+use brainfck::tape_diff::TapeDiffRecorder;
+use observers::{resolve_watch, Explainer, OutputBreakpoint, Progress, RunObservers, SourceTracer, Watch, Watcher};
+
+use brainfck::{
+    compile, execute_code, execute_observed, highlight_position, line_col, position_at, run_pipeline, parse_bytes_with, verify_determinism, Divergence, ExecutionConfig,
+    Gas, HaltReason, InputSpec, Instruction, Interpreter, OutputSpec, ParseError, ParseOptions, Program, Run, RunError, RunSummary, RuntimeError, Snapshot, Source, Stage, TapeMode, DYNAMIC_TAPE_LIMIT, TAPE_SIZE
+};
+
+const GOLF_VERIFY_STEPS: u64 = 100_000_000;
+const TRUTH_TABLE_STEPS: u64 = 1_000_000;
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+const INPUT_BATCH_SIZE: usize = 64 * 1024;
+const BISECT_STEPS: u64 = 100_000_000;
+/// Exit status of a process killed by SIGPIPE as a shell reports it, for `--on-broken-pipe exit`.
+const BROKEN_PIPE_STATUS: i32 = 128 + 13;
+/// Step budget of every `reduce` check without `--max-steps`, candidates often loop forever.
+const REDUCE_STEPS: u64 = 1_000_000;
 
-const TAPE_SIZE: usize = 30000;
+fn print_truth_table(program: &Program, interpreter: &mut Interpreter, max_steps: Option<u64>) {
+    let config: ExecutionConfig = ExecutionConfig { max_steps: Some(max_steps.unwrap_or(TRUTH_TABLE_STEPS)), ..ExecutionConfig::default() };
 
-#[derive(Debug)]
-enum Instruction {
-    IncrementPointer,
-    DecrementPointer,
+    for byte in 0..=u8::MAX {
+        interpreter.reset();
+        let mut output: Vec<u8> = vec![];
 
-    IncrementValue,
-    DecrementValue,
+        match execute_code(program, interpreter, &mut &[byte][..], &mut output, &config) {
+            Ok(_) => println!("{:3} -> \"{}\"", byte, output.escape_ascii()),
+            Err(RuntimeError::StepLimitExceeded) => println!("{:3} -> (no halt within {} steps)", byte, config.max_steps.unwrap_or_default()),
+            Err(err) => println!("{:3} -> ({})", byte, err)
+        }
+    }
+}
 
-    OutputValue,
-    InputValue,
+/// Where the output of a run goes: stdout as `--console-encoding` set it up, buffered unless per-step
+/// reports have to interleave with it, and the guard to drop once it is flushed.
+fn run_stdout(options: &cli::Options) -> (Option<CodePageGuard<SystemConsole>>, Box<dyn Write + Send>) {
+    let (code_page, transcoder) = console::prepare_stdout(options.console_encoding);
+    let stdout: Box<dyn Write + Send> = match transcoder {
+        Some(writer) => Box::new(writer),
+        None => Box::new(std::io::stdout())
+    };
+    let stdout: Box<dyn Write + Send> = if options.reports_steps() { stdout } else { Box::new(BufWriter::with_capacity(OUTPUT_BUFFER_SIZE, stdout)) };
+    match options.on_broken_pipe {
+        cli::BrokenPipe::Ignore => (code_page, Box::new(DiscardOnBrokenPipe::new(stdout))),
+        _ => (code_page, stdout)
+    }
+}
 
-    Begin(Option<usize>), // where to jump if zero
-    End(usize), // where to jump if not zero
+/// Ends the process as `--on-broken-pipe` says if `err` is the reader of stdout going away, returns
+/// for any other error (and with `ignore`, whose output never fails that way).
+fn stop_on_broken_pipe(err: &std::io::Error, options: &cli::Options) {
+    if err.kind() != std::io::ErrorKind::BrokenPipe {
+        return;
+    }
+    match options.on_broken_pipe {
+        cli::BrokenPipe::Exit => std::process::exit(BROKEN_PIPE_STATUS),
+        cli::BrokenPipe::Error => {
+            eprintln!("Output I/O error: {}", err);
+            std::process::exit(1);
+        },
+        cli::BrokenPipe::Ignore => {}
+    }
+}
 
-    Halt // added at the end of the buffer, prevents overflow if ] is the last instruction
+/// What `--bisect-passes` compares between builds of a program: output, final tape and how the run ended.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    output: Vec<u8>,
+    checksum: u64,
+    ending: String
 }
 
-#[derive(Debug)]
-struct Intepreter {
-    buffer: [u8; TAPE_SIZE],
-    pointer: usize
+fn outcome(program: &Program, options: &cli::Options, input: &[u8]) -> Outcome {
+    let config: ExecutionConfig =
+        ExecutionConfig { max_steps: Some(options.max_steps.unwrap_or(BISECT_STEPS)), cell_modulus: options.cell_modulus, loop_cell: options.loop_cell, max_pointer_range: options.max_pointer_range, ..ExecutionConfig::default() };
+    let mut interpreter: Interpreter = build_interpreter(options);
+    let mut output: Vec<u8> = vec![];
+    let ending: String = match execute_code(program, &mut interpreter, &mut &input[..], &mut output, &config) {
+        Ok(summary) => summary.halt.to_string(),
+        Err(err) => err.to_string()
+    };
+    Outcome { output, checksum: interpreter.tape_checksum(), ending }
 }
 
-impl Default for Intepreter {
-    fn default() -> Self {
-        Self {
-            buffer: [0; TAPE_SIZE],
-            pointer: TAPE_SIZE / 2
+/// Source ranges of the top-level loops of `program` and the straight-line stretches between them.
+fn segments(program: &Program) -> Vec<std::ops::RangeInclusive<usize>> {
+    let mut segments: Vec<std::ops::RangeInclusive<usize>> = vec![];
+    let entry_points: Vec<usize> = program.entry_points();
+    let mut entries = entry_points.iter().peekable();
+    while let Some(&index) = entries.next() {
+        let first: usize = program.positions()[index];
+        if program.instructions()[index] == Instruction::Begin {
+            segments.push(first..=program.positions()[program.jumps()[index] - 1]);
+            continue;
+        }
+        let mut last: usize = first;
+        while let Some(&&next) = entries.peek() {
+            if program.instructions()[next] == Instruction::Begin { break; }
+            last = program.positions()[next];
+            entries.next();
         }
+        segments.push(first..=last);
     }
+    segments
 }
 
-fn parse_code(code: &String) -> Result<Vec<Instruction>, &'static str> {
-    let mut parsed_instructions: Vec<Instruction> = vec![];
+fn bisect_passes(source: &[u8], options: &cli::Options, parse_options: &ParseOptions) {
+    let mut input: Vec<u8> = vec![];
+    input_reader(&options.input).read_to_end(&mut input).expect("Input I/O error");
 
+    let build = |passes: usize| -> Program {
+        passes::build(source, parse_options, &passes::PIPELINE[..passes]).unwrap_or_else(|err| {
+            println!("{}", err);
+            std::process::exit(1);
+        })
+    };
+    let reference: Outcome = outcome(&build(0), options, &input);
+    if reference.ending == RuntimeError::StepLimitExceeded.to_string() {
+        eprintln!("the unoptimized run doesn't halt within its step budget, raise --max-steps");
+        std::process::exit(2);
+    }
+    let Some(low) = passes::first_divergence(passes::PIPELINE.len(), |passes: usize| outcome(&build(passes), options, &input) != reference) else {
+        println!("no divergence: all {} passes give the same output, tape and ending", passes::PIPELINE.len());
+        return;
+    };
+    let pass: passes::Pass = passes::PIPELINE[low - 1];
+    let diverged: Outcome = outcome(&build(low), options, &input);
+    println!("pass `{}` ({} of {}) changes the run:", pass.name(), low, passes::PIPELINE.len());
+    if reference.output == diverged.output {
+        println!("  output   unchanged");
+    } else {
+        let at: usize = reference.output.iter().zip(&diverged.output).take_while(|(a, b)| a == b).count();
+        println!("  output   differs from byte {} on ({} bytes -> {} bytes)", at, reference.output.len(), diverged.output.len());
+    }
+    println!("  tape     checksum {} -> {}", reference.checksum, diverged.checksum);
+    println!("  ending   {} -> {}", reference.ending, diverged.ending);
 
-    let mut stack: Vec<usize> = vec![]; // stack that keeps track of jump locations - [ ]
+    let (before, after): (Program, Program) = (build(low - 1), build(low));
+    let indices = |program: &Program, range: &std::ops::RangeInclusive<usize>| -> std::ops::Range<usize> {
+        let start: usize = program.positions().partition_point(|position| position < range.start());
+        let end: usize = program.positions().partition_point(|position| position <= range.end());
+        start..end
+    };
+    for segment in segments(&before) {
+        let (old, new): (std::ops::Range<usize>, std::ops::Range<usize>) = (indices(&before, &segment), indices(&after, &segment));
+        if before.instructions()[old.clone()] == after.instructions()[new.clone()] {
+            continue;
+        }
+        let (line, column) = line_col(source, *segment.start());
+        println!("\n--- before `{}`, line {}:{}", pass.name(), line, column);
+        print!("{}", ir::emit_ir_range(&before, source, old));
+        println!("--- after `{}`", pass.name());
+        if new.is_empty() { println!("    (removed)"); } else { print!("{}", ir::emit_ir_range(&after, source, new)); }
+    }
+}
 
-    for (index, operation) in code.chars().filter(|c| "><+-.,[]".contains(*c)).enumerate() {
-        match operation {
-            '>' => { parsed_instructions.push(Instruction::IncrementPointer); },
-            '<' => { parsed_instructions.push(Instruction::DecrementPointer); },
+fn reduce_program(source: &[u8], source_input: Vec<u8>, options: &cli::Options, parse_options: &ParseOptions, failure: Failure, max_tests: usize) {
+    let mut input: Vec<u8> = source_input;
+    input_reader(&options.input).read_to_end(&mut input).expect("Input I/O error");
 
-            '+' => { parsed_instructions.push(Instruction::IncrementValue); },
-            '-' => { parsed_instructions.push(Instruction::DecrementValue); },
+    // the build `run` would make, a divergence is looked for in all passes unless some are chosen
+    let mut passes: Vec<passes::Pass> = passes::PIPELINE.iter().copied().filter(|&pass| match pass {
+        passes::Pass::Fold => options.single_pass,
+        passes::Pass::ConstProp => options.constprop
+    }).collect();
+    if passes.is_empty() && failure == Failure::Diverges {
+        passes = passes::PIPELINE.to_vec();
+    }
+    let oracle: Oracle = Oracle {
+        failure,
+        parse_options: *parse_options,
+        passes,
+        interpreter: build_interpreter(options),
+        config: ExecutionConfig {
+            max_steps: Some(options.max_steps.unwrap_or(REDUCE_STEPS)),
+            gas: options.gas_limit.map(|limit| Gas { limit, costs: options.gas_costs }),
+            cell_modulus: options.cell_modulus,
+            loop_cell: options.loop_cell,
+            max_pointer_range: options.max_pointer_range,
+            break_on_output: options.break_on_output,
+            halt_on_output: options.halt_on_output,
+            emit_sentinel: options.emit_sentinel,
+            ..ExecutionConfig::default()
+        },
+        input,
+        sentinel_exit_code: options.sentinel_exit_code
+    };
 
-            '.' => { parsed_instructions.push(Instruction::OutputValue); },
-            ',' => { parsed_instructions.push(Instruction::InputValue); },
+    if failure == Failure::Panics {
+        // every panic is caught, their messages would bury the result
+        std::panic::set_hook(Box::new(|_| {}));
+    }
+    if !oracle.holds(source) {
+        eprintln!("the program doesn't show the failure `{}` to begin with, nothing to reduce", failure);
+        std::process::exit(2);
+    }
+    let reduction: Reduction = reduce(source, parse_options, max_tests, &mut |code: &[u8]| oracle.holds(code)).unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    });
+    let _ = std::panic::take_hook();
 
-            '[' => {
-                stack.push(index);
-                parsed_instructions.push(Instruction::Begin(None));
+    println!("{}", String::from_utf8_lossy(&reduction.code));
+    eprint!("reduced {} bytes to {} in {} checks", source.len(), reduction.code.len(), reduction.tests);
+    if reduction.exhausted {
+        eprint!(", --max-tests ran out so it may shrink further");
+    }
+    eprintln!();
+}
+
+fn golf_file(source: &[u8], options: &cli::Options, apply: bool) {
+    let interpreter: Interpreter = build_interpreter(options);
+    let config: ExecutionConfig = ExecutionConfig { max_steps: Some(options.max_steps.unwrap_or(GOLF_VERIFY_STEPS)), ..run_settings(options).config() };
+    let suggestions: Vec<Suggestion> = golf::suggest_within(source, &golf::Bounds::of(&interpreter, &config));
+
+    for suggestion in &suggestions {
+        let (line, column) = line_col(source, suggestion.span.start);
+        println!(
+            "{}:{}: {:?} -> {:?} saves {} byte(s) ({})",
+            line,
+            column,
+            String::from_utf8_lossy(&source[suggestion.span.clone()]),
+            suggestion.replacement,
+            suggestion.savings(),
+            suggestion.rule
+        );
+    }
+    let total: usize = suggestions.iter().map(Suggestion::savings).sum();
+    println!("{} suggestion(s), {} byte(s) total", suggestions.len(), total);
+
+    if !apply || suggestions.is_empty() {
+        return;
+    }
+
+    let mut input: Vec<u8> = vec![];
+    input_reader(&options.input).read_to_end(&mut input).expect("Input I/O error");
+    let inputs: &[&[u8]] = if input.is_empty() { &[&[]] } else { &[&[], &input] };
+
+    let rewritten: Vec<u8> = golf::apply(source, &suggestions);
+    let parse_options: ParseOptions = run_settings(options).parse_options();
+    if !inputs.iter().all(|input| golf::verify_on(source, &rewritten, &parse_options, &interpreter, &config, input)) {
+        eprintln!("Rewritten program could not be verified against the original, nothing written");
+        std::process::exit(1);
+    }
+    std::fs::write(&options.filepath, rewritten).expect("File I/O error");
+    println!("Applied to {}", options.filepath);
+}
+
+/// `reduce` without an oracle: the program built with every pass and written back as brainfuck, if the
+/// rewrite behaves the same as the original.
+fn minimize_program(source: &[u8], source_input: Vec<u8>, options: &cli::Options, parse_options: &ParseOptions) {
+    let mut input: Vec<u8> = source_input;
+    input_reader(&options.input).read_to_end(&mut input).expect("Input I/O error");
+
+    let program: Program = passes::build(source, parse_options, &passes::PIPELINE).unwrap_or_else(|err| {
+        println!("{}", err);
+        std::process::exit(1);
+    });
+    let minimized: String = program.to_source();
+
+    let max_steps: u64 = options.max_steps.unwrap_or(GOLF_VERIFY_STEPS);
+    let interpreter: Interpreter = build_interpreter(options);
+    let config: ExecutionConfig = ExecutionConfig { max_steps: Some(max_steps), ..run_settings(options).config() };
+    let inputs: &[&[u8]] = if input.is_empty() { &[&[]] } else { &[&[], &input] };
+    if !inputs.iter().all(|input| golf::verify_on(source, minimized.as_bytes(), parse_options, &interpreter, &config, input)) {
+        eprintln!("the optimized program could not be verified against the original within {} steps, nothing printed", max_steps);
+        std::process::exit(1);
+    }
+
+    println!("{}", minimized);
+    eprintln!("reduced {} bytes to {}", source.len(), minimized.len());
+}
+
+fn run_manifest(filepath: &str, text: &str, parallel: bool, threads: Option<usize>) {
+    let base: &std::path::Path = std::path::Path::new(filepath).parent().unwrap_or(std::path::Path::new(""));
+    let jobs: Vec<Job> = parse_manifest(text, base).unwrap_or_else(|err| {
+        eprintln!("{}: {}", filepath, err);
+        std::process::exit(2);
+    });
+
+    let threads: usize = threads.unwrap_or(if parallel { jobs.len() } else { 1 });
+    let reports: Vec<JobReport> = run_jobs(&jobs, threads);
+
+    let width: usize = reports.iter().map(|report| report.name.len()).max().unwrap_or(0).max(3);
+    println!("{:<width$}  {:>6}  {:>12}  {:>10}  {:>20}", "job", "result", "steps", "output", "tape checksum");
+    for report in &reports {
+        let result: &str = if report.passed() { "ok" } else { "FAIL" };
+        println!("{:<width$}  {:>6}  {:>12}  {:>10}  {:>20}", report.name, result, report.steps, report.output_bytes, report.checksum);
+        for failure in &report.failures {
+            println!("{:<width$}    {}", "", failure);
+        }
+    }
+
+    let failed: usize = reports.iter().filter(|report| !report.passed()).count();
+    println!("{} job(s), {} failed", reports.len(), failed);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn run_pipe(options: &cli::Options, programs: &[String], parse_options: &ParseOptions) {
+    let config: ExecutionConfig = ExecutionConfig { max_steps: options.max_steps, cell_modulus: options.cell_modulus, loop_cell: options.loop_cell, max_pointer_range: options.max_pointer_range, ..ExecutionConfig::default() };
+
+    let mut stages: Vec<Stage> = programs.iter().map(|path| {
+        let source: Vec<u8> = std::fs::read(path).expect("File I/O error");
+        match compile(&source, parse_options, !options.no_auto_halt) {
+            Ok(program) => {
+                Stage { name: path.clone(), program, interpreter: build_interpreter(options), config: config.clone() }
             },
+            Err(err) => {
+                println!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }).collect();
+
+    let mut input: Box<dyn Read + Send> = input_reader(&options.input);
+    let mut stdout: Box<dyn Write + Send> = match options.on_broken_pipe {
+        cli::BrokenPipe::Ignore => Box::new(DiscardOnBrokenPipe::new(std::io::stdout())),
+        _ => Box::new(std::io::stdout())
+    };
+    let results: Vec<Result<RunSummary, RuntimeError>> = run_pipeline(&mut stages, &mut input, &mut stdout);
+    if let Some(Err(RuntimeError::Io(err))) = results.last() {
+        stop_on_broken_pipe(err, options);
+    }
+
+    // a broken pipe is usually just the teardown after the stage which really failed
+    let is_teardown = |result: &Result<RunSummary, RuntimeError>| matches!(result, Err(RuntimeError::Io(err)) if err.kind() == std::io::ErrorKind::BrokenPipe);
+    let only_teardown: bool = results.iter().filter(|result| result.is_err()).all(is_teardown);
+
+    let mut failed: bool = false;
+    for (number, (stage, result)) in stages.iter().zip(&results).enumerate() {
+        if let Err(err) = result {
+            failed = true;
+            if only_teardown || !is_teardown(result) {
+                eprintln!("stage {} ({}): {}", number + 1, stage.name, err);
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn run_multi(options: &cli::Options, programs: &[String], slice: u64, input_to: Option<&str>, parse_options: &ParseOptions) {
+    // `--max-steps` limits the programs together, see `run_round_robin`
+    let config: ExecutionConfig = ExecutionConfig { cell_modulus: options.cell_modulus, loop_cell: options.loop_cell, max_pointer_range: options.max_pointer_range, ..ExecutionConfig::default() };
+    let mut stages: Vec<Stage> = programs.iter().map(|path| {
+        let source: Vec<u8> = std::fs::read(path).expect("File I/O error");
+        match compile(&source, parse_options, !options.no_auto_halt) {
+            Ok(program) => Stage { name: path.clone(), program, interpreter: build_interpreter(options), config: config.clone() },
+            Err(err) => {
+                println!("{}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+    }).collect();
+    let active: usize = match input_to {
+        Some(name) => programs.iter().position(|path| path == name).unwrap_or_else(|| {
+            eprintln!("--input-to {} names none of the programs", name);
+            std::process::exit(2);
+        }),
+        None => 0
+    };
+
+    let mut input: Box<dyn Read + Send> = input_reader(&options.input);
+    let mut stdout: Box<dyn Write> = match options.on_broken_pipe {
+        cli::BrokenPipe::Ignore => Box::new(DiscardOnBrokenPipe::new(std::io::stdout().lock())),
+        _ => Box::new(std::io::stdout().lock())
+    };
+    let endings: Vec<Ending> = run_round_robin(&mut stages, slice, options.max_steps, active, &mut input, &mut stdout).unwrap_or_else(|err| {
+        stop_on_broken_pipe(&err, options);
+        panic!("Output I/O error: {}", err);
+    });
 
-            ']' => {
-                let previous_begin_index: usize = stack.pop().expect("Unmatched `]`, missing `[`");
-                parsed_instructions[previous_begin_index] = Instruction::Begin(Some(index + 1));
-                parsed_instructions.push(Instruction::End(previous_begin_index + 1));
+    let mut failed: bool = false;
+    for (stage, ending) in stages.iter().zip(endings) {
+        match ending {
+            Some(Ok(_)) => {},
+            Some(Err(err)) => {
+                failed = true;
+                eprintln!("[{}] {}", stage.name, err);
             },
+            None => {
+                failed = true;
+                eprintln!("[{}] still running after {} steps when the step limit was reached", stage.name, stage.interpreter.steps());
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn run_spec(filepath: &str) {
+    let text: String = std::fs::read_to_string(filepath).expect("Spec file I/O error");
+    let base: &std::path::Path = std::path::Path::new(filepath).parent().unwrap_or(std::path::Path::new(""));
+    let job: Job = parse_spec(&text, base).unwrap_or_else(|err| {
+        eprintln!("{}: {}", filepath, err);
+        std::process::exit(2);
+    });
+
+    let report: JobReport = job.run();
+    if report.passed() {
+        println!("PASS {} ({} steps)", filepath, report.steps);
+        return;
+    }
+    println!("FAIL {}", filepath);
+    for failure in &report.failures {
+        println!("    {}", failure);
+    }
+    std::process::exit(1);
+}
+
+fn cell_label(layout: Option<&Layout>, interpreter: &Interpreter, index: usize) -> Option<String> {
+    layout?.label(index, interpreter.start_pointer())
+}
+
+/// The library's settings of a run with these options, the program, input and output aside.
+fn run_settings(options: &cli::Options) -> Run {
+    run_of(Source::Path(options.filepath.clone().into()), options)
+}
+
+/// `source` run with the settings of `options`.
+fn run_of(source: Source, options: &cli::Options) -> Run {
+    let input: InputSpec = match &options.input {
+        cli::Input::Stdin => InputSpec::Stdin,
+        cli::Input::File(path) => InputSpec::File(path.into()),
+        cli::Input::String(text) => InputSpec::Bytes(text.clone().into_bytes()),
+        cli::Input::Random(seed) => InputSpec::Random(*seed)
+    };
+    Run::new(source)
+        .alt_brackets(options.alt_brackets)
+        .extensions(options.extensions)
+        .max_nesting(options.max_nesting)
+        .single_pass(options.single_pass)
+        .constprop(options.constprop)
+        .auto_halt(!options.no_auto_halt)
+        .compat(options.compat)
+        .tape_mode(options.tape_mode)
+        .tape_size(options.tape_size)
+        .sandbox(options.sandbox.clone())
+        .max_steps(options.max_steps)
+        .max_output(options.max_output)
+        .gas(options.gas_limit.map(|limit| Gas { limit, costs: options.gas_costs }))
+        .cell_modulus(options.cell_modulus)
+        .loop_cell(options.loop_cell)
+        .max_pointer_range(options.max_pointer_range)
+        .break_on_output(options.break_on_output)
+        .halt_on_output(options.halt_on_output, options.emit_sentinel)
+        .filter(options.filter)
+        .input(input)
+}
 
-            _ => {}
+fn build_interpreter(options: &cli::Options) -> Interpreter {
+    let settings: Run = run_settings(options);
+    settings.interpreter().unwrap_or_else(|err| {
+        if let RunError::Sandbox(region) = err {
+            let tape: usize = settings.sandbox(None).interpreter().map_or(0, |interpreter| interpreter.tape().len());
+            eprintln!("--sandbox-region {}:{} is not a non-empty range of the {} cell tape", region.start, region.end, tape);
+        } else {
+            eprintln!("{}", err);
         }
+        std::process::exit(2);
+    })
+}
 
+fn dump_tape(interpreter: &Interpreter, layout: Option<&Layout>) {
+    let tape: &[u8] = interpreter.tape();
+    let origin: usize = interpreter.start_pointer();
+
+    eprintln!("tape (pointer at {}):", interpreter.pointer());
+    if let Some(layout) = layout {
+        for field in layout.fields() {
+            match field.cells(origin) {
+                Some(cells) => eprintln!("  {} = {} (cells {}..{})", field.name, field.render(tape, origin), cells.start, cells.end),
+                None => eprintln!("  {} = (off the tape)", field.name)
+            }
+        }
     }
+    for (index, &value) in tape.iter().enumerate().filter(|&(_, &value)| value != 0) {
+        let name: String = layout.and_then(|layout| layout.label(index, origin)).map(|name| format!(" {}", name)).unwrap_or_default();
+        eprintln!("  [{:>6}] {:3}{}", index, value, name);
+    }
+}
 
-    return Ok(parsed_instructions);
+/// `index,value` rows of the cells in `range` (relative to the starting cell, the cells the pointer
+/// reached if `None`) that are on the tape, under a header row.
+fn tape_csv(interpreter: &Interpreter, range: Option<&std::ops::Range<isize>>) -> String {
+    let (lowest, highest) = interpreter.pointer_range();
+    let range: std::ops::Range<isize> = range.cloned().unwrap_or(lowest..highest + 1);
+    let origin: isize = interpreter.start_pointer() as isize;
+
+    let mut csv: String = String::from("index,value\n");
+    for index in range {
+        let Some(&value) = usize::try_from(origin + index).ok().and_then(|cell| interpreter.tape().get(cell)) else {
+            continue;
+        };
+        csv.push_str(&format!("{},{}\n", index, value));
+    }
+    csv
 }
 
-fn execute_code(parsed_code: Vec<Instruction>, interpreter: &mut Intepreter) {
-    let mut instruction_index: usize = 0;
-    let mut input_buffer: [u8; 1] = [0; 1];
+fn input_reader(input: &cli::Input) -> Box<dyn Read + Send> {
+    match input {
+        cli::Input::Stdin => Box::new(std::io::stdin()),
+        cli::Input::File(path) => Box::new(BufReader::new(File::open(path).expect("Input file I/O error"))),
+        cli::Input::String(text) => Box::new(std::io::Cursor::new(text.clone().into_bytes())),
+        cli::Input::Random(seed) => Box::new(RandomInput::new(*seed))
+    }
+}
 
-    loop {
+/// PNG for a `.png` path (the command line only allows that with the `png` feature), PGM otherwise.
+#[cfg_attr(not(feature = "png"), allow(unused_variables))]
+fn encode_image(path: &str, pixels: &[u8], width: usize) -> Vec<u8> {
+    #[cfg(feature = "png")]
+    if path.ends_with(".png") {
+        return image::encode_png(pixels, width);
+    }
+    image::encode_pgm(pixels, width)
+}
 
-        match *parsed_code.get(instruction_index).unwrap() {
-            Instruction::IncrementPointer => {
-                if interpreter.pointer >= TAPE_SIZE { println!("Pointer out of bounds, overflow"); return; }
-                interpreter.pointer += 1;
+/// A parse error as `--explain-errors` shows it: the message, where it is and what to do about it.
+fn explained_parse_error(err: &ParseError, source: &[u8]) -> String {
+    let (line, column) = line_col(source, err.position());
+    format!("{}\n\n  --> {}:{}\n{}\n\n{}", err, line, column, highlight_position(source, err.position()), err.explain(source))
+}
 
-                instruction_index += 1;
-            },
-            Instruction::DecrementPointer => {
-                if interpreter.pointer <= 0 { println!("Pointer out of bounds, underflow"); return; }
-                interpreter.pointer -= 1;
+/// A manifest or an assembly listing, which unlike a program has to be UTF-8 text.
+fn text_of(filepath: &str, content: &[u8]) -> String {
+    String::from_utf8(content.to_vec()).unwrap_or_else(|err| {
+        eprintln!("{}: not UTF-8 text ({})", filepath, err.utf8_error());
+        std::process::exit(2);
+    })
+}
 
-                instruction_index += 1;
-            },
+/// Prints PASS or FAIL to stderr for a run with `output` that should have written `expected`, and
+/// on a mismatch the line holding the first differing byte in both. Tells whether they matched.
+fn check_output(expected: &[u8], source: &Data, output: &[u8]) -> bool {
+    let name: String = match source {
+        Data::File(path) => path.display().to_string(),
+        Data::Inline(_) => "the expected output".to_string()
+    };
+    if expected == output {
+        eprintln!("PASS output matches {} ({} bytes)", name, output.len());
+        return true;
+    }
 
-            Instruction::IncrementValue => {
-                interpreter.buffer[interpreter.pointer] = interpreter.buffer[interpreter.pointer].wrapping_add(1);
-                instruction_index += 1;
-            },
-            Instruction::DecrementValue => {
-                interpreter.buffer[interpreter.pointer] = interpreter.buffer[interpreter.pointer].wrapping_sub(1);
-                instruction_index += 1;
-            },
+    let at: usize = expected.iter().zip(output).take_while(|(a, b)| a == b).count();
+    let line_start: usize = expected[..at].iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+    let line = |bytes: &[u8]| -> String {
+        let end: usize = bytes[line_start..].iter().position(|&byte| byte == b'\n').map_or(bytes.len(), |length| line_start + length);
+        format!("{:?}", String::from_utf8_lossy(&bytes[line_start..end]))
+    };
+    let line_number: usize = expected[..at].iter().filter(|&&byte| byte == b'\n').count() + 1;
+    eprintln!(
+        "FAIL output differs from {} at byte {} (line {}, column {}), {} bytes expected and {} written",
+        name, at, line_number, at - line_start + 1, expected.len(), output.len()
+    );
+    eprintln!("  expected: {}", line(expected));
+    eprintln!("  actual:   {}", line(output));
+    false
+}
 
-            Instruction::InputValue => {
-                std::io::stdin().read_exact(&mut input_buffer).expect("Input error");
-                interpreter.buffer[interpreter.pointer] = input_buffer[0];
-            },
-            Instruction::OutputValue => {
-                print!("{}", interpreter.buffer[interpreter.pointer] as char);
-                instruction_index += 1;
-            },
+/// The settings of a run as `--report` lists them.
+fn report_settings(options: &cli::Options) -> Vec<(String, String)> {
+    let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let tape: String = match options.tape_mode {
+        TapeMode::Fixed => format!("fixed, {} cells", options.tape_size.unwrap_or(TAPE_SIZE)),
+        TapeMode::Growable => format!("growing to the right, up to {} cells", options.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT)),
+        TapeMode::Bidirectional => format!("growing both ways, up to {} cells", options.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT))
+    };
+    let input: String = match &options.input {
+        cli::Input::Stdin => "stdin".to_string(),
+        cli::Input::File(path) => format!("file {}", path),
+        cli::Input::String(text) => format!("string {:?}", text),
+        cli::Input::Random(seed) => format!("random, seed {}", seed)
+    };
+    vec![
+        ("program".to_string(), options.filepath.clone()),
+        ("input".to_string(), input),
+        ("tape".to_string(), tape),
+        ("single pass".to_string(), options.single_pass.to_string()),
+        ("constprop".to_string(), options.constprop.to_string()),
+        ("alt brackets".to_string(), options.alt_brackets.to_string()),
+        ("extensions".to_string(), options.extensions.to_string()),
+        ("cell modulus".to_string(), options.cell_modulus.map_or("256".to_string(), |modulus| modulus.to_string())),
+        ("max steps".to_string(), or_none(options.max_steps.map(|steps| steps.to_string()))),
+        ("gas limit".to_string(), or_none(options.gas_limit.map(|limit| limit.to_string())))
+    ]
+}
 
-            Instruction::Begin(jump_address) => {
-                if interpreter.buffer[interpreter.pointer] == 0 {
-                    instruction_index = jump_address.unwrap();
-                } else {
-                    instruction_index += 1;
-                }
-            },
+fn report_warnings(warnings: &[Warning], source: &[u8]) {
+    for warning in warnings {
+        let (line, column) = line_col(source, warning.position);
+        eprintln!("warning: {}:{}: {} ({})", line, column, warning.rule.explanation(), warning.rule);
+    }
+}
 
-            Instruction::End(jump_address) => {
-                if interpreter.buffer[interpreter.pointer] != 0 {
-                    instruction_index = jump_address;
-                } else {
-                    instruction_index += 1;
-                }
-            },
+fn report_cycles(profiler: &CycleProfiler) {
+    let kinds: Vec<KindTime> = profiler.kinds();
+    let total: std::time::Duration = kinds.iter().map(|kind| kind.estimated).sum();
+    let samples: u64 = kinds.iter().map(|kind| kind.samples).sum();
+
+    eprintln!(
+        "cycle profile: about {:.3?} in {} samples (1 step in {} on average), {:?} timer overhead taken off each",
+        total, samples, SAMPLE_INTERVAL, profiler.overhead()
+    );
+    for kind in kinds.iter().filter(|kind| kind.count > 0) {
+        let share: f64 = if total.is_zero() { 0.0 } else { 100.0 * kind.estimated.as_secs_f64() / total.as_secs_f64() };
+        let mean: String = kind.mean.map_or("-".to_string(), |mean| format!("{:?}", mean));
+        eprintln!("  {:<10} {:>5.1}%  {:>12} steps  {:>8} each", kind.name, share, kind.count, mean);
+    }
+}
+
+fn report_taint(tracker: &TaintTracker, source: &[u8]) {
+    let at = |position: usize| -> String {
+        let (line, column) = line_col(source, position);
+        format!("{}:{}", line, column)
+    };
+
+    for conflict in tracker.conflicts() {
+        eprintln!(
+            "taint: step {}: cell {:+} tagged `{}` at {} is written at {} by code of `{}` ({})",
+            conflict.step,
+            conflict.cell,
+            conflict.tag.name,
+            at(conflict.tag.position),
+            at(conflict.write_position),
+            conflict.writer.name,
+            at(conflict.writer.position)
+        );
+    }
+    eprintln!("taint: {} conflict(s)", tracker.conflicts().len());
+}
 
-            Instruction::Halt => { /* print!("\n\nExecution ended\n"); */ break; },
+fn stop_index(program: &Program, source: &[u8], stop_at: cli::StopAt) -> Result<usize, String> {
+    match stop_at {
+        cli::StopAt::Instruction(index) if index < program.len() => Ok(index),
+        cli::StopAt::Instruction(index) => match program.len().checked_sub(1) {
+            Some(last) => Err(format!("--stop-at-instruction {} is past the last instruction ({})", index, last)),
+            None => Err(format!("--stop-at-instruction {} is past the end of the program, it has no instructions", index))
+        },
+        cli::StopAt::Source { line, column } => position_at(source, line, column)
+            .and_then(|position| program.index_at(position))
+            .ok_or(format!("--stop-at {}:{} is not a position in the program", line, column))
+    }
+}
+
+fn report_error(err: RuntimeError, program: &Program, interpreter: &Interpreter, source: &[u8], layout: Option<&Layout>) {
+    if let RuntimeError::OutputBreakpoint(_) = err {
+        let _ = std::io::stdout().flush();
+        let position: usize = program.positions().get(interpreter.instruction_index()).copied().unwrap_or(source.len());
+        let (line, column) = line_col(source, position);
+        eprintln!("{} at step {}, pointer {}, source {}:{}", err, interpreter.steps(), interpreter.pointer(), line, column);
+        std::process::exit(1);
+    }
+    let message: String = match cell_label(layout, interpreter, interpreter.pointer()) {
+        Some(name) => format!("{} at cell {} ({})", err, interpreter.pointer(), name),
+        None => err.to_string()
+    };
+    // stdout may be a pipe `--on-broken-pipe ignore` found closed
+    if writeln!(std::io::stdout(), "{}", message).is_err() {
+        eprintln!("{}", message);
+    }
+}
+
+/// With the `logging` feature, `BRAINFCK_LOG=1` prints the library's debug events to stderr.
+#[cfg(feature = "logging")]
+struct StderrLog;
+
+#[cfg(feature = "logging")]
+impl brainfck::logging::Log for StderrLog {
+    fn log(&self, target: &str, message: std::fmt::Arguments) {
+        eprintln!("[{}] {}", target, message);
+    }
+}
+
+/// `slice`: runs the program once with a [`Slicer`], lists the instructions output byte `output_index`
+/// depends on and shows them under the source.
+fn slice_program(source: &[u8], source_input: Vec<u8>, options: &cli::Options, parse_options: &ParseOptions, output_index: u64, emit_sliced: Option<&str>) {
+    let program: Program = match compile(source, parse_options, !options.no_auto_halt) {
+        Ok(program) => program,
+        Err(err) => {
+            println!("{}", err);
+            return;
         }
+    };
+    let mut interpreter: Interpreter = build_interpreter(options);
+    let mut slicer: Slicer = Slicer::new(output_index);
+    // the run may end soon after the byte, nothing later changes the slice (`:` writes up to 3 bytes)
+    let config: ExecutionConfig = ExecutionConfig {
+        max_steps: options.max_steps,
+        max_output: Some(output_index + 3),
+        cell_modulus: options.cell_modulus,
+        loop_cell: options.loop_cell,
+        max_pointer_range: options.max_pointer_range,
+        ..ExecutionConfig::default()
+    };
+    let mut input = std::io::Cursor::new(source_input).chain(input_reader(&options.input));
+    let result: Result<RunSummary, RuntimeError> = execute_observed(&program, &mut interpreter, &mut input, &mut std::io::sink(), &config, &mut slicer);
+
+    let Some(slice) = slicer.slice(&program) else {
+        if let Err(err) = result {
+            eprintln!("{}", err);
+        }
+        eprintln!("The program wrote {} output byte(s), there is no byte {}", slicer.outputs(), output_index);
+        std::process::exit(1);
+    };
+
+    println!("output byte {} depends on {} instruction(s):", output_index, slice.len());
+    let positions: Vec<usize> = slice.iter().map(|&index| program.positions()[index]).collect();
+    for &position in &positions {
+        let (line, column) = line_col(source, position);
+        println!("  {}:{} {}", line, column, source[position] as char);
+    }
+    print!("{}", render_slice(source, &positions));
+
+    if let Some(path) = emit_sliced {
+        let text: String = format!("sliced for output byte {}\n{}\n", output_index, sliced_source(&program, &slice));
+        std::fs::write(path, text).expect("File I/O error");
+        println!("Sliced program written to {}, an approximation that may not run on its own", path);
     }
+}
 
+/// A run `Run::execute` makes on its own: stdout as the only output, reported like any other run.
+fn run_plain(options: &cli::Options, program: &Program, original: Vec<u8>, source: &[u8], layout: Option<&Layout>, expected_output: Option<Vec<u8>>) {
+    let (code_page, stdout) = run_stdout(options);
+    let report: brainfck::RunReport = run_of(Source::Bytes(original), options).expect(expected_output.clone()).output(OutputSpec::Writer(stdout)).execute();
+    drop(code_page);
 
+    let result: Result<RunSummary, RuntimeError> = match report.result {
+        Ok(halt) => Ok(RunSummary { steps: report.steps, gas_used: report.gas_used, output_bytes: report.output_bytes, halt }),
+        Err(RunError::Runtime(err)) if options.filter.is_none() => Err(err),
+        Err(err) => {
+            match &err {
+                RunError::Io(err) => {
+                    stop_on_broken_pipe(err, options);
+                    eprintln!("I/O error: {}", err);
+                },
+                RunError::Runtime(RuntimeError::Io(io)) | RunError::InputByte(_, RuntimeError::Io(io)) => {
+                    stop_on_broken_pipe(io, options);
+                    eprintln!("{}", err);
+                },
+                _ => eprintln!("{}", err)
+            }
+            std::process::exit(1);
+        }
+    };
+    if let Err(RuntimeError::Io(err)) = &result {
+        stop_on_broken_pipe(err, options);
+    }
+    let matched: bool = match (&expected_output, &options.expect, &report.output) {
+        (Some(expected), Some(source), Some(output)) => check_output(expected, source, output),
+        _ => true
+    };
+    finish_run(options, program, source, &report.interpreter, layout, result, matched);
+}
+
+/// What every run reports once it is over, on stderr unless it failed, and how it exits.
+fn finish_run(options: &cli::Options, program: &Program, source: &[u8], interpreter: &Interpreter, layout: Option<&Layout>, result: Result<RunSummary, RuntimeError>, matched: bool) {
+    if options.dump_tape {
+        dump_tape(interpreter, layout);
+    }
+    if let Some(path) = &options.dump_csv {
+        std::fs::write(path, tape_csv(interpreter, options.csv_range.as_ref())).unwrap_or_else(|err| {
+            eprintln!("Cannot write CSV `{}`: {}", path, err);
+            std::process::exit(2);
+        });
+    }
+    if options.tape_checksum {
+        eprintln!("tape checksum {}", interpreter.tape_checksum());
+    }
+    if options.pointer_range {
+        let (lowest, highest) = interpreter.pointer_range();
+        eprintln!("pointer range {}..={} ({} cells)", lowest, highest, highest - lowest + 1);
+    }
+    if let cli::Input::Random(seed) = options.input {
+        eprintln!("random input seed {}, {} bytes read", seed, interpreter.input_bytes());
+    }
+    if let Some(limit) = options.gas_limit {
+        eprintln!("gas used {} of {}, {} left", interpreter.gas_used(), limit, limit - interpreter.gas_used());
+    }
+
+    let summary: RunSummary = match result {
+        Ok(summary) => summary,
+        Err(err) => {
+            report_error(err, program, interpreter, source, layout);
+            if !matched { std::process::exit(1); }
+            return;
+        }
+    };
+    if options.print_result {
+        eprintln!("pointer={} cell={}", interpreter.pointer() as isize - interpreter.start_pointer() as isize, interpreter.current());
+    }
+    if let Some(path) = &options.save_snapshot {
+        std::fs::write(path, interpreter.snapshot().to_bytes(program.len())).expect("Snapshot file I/O error");
+    }
+    if summary.halt == HaltReason::Stopped {
+        let (line, column) = line_col(source, program.positions()[interpreter.instruction_index()]);
+        eprintln!("stopped at {}:{} (instruction {}) after {} steps", line, column, interpreter.instruction_index(), summary.steps);
+    }
+    if !matched {
+        std::process::exit(1);
+    }
+    if summary.halt == HaltReason::Sentinel {
+        eprintln!("halted by sentinel after {} steps", summary.steps);
+        std::process::exit(options.sentinel_exit_code);
+    }
 }
 
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let filepath: &String = args.get(1).expect("Input filepath expected");
+    #[cfg(feature = "logging")]
+    if std::env::var_os("BRAINFCK_LOG").is_some() {
+        let _ = brainfck::logging::set_logger(Box::new(StderrLog));
+    }
 
-    let file: File = File::open(filepath).expect("File I/O error");
-    let mut reader: BufReader<File> = BufReader::new(file);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let loaded: cli::Loaded = match cli::load(&args) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            if !err.is_empty() { eprintln!("{}\n", err); }
+            eprintln!("{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    for warning in &loaded.warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if loaded.options.print_config {
+        print!("{}", cli::describe_config(&loaded.settings));
+        return;
+    }
+    if let cli::Command::Completions { shell } = loaded.options.command {
+        print!("{}", completions::completion_script(shell));
+        return;
+    }
+    let mut options: cli::Options = loaded.options;
 
-    let mut file_content: String = String::new();
-    reader.read_to_string(&mut file_content).expect("Error reading from file to a string");
+    if let Some(spec) = &options.spec {
+        run_spec(spec);
+        return;
+    }
 
+    // raw bytes, only the instruction bytes have to mean anything and comments may be in any encoding
+    let mut file_content: Vec<u8> = vec![];
+    if let cli::Command::Examples { action } = options.command {
+        if action == cli::ExampleAction::List {
+            for example in corpus::all() {
+                println!("{:<12} {}", example.name, example.description);
+            }
+            return;
+        }
+        let example: &Example = corpus::find(&options.filepath).unwrap_or_else(|| {
+            eprintln!("Unknown example `{}`, `examples list` shows them all", options.filepath);
+            std::process::exit(2);
+        });
+        if action == cli::ExampleAction::Show {
+            print!("{}", example.source);
+            if !example.source.ends_with('\n') {
+                println!();
+            }
+            return;
+        }
+        file_content.extend_from_slice(example.source.as_bytes());
+        if options.input == cli::Input::Stdin && !example.input.is_empty() {
+            options.input = cli::Input::String(example.input.to_string());
+        }
+    } else {
+        file_content = std::fs::read(&options.filepath).expect("File I/O error");
+    }
 
-    let mut interpreter: Intepreter = Intepreter::default();
-    let parsed_instructions: Vec<Instruction> = match parse_code(&file_content) {
-        Ok(mut instructions) => {
-            instructions.push(Instruction::Halt);
-            instructions
+    if let cli::Command::Golf { apply } = options.command {
+        golf_file(&file_content, &options, apply);
+        return;
+    }
+    if let cli::Command::RunManifest { parallel, jobs } = options.command {
+        run_manifest(&options.filepath, &text_of(&options.filepath, &file_content), parallel, jobs);
+        return;
+    }
+    let original: Vec<u8> = file_content.clone();
+    // dbfi and bff read a program and its input from one stream, separated by a `!`
+    let source_input: Vec<u8> = match options.compat.and_then(|_| file_content.iter().position(|&byte| byte == b'!')) {
+        Some(bang) => {
+            let rest: Vec<u8> = file_content[bang + 1..].to_vec();
+            file_content.truncate(bang);
+            rest
         },
+        None => vec![]
+    };
+
+    let parse_options: ParseOptions = run_settings(&options).parse_options();
+    if let cli::Command::Pipe { programs } = &options.command {
+        run_pipe(&options, programs, &parse_options);
+        return;
+    }
+    if let cli::Command::Multi { programs, slice, input_to } = &options.command {
+        run_multi(&options, programs, *slice, input_to.as_deref(), &parse_options);
+        return;
+    }
+
+    if options.bisect_passes {
+        bisect_passes(&file_content, &options, &parse_options);
+        return;
+    }
+
+    if let cli::Command::Slice { output_index, emit_sliced } = &options.command {
+        slice_program(&file_content, source_input, &options, &parse_options, *output_index, emit_sliced.as_deref());
+        return;
+    }
+
+    if let cli::Command::Reduce { oracle, max_tests } = options.command {
+        match oracle {
+            Some(oracle) => reduce_program(&file_content, source_input, &options, &parse_options, oracle, max_tests),
+            None => minimize_program(&file_content, source_input, &options, &parse_options)
+        }
+        return;
+    }
+
+    if options.command == cli::Command::Size {
+        // always measured on the unfolded parse, folding would hide the minified length
+        let parse_options: ParseOptions = ParseOptions { fold: false, ..parse_options };
+        match parse_bytes_with(&file_content, &parse_options) {
+            Ok(program) => {
+                let stats: Stats = analyze(&program);
+                println!("size {}  loops {}  max nesting {}", stats.instructions, stats.loops, stats.max_depth);
+            },
+            Err(err) => println!("{}", err)
+        }
+        return;
+    }
+
+    let compiled: Result<Program, String> = if options.asm {
+        asm::assemble(&text_of(&options.filepath, &file_content), !options.no_auto_halt).map_err(|err| err.to_string())
+    } else {
+        compile(&file_content, &parse_options, !options.no_auto_halt).map_err(|err| {
+            if options.explain_errors { explained_parse_error(&err, &file_content) } else { err.to_string() }
+        })
+    };
+    if let (Ok(program), true) = (&compiled, options.warn) {
+        report_warnings(&lint::lint(program), &file_content);
+    }
+    let mut program: Program = match compiled {
+        Ok(program) if options.constprop => constprop::propagate(&program),
+        Ok(program) => program,
         Err(err) => {
             println!("{}", err);
             return;
         }
     };
+    let mut interpreter: Interpreter = match &options.start_snapshot {
+        Some(path) => {
+            let bytes: Vec<u8> = std::fs::read(path).expect("Snapshot file I/O error");
+            let snapshot: Snapshot = Snapshot::from_bytes(&bytes, program.len()).unwrap_or_else(|err| {
+                eprintln!("{}: {}", path, err);
+                std::process::exit(2);
+            });
+            if options.sandbox.is_some() && options.sandbox != snapshot.sandbox {
+                let region: std::ops::Range<usize> = options.sandbox.clone().unwrap_or_default();
+                let taken: String = snapshot.sandbox.as_ref().map_or("no sandbox".to_string(), |taken| format!("--sandbox-region {}:{}", taken.start, taken.end));
+                eprintln!("--sandbox-region {}:{} differs from the snapshot, which was taken with {}", region.start, region.end, taken);
+                std::process::exit(2);
+            }
+            Interpreter::restore(&snapshot)
+        },
+        None => build_interpreter(&options)
+    };
+
+    let stop_index: Option<usize> = options.stop_at.map(|stop_at| {
+        stop_index(&program, &file_content, stop_at).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        })
+    });
+    if let Some(index) = stop_index {
+        program.halt_at(index);
+    }
+    if let Some(index) = options.start_at {
+        if let Err(err) = interpreter.start_at(&program, index) {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    }
+
+    if options.emit_asm {
+        print!("{}", asm::disassemble(&program));
+        return;
+    }
+    if options.emit_ir {
+        print!("{}", ir::emit_ir(&program, &file_content));
+        return;
+    }
+    let layout: Option<Layout> = options.layout.as_ref().map(|path| {
+        let text: String = std::fs::read_to_string(path).expect("Layout file I/O error");
+        Layout::parse(&text).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        })
+    });
+    let expected_output: Option<Vec<u8>> = options.expect.as_ref().map(|expected| {
+        expected.load().unwrap_or_else(|err| {
+            eprintln!("Cannot read the expected output {}", err);
+            std::process::exit(2);
+        })
+    });
+    if options.filter.is_some() || options.plain_run() {
+        run_plain(&options, &program, original, &file_content, layout.as_ref(), expected_output);
+        return;
+    }
+    if options.truth_table {
+        print_truth_table(&program, &mut interpreter, options.max_steps);
+        return;
+    }
+
+    // while single-stepping a breakpoint pauses the run (see `OutputBreakpoint`) instead of ending it
+    let break_on_output: Option<u8> = options.break_on_output.filter(|_| !options.single_stepping());
+    let config: ExecutionConfig = ExecutionConfig {
+        break_on_output,
+        // a typist's bytes have to reach the program as they come, not once a batch is full
+        input_batch: (options.input != cli::Input::Stdin || !std::io::stdin().is_terminal()).then_some(INPUT_BATCH_SIZE),
+        ..run_settings(&options).config()
+    };
+
+    if let Some(runs) = options.verify_determinism {
+        let input: Vec<u8> = match &options.input {
+            cli::Input::Stdin | cli::Input::Random(_) => {
+                eprintln!("--verify-determinism needs a recorded input, use --input-file or --input-string");
+                std::process::exit(2);
+            },
+            cli::Input::File(path) => std::fs::read(path).expect("Input file I/O error"),
+            cli::Input::String(text) => text.clone().into_bytes()
+        };
+        let input: Vec<u8> = [source_input, input].concat();
+
+        let divergences: Vec<Divergence> = verify_determinism(&program, &mut interpreter, &input, runs, &config);
+        for divergence in &divergences {
+            println!("{}", divergence);
+        }
+        if !divergences.is_empty() {
+            std::process::exit(1);
+        }
+        println!("{} run(s), all identical", runs);
+        return;
+    }
+
+    let watches: Vec<Watch> = options.watches.iter().map(|cell| {
+        resolve_watch(cell, layout.as_ref(), interpreter.start_pointer()).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        })
+    }).collect();
+
+    let mut input: Box<dyn Read> = Box::new(std::io::Cursor::new(source_input).chain(input_reader(&options.input)));
+    if interpreter.input_bytes() > 0 {
+        // a resumed run already consumed this much of the input
+        std::io::copy(&mut input.by_ref().take(interpreter.input_bytes()), &mut std::io::sink()).expect("Input I/O error");
+    }
+    // output goes out in big chunks unless per-step reports have to interleave with it, `execute_code`
+    // flushes before every `,` and once the run ends
+    let (code_page, mut stdout) = run_stdout(&options);
+    let mut counter: CountingWriter = CountingWriter::default();
+    let mut pixels: Vec<u8> = vec![];
+    let mut report_output: Vec<u8> = vec![];
+    let mut checked_output: Vec<u8> = vec![];
+    let mut tee: TeeWriter = TeeWriter::new(match (options.count_output, &options.output_image) {
+        (true, _) => &mut counter as &mut dyn Write,
+        (false, Some(_)) => &mut pixels,
+        (false, None) => &mut stdout
+    });
+    for path in &options.outputs {
+        let file: File = File::create(path).unwrap_or_else(|err| {
+            eprintln!("Cannot create output file `{}`: {}", path, err);
+            std::process::exit(2);
+        });
+        tee.add(path.as_str(), BufWriter::new(file));
+    }
+    if options.report.is_some() {
+        tee.add("the report", &mut report_output);
+    }
+    if expected_output.is_some() {
+        tee.add("the expected output check", &mut checked_output);
+    }
+    let output: &mut dyn Write = &mut tee;
+
+    let mut observers: RunObservers = RunObservers {
+        post_mortem: options.post_mortem.map(|(step_capacity, io_capacity)| PostMortem::new(step_capacity, io_capacity)),
+        explainer: options.explain.map(|settings| {
+            Explainer { program: &program, source: &file_content, settings, explained: 0 }
+        }),
+        tracer: options.trace_source.map(|settings| {
+            SourceTracer { program: &program, source: &file_content, settings, traced: 0 }
+        }),
+
+        watcher: (!watches.is_empty()).then_some(Watcher { program: &program, source: &file_content, watches }),
+        breakpoint: options.break_on_output.filter(|_| options.single_stepping()).map(|byte| OutputBreakpoint { byte }),
+
+        progress: options.progress.map(|every| Progress { every }),
+        taint: options.taint.then(|| TaintTracker::new(&program, &file_content)),
+        cost_meter: options.cost_model.as_ref().map(|spec| {
+            CostMeter::new(CostModel::load(spec, std::path::Path::new("")).unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                std::process::exit(2);
+            }))
+        }),
+        diff_recorder: options.record_diffs.as_ref().map(|path| {
+            File::create(path).and_then(|file| TapeDiffRecorder::new(BufWriter::new(file), &interpreter)).unwrap_or_else(|err| {
+                eprintln!("Cannot write tape diffs `{}`: {}", path, err);
+                std::process::exit(2);
+            })
+        }),
+        profiler: options.report.as_ref().map(|_| Profiler::new(&program)),
+        cycle_profiler: options.cycle_profile.then(CycleProfiler::new)
+    };
+    let mut effective_operations: Option<u64> = None;
+    let mut profiled: Option<Profiler> = None;
+    let mut cycles: Option<CycleProfiler> = None;
+
+    let result: Result<RunSummary, RuntimeError> = if observers.any() {
+        let result = execute_observed(&program, &mut interpreter, &mut input, output, &config, &mut observers);
+        if let (Err(err), Some(recorder)) = (&result, &observers.post_mortem) {
+            let _ = output.flush();
+            let label = |index: usize| cell_label(layout.as_ref(), &interpreter, index);
+            eprint!("{}", recorder.dump_labeled(err, &interpreter, &label));
+        }
+        if let Some(tracker) = &observers.taint {
+            let _ = output.flush();
+            report_taint(tracker, &file_content);
+        }
+        effective_operations = observers.cost_meter.map(|meter| meter.total);
+        profiled = observers.profiler;
+        cycles = observers.cycle_profiler;
+        if let (Some(recorder), Some(path)) = (observers.diff_recorder, &options.record_diffs) {
+            if let Err(err) = recorder.finish() {
+                eprintln!("Cannot write tape diffs `{}`: {}", path, err);
+            }
+        }
+        result
+    } else {
+        execute_code(&program, &mut interpreter, &mut input, output, &config)
+    };
+    let _ = output.flush();
+    for (path, err) in tee.dropped() {
+        eprintln!("warning: stopped writing to `{}`: {}", path, err);
+    }
+    drop(tee);
+    let _ = stdout.flush();
+    drop(stdout);
+    drop(code_page);
+    if let Err(RuntimeError::Io(err)) = &result {
+        stop_on_broken_pipe(err, &options);
+    }
 
-    execute_code(parsed_instructions, &mut interpreter);
+    if options.count_output {
+        println!("{} bytes of output", counter.count);
+    }
+    if let Some((path, width)) = &options.output_image {
+        std::fs::write(path, encode_image(path, &pixels, *width)).unwrap_or_else(|err| {
+            eprintln!("Cannot write image `{}`: {}", path, err);
+            std::process::exit(2);
+        });
+    }
+    if let (Some(total), Some(spec)) = (effective_operations, &options.cost_model) {
+        eprintln!("effective primitive operations {} ({} cost model)", total, spec);
+    }
+    if let Some(profiler) = &cycles {
+        report_cycles(profiler);
+    }
+    if let (Some(profiler), Some(path)) = (&profiled, &options.report) {
+        let ending: String = match &result {
+            Ok(summary) => summary.halt.to_string(),
+            Err(err) => err.to_string()
+        };
+        let source: &[u8] = &file_content;
+        let report: RunReport =
+            RunReport::new(&options.filepath, source, &program, analyze(&program), profiler, &interpreter, ending, report_output, report_settings(&options));
+        std::fs::write(path, render_html(&report)).unwrap_or_else(|err| {
+            eprintln!("Cannot write report `{}`: {}", path, err);
+            std::process::exit(2);
+        });
+    }
+
+    let matched: bool = match (&expected_output, &options.expect) {
+        (Some(expected), Some(source)) => check_output(expected, source, &checked_output),
+        _ => true
+    };
+    finish_run(&options, &program, &file_content, &interpreter, layout.as_ref(), result, matched);
 }
@@ -0,0 +1,631 @@
+//! Batches of runs described as data: a TOML subset with one `[[job]]` table per run.
+//!
+//! ```toml
+//! [[job]]
+//! name = "hello"
+//! program = "hello.bf"                 # paths are relative to the manifest
+//! input_string = "abc"                 # or input_file = "in.txt", or input_seed = 7 for endless
+//!                                      # pseudo-random bytes (see `crate::input`), no input otherwise
+//! single_pass = true
+//! cell_modulus = 16                    # `+` and `-` wrap at 16, see `ExecutionConfig::cell_modulus`
+//! max_steps = 1000000
+//! expect_output = "hello.out"          # compared byte for byte with the output
+//! expect_checksum = 1234567890         # final tape checksum, see `Interpreter::tape_checksum`
+//! stats_json = "hello.stats.json"      # written after the run
+//! cost_model = "classic"               # adds `effective_operations` to the stats, see `crate::cost`
+//! ```
+//!
+//! A single test case can also be packaged as a spec file (see [`parse_spec`]): the keys of one job
+//! without the `[[job]]` header, typically with the program, input and expected output inline.
+//!
+//! ```toml
+//! source = ",[.,]"
+//! input_string = "a\x00b"
+//! expect_output_string = "a"
+//! eof = "zero"                         # `,` at the end of the input: "unchanged", "zero" or "max"
+//! tape_size = 100
+//! ```
+//!
+//! Strings understand the escapes `\n` `\r` `\t` `\0` `\"` `\\` and `\xNN` (any byte).
+
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::analysis::{analyze, Stats};
+use crate::cost::{CostMeter, CostModel};
+use crate::input::RandomInput;
+use crate::interpreter::{execute_code, execute_observed, EofBehavior, ExecutionConfig, HaltReason, Interpreter, RunSummary, RuntimeError, TapeMode, TAPE_SIZE};
+use crate::layout::strip_comment;
+use crate::parser::{compile, ParseOptions, Program};
+
+/// Growth limit of a dynamic tape when the job doesn't set `tape_size`.
+const DYNAMIC_TAPE_LIMIT: usize = 1 << 26;
+
+/// Bytes given either by a file or inline in the manifest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Data {
+    File(PathBuf),
+    Inline(Vec<u8>)
+}
+
+impl Data {
+    pub fn load(&self) -> Result<Vec<u8>, String> {
+        match self {
+            Data::File(path) => std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err)),
+            Data::Inline(bytes) => Ok(bytes.clone())
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Data::File(path) => path.display().to_string(),
+            Data::Inline(_) => "the expected output".to_string()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub name: String, // defaults to `job N`
+    pub program: Data,
+    pub input: Option<Data>,
+    pub input_seed: Option<u64>, // endless `RandomInput` instead of `input`, like `InputSpec::Random`
+    pub parse: ParseOptions,
+    pub eof: EofBehavior,
+    pub cell_modulus: Option<u16>,
+    pub tape_size: Option<usize>,
+    pub tape_mode: TapeMode,
+    pub max_steps: Option<u64>,
+    pub output: Option<PathBuf>, // the run's output is written here
+    pub expect_output: Option<Data>,
+    pub expect_checksum: Option<u64>,
+    pub stats_json: Option<PathBuf>,
+    pub cost_model: Option<CostModel>
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    pub line: usize,
+    pub job: Option<String>, // name (or `job N`) of the job the error is in
+    pub key: Option<String>,
+    pub message: String
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(job) = &self.job {
+            write!(f, ", {}", job)?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, ", key `{}`", key)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    String(Vec<u8>), // bytes rather than text, `\xNN` escapes may produce invalid UTF-8
+    Integer(u64),
+    Bool(bool)
+}
+
+fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(character) = chars.next() {
+        if character != '\\' {
+            let mut encoded: [u8; 4] = [0; 4];
+            bytes.extend_from_slice(character.encode_utf8(&mut encoded).as_bytes());
+            continue;
+        }
+        bytes.push(match chars.next()? {
+            'n' => b'\n',
+            'r' => b'\r',
+            't' => b'\t',
+            '0' => 0,
+            '"' => b'"',
+            '\\' => b'\\',
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+                u8::from_str_radix(&digits, 16).ok()?
+            },
+            _ => return None
+        });
+    }
+
+    Some(bytes)
+}
+
+fn parse_value(text: &str) -> Option<Value> {
+    if let Some(inner) = text.strip_prefix('"').and_then(|rest| rest.strip_suffix('"')) {
+        return unescape(inner).map(Value::String);
+    }
+    match text {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => text.replace('_', "").parse().ok().map(Value::Integer)
+    }
+}
+
+/// Keys of a job table as written, checked and converted once the table is complete.
+struct RawJob {
+    line: usize,
+    kind: String, // `job N` or `spec`
+    entries: Vec<(usize, String, Value)>
+}
+
+impl RawJob {
+    fn label(&self) -> String {
+        match self.entries.iter().find(|(_, key, _)| key == "name") {
+            Some((_, _, Value::String(name))) => format!("{} (`{}`)", self.kind, String::from_utf8_lossy(name)),
+            _ => self.kind.clone()
+        }
+    }
+
+    fn add(&mut self, line: usize, key: &str, value: &str) -> Result<(), ManifestError> {
+        let error = |message: String| ManifestError { line, job: Some(self.label()), key: Some(key.to_string()), message };
+
+        if self.entries.iter().any(|(_, existing, _)| existing == key) {
+            return Err(error("given twice".to_string()));
+        }
+        let value: Value = parse_value(value).ok_or_else(|| error(format!("`{}` is not a string, integer or boolean", value)))?;
+        self.entries.push((line, key.to_string(), value));
+        Ok(())
+    }
+
+    fn into_job(self, base: &Path) -> Result<Job, ManifestError> {
+        let label: String = self.label();
+        let error = |line: usize, key: &str, message: String| ManifestError { line, job: Some(label.clone()), key: Some(key.to_string()), message };
+
+        let mut job: Job = Job {
+            name: self.kind.clone(),
+            program: Data::Inline(vec![]),
+            input: None,
+            input_seed: None,
+            parse: ParseOptions::default(),
+            eof: EofBehavior::Unchanged,
+            cell_modulus: None,
+            tape_size: None,
+            tape_mode: TapeMode::Fixed,
+            max_steps: None,
+            output: None,
+            expect_output: None,
+            expect_checksum: None,
+            stats_json: None,
+            cost_model: None
+        };
+        let mut has_program: bool = false;
+        let mut modulus_line: usize = 0;
+
+        for (line, key, value) in self.entries {
+            let expected = |kind: &str| error(line, &key, format!("expected {}", kind));
+            let bytes = || match &value { Value::String(bytes) => Ok(bytes.clone()), _ => Err(expected("a string")) };
+            let text = || bytes().map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+            let path = || text().map(|text| base.join(text));
+            let twice = |first: &str, second: &str| error(line, &key, format!("only one of `{}` and `{}` can be given", first, second));
+            let integer = || match value { Value::Integer(number) => Ok(number), _ => Err(expected("an integer")) };
+            let flag = || match value { Value::Bool(flag) => Ok(flag), _ => Err(expected("`true` or `false`")) };
+
+            match key.as_str() {
+                "name" => { job.name = text()?; },
+                "program" | "source" if has_program => { return Err(twice("program", "source")); },
+                "program" => {
+                    job.program = Data::File(path()?);
+                    has_program = true;
+                },
+                "source" => {
+                    job.program = Data::Inline(bytes()?);
+                    has_program = true;
+                },
+                "input_file" | "input_string" | "input_seed" if job.input.is_some() || job.input_seed.is_some() => {
+                    return Err(error(line, &key, "only one of `input_file`, `input_string` and `input_seed` can be given".to_string()));
+                },
+                "input_file" => { job.input = Some(Data::File(path()?)); },
+                "input_string" => { job.input = Some(Data::Inline(bytes()?)); },
+                "input_seed" => { job.input_seed = Some(integer()?); },
+                "eof" => {
+                    job.eof = match text()?.as_str() {
+                        "unchanged" => EofBehavior::Unchanged,
+                        "zero" => EofBehavior::Zero,
+                        "max" => EofBehavior::Max,
+                        other => return Err(error(line, &key, format!("expected \"unchanged\", \"zero\" or \"max\", found \"{}\"", other)))
+                    };
+                },
+                "alt_brackets" => { job.parse.alt_brackets = flag()?; },
+                "single_pass" => { job.parse.fold = flag()?; },
+                "extensions" => { job.parse.extensions = flag()?; },
+                "cell_modulus" => {
+                    let modulus: u64 = integer()?;
+                    if !(2..=256).contains(&modulus) {
+                        return Err(error(line, &key, "has to be between 2 and 256, cells are 8 bits wide".to_string()));
+                    }
+                    job.cell_modulus = Some(modulus as u16);
+                    modulus_line = line;
+                },
+                "tape_size" => { job.tape_size = Some(integer()? as usize); },
+                "dynamic_tape" => {
+                    job.tape_mode = match text()?.as_str() {
+                        "none" => TapeMode::Fixed,
+                        "right" => TapeMode::Growable,
+                        "bidirectional" => TapeMode::Bidirectional,
+                        other => return Err(error(line, &key, format!("expected \"none\", \"right\" or \"bidirectional\", found \"{}\"", other)))
+                    };
+                },
+                "max_steps" => { job.max_steps = Some(integer()?); },
+                "output" => { job.output = Some(path()?); },
+                "expect_output" | "expect_output_string" if job.expect_output.is_some() => {
+                    return Err(twice("expect_output", "expect_output_string"));
+                },
+                "expect_output" => { job.expect_output = Some(Data::File(path()?)); },
+                "expect_output_string" => { job.expect_output = Some(Data::Inline(bytes()?)); },
+                "expect_checksum" => { job.expect_checksum = Some(integer()?); },
+                "stats_json" => { job.stats_json = Some(path()?); },
+                "cost_model" => { job.cost_model = Some(CostModel::load(&text()?, base).map_err(|err| error(line, &key, err))?); },
+                _ => return Err(error(line, &key, "unknown key".to_string()))
+            }
+        }
+
+        if job.parse.fold && job.cell_modulus.is_some_and(|modulus| 256 % modulus != 0) {
+            return Err(error(modulus_line, "cell_modulus", "`single_pass` folds runs modulo 256, the modulus has to divide 256 with it".to_string()));
+        }
+        if !has_program {
+            return Err(ManifestError {
+                line: self.line,
+                job: Some(label),
+                key: Some("program".to_string()),
+                message: "missing, give `program` or `source`".to_string()
+            });
+        }
+        Ok(job)
+    }
+}
+
+/// Parses a manifest, relative paths in it are resolved against `base` (usually the manifest's directory).
+pub fn parse_manifest(text: &str, base: &Path) -> Result<Vec<Job>, ManifestError> {
+    let mut raw_jobs: Vec<RawJob> = vec![];
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line: usize = index + 1;
+        let content: &str = strip_comment(raw_line).trim();
+        if content.is_empty() {
+            continue;
+        }
+        if content == "[[job]]" {
+            raw_jobs.push(RawJob { line, kind: format!("job {}", raw_jobs.len() + 1), entries: vec![] });
+            continue;
+        }
+
+        let (key, value) = content.split_once('=').map(|(key, value)| (key.trim(), value.trim())).ok_or_else(|| ManifestError {
+            line,
+            job: None,
+            key: None,
+            message: format!("expected `[[job]]` or `key = value`, found `{}`", content)
+        })?;
+        let Some(current) = raw_jobs.last_mut() else {
+            return Err(ManifestError { line, job: None, key: Some(key.to_string()), message: "must be inside a `[[job]]` table".to_string() });
+        };
+        current.add(line, key, value)?;
+    }
+
+    raw_jobs.into_iter().map(|raw_job| raw_job.into_job(base)).collect()
+}
+
+/// Parses a spec: the keys of a single job, without a `[[job]]` header.
+pub fn parse_spec(text: &str, base: &Path) -> Result<Job, ManifestError> {
+    let mut raw_job: RawJob = RawJob { line: 1, kind: "spec".to_string(), entries: vec![] };
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line: usize = index + 1;
+        let content: &str = strip_comment(raw_line).trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        let (key, value) = content.split_once('=').map(|(key, value)| (key.trim(), value.trim())).ok_or_else(|| ManifestError {
+            line,
+            job: None,
+            key: None,
+            message: format!("expected `key = value`, found `{}`", content)
+        })?;
+        raw_job.add(line, key, value)?;
+    }
+
+    raw_job.into_job(base)
+}
+
+/// What happened to one job, `failures` is empty if it met every expectation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobReport {
+    pub name: String,
+    pub steps: u64,
+    pub output_bytes: u64,
+    pub checksum: u64,
+    pub effective_operations: Option<u64>, // with a `cost_model`
+    pub failures: Vec<String>
+}
+
+impl JobReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+fn stats_json(stats: &Stats, report: &JobReport, halt: Option<HaltReason>) -> String {
+    let halt: &str = match halt {
+        Some(HaltReason::EndOfProgram) => "end_of_program",
+        Some(HaltReason::Stopped) => "stopped",
+        Some(HaltReason::Cancelled) => "cancelled",
+        Some(HaltReason::Sentinel) => "sentinel",
+        None => "error"
+    };
+    let effective_operations: String = report.effective_operations.map(|total| format!(",\"effective_operations\":{}", total)).unwrap_or_default();
+    format!(
+        "{{\"instructions\":{},\"loops\":{},\"max_depth\":{},\"steps\":{},\"output_bytes\":{},\"tape_checksum\":{},\"halt\":\"{}\"{}}}\n",
+        stats.instructions, stats.loops, stats.max_depth, report.steps, report.output_bytes, report.checksum, halt, effective_operations
+    )
+}
+
+impl Job {
+    fn interpreter(&self) -> Interpreter {
+        match self.tape_mode {
+            TapeMode::Fixed => Interpreter::fixed(self.tape_size.unwrap_or(TAPE_SIZE)),
+            TapeMode::Growable => Interpreter::growable(self.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT)),
+            TapeMode::Bidirectional => Interpreter::bidirectional(self.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT))
+        }
+    }
+
+    /// Runs the job, writes its artifacts and checks its expectations. I/O and parse problems are
+    /// reported as failures too, so one broken job doesn't stop the batch.
+    pub fn run(&self) -> JobReport {
+        let mut report: JobReport =
+            JobReport { name: self.name.clone(), steps: 0, output_bytes: 0, checksum: 0, effective_operations: None, failures: vec![] };
+        let source: Vec<u8> = match self.program.load() {
+            Ok(source) => source,
+            Err(err) => {
+                report.failures.push(err);
+                return report;
+            }
+        };
+        let program: Program = match compile(&source, &self.parse, true) {
+            Ok(program) => program,
+            Err(err) => {
+                report.failures.push(err.to_string());
+                return report;
+            }
+        };
+
+        let bytes: Vec<u8> = match self.input.as_ref().map_or(Ok(vec![]), Data::load) {
+            Ok(input) => input,
+            Err(err) => {
+                report.failures.push(err);
+                return report;
+            }
+        };
+        let mut input: Box<dyn Read> = match self.input_seed {
+            Some(seed) => Box::new(RandomInput::new(seed)),
+            None => Box::new(&bytes[..])
+        };
+
+        let mut interpreter: Interpreter = self.interpreter();
+        let mut output: Vec<u8> = vec![];
+        let config: ExecutionConfig = ExecutionConfig { max_steps: self.max_steps, eof: self.eof, cell_modulus: self.cell_modulus, ..ExecutionConfig::default() };
+        // metered runs step every instruction, only jobs asking for the cost pay for that
+        let result: Result<RunSummary, RuntimeError> = match self.cost_model {
+            Some(model) => {
+                let mut meter: CostMeter = CostMeter::new(model);
+                let result = execute_observed(&program, &mut interpreter, &mut input, &mut output, &config, &mut meter);
+                report.effective_operations = Some(meter.total);
+                result
+            },
+            None => execute_code(&program, &mut interpreter, &mut input, &mut output, &config)
+        };
+
+        report.steps = interpreter.steps();
+        report.output_bytes = output.len() as u64;
+        report.checksum = interpreter.tape_checksum();
+        let halt: Option<HaltReason> = result.as_ref().ok().map(|summary| summary.halt);
+        if let Err(err) = result {
+            report.failures.push(err.to_string());
+        }
+
+        if let Some(path) = &self.output {
+            if let Err(err) = std::fs::write(path, &output) {
+                report.failures.push(format!("{}: {}", path.display(), err));
+            }
+        }
+        if let Some(path) = &self.stats_json {
+            let json: String = stats_json(&analyze(&program), &report, halt);
+            if let Err(err) = std::fs::write(path, json) {
+                report.failures.push(format!("{}: {}", path.display(), err));
+            }
+        }
+
+        if let Some(expected) = &self.expect_output {
+            match expected.load() {
+                Ok(expected_bytes) if expected_bytes != output => {
+                    let at: usize = expected_bytes.iter().zip(&output).take_while(|(a, b)| a == b).count();
+                    report.failures.push(format!("output differs from {} at byte {}", expected.describe(), at));
+                },
+                Ok(_) => {},
+                Err(err) => report.failures.push(err)
+            }
+        }
+        if let Some(expected) = self.expect_checksum {
+            if expected != report.checksum {
+                report.failures.push(format!("tape checksum {} expected, got {}", expected, report.checksum));
+            }
+        }
+
+        report
+    }
+}
+
+/// Runs `jobs` on up to `threads` threads, each job on its own interpreter with its output kept in
+/// memory, and returns their reports in manifest order. One thread runs them one after the other.
+pub fn run_jobs(jobs: &[Job], threads: usize) -> Vec<JobReport> {
+    if threads <= 1 {
+        return jobs.iter().map(Job::run).collect();
+    }
+
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let mut reports: Vec<(usize, JobReport)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.min(jobs.len())).map(|_| scope.spawn(|| {
+            let mut done: Vec<(usize, JobReport)> = vec![];
+            loop {
+                let index: usize = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else { break };
+                done.push((index, job.run()));
+            }
+            done
+        })).collect();
+        workers.into_iter().flat_map(|worker| worker.join().expect("Job thread panicked")).collect()
+    });
+    reports.sort_by_key(|&(index, _)| index);
+    reports.into_iter().map(|(_, report)| report).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+# one job passing, two failing their expectations and one failing to run
+[[job]]
+name = "echo"
+source = ",[.[-],]"
+input_string = "ab\x00"
+expect_output_string = "ab"
+
+[[job]]
+source = "++++++:"          # prints "6"
+extensions = true
+expect_output_string = "7"
+
+[[job]]
+name = "checksum"
+source = "+>++"
+expect_checksum = 1
+
+[[job]]
+name = "slow"
+source = "+[]"
+max_steps = 1_000
+"#;
+
+    fn error(text: &str) -> ManifestError {
+        parse_manifest(text, Path::new("")).unwrap_err()
+    }
+
+    #[test]
+    fn passing_and_failing_jobs_are_reported_in_order() {
+        let jobs: Vec<Job> = parse_manifest(MANIFEST, Path::new("")).unwrap();
+        assert_eq!(jobs.iter().map(|job| job.name.as_str()).collect::<Vec<&str>>(), ["echo", "job 2", "checksum", "slow"]);
+        assert_eq!(jobs[0].input, Some(Data::Inline(b"ab\0".to_vec())));
+
+        let reports: Vec<JobReport> = run_jobs(&jobs, 1);
+        assert!(reports[0].passed(), "{:?}", reports[0]);
+        assert_eq!(reports[1].failures, ["output differs from the expected output at byte 0"]);
+        assert_eq!(reports[2].failures, [format!("tape checksum 1 expected, got {}", reports[2].checksum)]);
+        assert_eq!(reports[3].failures, ["Step limit exceeded"]);
+        assert_eq!(reports[3].steps, 1_000);
+
+        assert_eq!(run_jobs(&jobs, 3), reports, "threads change the schedule, not the reports");
+    }
+
+    #[test]
+    fn artifacts_are_written_next_to_the_manifest() {
+        let base: PathBuf = std::env::temp_dir().join(format!("brainfck-manifest-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("hi.bf"), "++++++++[>++++++++<-]>+.+.").unwrap();
+        let manifest: &str = "[[job]]\nprogram = \"hi.bf\"\noutput = \"hi.out\"\nstats_json = \"hi.json\"\n";
+
+        let reports: Vec<JobReport> = run_jobs(&parse_manifest(manifest, &base).unwrap(), 1);
+        assert!(reports[0].passed(), "{:?}", reports[0]);
+        assert_eq!(std::fs::read(base.join("hi.out")).unwrap(), b"AB");
+        let json: String = std::fs::read_to_string(base.join("hi.json")).unwrap();
+        assert!(json.starts_with("{\"instructions\":") && json.contains("\"output_bytes\":2,") && json.contains("\"halt\":\"end_of_program\""), "{}", json);
+
+        let missing: Vec<JobReport> = run_jobs(&parse_manifest("[[job]]\nprogram = \"nowhere.bf\"\n", &base).unwrap(), 1);
+        assert!(missing[0].failures[0].contains("nowhere.bf"), "{:?}", missing[0]);
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn malformed_manifests_name_the_job_and_key() {
+        let unknown: ManifestError = error("[[job]]\nname = \"a\"\nsource = \"+\"\nmax_stpes = 5\n");
+        assert_eq!(unknown.to_string(), "line 4, job 1 (`a`), key `max_stpes`: unknown key");
+
+        let wrong_type: ManifestError = error("[[job]]\nsource = \"+\"\n[[job]]\nsource = \"-\"\nmax_steps = \"many\"\n");
+        assert_eq!((wrong_type.line, wrong_type.job.as_deref(), wrong_type.key.as_deref()), (5, Some("job 2"), Some("max_steps")));
+        assert_eq!(wrong_type.message, "expected an integer");
+
+        assert_eq!(error("[[job]]\nsource = \"+\"\nsource = \"-\"\n").message, "given twice");
+        assert_eq!(error("[[job]]\nsource = \"+\"\nprogram = \"a.bf\"\n").message, "only one of `program` and `source` can be given");
+        assert_eq!(error("[[job]]\nsource = \"\\q\"\n").message, "`\"\\q\"` is not a string, integer or boolean");
+        assert_eq!(error("[[job]]\nsource = \"+\"\ndynamic_tape = \"left\"\n").message, "expected \"none\", \"right\" or \"bidirectional\", found \"left\"");
+
+        let missing: ManifestError = error("\n[[job]]\nname = \"empty\"\n");
+        assert_eq!(missing.to_string(), "line 2, job 1 (`empty`), key `program`: missing, give `program` or `source`");
+        let outside: ManifestError = error("source = \"+\"\n");
+        assert_eq!((outside.job, outside.message.as_str()), (None, "must be inside a `[[job]]` table"));
+        assert_eq!(error("[[job]]\nsource\n").message, "expected `[[job]]` or `key = value`, found `source`");
+    }
+
+    #[test]
+    fn an_input_seed_feeds_endless_random_bytes() {
+        let mut expected: [u8; 4] = [0; 4];
+        RandomInput::new(7).read_exact(&mut expected).unwrap();
+        let jobs: Vec<Job> = parse_manifest("[[job]]\nsource = \",.,.,.,.\"\ninput_seed = 7\n[[job]]\nsource = \",[.,]\"\ninput_seed = 7\nmax_steps = 100\n", Path::new("")).unwrap();
+        assert_eq!((jobs[0].input_seed, &jobs[0].input), (Some(7), &None));
+
+        let mut job: Job = jobs[0].clone();
+        let base: PathBuf = std::env::temp_dir().join(format!("brainfck-seed-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        job.output = Some(base.join("random.out"));
+        assert!(job.run().passed());
+        assert_eq!(std::fs::read(base.join("random.out")).unwrap(), expected);
+        std::fs::remove_dir_all(&base).unwrap();
+        // a program reading up to the end of the input never gets there
+        assert_eq!(jobs[1].run().failures, ["Step limit exceeded"]);
+
+        for other in ["input_string = \"a\"", "input_file = \"in.txt\""] {
+            let both: ManifestError = error(&format!("[[job]]\nsource = \",\"\n{}\ninput_seed = 7\n", other));
+            assert_eq!((both.line, both.key.as_deref()), (4, Some("input_seed")));
+            assert_eq!(both.message, "only one of `input_file`, `input_string` and `input_seed` can be given");
+        }
+        assert_eq!(error("[[job]]\nsource = \",\"\ninput_seed = \"seven\"\n").message, "expected an integer");
+    }
+
+    #[test]
+    fn a_cell_modulus_wraps_the_job_cells() {
+        let job: Job = parse_spec("source = \"----------:\"\nextensions = true\ncell_modulus = 7\nexpect_output_string = \"4\"\n", Path::new("")).unwrap();
+        assert_eq!(job.cell_modulus, Some(7));
+        assert!(job.run().passed(), "{:?}", job.run());
+        let folded: Job = parse_spec("source = \"----------:\"\nextensions = true\nsingle_pass = true\ncell_modulus = 16\nexpect_output_string = \"6\"\n", Path::new("")).unwrap();
+        assert!(folded.run().passed(), "{:?}", folded.run());
+
+        for modulus in [0, 1, 257] {
+            let out_of_range: ManifestError = error(&format!("[[job]]\nsource = \"+\"\ncell_modulus = {}\n", modulus));
+            assert_eq!((out_of_range.line, out_of_range.key.as_deref()), (3, Some("cell_modulus")));
+            assert_eq!(out_of_range.message, "has to be between 2 and 256, cells are 8 bits wide");
+        }
+        let folding: ManifestError = error("[[job]]\nsource = \"+\"\ncell_modulus = 7\nsingle_pass = true\n");
+        assert_eq!((folding.line, folding.key.as_deref()), (3, Some("cell_modulus")));
+        assert_eq!(folding.message, "`single_pass` folds runs modulo 256, the modulus has to divide 256 with it");
+    }
+
+    #[test]
+    fn a_spec_is_one_job_without_a_header() {
+        let job: Job = parse_spec("source = \",.\"\ninput_string = \"\\xff\"\neof = \"zero\"\ntape_size = 100\n", Path::new("")).unwrap();
+        assert_eq!((job.name.as_str(), job.eof, job.tape_size), ("spec", EofBehavior::Zero, Some(100)));
+        assert_eq!(job.input, Some(Data::Inline(vec![0xff])));
+        assert_eq!(parse_spec("[[job]]\n", Path::new("")).unwrap_err().message, "expected `key = value`, found `[[job]]`");
+    }
+}
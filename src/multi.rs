@@ -0,0 +1,143 @@
+//! Several independent programs sharing one thread and one terminal, see the `multi` command.
+//!
+//! The programs take turns, round robin, each running a slice of steps per turn on its own tape.
+//! Their output is split into lines and every complete line goes out tagged with the program's name
+//! at the end of the turn producing it, so lines of different programs never mix. Only the active
+//! program reads the input, `,` in any other one sees the end of the input.
+
+use std::io::{self, Read, Write};
+
+use crate::interpreter::{HaltReason, RuntimeError};
+use crate::pipeline::Stage;
+
+/// How one program of [`run_round_robin`] ended, `None` if the global step limit came first.
+pub type Ending = Option<Result<HaltReason, RuntimeError>>;
+
+/// Writes the complete lines of `pending` as `[name] line`, keeping an unfinished last line.
+fn write_lines(name: &str, pending: &mut Vec<u8>, output: &mut dyn Write) -> io::Result<()> {
+    let Some(last_newline) = pending.iter().rposition(|&byte| byte == b'\n') else {
+        return Ok(());
+    };
+    for line in pending[..last_newline].split(|&byte| byte == b'\n') {
+        output.write_all(format!("[{}] ", name).as_bytes())?;
+        output.write_all(line)?;
+        output.write_all(b"\n")?;
+    }
+    pending.drain(..=last_newline);
+    Ok(())
+}
+
+/// Runs `stages` by turns of `slice` steps until every one has halted or failed, or `max_steps`
+/// steps were executed in total. `active` is the index of the program reading `input`. The stages'
+/// own configs apply to each of them, their `max_steps` counting that program's steps.
+pub fn run_round_robin(
+    stages: &mut [Stage],
+    slice: u64,
+    max_steps: Option<u64>,
+    active: usize,
+    input: &mut dyn Read,
+    output: &mut dyn Write
+) -> io::Result<Vec<Ending>> {
+    let mut endings: Vec<Ending> = stages.iter().map(|_| None).collect();
+    let mut pending: Vec<Vec<u8>> = vec![vec![]; stages.len()];
+    let mut total: u64 = 0;
+
+    while endings.iter().any(Option::is_none) && max_steps.is_none_or(|max_steps| total < max_steps) {
+        for (index, stage) in stages.iter_mut().enumerate() {
+            if endings[index].is_some() {
+                continue;
+            }
+            let budget: u64 = max_steps.map_or(slice, |max_steps| slice.min(max_steps - total));
+            if budget == 0 {
+                break;
+            }
+
+            let before: u64 = stage.interpreter.steps();
+            let stage_input: &mut dyn Read = if index == active { &mut *input } else { &mut io::empty() };
+            match stage.interpreter.run_for(&stage.program, budget, stage_input, &mut pending[index], &stage.config) {
+                Ok(Some(halt)) => { endings[index] = Some(Ok(halt)); },
+                Ok(None) => {},
+                Err(err) => { endings[index] = Some(Err(err)); }
+            }
+            total += stage.interpreter.steps() - before;
+            write_lines(&stage.name, &mut pending[index], output)?;
+        }
+        output.flush()?;
+    }
+
+    // what is left of the last lines goes out too, as lines of their own
+    for (stage, pending) in stages.iter().zip(&mut pending) {
+        if !pending.is_empty() {
+            pending.push(b'\n');
+            write_lines(&stage.name, pending, output)?;
+        }
+    }
+    output.flush()?;
+    Ok(endings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{ExecutionConfig, Interpreter};
+    use crate::parser::{compile, ParseOptions, Program};
+
+    /// A folded program printing `text`, three steps per byte: `[-]`, one `Add` and `.`.
+    fn printing(name: &str, text: &str) -> Stage {
+        let code: String = text.bytes().map(|byte| format!("[-]{}.", "+".repeat(byte as usize))).collect();
+        let program: Program = compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, true).unwrap();
+        Stage { name: name.to_string(), program, interpreter: Interpreter::default(), config: ExecutionConfig::default() }
+    }
+
+    fn round_robin(stages: &mut [Stage], slice: u64, max_steps: Option<u64>) -> (String, Vec<Ending>) {
+        let mut output: Vec<u8> = vec![];
+        let endings: Vec<Ending> = run_round_robin(stages, slice, max_steps, 0, &mut io::empty(), &mut output).unwrap();
+        (String::from_utf8(output).unwrap(), endings)
+    }
+
+    /// The lines tagged `[name]`, without the tag.
+    fn lines_of<'a>(output: &'a str, name: &str) -> Vec<&'a str> {
+        let tag: String = format!("[{}] ", name);
+        output.lines().filter_map(|line| line.strip_prefix(tag.as_str())).collect()
+    }
+
+    #[test]
+    fn lines_go_out_at_the_end_of_the_turn_completing_them() {
+        // a line of two characters and its newline takes three turns of one character
+        let mut stages: Vec<Stage> = vec![printing("a", "ab\ncd\n"), printing("b", "xy\n")];
+        let (output, endings) = round_robin(&mut stages, 3, None);
+        assert_eq!(output, "[a] ab\n[b] xy\n[a] cd\n");
+        assert!(endings.iter().all(|ending| matches!(ending, Some(Ok(HaltReason::EndOfProgram)))));
+
+        // a turn long enough for a whole program writes all of its lines before the next one runs
+        let mut stages: Vec<Stage> = vec![printing("a", "ab\ncd\n"), printing("b", "xy\n")];
+        assert_eq!(round_robin(&mut stages, 1000, None).0, "[a] ab\n[a] cd\n[b] xy\n");
+    }
+
+    #[test]
+    fn turns_ending_inside_a_line_or_a_byte_never_split_it() {
+        let texts: [(&str, &str); 3] = [("first", "one\ntwo\nthree\n"), ("second", "alpha\nbeta\n"), ("third", "x\ny\nz\nw\n")];
+        for slice in 1..=10 {
+            let mut stages: Vec<Stage> = texts.iter().map(|&(name, text)| printing(name, text)).collect();
+            let (output, endings) = round_robin(&mut stages, slice, None);
+            assert!(endings.iter().all(|ending| matches!(ending, Some(Ok(HaltReason::EndOfProgram)))), "slice {}", slice);
+            assert_eq!(output.lines().count(), 9, "slice {}: {}", slice, output);
+            for (name, text) in texts {
+                assert_eq!(lines_of(&output, name), text.lines().collect::<Vec<&str>>(), "slice {}", slice);
+            }
+        }
+    }
+
+    #[test]
+    fn unfinished_lines_go_out_last_and_the_step_limit_ends_every_program() {
+        let mut stages: Vec<Stage> = vec![printing("a", "ab"), printing("b", "x\ny")];
+        assert_eq!(round_robin(&mut stages, 3, None).0, "[b] x\n[a] ab\n[b] y\n");
+
+        // 10 steps: "a" halts in its second turn, "b" is left a single step of its second one and
+        // ends part way through the newline
+        let mut stages: Vec<Stage> = vec![printing("a", "ab"), printing("b", "x\ny")];
+        let (output, endings) = round_robin(&mut stages, 3, Some(10));
+        assert_eq!(output, "[a] ab\n[b] x\n");
+        assert!(matches!(endings[..], [Some(Ok(HaltReason::EndOfProgram)), None]));
+    }
+}
@@ -0,0 +1,193 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use brainfck::cost::CostMeter;
+use brainfck::cycles::CycleProfiler;
+use brainfck::layout::{Field, Layout};
+use brainfck::post_mortem::PostMortem;
+use brainfck::report::Profiler;
+use brainfck::taint::TaintTracker;
+use brainfck::tape_diff::TapeDiffRecorder;
+use brainfck::{explain_step, highlight_position, line_col, Instruction, Interpreter, Observer, Program, StepEvent};
+
+use crate::cli::Pacing;
+
+/// Waits between two reported steps as configured.
+fn pace(settings: &Pacing) {
+    if settings.wait_for_enter {
+        let _ = std::io::stdin().read_line(&mut String::new());
+    } else if settings.delay_ms > 0 {
+        std::thread::sleep(std::time::Duration::from_millis(settings.delay_ms));
+    }
+}
+
+/// Prints a sentence about every executed instruction.
+pub struct Explainer<'a> {
+    pub program: &'a Program,
+    pub source: &'a [u8],
+    pub settings: Pacing,
+    pub explained: u64
+}
+
+impl Observer for Explainer<'_> {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if self.settings.limit.is_some_and(|limit| self.explained >= limit) {
+            return;
+        }
+        self.explained += 1;
+
+        let _ = std::io::stdout().flush(); // program output so far goes out before the explanation
+        eprintln!("{}", explain_step(event, interpreter.instruction_index(), self.program, self.source));
+
+        pace(&self.settings);
+    }
+}
+
+pub struct Watch {
+    pub label: String,
+    pub offsets: std::ops::Range<isize>, // relative to the start pointer, stays valid when a bidirectional tape grows
+    pub field: Option<Field>
+}
+
+pub struct Watcher<'a> {
+    pub program: &'a Program,
+    pub source: &'a [u8],
+    pub watches: Vec<Watch>
+}
+
+impl Observer for Watcher<'_> {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if event.before == event.after {
+            return;
+        }
+
+        let offset: isize = event.pointer as isize - interpreter.start_pointer() as isize;
+        for watch in self.watches.iter().filter(|watch| watch.offsets.contains(&offset)) {
+            let value: String = match &watch.field {
+                Some(field) => field.render(interpreter.tape(), interpreter.start_pointer()),
+                None => event.after.to_string()
+            };
+            let (line, _) = line_col(self.source, self.program.positions()[event.index]);
+
+            let _ = std::io::stdout().flush();
+            eprintln!("{}={} at line {}", watch.label, value, line);
+        }
+    }
+}
+
+pub fn resolve_watch(cell: &str, layout: Option<&Layout>, origin: usize) -> Result<Watch, String> {
+    if let Ok(index) = cell.parse::<usize>() {
+        let offset: isize = index as isize - origin as isize;
+        return Ok(Watch { label: format!("[{}]", index), offsets: offset..offset + 1, field: None });
+    }
+
+    let layout: &Layout = layout.ok_or(format!("`{}` is not a cell index, names need a --layout", cell))?;
+    let field: &Field = layout.resolve(cell)?;
+    let offsets = field.offset..field.offset + field.width as isize;
+
+    Ok(Watch { label: field.name.clone(), offsets, field: Some(field.clone()) })
+}
+
+/// Prints the source line of every executed instruction with a caret under it.
+pub struct SourceTracer<'a> {
+    pub program: &'a Program,
+    pub source: &'a [u8],
+    pub settings: Pacing,
+    pub traced: u64
+}
+
+impl Observer for SourceTracer<'_> {
+    fn on_step(&mut self, event: &StepEvent, _interpreter: &Interpreter) {
+        if self.settings.limit.is_some_and(|limit| self.traced >= limit) {
+            return;
+        }
+        self.traced += 1;
+
+        let position: usize = self.program.positions()[event.index];
+        let (line, column) = line_col(self.source, position);
+
+        let _ = std::io::stdout().flush();
+        eprintln!("step {} at {}:{} ({}), pointer {}", event.step, line, column, event.instruction, event.pointer);
+        eprintln!("{}", highlight_position(self.source, position));
+
+        pace(&self.settings);
+    }
+}
+
+/// `--break-on-output` while single-stepping: pauses until Enter when `.` outputs `byte`.
+pub struct OutputBreakpoint {
+    pub byte: u8
+}
+
+impl Observer for OutputBreakpoint {
+    fn on_step(&mut self, event: &StepEvent, _interpreter: &Interpreter) {
+        if event.instruction != Instruction::OutputValue || event.before != self.byte {
+            return;
+        }
+
+        let _ = std::io::stdout().flush();
+        eprintln!("output breakpoint: byte {} ({:?}) at step {}, pointer {}, press Enter to continue", self.byte, self.byte as char, event.step, event.pointer);
+        let _ = std::io::stdin().read_line(&mut String::new());
+    }
+}
+
+/// `--progress`: a sign of life every `every` steps of a long run.
+pub struct Progress {
+    pub every: u64
+}
+
+impl Observer for Progress {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if event.step.is_multiple_of(self.every) {
+            eprintln!("progress: {} steps, pointer {}", event.step, interpreter.pointer());
+        }
+    }
+}
+
+/// Everything watching a plain run, each one there when its option was given.
+pub struct RunObservers<'a> {
+    pub post_mortem: Option<PostMortem>,
+    pub explainer: Option<Explainer<'a>>,
+    pub tracer: Option<SourceTracer<'a>>,
+    pub watcher: Option<Watcher<'a>>,
+    pub breakpoint: Option<OutputBreakpoint>,
+    pub progress: Option<Progress>,
+    pub taint: Option<TaintTracker>,
+    pub cost_meter: Option<CostMeter>,
+    pub diff_recorder: Option<TapeDiffRecorder<BufWriter<File>>>,
+    pub profiler: Option<Profiler>,
+    pub cycle_profiler: Option<CycleProfiler>
+}
+
+impl RunObservers<'_> {
+    /// Whether any is there, without one the run takes the faster unobserved path.
+    pub fn any(&self) -> bool {
+        self.post_mortem.is_some()
+            || self.explainer.is_some()
+            || self.tracer.is_some()
+            || self.watcher.is_some()
+            || self.breakpoint.is_some()
+            || self.progress.is_some()
+            || self.taint.is_some()
+            || self.cost_meter.is_some()
+            || self.diff_recorder.is_some()
+            || self.profiler.is_some()
+            || self.cycle_profiler.is_some()
+    }
+}
+
+impl Observer for RunObservers<'_> {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        self.post_mortem.on_step(event, interpreter);
+        self.explainer.on_step(event, interpreter);
+        self.tracer.on_step(event, interpreter);
+        self.watcher.on_step(event, interpreter);
+        self.breakpoint.on_step(event, interpreter);
+        self.progress.on_step(event, interpreter);
+        self.taint.on_step(event, interpreter);
+        self.cost_meter.on_step(event, interpreter);
+        self.diff_recorder.on_step(event, interpreter);
+        self.profiler.on_step(event, interpreter);
+        self.cycle_profiler.on_step(event, interpreter);
+    }
+}
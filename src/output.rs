@@ -0,0 +1,223 @@
+//! Output sinks for [`crate::execute_code`] and friends.
+
+use std::io::{self, Write};
+
+/// Discards everything written to it, only counting the bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CountingWriter {
+    pub count: u64
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.count += bytes.len() as u64;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Fans output out to a primary sink and any number of secondary ones.
+///
+/// A failing primary sink fails the write (and so the run), a failing secondary one is dropped and
+/// remembered in [`TeeWriter::dropped`] while the others carry on.
+pub struct TeeWriter<'a> {
+    primary: Box<dyn Write + 'a>,
+    secondaries: Vec<(String, Box<dyn Write + 'a>)>,
+    dropped: Vec<(String, io::Error)>
+}
+
+impl<'a> TeeWriter<'a> {
+    pub fn new(primary: impl Write + 'a) -> Self {
+        Self { primary: Box::new(primary), secondaries: vec![], dropped: vec![] }
+    }
+
+    /// Adds a secondary sink, `name` identifies it in [`TeeWriter::dropped`].
+    pub fn add(&mut self, name: impl Into<String>, sink: impl Write + 'a) {
+        self.secondaries.push((name.into(), Box::new(sink)));
+    }
+
+    /// Secondary sinks that failed, with the error that made them drop out.
+    pub fn dropped(&self) -> &[(String, io::Error)] {
+        &self.dropped
+    }
+
+    fn for_each_secondary(&mut self, mut action: impl FnMut(&mut dyn Write) -> io::Result<()>) {
+        let mut index: usize = 0;
+        while index < self.secondaries.len() {
+            match action(&mut self.secondaries[index].1) {
+                Ok(()) => { index += 1; },
+                Err(err) => {
+                    let (name, _) = self.secondaries.remove(index);
+                    self.dropped.push((name, err));
+                }
+            }
+        }
+    }
+}
+
+impl Write for TeeWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.primary.write_all(bytes)?;
+        self.for_each_secondary(|sink| sink.write_all(bytes));
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.for_each_secondary(|sink| sink.flush());
+        self.primary.flush()
+    }
+}
+
+/// Writes to `inner` until it fails with [`io::ErrorKind::BrokenPipe`], then discards everything,
+/// so a program whose reader went away runs on without output.
+pub struct DiscardOnBrokenPipe<W: Write> {
+    inner: W,
+    closed: bool
+}
+
+impl<W: Write> DiscardOnBrokenPipe<W> {
+    pub fn new(inner: W) -> Self {
+        Self { inner, closed: false }
+    }
+
+    /// Whether the pipe broke and the output is being discarded.
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    fn guard(&mut self, result: io::Result<()>) -> io::Result<()> {
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed = true;
+                Ok(())
+            },
+            result => result
+        }
+    }
+}
+
+impl<W: Write> Write for DiscardOnBrokenPipe<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if !self.closed {
+            let result: io::Result<()> = self.inner.write_all(bytes);
+            self.guard(result)?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        let result: io::Result<()> = self.inner.flush();
+        self.guard(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_code, ExecutionConfig, Interpreter, RuntimeError};
+    use crate::parser::{compile, ParseOptions, Program};
+
+    /// Keeps what was written and how much of it had been written at every flush.
+    #[derive(Default)]
+    struct Recorder {
+        bytes: Vec<u8>,
+        flushed_at: Vec<usize>
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.bytes.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed_at.push(self.bytes.len());
+            Ok(())
+        }
+    }
+
+    /// Takes `room` bytes, then fails every write like a full disk.
+    struct Full {
+        room: usize
+    }
+
+    impl Write for Full {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::Error::new(io::ErrorKind::StorageFull, "no space left"));
+            }
+            let count: usize = bytes.len().min(self.room);
+            self.room -= count;
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(code: &str, input: &[u8], output: &mut dyn Write) -> Result<(), RuntimeError> {
+        let program: Program = compile(code.as_bytes(), &ParseOptions::default(), true).unwrap();
+        execute_code(&program, &mut Interpreter::default(), &mut &input[..], output, &ExecutionConfig::default()).map(|_| ())
+    }
+
+    #[test]
+    fn every_sink_gets_the_same_bytes_and_flushes() {
+        let (mut primary, mut file, mut capture) = (Recorder::default(), Recorder::default(), Recorder::default());
+        let mut tee: TeeWriter = TeeWriter::new(&mut primary);
+        tee.add("file", &mut file);
+        tee.add("capture", &mut capture);
+        // `,` flushes first, so a prompt is out before the program waits for the answer
+        run("++++++++[>++++++++<-]>+.+.,.", b"!", &mut tee).unwrap();
+        assert!(tee.dropped().is_empty());
+        drop(tee);
+
+        for sink in [&primary, &file, &capture] {
+            assert_eq!(sink.bytes, b"AB!");
+            assert_eq!(sink.flushed_at.first(), Some(&2));
+            assert_eq!(sink.flushed_at.last(), Some(&3), "flushed once the run ended");
+        }
+    }
+
+    #[test]
+    fn a_failing_secondary_sink_is_dropped_and_the_run_goes_on() {
+        let (mut primary, mut capture) = (Recorder::default(), Recorder::default());
+        let mut tee: TeeWriter = TeeWriter::new(&mut primary);
+        tee.add("full disk", Full { room: 2 });
+        tee.add("capture", &mut capture);
+        run("++++++++[>++++++++<-]>+.+.+.+.", b"", &mut tee).unwrap();
+
+        let dropped: &[(String, io::Error)] = tee.dropped();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!((dropped[0].0.as_str(), dropped[0].1.kind()), ("full disk", io::ErrorKind::StorageFull));
+        drop(tee);
+        assert_eq!(primary.bytes, b"ABCD");
+        assert_eq!(capture.bytes, b"ABCD");
+    }
+
+    #[test]
+    fn a_failing_primary_sink_fails_the_run() {
+        let mut capture: Recorder = Recorder::default();
+        let mut tee: TeeWriter = TeeWriter::new(Full { room: 1 });
+        tee.add("capture", &mut capture);
+        let result: Result<(), RuntimeError> = run("++++++++[>++++++++<-]>+.+.+.", b"", &mut tee);
+
+        assert!(matches!(result, Err(RuntimeError::Io(err)) if err.kind() == io::ErrorKind::StorageFull));
+        assert!(tee.dropped().is_empty());
+        drop(tee);
+        assert_eq!(capture.bytes, b"A", "the byte the primary sink refused went nowhere");
+    }
+
+    #[test]
+    fn counting_writer_counts() {
+        let mut counter: CountingWriter = CountingWriter::default();
+        run("+[.+]", b"", &mut counter).unwrap();
+        assert_eq!(counter.count, 255);
+    }
+}
@@ -0,0 +1,516 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    IncrementPointer,
+    DecrementPointer,
+
+    IncrementValue,
+    DecrementValue,
+
+    OutputValue,
+    InputValue,
+    OutputDecimal, // `:` with `ParseOptions::extensions`, writes the cell as a decimal number
+
+    Begin, // jumps past the matching `End` if zero
+    End, // jumps back past the matching `Begin` if not zero
+
+    // only produced when folding while parsing, see `ParseOptions::fold`
+    Add(u8), // run of `+`/`-`, wrapping
+    Move(isize), // run of `>`/`<`
+    SetZero, // `[-]` or `[+]`
+
+    Halt // ends the run, appended by `compile` unless told otherwise
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::IncrementPointer => write!(f, ">"),
+            Instruction::DecrementPointer => write!(f, "<"),
+            Instruction::IncrementValue => write!(f, "+"),
+            Instruction::DecrementValue => write!(f, "-"),
+            Instruction::OutputValue => write!(f, "."),
+            Instruction::InputValue => write!(f, ","),
+            Instruction::OutputDecimal => write!(f, ":"),
+            Instruction::Begin => write!(f, "["),
+            Instruction::End => write!(f, "]"),
+            Instruction::Add(delta) if *delta > 128 => write!(f, "-{}", delta.wrapping_neg()),
+            Instruction::Add(delta) => write!(f, "+{}", delta),
+            Instruction::Move(offset) if *offset < 0 => write!(f, "<{}", offset.unsigned_abs()),
+            Instruction::Move(offset) => write!(f, ">{}", offset),
+            Instruction::SetZero => write!(f, "[-]"),
+            Instruction::Halt => write!(f, "halt")
+        }
+    }
+}
+
+impl Instruction {
+    /// Brainfuck source parsing back to this instruction, a folded one written out as the shortest
+    /// run it stands for (`Add(254)` as `--`, `SetZero` as `[-]`). Empty for `Halt`.
+    pub fn to_source(&self) -> String {
+        match self {
+            Instruction::Move(offset) if *offset < 0 => "<".repeat(offset.unsigned_abs()),
+            Instruction::Move(offset) => ">".repeat(*offset as usize),
+            Instruction::Add(delta) if *delta > 128 => "-".repeat(delta.wrapping_neg() as usize),
+            Instruction::Add(delta) => "+".repeat(*delta as usize),
+            Instruction::Halt => String::new(),
+            instruction => instruction.to_string()
+        }
+    }
+}
+
+/// Parsed instructions plus a jump table and a source map running parallel to them.
+///
+/// `jumps[i]` is where execution continues when the loop instruction at `i` takes its jump, entries
+/// of non-loop instructions are unused. Keeping the targets out of `Instruction` means the hot loop
+/// never has to destructure a payload to branch. `positions[i]` is the byte offset in the source the
+/// instruction was parsed from (the first byte of a folded run, the end of the source for `Halt`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    jumps: Vec<usize>,
+    positions: Vec<usize>,
+    source_len: usize,
+    folded: bool // runs of `+`/`-` were folded modulo 256, see `Program::is_folded`
+}
+
+impl Program {
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn jumps(&self) -> &[usize] {
+        &self.jumps
+    }
+
+    pub fn positions(&self) -> &[usize] {
+        &self.positions
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub(crate) fn source_len(&self) -> usize {
+        self.source_len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// Whether runs of `+`/`-` only survive modulo 256: folded into an `Add`, or dropped when they add up
+    /// to a multiple of 256.
+    pub fn is_folded(&self) -> bool {
+        self.folded
+    }
+
+    /// Minified source of the program, without comments. Parsing it with the options the program was
+    /// parsed with gives back the same instructions (save the closing `Halt` of [`compile`]).
+    pub fn to_source(&self) -> String {
+        self.instructions.iter().map(Instruction::to_source).collect()
+    }
+
+    pub fn push_halt(&mut self) {
+        self.push(Instruction::Halt, 0, self.source_len);
+    }
+
+    /// Index of the first instruction parsed from `position` or later in the source.
+    pub fn index_at(&self, position: usize) -> Option<usize> {
+        let index: usize = self.positions.partition_point(|&start| start < position);
+        (index < self.len()).then_some(index)
+    }
+
+    /// Replaces the instruction at `index` with `Halt`, a run then stops cleanly the first time it gets
+    /// there (with `HaltReason::Stopped`) and can be resumed with the unpatched program.
+    pub fn halt_at(&mut self, index: usize) {
+        self.instructions[index] = Instruction::Halt;
+    }
+
+    /// Whether the instruction at `index` is a `Halt` placed by [`Program::halt_at`]. Those keep the
+    /// source position of the instruction they replaced, the closing one sits at the end of the source.
+    pub fn is_stop(&self, index: usize) -> bool {
+        self.instructions[index] == Instruction::Halt && self.positions[index] < self.source_len
+    }
+
+    /// Innermost `Begin` whose loop body holds instruction `index`, `None` for top-level instructions.
+    pub fn enclosing_loop(&self, index: usize) -> Option<usize> {
+        let mut stack: Vec<usize> = vec![];
+        for (current, instruction) in self.instructions[..index.min(self.len())].iter().enumerate() {
+            match instruction {
+                Instruction::Begin => { stack.push(current); },
+                Instruction::End => { stack.pop(); },
+                _ => {}
+            }
+        }
+        stack.last().copied()
+    }
+
+    /// Instructions outside every loop, where a run can start with the jump table still making sense.
+    pub fn entry_points(&self) -> Vec<usize> {
+        let mut depth: usize = 0;
+        let mut entry_points: Vec<usize> = vec![];
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            match instruction {
+                Instruction::Begin => {
+                    if depth == 0 { entry_points.push(index); }
+                    depth += 1;
+                },
+                Instruction::End => { depth -= 1; },
+                _ if depth == 0 => { entry_points.push(index); },
+                _ => {}
+            }
+        }
+        entry_points
+    }
+
+    /// Program of `instructions` (with the source positions they came from) and their freshly resolved
+    /// jumps, for passes rewriting a parsed program. The loops must be balanced.
+    pub(crate) fn from_parts(instructions: &[(Instruction, usize)], source_len: usize, folded: bool) -> Self {
+        let mut program: Program = Program { source_len, folded, ..Program::default() };
+        let mut stack: Vec<usize> = vec![];
+        for &(instruction, position) in instructions {
+            let index: usize = program.len();
+            match instruction {
+                Instruction::Begin => {
+                    stack.push(index);
+                    program.push(instruction, 0, position);
+                },
+                Instruction::End => {
+                    let begin: usize = stack.pop().expect("unbalanced loops");
+                    program.jumps[begin] = index + 1;
+                    program.push(instruction, begin + 1, position);
+                },
+                _ => { program.push(instruction, 0, position); }
+            }
+        }
+        program
+    }
+
+    fn push(&mut self, instruction: Instruction, jump: usize, position: usize) {
+        self.instructions.push(instruction);
+        self.jumps.push(jump);
+        self.positions.push(position);
+    }
+
+    fn pop(&mut self) {
+        self.instructions.pop();
+        self.jumps.pop();
+        self.positions.pop();
+    }
+
+    // Folding into the last instruction never moves a jump target: targets always point right after a
+    // bracket, which is where a run starts. A run that cancels out is dropped, its slot then simply
+    // goes to whatever comes next.
+    fn fold_add(&mut self, delta: u8, position: usize) {
+        match self.instructions.last_mut() {
+            Some(Instruction::Add(total)) => {
+                *total = total.wrapping_add(delta);
+                if *total == 0 { self.pop(); }
+            },
+            _ => self.push(Instruction::Add(delta), 0, position)
+        }
+    }
+
+    fn fold_move(&mut self, offset: isize, position: usize) {
+        match self.instructions.last_mut() {
+            Some(Instruction::Move(total)) => {
+                *total += offset;
+                if *total == 0 { self.pop(); }
+            },
+            _ => self.push(Instruction::Move(offset), 0, position)
+        }
+    }
+}
+
+/// Bracket mismatch or overly deep nesting found while parsing, `position` is the byte offset of the offending bracket in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    UnmatchedEnd { position: usize },
+    UnmatchedBegin { position: usize },
+    MismatchedEnd { position: usize, begin_position: usize }, // `(` closed by `]` or `[` closed by `)`
+    NestingTooDeep { position: usize, depth: usize, limit: usize } // see `ParseOptions::max_depth`
+}
+
+/// Dialect switches for the parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseOptions {
+    /// Accept `(` `)` as a second pair of loop delimiters. A loop opened with `(` must be closed with `)`
+    /// and a loop opened with `[` with `]`, but both kinds may nest inside each other freely. When off,
+    /// parentheses are comments.
+    pub alt_brackets: bool,
+    /// Single-pass optimization: coalesce runs of `+`/`-` and `>`/`<` into `Add`/`Move` and turn
+    /// `[-]`/`[+]` into `SetZero` while the instructions are being emitted.
+    pub fold: bool,
+    /// Non-standard instructions: `:` outputs the current cell as a decimal number. When off, `:` is a
+    /// comment.
+    pub extensions: bool,
+    /// Deepest loop nesting accepted, `None` for any. Nothing walking a program's loops recurses, so
+    /// this isn't needed against stack overflows, it caps what an untrusted source can make the
+    /// analyses spend.
+    pub max_depth: Option<usize>
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnmatchedEnd { position } => write!(f, "Unmatched `]` at position {}, missing `[`", position),
+            ParseError::UnmatchedBegin { position } => write!(f, "Unmatched `[` at position {}, missing `]`", position),
+            ParseError::MismatchedEnd { position, begin_position } => {
+                write!(f, "Loop opened at position {} is closed by the wrong bracket at position {}", begin_position, position)
+            },
+            ParseError::NestingTooDeep { position, depth, limit } => {
+                write!(f, "Loop at position {} is nested {} deep, more than the limit of {}", position, depth, limit)
+            }
+        }
+    }
+}
+
+impl ParseError {
+    /// Byte offset of the offending bracket.
+    pub fn position(&self) -> usize {
+        match *self {
+            ParseError::UnmatchedEnd { position }
+            | ParseError::UnmatchedBegin { position }
+            | ParseError::MismatchedEnd { position, .. }
+            | ParseError::NestingTooDeep { position, .. } => position
+        }
+    }
+
+    /// What the error means in `source` (the code it came from) and how it is usually fixed, in a few
+    /// sentences for people new to brainfuck. See `--explain-errors`.
+    pub fn explain(&self, source: &[u8]) -> String {
+        let bracket: u8 = source.get(self.position()).copied().unwrap_or(b'[');
+        let (open, close): (u8, u8) = if matches!(bracket, b'(' | b')') { (b'(', b')') } else { (b'[', b']') };
+        let count = |code: &[u8], byte: u8| code.iter().filter(|&&other| other == byte).count();
+        let (opens, closes): (usize, usize) = (count(source, open), count(source, close));
+        let (open, close): (char, char) = (open as char, close as char);
+
+        match *self {
+            ParseError::UnmatchedEnd { position } => {
+                let code: &[u8] = source.get(..=position).unwrap_or(source);
+                format!(
+                    "Every `{1}` ends the loop of the nearest `{0}` before it that is still open, and this one has none left: up to here \
+                     the source has {2} `{0}` and {3} `{1}`. Add a `{0}` earlier where the loop should start, or remove this `{1}`.",
+                    open, close, count(code, open as u8), count(code, close as u8)
+                )
+            },
+            ParseError::UnmatchedBegin { .. } => format!(
+                "This `{0}` starts a loop that is never closed, it is still open at the end: the source has {2} `{0}` \
+                 and only {3} `{1}`. Add a `{1}` where the loop should end, or remove this `{0}`.",
+                open, close, opens, closes
+            ),
+            ParseError::MismatchedEnd { begin_position, .. } => {
+                let (line, column) = crate::source::line_col(source, begin_position);
+                let (wrong, right): (char, char) = if bracket == b')' { (')', ']') } else { (']', ')') };
+                format!(
+                    "With --alt-brackets each kind of loop closes with its own bracket, and the loop still open here started at {}:{} with \
+                     `{}`. Write `{}` instead of `{}`, or close the inner loop first if one of them is missing its bracket.",
+                    line, column, if right == ']' { '[' } else { '(' }, right, wrong
+                )
+            },
+            ParseError::NestingTooDeep { depth, limit, .. } => format!(
+                "This loop sits {} loops deep, deeper than the --max-nesting limit of {}. Move some of the inner loops out \
+                 after their enclosing loop, or raise the limit if the nesting is intended.",
+                depth, limit
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse_code(code: &str) -> Result<Program, ParseError> {
+    parse_bytes(code.as_bytes())
+}
+
+/// Same as [`parse_code`], but works on raw bytes so the source doesn't have to be valid UTF-8.
+pub fn parse_bytes(code: &[u8]) -> Result<Program, ParseError> {
+    parse_bytes_with(code, &ParseOptions::default())
+}
+
+/// Parses `code` for execution, `auto_halt` appends the closing `Instruction::Halt`. Without it the run
+/// simply ends when the instruction index runs off the end of the program.
+pub fn compile(code: &[u8], options: &ParseOptions, auto_halt: bool) -> Result<Program, ParseError> {
+    debug!("parsing {} bytes, {:?}", code.len(), options);
+    let mut program: Program = parse_bytes_with(code, options).inspect_err(|err| debug!("parse failed: {}", err))?;
+    if auto_halt {
+        program.push_halt();
+    }
+    debug!("parsed {} instructions", program.len());
+    Ok(program)
+}
+
+/// Source positions of every matched `[`/`]` pair, ordered by the opening bracket, for editors
+/// highlighting matching brackets. Only checks the brackets, no program is built.
+pub fn bracket_pairs(code: &str) -> Result<Vec<(usize, usize)>, ParseError> {
+    let mut pairs: Vec<(usize, usize)> = vec![];
+    let mut stack: Vec<usize> = vec![];
+
+    for (position, byte) in code.bytes().enumerate() {
+        match byte {
+            b'[' => { stack.push(position); },
+            b']' => {
+                let begin_position: usize = stack.pop().ok_or(ParseError::UnmatchedEnd { position })?;
+                pairs.push((begin_position, position));
+            },
+            _ => {}
+        }
+    }
+
+    if let Some(&position) = stack.last() {
+        return Err(ParseError::UnmatchedBegin { position });
+    }
+
+    pairs.sort_unstable();
+    Ok(pairs)
+}
+
+pub fn parse_bytes_with(code: &[u8], options: &ParseOptions) -> Result<Program, ParseError> {
+    let mut program: Program = Program { source_len: code.len(), folded: options.fold, ..Program::default() };
+
+    let mut stack: Vec<(usize, usize, u8)> = vec![]; // stack that keeps track of jump locations - [ ], with their source positions and opening bracket
+
+    for (position, &byte) in code.iter().enumerate() {
+        let index: usize = program.len();
+
+        let operation: u8 = match byte {
+            b'(' if options.alt_brackets => b'[',
+            b')' if options.alt_brackets => b']',
+            _ => byte
+        };
+
+        match operation {
+            b'>' if options.fold => { program.fold_move(1, position); },
+            b'<' if options.fold => { program.fold_move(-1, position); },
+
+            b'+' if options.fold => { program.fold_add(1, position); },
+            b'-' if options.fold => { program.fold_add(u8::MAX, position); },
+
+            b'>' => { program.push(Instruction::IncrementPointer, 0, position); },
+            b'<' => { program.push(Instruction::DecrementPointer, 0, position); },
+
+            b'+' => { program.push(Instruction::IncrementValue, 0, position); },
+            b'-' => { program.push(Instruction::DecrementValue, 0, position); },
+
+            b'.' => { program.push(Instruction::OutputValue, 0, position); },
+            b',' => { program.push(Instruction::InputValue, 0, position); },
+            b':' if options.extensions => { program.push(Instruction::OutputDecimal, 0, position); },
+
+            b'[' => {
+                if let Some(limit) = options.max_depth.filter(|&limit| stack.len() >= limit) {
+                    return Err(ParseError::NestingTooDeep { position, depth: stack.len() + 1, limit });
+                }
+                stack.push((index, position, byte));
+                program.push(Instruction::Begin, 0, position); // target patched once the matching `]` is found
+            },
+
+            b']' => {
+                let (previous_begin_index, begin_position, opening) = stack.pop().ok_or(ParseError::UnmatchedEnd { position })?;
+                if (opening == b'(') != (byte == b')') {
+                    return Err(ParseError::MismatchedEnd { position, begin_position });
+                }
+
+                let clear_loop: bool = options.fold
+                    && index == previous_begin_index + 2
+                    && matches!(program.instructions[previous_begin_index + 1], Instruction::Add(1 | u8::MAX));
+
+                if clear_loop {
+                    program.pop();
+                    program.pop();
+                    program.push(Instruction::SetZero, 0, begin_position);
+                } else {
+                    program.jumps[previous_begin_index] = index + 1;
+                    program.push(Instruction::End, previous_begin_index + 1, position);
+                }
+            },
+
+            _ => {}
+        }
+    }
+
+    if let Some(&(_, position, _)) = stack.last() {
+        return Err(ParseError::UnmatchedBegin { position });
+    }
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift, tests want the same programs on every run.
+    struct Generator(u64);
+
+    impl Generator {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        /// Balanced program of about `length` bytes: instructions in runs (so folding has something to
+        /// fold), both kinds of brackets, clear loops, `:` and comment bytes.
+        fn program(&mut self, length: usize) -> Vec<u8> {
+            const BYTES: &[u8] = b"><+-.,:";
+            let mut code: Vec<u8> = vec![];
+            let mut open: Vec<u8> = vec![];
+            while code.len() < length {
+                match self.below(10) {
+                    0 => {
+                        let opening: u8 = if self.below(2) == 0 { b'[' } else { b'(' };
+                        code.push(opening);
+                        open.push(opening);
+                    },
+                    1 => {
+                        if let Some(opening) = open.pop() { code.push(if opening == b'[' { b']' } else { b')' }); }
+                    },
+                    2 => { code.extend_from_slice(if self.below(2) == 0 { b"[-]" } else { b"[+]" }); },
+                    3 => { code.push(b"ab \n!#"[self.below(6)]); },
+                    _ => {
+                        let byte: u8 = BYTES[self.below(BYTES.len())];
+                        let longest: usize = if self.below(8) == 0 { 300 } else { 4 };
+                        let run: usize = 1 + self.below(longest);
+                        code.extend(std::iter::repeat_n(byte, run));
+                    }
+                }
+            }
+            while let Some(opening) = open.pop() {
+                code.push(if opening == b'[' { b']' } else { b')' });
+            }
+            code
+        }
+    }
+
+    #[test]
+    fn to_source_parses_back_to_the_same_program() {
+        let mut generator: Generator = Generator(0x9e37_79b9_7f4a_7c15);
+        for round in 0..400 {
+            let length: usize = 1 + generator.below(200);
+            let code: Vec<u8> = generator.program(length);
+            for (fold, extensions, alt_brackets) in [(false, false, false), (true, false, false), (false, true, true), (true, true, true)] {
+                let options: ParseOptions = ParseOptions { fold, extensions, alt_brackets, ..ParseOptions::default() };
+                let program: Program = parse_bytes_with(&code, &options).unwrap();
+                let source: String = program.to_source();
+                let reparsed: Program = parse_bytes_with(source.as_bytes(), &options).unwrap();
+
+                let context = || format!("round {} with {:?}: {:?} -> {:?}", round, options, String::from_utf8_lossy(&code), source);
+                assert_eq!(reparsed.instructions(), program.instructions(), "{}", context());
+                assert_eq!(reparsed.jumps(), program.jumps(), "{}", context());
+                assert_eq!(reparsed.to_source(), source, "{}", context());
+            }
+        }
+    }
+
+    #[test]
+    fn to_source_writes_folded_runs_the_short_way_around() {
+        let options: ParseOptions = ParseOptions { fold: true, ..ParseOptions::default() };
+        let program: Program = parse_bytes_with(format!("{}>>><[+]{}", "+".repeat(300), "-".repeat(130)).as_bytes(), &options).unwrap();
+        assert_eq!(program.instructions(), [Instruction::Add(44), Instruction::Move(2), Instruction::SetZero, Instruction::Add(126)]);
+        assert_eq!(program.to_source(), format!("{}>>[-]{}", "+".repeat(44), "+".repeat(126)));
+        assert_eq!(parse_bytes_with(&"+".repeat(256).into_bytes(), &options).unwrap().to_source(), "");
+    }
+}
@@ -0,0 +1,118 @@
+//! The optimization passes in the order a full build applies them, so tools can rebuild a program
+//! with any prefix of the pipeline from the same source.
+
+use crate::constprop;
+use crate::parser::{compile, ParseError, ParseOptions, Program};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    Fold, // `ParseOptions::fold`, applied while parsing
+    ConstProp // `constprop::propagate`
+}
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Fold => "fold",
+            Pass::ConstProp => "constprop"
+        }
+    }
+}
+
+/// Every pass, in the order they run.
+pub const PIPELINE: [Pass; 2] = [Pass::Fold, Pass::ConstProp];
+
+/// `code` compiled with exactly `passes` (in pipeline order), whatever `options.fold` says.
+pub fn build(code: &[u8], options: &ParseOptions, passes: &[Pass]) -> Result<Program, ParseError> {
+    let options: ParseOptions = ParseOptions { fold: passes.contains(&Pass::Fold), ..*options };
+    let mut program: Program = compile(code, &options, true)?;
+    for pass in PIPELINE.iter().filter(|pass| passes.contains(pass)) {
+        match pass {
+            Pass::Fold => {},
+            Pass::ConstProp => { program = constprop::propagate(&program); }
+        }
+        debug!("applied pass {}", pass.name());
+    }
+    Ok(program)
+}
+
+/// Number of passes of the shortest prefix of a pipeline of `passes` whose build `diverges`, found by
+/// binary search assuming a build stays diverged once it did. `None` if the whole pipeline doesn't.
+pub fn first_divergence(passes: usize, mut diverges: impl FnMut(usize) -> bool) -> Option<usize> {
+    if !diverges(passes) {
+        return None;
+    }
+    let (mut low, mut high): (usize, usize) = (1, passes);
+    while low < high {
+        let middle: usize = (low + high) / 2;
+        if diverges(middle) { high = middle; } else { low = middle + 1; }
+    }
+    Some(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_code, ExecutionConfig, Interpreter};
+    use crate::parser::Instruction;
+
+    type ToyPass = fn(&Program) -> Program;
+
+    /// Clears a cell fine unless it's about to be counted up again, then "knows" it is 0 already.
+    fn buggy_clears(program: &Program) -> Program {
+        let instructions: Vec<(Instruction, usize)> = program.instructions().iter().zip(program.positions()).enumerate()
+            .filter(|&(index, (&instruction, _))| instruction != Instruction::SetZero || !matches!(program.instructions().get(index + 1), Some(Instruction::Add(_))))
+            .map(|(_, (&instruction, &position))| (instruction, position))
+            .collect();
+        Program::from_parts(&instructions, program.source_len(), program.is_folded())
+    }
+
+    fn output(program: &Program) -> Vec<u8> {
+        let mut output: Vec<u8> = vec![];
+        execute_code(program, &mut Interpreter::default(), &mut &b""[..], &mut output, &ExecutionConfig::default()).unwrap();
+        output
+    }
+
+    /// Where the search over `pipeline` stops and how many builds it ran to get there.
+    fn bisect(code: &str, pipeline: &[ToyPass]) -> (Option<usize>, usize) {
+        let program: Program = build(code.as_bytes(), &ParseOptions::default(), &[Pass::Fold]).unwrap();
+        let reference: Vec<u8> = output(&program);
+        let mut builds: usize = 0;
+        let diverges = |passes: usize| -> bool {
+            builds += 1;
+            output(&pipeline[..passes].iter().fold(program.clone(), |program, pass| pass(&program))) != reference
+        };
+        (first_divergence(pipeline.len(), diverges), builds)
+    }
+
+    const CODE: &str = "+++++++[>++++++++++<-]>-.[-]+++++++++++++++++++++++++++++++++.";
+
+    #[test]
+    fn the_buggy_pass_is_found_wherever_it_sits() {
+        assert_eq!(output(&buggy_clears(&build(CODE.as_bytes(), &ParseOptions::default(), &[Pass::Fold]).unwrap())), b"Ef", "the toy pass is buggy indeed");
+        for position in 0..7 {
+            let mut pipeline: Vec<ToyPass> = vec![Program::clone, constprop::propagate, Program::clone, constprop::propagate, Program::clone, Program::clone];
+            pipeline.insert(position, buggy_clears);
+            let (found, builds): (Option<usize>, usize) = bisect(CODE, &pipeline);
+            assert_eq!(found, Some(position + 1), "buggy pass at {}", position);
+            assert!(builds <= 4, "{} builds for 7 passes", builds);
+        }
+    }
+
+    #[test]
+    fn a_pipeline_of_sound_passes_does_not_diverge() {
+        assert_eq!(bisect(CODE, &[Program::clone, constprop::propagate, Program::clone]), (None, 1));
+        assert_eq!(bisect("[-]+.", &[buggy_clears]), (None, 1), "a bug the program doesn't trip over isn't found");
+        assert_eq!(first_divergence(0, |_| false), None);
+    }
+
+    #[test]
+    fn every_prefix_of_the_real_pipeline_runs_the_samples_alike() {
+        for name in ["helloworld.bf", "rot13.bf", "squares.bf"] {
+            let code: Vec<u8> = std::fs::read(format!("{}/samples/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+            let reference: Vec<u8> = output(&build(&code, &ParseOptions::default(), &[]).unwrap());
+            let diverges = |passes: usize| output(&build(&code, &ParseOptions::default(), &PIPELINE[..passes]).unwrap()) != reference;
+            assert_eq!(first_divergence(PIPELINE.len(), diverges), None, "{}", name);
+        }
+    }
+}
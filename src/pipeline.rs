@@ -0,0 +1,204 @@
+//! Several programs running concurrently as a chain of filters, the output of each stage feeding the
+//! input of the next one through a bounded in-memory channel.
+
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::interpreter::{execute_code, ExecutionConfig, Interpreter, RunControl, RunSummary, RuntimeError};
+use crate::parser::Program;
+
+/// Bytes a stage writes before they are handed on to the next stage.
+const CHUNK_SIZE: usize = 4096;
+/// Chunks in flight between two stages, a stage producing faster than its consumer blocks beyond this.
+const CHANNEL_CAPACITY: usize = 16;
+
+/// One program of a pipeline together with the state and limits it runs with.
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub name: String,
+    pub program: Program,
+    pub interpreter: Interpreter,
+    pub config: ExecutionConfig
+}
+
+/// Writing end of a channel between two stages, sends whatever is buffered when dropped.
+struct ChannelWriter {
+    sender: SyncSender<Vec<u8>>,
+    buffer: Vec<u8>
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk: Vec<u8> = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender.send(chunk).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "next stage stopped reading"))
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/// Reading end of a channel, reports end of input once the previous stage is gone.
+struct ChannelReader {
+    receiver: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                },
+                Err(_) => return Ok(0)
+            }
+        }
+
+        let count: usize = buffer.len().min(self.chunk.len() - self.position);
+        buffer[..count].copy_from_slice(&self.chunk[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Runs every stage on its own thread, the first one reading `input` and the last one writing `output`.
+///
+/// Returns the result of each stage in order. When a stage fails the others are cancelled: stages
+/// after it see the end of their input and then stop at their next poll point, stages before it
+/// stop at their next poll point or fail with a broken pipe once they write.
+pub fn run_pipeline(
+    stages: &mut [Stage],
+    input: &mut (dyn Read + Send),
+    output: &mut (dyn Write + Send)
+) -> Vec<Result<RunSummary, RuntimeError>> {
+    let control: Arc<RunControl> = Arc::new(RunControl::default());
+
+    let mut readers: Vec<Option<Box<dyn Read + Send + '_>>> = vec![Some(Box::new(input))];
+    let mut writers: Vec<Option<Box<dyn Write + Send + '_>>> = vec![];
+    for _ in 1..stages.len() {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        writers.push(Some(Box::new(ChannelWriter { sender, buffer: Vec::with_capacity(CHUNK_SIZE) })));
+        readers.push(Some(Box::new(ChannelReader { receiver, chunk: vec![], position: 0 })));
+    }
+    writers.push(Some(Box::new(output)));
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = stages
+            .iter_mut()
+            .zip(readers.iter_mut().zip(writers.iter_mut()))
+            .map(|(stage, (reader, writer))| {
+                let (mut reader, mut writer) = (reader.take().unwrap(), writer.take().unwrap());
+                let control: Arc<RunControl> = Arc::clone(&control);
+
+                scope.spawn(move || {
+                    stage.config.control = Some(Arc::clone(&control));
+                    let result = execute_code(&stage.program, &mut stage.interpreter, &mut reader, &mut writer, &stage.config)
+                        .and_then(|summary| writer.flush().map(|_| summary).map_err(RuntimeError::from));
+                    if result.is_err() {
+                        control.cancel();
+                    }
+                    result
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))).collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::HaltReason;
+    use crate::parser::{compile, ParseOptions};
+
+    const GENERATOR: &str = "++++++++++[>++++++++++<-]>++++.---.+++++++..+++."; // hello
+    const ROT13: &str = include_str!("../samples/rot13.bf");
+    const UPPERCASE: &str = ",[--------------------------------.[-],]"; // for lowercase letters
+    const ECHO: &str = ",[.[-],]"; // up to the first zero byte
+
+    fn stage(name: &str, code: &str, interpreter: Interpreter) -> Stage {
+        let program: Program = compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, true).unwrap();
+        Stage { name: name.to_string(), program, interpreter, config: ExecutionConfig::default() }
+    }
+
+    /// The output of running the programs one after another, each on the whole output of the one before.
+    fn composed(codes: &[&str], input: &[u8]) -> Vec<u8> {
+        codes.iter().fold(input.to_vec(), |input: Vec<u8>, code: &&str| {
+            let mut stage: Stage = stage(code, code, Interpreter::default());
+            let mut output: Vec<u8> = vec![];
+            execute_code(&stage.program, &mut stage.interpreter, &mut &input[..], &mut output, &stage.config).unwrap();
+            output
+        })
+    }
+
+    fn pipeline(stages: &mut [Stage], input: &[u8]) -> (Vec<Result<RunSummary, RuntimeError>>, Vec<u8>) {
+        let mut output: Vec<u8> = vec![];
+        let results: Vec<Result<RunSummary, RuntimeError>> = run_pipeline(stages, &mut &input[..], &mut output);
+        (results, output)
+    }
+
+    #[test]
+    fn three_stages_print_what_the_programs_composed_print() {
+        let mut stages: Vec<Stage> = [("generator", GENERATOR), ("rot13", ROT13), ("uppercase", UPPERCASE)]
+            .iter()
+            .map(|&(name, code)| stage(name, code, Interpreter::default()))
+            .collect();
+        let (results, output) = pipeline(&mut stages, b"");
+
+        assert_eq!(output, b"URYYB");
+        assert_eq!(output, composed(&[GENERATOR, ROT13, UPPERCASE], b""));
+        assert!(results.iter().all(|result| matches!(result, Ok(summary) if summary.halt == HaltReason::EndOfProgram)), "{:?}", results);
+    }
+
+    #[test]
+    fn more_than_the_channels_hold_goes_through() {
+        let input: Vec<u8> = (0..200_000).map(|index: u32| b'a' + (index % 26) as u8).collect();
+        let mut stages: Vec<Stage> = (0..3).map(|index| stage(&index.to_string(), ECHO, Interpreter::default())).collect();
+        let (results, output) = pipeline(&mut stages, &input);
+
+        assert!(results.iter().all(Result::is_ok), "{:?}", results);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn a_failing_stage_tears_the_pipeline_down() {
+        let mut stages: Vec<Stage> = vec![
+            stage("endless", "+[.]", Interpreter::default()),
+            // fails after handing on two bytes, `fixed(1)` starts on its only cell
+            stage("failing", ",.,.<", Interpreter::fixed(1)),
+            stage("echo", ECHO, Interpreter::default())
+        ];
+        let (results, output) = pipeline(&mut stages, b"");
+
+        assert!(matches!(results[1], Err(RuntimeError::PointerUnderflow)), "{:?}", results[1]);
+        // the stage before it either saw the cancellation or the channel closing under its writes
+        match &results[0] {
+            Ok(summary) => assert_eq!(summary.halt, HaltReason::Cancelled),
+            Err(RuntimeError::Io(err)) => assert_eq!(err.kind(), io::ErrorKind::BrokenPipe),
+            Err(err) => panic!("{}", err)
+        }
+        // the stage after it ends within its first poll interval, the cancellation never reaches it
+        // and it gets everything the failing stage wrote before its input ended
+        assert!(results[2].is_ok(), "{:?}", results[2]);
+        assert_eq!(output, [1, 1]);
+    }
+}
@@ -0,0 +1,278 @@
+//! Interpreters kept around between runs, for embedders running many short programs on big tapes
+//! who would rather not allocate and zero a fresh tape for every run.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::interpreter::{Interpreter, TapeMode};
+
+/// Shape of a tape, interpreters of one pool all share it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TapeConfig {
+    pub mode: TapeMode,
+    pub cells: usize // size of a fixed tape, limit of a dynamic one
+}
+
+impl TapeConfig {
+    pub fn of(interpreter: &Interpreter) -> Self {
+        Self { mode: interpreter.tape_mode(), cells: interpreter.max_cells() }
+    }
+
+    /// Fresh interpreter with a tape of this shape.
+    pub fn build(&self) -> Interpreter {
+        match self.mode {
+            TapeMode::Fixed => Interpreter::fixed(self.cells),
+            TapeMode::Growable => Interpreter::growable(self.cells),
+            TapeMode::Bidirectional => Interpreter::bidirectional(self.cells)
+        }
+    }
+}
+
+/// Counters of an [`InterpreterPool`], for monitoring how well it is sized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub hits: u64, // checkouts served by a pooled interpreter
+    pub misses: u64, // checkouts which had to build one, mismatched configurations included
+    pub resets: u64 // interpreters reset on their way back into the pool
+}
+
+/// Bounded set of idle interpreters sharing one [`TapeConfig`], safe to share between threads.
+///
+/// Interpreters are reset with [`Interpreter::reset`] when they come back, which clears the cells the
+/// run reached but keeps the allocation, so a checkout never sees state of an earlier run. One which no longer has the
+/// pool's configuration (a sandbox was set, say) or finds the pool full is dropped instead.
+#[derive(Debug)]
+pub struct InterpreterPool {
+    config: TapeConfig,
+    max_size: usize,
+    idle: Mutex<Vec<Interpreter>>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+    resets: AtomicU64
+}
+
+impl InterpreterPool {
+    /// Empty pool keeping at most `max_size` idle interpreters, which are built on demand.
+    pub fn new(config: TapeConfig, max_size: usize) -> Self {
+        Self {
+            config,
+            max_size,
+            idle: Mutex::new(Vec::with_capacity(max_size)),
+
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            resets: AtomicU64::new(0)
+        }
+    }
+
+    pub fn config(&self) -> TapeConfig {
+        self.config
+    }
+
+    /// Interpreter with the pool's configuration, going back into the pool when dropped.
+    pub fn get(&self) -> PooledInterpreter<'_> {
+        let pooled: Option<Interpreter> = self.idle.lock().unwrap_or_else(|err| err.into_inner()).pop();
+        let interpreter: Interpreter = match pooled {
+            Some(interpreter) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                interpreter
+            },
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.config.build()
+            }
+        };
+        PooledInterpreter { interpreter: Some(interpreter), pool: Some(self) }
+    }
+
+    /// Like [`InterpreterPool::get`], a `config` other than the pool's gets a one-off interpreter which
+    /// is simply dropped afterwards.
+    pub fn get_with(&self, config: TapeConfig) -> PooledInterpreter<'_> {
+        if config == self.config {
+            return self.get();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        PooledInterpreter { interpreter: Some(config.build()), pool: None }
+    }
+
+    /// Interpreters currently waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.idle.lock().unwrap_or_else(|err| err.into_inner()).len()
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            resets: self.resets.load(Ordering::Relaxed)
+        }
+    }
+
+    fn check_in(&self, mut interpreter: Interpreter) {
+        if TapeConfig::of(&interpreter) != self.config || interpreter.sandbox().is_some() {
+            return;
+        }
+        if self.idle() >= self.max_size {
+            return;
+        }
+        // reset outside the lock, clearing a big tape (a restored one, say) takes a while
+        interpreter.reset();
+        self.resets.fetch_add(1, Ordering::Relaxed);
+
+        let mut idle = self.idle.lock().unwrap_or_else(|err| err.into_inner());
+        if idle.len() < self.max_size {
+            idle.push(interpreter);
+        }
+    }
+}
+
+/// Interpreter checked out of an [`InterpreterPool`], dereferences to the [`Interpreter`].
+#[derive(Debug)]
+pub struct PooledInterpreter<'a> {
+    interpreter: Option<Interpreter>, // only `None` while dropping
+    pool: Option<&'a InterpreterPool> // `None` for a one-off interpreter
+}
+
+impl PooledInterpreter<'_> {
+    /// Takes the interpreter out for good, it never goes back into the pool.
+    pub fn detach(mut self) -> Interpreter {
+        self.pool = None;
+        self.interpreter.take().expect("interpreter already taken")
+    }
+}
+
+impl Deref for PooledInterpreter<'_> {
+    type Target = Interpreter;
+
+    fn deref(&self) -> &Interpreter {
+        self.interpreter.as_ref().expect("interpreter already taken")
+    }
+}
+
+impl DerefMut for PooledInterpreter<'_> {
+    fn deref_mut(&mut self) -> &mut Interpreter {
+        self.interpreter.as_mut().expect("interpreter already taken")
+    }
+}
+
+impl Drop for PooledInterpreter<'_> {
+    fn drop(&mut self) {
+        if let (Some(pool), Some(interpreter)) = (self.pool, self.interpreter.take()) {
+            pool.check_in(interpreter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::interpreter::{execute_code, ExecutionConfig, MergeMode};
+    use crate::parser::{compile, ParseOptions, Program};
+
+    const FIXED: TapeConfig = TapeConfig { mode: TapeMode::Fixed, cells: 64 };
+
+    fn run(interpreter: &mut Interpreter, code: &str) -> Vec<u8> {
+        let program: Program = compile(code.as_bytes(), &ParseOptions::default(), true).unwrap();
+        let mut output: Vec<u8> = vec![];
+        execute_code(&program, interpreter, &mut &b""[..], &mut output, &ExecutionConfig::default()).unwrap();
+        output
+    }
+
+    /// Prints the cells around the start, which have to be clean, then leaves `mark` in them and the
+    /// pointer elsewhere for the next user of the interpreter to trip over.
+    fn probe(interpreter: &mut Interpreter, mark: usize) -> Vec<u8> {
+        assert_eq!((interpreter.steps(), interpreter.output_bytes(), interpreter.instruction_index()), (0, 0, 0));
+        run(interpreter, &format!("<.>.>.<{}>{}>", "+".repeat(mark), "+".repeat(mark)))
+    }
+
+    #[test]
+    fn back_to_back_runs_see_no_state_of_each_other() {
+        let pool: InterpreterPool = InterpreterPool::new(FIXED, 1);
+        for mark in 1..5 {
+            let mut interpreter: PooledInterpreter = pool.get();
+            assert_eq!(probe(&mut interpreter, mark), [0, 0, 0], "run {}", mark);
+        }
+        assert_eq!(pool.stats(), PoolStats { hits: 3, misses: 1, resets: 4 });
+
+        let growing: InterpreterPool = InterpreterPool::new(TapeConfig { mode: TapeMode::Bidirectional, cells: 64 }, 1);
+        run(&mut growing.get(), "<<<+>>>>>>+");
+        let interpreter: PooledInterpreter = growing.get();
+        assert_eq!((interpreter.tape(), interpreter.pointer_range()), (&[0][..], (0, 0)));
+    }
+
+    #[test]
+    fn a_reused_interpreter_starts_on_a_clean_tape() {
+        let pool: InterpreterPool = InterpreterPool::new(FIXED, 1);
+        let clean: Vec<u8> = vec![0; FIXED.cells];
+
+        // both ends of the tape, with a hoisted loop reaching past the pointer
+        run(&mut pool.get(), &format!("{}+{}+{}++[->>>>+<<<<]", "<".repeat(32), ">".repeat(59), "<".repeat(8)));
+        assert_eq!(pool.get().tape(), &clean[..]);
+
+        let mut dirty: Interpreter = FIXED.build();
+        run(&mut dirty, &format!("{}+{}+", "<".repeat(30), ">".repeat(60)));
+        let mut overlaid: PooledInterpreter = pool.get();
+        overlaid.overlay(&dirty, MergeMode::Or);
+        drop(overlaid);
+        assert_eq!(pool.get().tape(), &clean[..], "overlaid cells the pointer never reached");
+
+        let mut restored: PooledInterpreter = pool.get();
+        *restored = Interpreter::restore(&dirty.snapshot());
+        drop(restored);
+        assert_eq!(pool.get().tape(), &clean[..], "a restored tape");
+
+        let mut moved: Interpreter = FIXED.build();
+        run(&mut moved, ">>>>+");
+        assert!(moved.set_sandbox(0..8));
+        moved.reset();
+        assert_eq!(moved.tape(), &clean[..], "the start pointer moved into the sandbox");
+        assert_eq!(pool.stats().resets, 6);
+    }
+
+    #[test]
+    fn concurrent_checkouts_stay_apart() {
+        let pool: InterpreterPool = InterpreterPool::new(FIXED, 4);
+        let (threads, runs): (usize, usize) = (8, 50);
+        thread::scope(|scope| {
+            for thread in 0..threads {
+                let pool: &InterpreterPool = &pool;
+                scope.spawn(move || {
+                    for run in 0..runs {
+                        let mut interpreter: PooledInterpreter = pool.get();
+                        assert_eq!(probe(&mut interpreter, 1 + (thread * runs + run) % 200), [0, 0, 0]);
+                    }
+                });
+            }
+        });
+
+        let stats: PoolStats = pool.stats();
+        assert_eq!(stats.hits + stats.misses, (threads * runs) as u64);
+        assert!(stats.misses >= 1 && stats.misses <= (threads * runs) as u64);
+        assert!(pool.idle() <= 4);
+        assert!(stats.resets >= pool.idle() as u64);
+    }
+
+    #[test]
+    fn the_pool_keeps_only_matching_interpreters_up_to_its_size() {
+        let pool: InterpreterPool = InterpreterPool::new(FIXED, 2);
+        let checked_out: Vec<PooledInterpreter> = (0..3).map(|_| pool.get()).collect();
+        drop(checked_out);
+        assert_eq!(pool.idle(), 2, "the third one found the pool full");
+
+        let other: PooledInterpreter = pool.get_with(TapeConfig { mode: TapeMode::Growable, cells: 64 });
+        assert_eq!(other.tape_mode(), TapeMode::Growable);
+        drop(other);
+        assert_eq!((pool.idle(), pool.stats().misses), (2, 4), "a one-off interpreter is not pooled");
+
+        let mut sandboxed: PooledInterpreter = pool.get();
+        assert!(sandboxed.set_sandbox(8..16));
+        drop(sandboxed);
+        pool.get().detach();
+        assert_eq!(pool.idle(), 0, "neither the sandboxed nor the detached one came back");
+        assert_eq!(pool.stats(), PoolStats { hits: 2, misses: 4, resets: 2 });
+    }
+}
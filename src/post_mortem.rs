@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::interpreter::{Interpreter, Observer, RuntimeError, StepEvent};
+use crate::parser::Instruction;
+
+/// Cells shown on each side of the pointer in a dump.
+const TAPE_WINDOW: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoEvent {
+    Output { step: u64, byte: u8 },
+    Input { step: u64, byte: u8 }
+}
+
+/// Keeps the last few executed instructions and I/O events so a failed run can be explained
+/// after the fact, see [`PostMortem::dump`].
+#[derive(Debug, Clone)]
+pub struct PostMortem {
+    steps: VecDeque<StepEvent>,
+    io: VecDeque<IoEvent>,
+    step_capacity: usize,
+    io_capacity: usize
+}
+
+impl PostMortem {
+    pub fn new(step_capacity: usize, io_capacity: usize) -> Self {
+        Self {
+            steps: VecDeque::with_capacity(step_capacity),
+            io: VecDeque::with_capacity(io_capacity),
+            step_capacity,
+            io_capacity
+        }
+    }
+
+    /// Recorded instructions, oldest first.
+    pub fn steps(&self) -> impl Iterator<Item = &StepEvent> {
+        self.steps.iter()
+    }
+
+    pub fn io_events(&self) -> impl Iterator<Item = &IoEvent> {
+        self.io.iter()
+    }
+
+    /// Report of how the run got to `error`: recorded history plus the tape around the pointer.
+    pub fn dump(&self, error: &RuntimeError, interpreter: &Interpreter) -> String {
+        self.dump_labeled(error, interpreter, &|_| None)
+    }
+
+    /// [`PostMortem::dump`] with tape cells annotated by `label`, e.g. names from a [`crate::layout::Layout`].
+    pub fn dump_labeled(&self, error: &RuntimeError, interpreter: &Interpreter, label: &dyn Fn(usize) -> Option<String>) -> String {
+        let mut report: String = String::new();
+
+        let _ = writeln!(
+            report,
+            "post-mortem: {} at instruction {} after {} steps",
+            error,
+            interpreter.instruction_index(),
+            interpreter.steps()
+        );
+
+        let _ = writeln!(report, "last {} instructions (oldest first):", self.steps.len());
+        for event in &self.steps {
+            let _ = writeln!(
+                report,
+                "  step {:>10}  #{:<6} {:<4} pointer {:<6} cell {} -> {}",
+                event.step, event.index, event.instruction, event.pointer, event.before, event.after
+            );
+        }
+
+        let _ = writeln!(report, "last {} I/O events (oldest first):", self.io.len());
+        for event in &self.io {
+            let _ = match event {
+                IoEvent::Output { step, byte } => writeln!(report, "  step {:>10}  output {:3} {:?}", step, byte, *byte as char),
+                IoEvent::Input { step, byte } => writeln!(report, "  step {:>10}  input  {:3} {:?}", step, byte, *byte as char)
+            };
+        }
+
+        let tape: &[u8] = interpreter.tape();
+        let pointer: usize = interpreter.pointer();
+        let from: usize = pointer.saturating_sub(TAPE_WINDOW);
+        let to: usize = (pointer + TAPE_WINDOW + 1).min(tape.len());
+
+        let _ = writeln!(report, "tape around pointer {}:", pointer);
+        for (index, value) in tape.iter().enumerate().take(to).skip(from) {
+            let marker: &str = if index == pointer { " <-" } else { "" };
+            let name: String = label(index).map(|name| format!(" {}", name)).unwrap_or_default();
+            let _ = writeln!(report, "  [{:>6}] {:3}{}{}", index, value, name, marker);
+        }
+
+        report
+    }
+}
+
+impl Observer for PostMortem {
+    fn on_step(&mut self, event: &StepEvent, _interpreter: &Interpreter) {
+        if self.step_capacity > 0 {
+            if self.steps.len() == self.step_capacity { self.steps.pop_front(); }
+            self.steps.push_back(*event);
+        }
+
+        let io_event: IoEvent = match event.instruction {
+            Instruction::OutputValue => IoEvent::Output { step: event.step, byte: event.after },
+            Instruction::InputValue => IoEvent::Input { step: event.step, byte: event.after },
+            _ => return
+        };
+        if self.io_capacity > 0 {
+            if self.io.len() == self.io_capacity { self.io.pop_front(); }
+            self.io.push_back(io_event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_observed, ExecutionConfig};
+    use crate::parser::{compile, ParseOptions, Program};
+
+    #[test]
+    fn an_out_of_bounds_move_dumps_the_instructions_before_it_in_order() {
+        let program: Program = compile(b"+.>++.>+++", &ParseOptions::default(), true).unwrap();
+        let mut interpreter: Interpreter = Interpreter::fixed(4);
+        let mut post_mortem: PostMortem = PostMortem::new(5, 1);
+        let error: RuntimeError = execute_observed(&program, &mut interpreter, &mut &b""[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut post_mortem).unwrap_err();
+
+        // the failing `>` (instruction 6) never completed, the five before it did, the first `+` fell out
+        assert!(matches!(error, RuntimeError::PointerOverflow), "{:?}", error);
+        let steps: Vec<(u64, usize)> = post_mortem.steps().map(|event| (event.step, event.index)).collect();
+        assert_eq!(steps, [(2, 1), (3, 2), (4, 3), (5, 4), (6, 5)]);
+        assert_eq!(post_mortem.dump(&error, &interpreter), [
+            "post-mortem: Pointer out of bounds, overflow at instruction 6 after 7 steps",
+            "last 5 instructions (oldest first):",
+            "  step          2  #1      . pointer 2      cell 1 -> 1",
+            "  step          3  #2      > pointer 2      cell 1 -> 1",
+            "  step          4  #3      + pointer 3      cell 0 -> 1",
+            "  step          5  #4      + pointer 3      cell 1 -> 2",
+            "  step          6  #5      . pointer 3      cell 2 -> 2",
+            "last 1 I/O events (oldest first):",
+            "  step          6  output   2 '\\u{2}'",
+            "tape around pointer 3:",
+            "  [     0]   0",
+            "  [     1]   0",
+            "  [     2]   1",
+            "  [     3]   2 <-",
+            ""
+        ].join("\n"));
+    }
+}
@@ -0,0 +1,329 @@
+//! Shrinking a program that triggers a bug down to a small reproducer, see the `reduce` command.
+//!
+//! [`reduce`] works on the commands of the source (comments go first) and tries, round after
+//! round: cutting off trailing code, dropping a whole loop or just its brackets, dropping a balanced
+//! chunk (halving the chunk size down to single commands) and shortening a run of `+`, `-`, `<` or
+//! `>`. A candidate replaces the program whenever the check still holds for it. Every removed chunk
+//! holds as many `[` as `]` and never closes a loop it didn't open, and brackets only go in matching
+//! pairs, so each candidate parses like the original.
+//! An [`Oracle`] is the usual check, it runs a candidate and tells whether it still shows the
+//! failure.
+
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use crate::interpreter::{execute_code, ExecutionConfig, HaltReason, Interpreter, RuntimeError};
+use crate::parser::{parse_bytes_with, Instruction, ParseError, ParseOptions, Program};
+use crate::passes::{self, Pass};
+
+/// The failure an [`Oracle`] looks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    Diverges, // the optimized build ends with other output, tape or ending than the unoptimized one
+    Panics, // running the program panics
+    ExitCode(i32) // the run ends with the exit status `run` would report, see `Oracle::exit_code`
+}
+
+impl Failure {
+    /// `diverges`, `panics` or `exitcode N`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_whitespace().collect::<Vec<&str>>()[..] {
+            ["diverges"] => Ok(Failure::Diverges),
+            ["panics"] => Ok(Failure::Panics),
+            ["exitcode", code] => code.parse().map(Failure::ExitCode).map_err(|_| format!("expected an exit status, found `{}`", code)),
+            _ => Err(format!("expected `diverges`, `panics` or `exitcode N`, found `{}`", spec))
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Diverges => write!(f, "diverges"),
+            Failure::Panics => write!(f, "panics"),
+            Failure::ExitCode(code) => write!(f, "exitcode {}", code)
+        }
+    }
+}
+
+/// Runs candidates and checks them for a [`Failure`]. A run reaching `config.max_steps` shows no
+/// failure, whatever the failure looked for, as it can't tell a slow candidate from a hanging one.
+#[derive(Debug, Clone)]
+pub struct Oracle {
+    pub failure: Failure,
+    pub parse_options: ParseOptions, // dialect of the candidates, `fold` is decided by `passes`
+    pub passes: Vec<Pass>, // the build under test, `Diverges` compares it with the unoptimized one
+    pub interpreter: Interpreter, // state every run starts from
+    pub config: ExecutionConfig, // should limit the steps, a candidate may well never halt
+    pub input: Vec<u8>, // what `,` reads in every run
+    pub sentinel_exit_code: i32 // exit status of a run ended by the sentinel byte
+}
+
+/// How one run of a candidate went, or `None` for a panic.
+type Ending = Option<(Vec<u8>, Interpreter, Result<HaltReason, RuntimeError>)>;
+
+impl Oracle {
+    /// Whether `code` still shows the failure. A candidate that doesn't parse doesn't.
+    pub fn holds(&self, code: &[u8]) -> bool {
+        match self.failure {
+            Failure::Diverges => {
+                let (Some(reference), Some(optimized)) = (self.run(code, &[]), self.run(code, &self.passes)) else {
+                    return false;
+                };
+                let ended = |ending: &Ending| !matches!(ending, Some((_, _, Err(RuntimeError::StepLimitExceeded))));
+                ended(&reference) && ended(&optimized) && !Self::same(&reference, &optimized)
+            },
+            Failure::Panics => self.run(code, &self.passes).is_some_and(|ending| ending.is_none()),
+            Failure::ExitCode(code_wanted) => {
+                self.run(code, &self.passes).is_some_and(|ending| self.exit_code(&ending) == Some(code_wanted))
+            }
+        }
+    }
+
+    /// Exit status of `run` for a run ending like this: 1 for an output breakpoint,
+    /// `sentinel_exit_code` for the sentinel byte, 101 for a panic and 0 otherwise, other runtime
+    /// errors included (`run` reports them on stdout). `None` at the step limit.
+    fn exit_code(&self, ending: &Ending) -> Option<i32> {
+        match ending {
+            None => Some(101),
+            Some((_, _, Err(RuntimeError::StepLimitExceeded))) => None,
+            Some((_, _, Err(RuntimeError::OutputBreakpoint(_)))) => Some(1),
+            Some((_, _, Err(_))) => Some(0),
+            Some((_, _, Ok(HaltReason::Sentinel))) => Some(self.sentinel_exit_code),
+            Some((_, _, Ok(_))) => Some(0)
+        }
+    }
+
+    fn same(a: &Ending, b: &Ending) -> bool {
+        match (a, b) {
+            (Some((output_a, tape_a, result_a)), Some((output_b, tape_b, result_b))) => {
+                output_a == output_b
+                    && tape_a.tape_checksum() == tape_b.tape_checksum()
+                    && tape_a.pointer() as isize - tape_a.start_pointer() as isize == tape_b.pointer() as isize - tape_b.start_pointer() as isize
+                    && result_a.as_ref().map_err(|err| err.to_string()) == result_b.as_ref().map_err(|err| err.to_string())
+            },
+            (a, b) => a.is_none() && b.is_none()
+        }
+    }
+
+    /// `None` when `code` doesn't build.
+    fn run(&self, code: &[u8], passes: &[Pass]) -> Option<Ending> {
+        let run = || -> Option<(Vec<u8>, Interpreter, Result<HaltReason, RuntimeError>)> {
+            let program: Program = passes::build(code, &self.parse_options, passes).ok()?;
+            let mut interpreter: Interpreter = self.interpreter.clone();
+            let mut output: Vec<u8> = vec![];
+            let result = execute_code(&program, &mut interpreter, &mut &self.input[..], &mut output, &self.config).map(|summary| summary.halt);
+            Some((output, interpreter, result))
+        };
+        catch_unwind(AssertUnwindSafe(run)).map_or(Some(None), |built| built.map(Some))
+    }
+}
+
+/// Result of [`reduce`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reduction {
+    pub code: Vec<u8>, // the smallest candidate the check held for, the comment-free original at worst
+    pub tests: usize, // times the check ran
+    pub exhausted: bool // whether `max_tests` ran out before a round changed nothing
+}
+
+/// Shrinks `code` while `holds` stays true for it, running `holds` at most `max_tests` times. The
+/// caller makes sure it holds for `code` to begin with.
+pub fn reduce(code: &[u8], options: &ParseOptions, max_tests: usize, holds: &mut dyn FnMut(&[u8]) -> bool) -> Result<Reduction, ParseError> {
+    let program: Program = parse_bytes_with(code, &ParseOptions { fold: false, ..*options })?;
+    let commands: Vec<u8> = program.positions().iter().map(|&position| code[position]).collect();
+    let mut reducer: Reducer = Reducer { current: code.to_vec(), options: *options, tests: 0, max_tests, holds };
+    reducer.offer(commands);
+
+    loop {
+        let size: usize = reducer.current.len();
+        reducer.cut_trailing();
+        reducer.drop_loops();
+        reducer.drop_chunks();
+        reducer.shorten_runs();
+        if reducer.current.len() == size || reducer.exhausted() {
+            break;
+        }
+    }
+
+    let exhausted: bool = reducer.exhausted();
+    Ok(Reduction { code: reducer.current, tests: reducer.tests, exhausted })
+}
+
+struct Reducer<'a> {
+    current: Vec<u8>, // commands only, unless the check failed for the comment-free original
+    options: ParseOptions,
+    tests: usize,
+    max_tests: usize,
+    holds: &'a mut dyn FnMut(&[u8]) -> bool
+}
+
+impl Reducer<'_> {
+    fn exhausted(&self) -> bool {
+        self.tests >= self.max_tests
+    }
+
+    /// Runs the check on `candidate` and keeps it if it holds.
+    fn offer(&mut self, candidate: Vec<u8>) -> bool {
+        if self.exhausted() || candidate.len() >= self.current.len() {
+            return false;
+        }
+        self.tests += 1;
+        let kept: bool = (self.holds)(&candidate);
+        if kept {
+            debug!("reduced to {} commands after {} tests", candidate.len(), self.tests);
+            self.current = candidate;
+        }
+        kept
+    }
+
+    fn without(&self, start: usize, end: usize) -> Vec<u8> {
+        [&self.current[..start], &self.current[end..]].concat()
+    }
+
+    /// Loop nesting depth before every command and at the end.
+    fn depths(&self) -> Vec<usize> {
+        let mut depths: Vec<usize> = vec![0];
+        for &byte in &self.current {
+            let depth: usize = *depths.last().unwrap_or(&0);
+            depths.push(match self.bracket(byte) {
+                Some(Instruction::Begin) => depth + 1,
+                Some(_) => depth - 1,
+                None => depth
+            });
+        }
+        depths
+    }
+
+    fn bracket(&self, byte: u8) -> Option<Instruction> {
+        match byte {
+            b'[' => Some(Instruction::Begin),
+            b'(' if self.options.alt_brackets => Some(Instruction::Begin),
+            b']' => Some(Instruction::End),
+            b')' if self.options.alt_brackets => Some(Instruction::End),
+            _ => None
+        }
+    }
+
+    /// Cuts the program after the shortest run of top-level code still failing, by binary search
+    /// over the top-level boundaries (assuming a prefix fails once a shorter one does).
+    fn cut_trailing(&mut self) {
+        let depths: Vec<usize> = self.depths();
+        let boundaries: Vec<usize> = (0..self.current.len()).filter(|&at| depths[at] == 0).collect();
+        let (mut low, mut high): (usize, usize) = (0, boundaries.len());
+        while low < high && !self.exhausted() {
+            let middle: usize = (low + high) / 2;
+            let candidate: Vec<u8> = self.current[..boundaries[middle]].to_vec();
+            if self.offer(candidate) { high = middle; } else { low = middle + 1; }
+        }
+    }
+
+    /// Tries every loop, outermost first: without it, then without just its brackets.
+    fn drop_loops(&mut self) {
+        let mut depths: Vec<usize> = self.depths();
+        let mut start: usize = 0;
+        while start < self.current.len() && !self.exhausted() {
+            if self.bracket(self.current[start]) != Some(Instruction::Begin) {
+                start += 1;
+                continue;
+            }
+            let end: usize = (start + 1..self.current.len()).find(|&at| depths[at + 1] == depths[start]).map_or(self.current.len(), |at| at + 1);
+            let unwrapped: Vec<u8> = [&self.current[..start], &self.current[start + 1..end - 1], &self.current[end..]].concat();
+            if self.offer(self.without(start, end)) || self.offer(unwrapped) {
+                depths = self.depths();
+            } else {
+                start += 1;
+            }
+        }
+    }
+
+    /// Tries every chunk of half the program, then a quarter and so on down to single commands,
+    /// each one that keeps the brackets balanced.
+    fn drop_chunks(&mut self) {
+        let mut size: usize = self.current.len() / 2;
+        while size > 0 && !self.exhausted() {
+            let mut depths: Vec<usize> = self.depths();
+            let mut start: usize = 0;
+            while start + size <= self.current.len() && !self.exhausted() {
+                let balanced: bool = depths[start + size] == depths[start] && depths[start..=start + size].iter().all(|&depth| depth >= depths[start]);
+                if balanced && self.offer(self.without(start, start + size)) {
+                    depths = self.depths();
+                } else {
+                    start += size;
+                }
+            }
+            size /= 2;
+        }
+    }
+
+    /// Halves every run of one of `+-<>`, or takes one command off it.
+    fn shorten_runs(&mut self) {
+        let mut start: usize = 0;
+        while start < self.current.len() && !self.exhausted() {
+            let byte: u8 = self.current[start];
+            let length: usize = self.current[start..].iter().take_while(|&&next| next == byte).count();
+            if !b"+-<>".contains(&byte) || length < 2 {
+                start += length;
+                continue;
+            }
+            if !self.offer(self.without(start, start + length / 2)) && !self.offer(self.without(start, start + 1)) {
+                start += length;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The bug the reduction hunts for, showing whenever these commands appear in a row.
+    const PATTERN: &[u8] = b"[->>+<<]";
+
+    /// Balanced program of about `length` bytes of commands and comments, from a xorshift seeded
+    /// with `seed` so every run gets the same one.
+    fn generate(mut seed: u64, length: usize) -> Vec<u8> {
+        let mut below = |bound: usize| -> usize {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+        let mut code: Vec<u8> = vec![];
+        let mut depth: usize = 0;
+        while code.len() < length {
+            match below(12) {
+                0 => { code.push(b'['); depth += 1; },
+                1 if depth > 0 => { code.push(b']'); depth -= 1; },
+                2 => { code.extend_from_slice(b" comment\n"); },
+                _ => { code.extend(std::iter::repeat_n(b"+-<>.,"[below(6)], 1 + below(5))); }
+            }
+        }
+        code.extend(std::iter::repeat_n(b']', depth));
+        code
+    }
+
+    #[test]
+    fn a_pattern_planted_in_a_large_program_is_all_that_is_left() {
+        for seed in [1, 0x5eed, 0xdead_beef] {
+            let mut code: Vec<u8> = generate(seed, 5000);
+            // inside a loop, so the loops around it have to go as well
+            let at: usize = code.iter().rposition(|&byte| byte == b'[').unwrap() + 1;
+            code.splice(at..at, PATTERN.iter().copied());
+            let shows = |candidate: &[u8]| candidate.windows(PATTERN.len()).any(|window| window == PATTERN);
+            assert_eq!(code.windows(PATTERN.len()).filter(|&window| window == PATTERN).count(), 1, "seed {}", seed);
+
+            let mut checked: Vec<Vec<u8>> = vec![];
+            let mut holds = |candidate: &[u8]| {
+                checked.push(candidate.to_vec());
+                shows(candidate)
+            };
+            let reduction: Reduction = reduce(&code, &ParseOptions::default(), 100000, &mut holds).unwrap();
+            assert_eq!(reduction.code, PATTERN, "seed {}", seed);
+            assert!(!reduction.exhausted, "seed {}", seed);
+            assert_eq!(reduction.tests, checked.len());
+            // every candidate parsed like the original
+            assert!(checked.iter().all(|candidate| parse_bytes_with(candidate, &ParseOptions::default()).is_ok()), "seed {}", seed);
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Self-contained HTML report of a profiled run, see `--report`.
+//!
+//! A [`Profiler`] counts how often every instruction runs and which cells the run visits and writes,
+//! [`RunReport::new`] turns that into per-source-byte heat, a loop profile and memory statistics next
+//! to the counters `stats_json` writes for manifest jobs, and [`render_html`] renders the report on
+//! its own: inline CSS, no scripts, nothing fetched.
+
+use std::collections::HashSet;
+
+use crate::analysis::Stats;
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+use crate::parser::{Instruction, Program};
+use crate::source::line_col;
+
+/// Output bytes shown in a report, the rest is only counted.
+const OUTPUT_SHOWN: usize = 64 * 1024;
+
+/// Loops listed in the profile table, the ones running the most steps.
+const LOOPS_SHOWN: usize = 50;
+
+/// [`Observer`] collecting what a [`RunReport`] shows about a run.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    counts: Vec<u64>, // executions per instruction
+    lowest: isize, // leftmost cell the pointer visited, relative to the starting pointer
+    highest: isize, // rightmost one
+    written: HashSet<isize> // cells some instruction stored a value in
+}
+
+impl Profiler {
+    pub fn new(program: &Program) -> Self {
+        Self { counts: vec![0; program.len()], ..Profiler::default() }
+    }
+
+    /// How often every instruction ran, by instruction index.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+}
+
+impl Observer for Profiler {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        self.counts[event.index] += 1;
+        let start: isize = interpreter.start_pointer() as isize;
+        let (before, after): (isize, isize) = (event.pointer as isize - start, interpreter.pointer() as isize - start);
+        self.lowest = self.lowest.min(after);
+        self.highest = self.highest.max(after);
+        if matches!(event.instruction, Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) | Instruction::SetZero | Instruction::InputValue) {
+            self.written.insert(before);
+        }
+    }
+}
+
+/// One loop of the profile table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopProfile {
+    pub position: usize, // source position of the `[`
+    pub entries: u64, // times the `[` ran
+    pub iterations: u64, // times the `]` ran, once per finished iteration
+    pub steps: u64 // steps spent in the loop, nested loops included
+}
+
+/// Cells the run used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+    pub allocated: usize, // tape cells at the end
+    pub lowest: isize, // leftmost cell visited, relative to the starting cell
+    pub highest: isize,
+    pub written: usize, // distinct cells written
+    pub nonzero: usize // cells holding something else than 0 at the end
+}
+
+/// Everything [`render_html`] shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunReport {
+    pub title: String,
+    pub source: Vec<u8>,
+    pub heat: Vec<Option<u64>>, // per source byte, executions of its instruction, `None` for comments
+    pub loops: Vec<LoopProfile>, // in source order
+    pub stats: Stats,
+    pub steps: u64,
+    pub checksum: u64,
+    pub ending: String, // how the run ended, a halt reason or the error
+    pub output: Vec<u8>,
+    pub memory: MemoryStats,
+    pub config: Vec<(String, String)> // the settings the run used, as name and value
+}
+
+impl RunReport {
+    /// Report of a finished run of `program`, parsed from `source` and watched by `profiler`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        title: &str,
+        source: &[u8],
+        program: &Program,
+        stats: Stats,
+        profiler: &Profiler,
+        interpreter: &Interpreter,
+        ending: String,
+        output: Vec<u8>,
+        config: Vec<(String, String)>
+    ) -> Self {
+        let mut heat: Vec<Option<u64>> = vec![None; source.len()];
+        for (index, (&instruction, &position)) in program.instructions().iter().zip(program.positions()).enumerate() {
+            for (offset, byte) in instruction_bytes(source, instruction, position) {
+                if b"+-<>[](),.:".contains(&byte) {
+                    heat[offset] = Some(profiler.counts[index]);
+                }
+            }
+        }
+
+        let loops: Vec<LoopProfile> = program.instructions().iter().enumerate()
+            .filter(|&(_, &instruction)| instruction == Instruction::Begin)
+            .map(|(begin, _)| {
+                let end: usize = program.jumps()[begin] - 1;
+                LoopProfile {
+                    position: program.positions()[begin],
+                    entries: profiler.counts[begin],
+                    iterations: profiler.counts[end],
+                    steps: profiler.counts[begin..=end].iter().sum()
+                }
+            })
+            .collect();
+
+        let memory: MemoryStats = MemoryStats {
+            allocated: interpreter.tape().len(),
+            lowest: profiler.lowest,
+            highest: profiler.highest,
+            written: profiler.written.len(),
+            nonzero: interpreter.tape().iter().filter(|&&value| value != 0).count()
+        };
+
+        Self {
+            title: title.to_string(),
+            source: source.to_vec(),
+            heat,
+            loops,
+            stats,
+            steps: interpreter.steps(),
+            checksum: interpreter.tape_checksum(),
+            ending,
+            output,
+            memory,
+            config
+        }
+    }
+}
+
+/// Source bytes making up the instruction at `position`: up to the next bracket for a run or a
+/// single command, through the `]` for a folded `[-]`. Comments in between are included and left out
+/// by the caller, code an optimization removed starts with a bracket and so is never included.
+fn instruction_bytes(source: &[u8], instruction: Instruction, position: usize) -> impl Iterator<Item = (usize, u8)> + '_ {
+    let rest = source.iter().copied().enumerate().skip(position);
+    let mut done: bool = false;
+    rest.take_while(move |&(offset, byte)| {
+        let first: bool = offset == position;
+        let taken: bool = !done && match instruction {
+            Instruction::SetZero => true,
+            Instruction::Add(_) | Instruction::Move(_) => first || !b"[]().,:".contains(&byte),
+            _ => first || !b"+-<>[]().,:".contains(&byte)
+        };
+        done |= instruction == Instruction::SetZero && matches!(byte, b']' | b')');
+        taken
+    })
+}
+
+/// `text` with `&`, `<`, `>` and `"` escaped, safe both in element content and in attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Background of code executed `count` times, on a logarithmic scale up to `max`.
+fn heat_color(count: u64, max: u64) -> String {
+    let level: f64 = ((count as f64).ln_1p() / (max as f64).ln_1p()).clamp(0.0, 1.0);
+    format!("rgba(255,{},0,{:.2})", 200 - (level * 160.0) as u32, 0.15 + level * 0.75)
+}
+
+const STYLE: &str = "\
+body{font-family:sans-serif;margin:2em;color:#222}
+h1{font-size:1.4em}h2{font-size:1.1em;margin-top:1.6em}
+table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:.2em .6em;text-align:right}
+th{background:#f3f3f3}td.text,th.text{text-align:left}
+pre{background:#fafafa;border:1px solid #ddd;padding:.8em;overflow-x:auto;line-height:1.35}
+.comment{color:#8a8a8a}.cold{color:#bbb}";
+
+/// The whole report as one HTML document.
+pub fn render_html(report: &RunReport) -> String {
+    let mut html: String = String::new();
+    let row = |html: &mut String, name: &str, value: String| {
+        html.push_str(&format!("<tr><th class=\"text\">{}</th><td>{}</td></tr>\n", escape_html(name), escape_html(&value)));
+    };
+
+    html.push_str(&format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n<style>\n{1}\n</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+        escape_html(&report.title), STYLE
+    ));
+
+    html.push_str("<h2>Run</h2>\n<table>\n");
+    row(&mut html, "ending", report.ending.clone());
+    row(&mut html, "steps", report.steps.to_string());
+    row(&mut html, "output bytes", report.output.len().to_string());
+    row(&mut html, "tape checksum", report.checksum.to_string());
+    row(&mut html, "instructions", report.stats.instructions.to_string());
+    row(&mut html, "loops", report.stats.loops.to_string());
+    row(&mut html, "max nesting", report.stats.max_depth.to_string());
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Memory</h2>\n<table>\n");
+    row(&mut html, "cells allocated", report.memory.allocated.to_string());
+    row(&mut html, "cells visited", format!("{} to {}", report.memory.lowest, report.memory.highest));
+    row(&mut html, "cells written", report.memory.written.to_string());
+    row(&mut html, "non-zero cells at the end", report.memory.nonzero.to_string());
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Configuration</h2>\n<table>\n");
+    for (name, value) in &report.config {
+        row(&mut html, name, value.clone());
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Source</h2>\n<p>Background: how often the code ran (hover for the count), grey: never.</p>\n<pre>");
+    let max: u64 = report.heat.iter().flatten().copied().max().unwrap_or(0);
+    // one character per byte unless the source is valid UTF-8, so offsets stay those of `heat`
+    let text: String = match std::str::from_utf8(&report.source) {
+        Ok(text) => text.to_string(),
+        Err(_) => report.source.iter().map(|&byte| if byte.is_ascii() { byte as char } else { '?' }).collect()
+    };
+    let mut bytes = text.char_indices().peekable();
+    while let Some((offset, _)) = bytes.next() {
+        // a run of characters sharing their count becomes one span
+        let heat: Option<u64> = report.heat.get(offset).copied().flatten();
+        let mut end: usize = offset + text[offset..].chars().next().map_or(1, char::len_utf8);
+        while let Some(&(next, c)) = bytes.peek() {
+            if report.heat.get(next).copied().flatten() != heat { break; }
+            end = next + c.len_utf8();
+            bytes.next();
+        }
+        let chunk: String = escape_html(&text[offset..end]);
+        match heat {
+            None => html.push_str(&format!("<span class=\"comment\">{}</span>", chunk)),
+            Some(0) => html.push_str(&format!("<span class=\"cold\" title=\"never run\">{}</span>", chunk)),
+            Some(count) => html.push_str(&format!("<span style=\"background:{}\" title=\"{} runs\">{}</span>", heat_color(count, max), count, chunk))
+        }
+    }
+    html.push_str("</pre>\n");
+
+    html.push_str("<h2>Loops</h2>\n");
+    let mut loops: Vec<&LoopProfile> = report.loops.iter().collect();
+    loops.sort_by_key(|profile| (std::cmp::Reverse(profile.steps), profile.position));
+    if loops.is_empty() {
+        html.push_str("<p>No loops.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th class=\"text\">loop at</th><th>entered</th><th>iterations</th><th>per entry</th><th>steps</th><th>share</th></tr>\n");
+        for profile in loops.iter().take(LOOPS_SHOWN) {
+            let (line, column) = line_col(&report.source, profile.position);
+            let per_entry: String = if profile.entries == 0 { "-".to_string() } else { format!("{:.1}", profile.iterations as f64 / profile.entries as f64) };
+            let share: f64 = if report.steps == 0 { 0.0 } else { profile.steps as f64 * 100.0 / report.steps as f64 };
+            html.push_str(&format!(
+                "<tr><td class=\"text\">{}:{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>\n",
+                line, column, profile.entries, profile.iterations, per_entry, profile.steps, share
+            ));
+        }
+        html.push_str("</table>\n");
+        if loops.len() > LOOPS_SHOWN {
+            html.push_str(&format!("<p>{} more loops not shown.</p>\n", loops.len() - LOOPS_SHOWN));
+        }
+    }
+
+    html.push_str("<h2>Output</h2>\n<pre>");
+    let shown: &[u8] = &report.output[..report.output.len().min(OUTPUT_SHOWN)];
+    html.push_str(&escape_html(&String::from_utf8_lossy(shown)));
+    html.push_str("</pre>\n");
+    if report.output.len() > shown.len() {
+        html.push_str(&format!("<p>{} more bytes not shown.</p>\n", report.output.len() - shown.len()));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
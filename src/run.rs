@@ -0,0 +1,525 @@
+//! One-call helpers composing parsing, folding and execution: [`run_full`], mostly for tests
+//! against the engine, and [`Run`], the settings of a command-line run for tools driving the engine
+//! like the binary does.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::constprop;
+use crate::gas::Gas;
+use crate::input::RandomInput;
+use crate::interpreter::{execute_code, EofBehavior, ExecutionConfig, HaltReason, Interpreter, RuntimeError, TapeMode, TAPE_SIZE};
+use crate::parser::{compile, ParseError, ParseOptions, Program};
+
+/// Most cells a growing tape takes unless [`Run::tape_size`] says otherwise.
+pub const DYNAMIC_TAPE_LIMIT: usize = 1 << 26;
+/// Bytes a `,` reading once per loop iteration fetches ahead from input nobody types.
+const INPUT_BATCH: usize = 64 * 1024;
+
+/// Why [`run_full`] didn't get to the end of the program.
+#[derive(Debug)]
+pub enum RunError {
+    Parse(ParseError),
+    Runtime(RuntimeError),
+    Io(io::Error), // reading the program or the input file, creating the output file
+    Sandbox(Range<usize>), // not a non-empty range of the tape
+    CellModulus(u16), // constant propagation counts modulo 256, it needs a modulus dividing 256
+    InputByte(u64, RuntimeError) // the run of `FilterMode::Byte` for the input byte at this offset failed
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Parse(err) => write!(f, "{}", err),
+            RunError::Runtime(err) => write!(f, "{}", err),
+            RunError::Io(err) => write!(f, "{}", err),
+            RunError::Sandbox(region) => write!(f, "sandbox region {}..{} is not a non-empty range of the tape", region.start, region.end),
+            RunError::CellModulus(modulus) => write!(f, "cell modulus {} doesn't divide 256, constant propagation can't be used with it", modulus),
+            RunError::InputByte(offset, err) => write!(f, "input byte {}: {}", offset, err)
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunError::Parse(err) => Some(err),
+            RunError::Runtime(err) | RunError::InputByte(_, err) => Some(err),
+            RunError::Io(err) => Some(err),
+            RunError::Sandbox(_) | RunError::CellModulus(_) => None
+        }
+    }
+}
+
+impl From<ParseError> for RunError {
+    fn from(err: ParseError) -> Self {
+        RunError::Parse(err)
+    }
+}
+
+impl From<RuntimeError> for RunError {
+    fn from(err: RuntimeError) -> Self {
+        RunError::Runtime(err)
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(err: io::Error) -> Self {
+        RunError::Io(err)
+    }
+}
+
+/// Runs `code` on a default tape with `input` and returns the output (lossily decoded as UTF-8)
+/// together with the interpreter as the program left it.
+///
+/// The program is folded while parsing, which changes the step count but nothing else. There is no
+/// step limit, reading past the end of `input` leaves the cell unchanged. The final state is open to
+/// [`Interpreter::tape`], [`Interpreter::pointer`], [`Interpreter::current`] and
+/// [`Interpreter::tape_checksum`]; a cell `offset` away from where the program started is
+/// `tape()[start_pointer() + offset]`.
+pub fn run_full(code: &str, input: &[u8]) -> Result<(String, Interpreter), RunError> {
+    let program: Program = compile(code.as_bytes(), &ParseOptions { fold: true, ..ParseOptions::default() }, true)?;
+    let mut interpreter: Interpreter = Interpreter::default();
+    let mut output: Vec<u8> = vec![];
+    execute_code(&program, &mut interpreter, &mut &input[..], &mut output, &ExecutionConfig::default())?;
+    Ok((String::from_utf8_lossy(&output).into_owned(), interpreter))
+}
+
+/// Where [`Run`] takes the program from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Path(PathBuf),
+    Text(String),
+    Bytes(Vec<u8>) // raw, only the instruction bytes have to mean anything
+}
+
+/// What `,` reads in a [`Run`].
+pub enum InputSpec {
+    Empty,
+    Bytes(Vec<u8>),
+    File(PathBuf),
+    Stdin, // read ahead unless a terminal is attached, a typist's bytes have to reach the program as they come
+    Reader(Box<dyn Read + Send>),
+    Random(u64) // seed of a `RandomInput`
+}
+
+/// Where `.` writes in a [`Run`].
+pub enum OutputSpec {
+    Capture, // kept in `RunReport::output`
+    Discard,
+    File(PathBuf),
+    Writer(Box<dyn Write + Send>)
+}
+
+/// Interpreter whose conventions [`Run::compat`] presets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compat {
+    Dbfi, // Daniel Cristofani's self-interpreter
+    Bff // Oleg Mazonka's bff
+}
+
+impl Compat {
+    /// Tape the interpreter's programs expect: dbfi keeps the program it interprets right of the
+    /// start cell and takes more of it with every level of nesting, bff lets programs walk left too.
+    pub fn tape_mode(&self) -> TapeMode {
+        match self {
+            Compat::Dbfi => TapeMode::Growable,
+            Compat::Bff => TapeMode::Bidirectional
+        }
+    }
+}
+
+impl fmt::Display for Compat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compat::Dbfi => write!(f, "dbfi"),
+            Compat::Bff => write!(f, "bff")
+        }
+    }
+}
+
+/// How [`Run::filter`] feeds the input to the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Stream, // one run over the whole input
+    Byte // one run per input byte, each on a fresh tape
+}
+
+/// A program run with command-line settings: the program, the options of the flags of the same
+/// name, the input and the output. Nothing is optimized, limited or captured unless asked for.
+///
+/// The binary builds its parse options, tape and execution config with it, so a tool setting the
+/// same options gets the same run.
+pub struct Run {
+    source: Source,
+    parse_options: ParseOptions,
+    constprop: bool,
+    auto_halt: bool,
+    tape_mode: TapeMode,
+    tape_size: Option<usize>, // default `TAPE_SIZE` for a fixed tape, `DYNAMIC_TAPE_LIMIT` for a growing one
+    sandbox: Option<Range<usize>>,
+    config: ExecutionConfig,
+    compat: Option<Compat>,
+    filter: Option<FilterMode>,
+    expect: Option<Vec<u8>>,
+    input: InputSpec,
+    output: OutputSpec
+}
+
+/// How a [`Run::execute`] went, what the binary reports about a run.
+#[derive(Debug)]
+pub struct RunReport {
+    pub output: Option<Vec<u8>>, // with `OutputSpec::Capture` or `Run::expect`
+    pub matched: Option<bool>, // whether the output was what `Run::expect` expected
+    pub elapsed: Duration, // building and running the program
+    pub steps: u64,
+    pub gas_used: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub interpreter: Interpreter, // as the run left it, a fresh one if the program didn't build
+    pub result: Result<HaltReason, RunError>
+}
+
+impl Run {
+    pub fn new(source: Source) -> Self {
+        Self {
+            source,
+            parse_options: ParseOptions::default(),
+            constprop: false,
+            auto_halt: true,
+            tape_mode: TapeMode::Fixed,
+            tape_size: None,
+            sandbox: None,
+            config: ExecutionConfig::default(),
+            compat: None,
+            filter: None,
+            expect: None,
+            input: InputSpec::Empty,
+            output: OutputSpec::Capture
+        }
+    }
+
+    /// `--alt-brackets`
+    pub fn alt_brackets(mut self, on: bool) -> Self {
+        self.parse_options.alt_brackets = on;
+        self
+    }
+
+    /// `--extensions`
+    pub fn extensions(mut self, on: bool) -> Self {
+        self.parse_options.extensions = on;
+        self
+    }
+
+    /// `--max-nesting`
+    pub fn max_nesting(mut self, depth: Option<usize>) -> Self {
+        self.parse_options.max_depth = depth;
+        self
+    }
+
+    /// `--single-pass`
+    pub fn single_pass(mut self, on: bool) -> Self {
+        self.parse_options.fold = on;
+        self
+    }
+
+    /// `--constprop`
+    pub fn constprop(mut self, on: bool) -> Self {
+        self.constprop = on;
+        self
+    }
+
+    /// The opposite of `--no-auto-halt`.
+    pub fn auto_halt(mut self, on: bool) -> Self {
+        self.auto_halt = on;
+        self
+    }
+
+    /// `--dynamic-tape`, `TapeMode::Fixed` for none.
+    pub fn tape_mode(mut self, mode: TapeMode) -> Self {
+        self.tape_mode = mode;
+        self
+    }
+
+    /// `--tape-size`
+    pub fn tape_size(mut self, cells: Option<usize>) -> Self {
+        self.tape_size = cells;
+        self
+    }
+
+    /// `--sandbox-region`
+    pub fn sandbox(mut self, region: Option<Range<usize>>) -> Self {
+        self.sandbox = region;
+        self
+    }
+
+    /// `--max-steps`
+    pub fn max_steps(mut self, steps: Option<u64>) -> Self {
+        self.config.max_steps = steps;
+        self
+    }
+
+    /// `--gas-limit` with the `--gas-cost` prices.
+    pub fn gas(mut self, gas: Option<Gas>) -> Self {
+        self.config.gas = gas;
+        self
+    }
+
+    /// Bytes `.` may write at most.
+    pub fn max_output(mut self, bytes: Option<u64>) -> Self {
+        self.config.max_output = bytes;
+        self
+    }
+
+    /// What `,` stores once the input is exhausted.
+    pub fn eof(mut self, eof: EofBehavior) -> Self {
+        self.config.eof = eof;
+        self
+    }
+
+    /// `--cell-modulus`, 2 to 256 and a divisor of 256 with folding or constant propagation, checked
+    /// by [`Run::execute`]
+    pub fn cell_modulus(mut self, modulus: Option<u16>) -> Self {
+        self.config.cell_modulus = modulus;
+        self
+    }
+
+    /// `--loop-cell`
+    pub fn loop_cell(mut self, cell: Option<isize>) -> Self {
+        self.config.loop_cell = cell;
+        self
+    }
+
+    /// `--max-pointer-range`
+    pub fn max_pointer_range(mut self, cells: Option<usize>) -> Self {
+        self.config.max_pointer_range = cells;
+        self
+    }
+
+    /// `--break-on-output`
+    pub fn break_on_output(mut self, byte: Option<u8>) -> Self {
+        self.config.break_on_output = byte;
+        self
+    }
+
+    /// `--halt-on-output`, `--emit-sentinel` writes the byte too.
+    pub fn halt_on_output(mut self, byte: Option<u8>, emit_sentinel: bool) -> Self {
+        self.config.halt_on_output = byte;
+        self.config.emit_sentinel = emit_sentinel;
+        self
+    }
+
+    /// `--compat`: presets the tape (a later [`Run::tape_mode`] overrides it) and ends the program at
+    /// its first `!`, what follows is read before the input.
+    pub fn compat(mut self, compat: Option<Compat>) -> Self {
+        if let Some(compat) = compat {
+            self.tape_mode = compat.tape_mode();
+        }
+        self.compat = compat;
+        self
+    }
+
+    /// `--filter`
+    pub fn filter(mut self, mode: Option<FilterMode>) -> Self {
+        self.filter = mode;
+        self
+    }
+
+    /// `--expect`, the output is kept in [`RunReport::output`] to be compared with `bytes`.
+    pub fn expect(mut self, bytes: Option<Vec<u8>>) -> Self {
+        self.expect = bytes;
+        self
+    }
+
+    pub fn input(mut self, input: InputSpec) -> Self {
+        self.input = input;
+        self
+    }
+
+    pub fn output(mut self, output: OutputSpec) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn parse_options(&self) -> ParseOptions {
+        self.parse_options
+    }
+
+    /// Execution settings of the run, without input batching: only the input knows whether it may
+    /// be read ahead.
+    pub fn config(&self) -> ExecutionConfig {
+        self.config.clone()
+    }
+
+    /// A fresh interpreter with the tape settings of the run.
+    pub fn interpreter(&self) -> Result<Interpreter, RunError> {
+        let mut interpreter: Interpreter = match self.tape_mode {
+            TapeMode::Fixed => Interpreter::fixed(self.tape_size.unwrap_or(TAPE_SIZE)),
+            TapeMode::Growable => Interpreter::growable(self.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT)),
+            TapeMode::Bidirectional => Interpreter::bidirectional(self.tape_size.unwrap_or(DYNAMIC_TAPE_LIMIT))
+        };
+        match &self.sandbox {
+            Some(region) if !interpreter.set_sandbox(region.clone()) => Err(RunError::Sandbox(region.clone())),
+            _ => Ok(interpreter)
+        }
+    }
+
+    /// The program as the run builds it from `code`.
+    pub fn program(&self, code: &[u8]) -> Result<Program, ParseError> {
+        let program: Program = compile(code, &self.parse_options, self.auto_halt)?;
+        Ok(if self.constprop { constprop::propagate(&program) } else { program })
+    }
+
+    /// Reads the program, builds and runs it. Every failure ends up in [`RunReport::result`].
+    pub fn execute(mut self) -> RunReport {
+        let started: Instant = Instant::now();
+        let mut captured: Vec<u8> = vec![];
+        let capture: bool = matches!(self.output, OutputSpec::Capture) || self.expect.is_some();
+        let expected: Option<Vec<u8>> = self.expect.take();
+        let mut interpreter: Interpreter = Interpreter::default();
+        let result: Result<HaltReason, RunError> = self.execute_into(&mut interpreter, &mut captured, capture);
+
+        RunReport {
+            matched: expected.map(|expected| expected == captured),
+            output: capture.then_some(captured),
+            elapsed: started.elapsed(),
+            steps: interpreter.steps(),
+            gas_used: interpreter.gas_used(),
+            input_bytes: interpreter.input_bytes(),
+            output_bytes: interpreter.output_bytes(),
+            interpreter,
+            result
+        }
+    }
+
+    fn execute_into(self, interpreter: &mut Interpreter, captured: &mut Vec<u8>, capture: bool) -> Result<HaltReason, RunError> {
+        let mut code: Vec<u8> = match self.source {
+            Source::Path(ref path) => std::fs::read(path)?,
+            Source::Text(ref text) => text.clone().into_bytes(),
+            Source::Bytes(ref bytes) => bytes.clone()
+        };
+        // dbfi and bff read a program and its input from one stream, separated by a `!`
+        let source_input: Vec<u8> = match self.compat.and_then(|_| code.iter().position(|&byte| byte == b'!')) {
+            Some(bang) => {
+                let rest: Vec<u8> = code[bang + 1..].to_vec();
+                code.truncate(bang);
+                rest
+            },
+            None => vec![]
+        };
+        match self.config.cell_modulus {
+            Some(modulus) if !(2..=256).contains(&modulus) => return Err(RunError::Runtime(RuntimeError::InvalidCellModulus(modulus))),
+            Some(modulus) if self.constprop && 256 % modulus != 0 => return Err(RunError::CellModulus(modulus)),
+            _ => {}
+        }
+        let program: Program = self.program(&code)?;
+        *interpreter = self.interpreter()?;
+
+        let mut config: ExecutionConfig = self.config;
+        config.input_batch = match self.input {
+            InputSpec::Reader(_) => None,
+            InputSpec::Stdin if io::stdin().is_terminal() => None,
+            // nobody types these
+            _ => Some(INPUT_BATCH)
+        };
+        let input: Box<dyn Read> = match self.input {
+            InputSpec::Empty => Box::new(io::empty()),
+            InputSpec::Bytes(bytes) => Box::new(io::Cursor::new(bytes)),
+            InputSpec::File(path) => Box::new(BufReader::new(File::open(path)?)),
+            InputSpec::Stdin => Box::new(io::stdin()),
+            InputSpec::Reader(reader) => reader,
+            InputSpec::Random(seed) => Box::new(RandomInput::new(seed))
+        };
+        let mut input: Box<dyn Read> = Box::new(io::Cursor::new(source_input).chain(input));
+        let mut output: Box<dyn Write> = match self.output {
+            OutputSpec::Capture => return run_filtered(&program, interpreter, &mut input, captured, &config, self.filter),
+            OutputSpec::Discard => Box::new(io::sink()),
+            OutputSpec::File(path) => Box::new(io::BufWriter::new(File::create(path)?)),
+            OutputSpec::Writer(writer) => writer
+        };
+        let result: Result<HaltReason, RunError> = if capture {
+            let mut recorded: Recorder<'_> = Recorder { inner: &mut output, recorded: captured };
+            run_filtered(&program, interpreter, &mut input, &mut recorded, &config, self.filter)
+        } else {
+            run_filtered(&program, interpreter, &mut input, &mut output, &config, self.filter)
+        };
+        let halt: HaltReason = result?;
+        output.flush()?;
+        Ok(halt)
+    }
+}
+
+/// Writes through to `inner` and keeps a copy of everything written, for [`Run::expect`].
+struct Recorder<'a> {
+    inner: &'a mut dyn Write,
+    recorded: &'a mut Vec<u8>
+}
+
+impl Write for Recorder<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written: usize = self.inner.write(buf)?;
+        self.recorded.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// One run of `program` over `input`, or with a filter mode the runs `--filter` makes of it.
+fn run_filtered(program: &Program, interpreter: &mut Interpreter, input: &mut dyn Read, output: &mut dyn Write, config: &ExecutionConfig, filter: Option<FilterMode>) -> Result<HaltReason, RunError> {
+    match filter {
+        None => Ok(execute_code(program, interpreter, input, output, config)?.halt),
+        Some(FilterMode::Stream) => {
+            let mut data: Vec<u8> = vec![];
+            input.read_to_end(&mut data)?;
+            Ok(execute_code(program, interpreter, &mut &data[..], output, config)?.halt)
+        },
+        Some(FilterMode::Byte) => {
+            let mut halt: HaltReason = HaltReason::EndOfProgram;
+            for (offset, byte) in BufReader::new(input).bytes().enumerate() {
+                let byte: u8 = byte?;
+                interpreter.reset();
+                halt = execute_code(program, interpreter, &mut &[byte][..], output, config).map_err(|err| RunError::InputByte(offset as u64, err))?.halt;
+            }
+            Ok(halt)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &str) -> Run {
+        Run::new(Source::Text(code.to_string())).extensions(true).output(OutputSpec::Capture)
+    }
+
+    #[test]
+    fn cell_modulus_keeps_loops_the_optimizations_would_drop() {
+        let code: String = format!("{}[:[-]]", "+".repeat(256));
+        assert_eq!(run(&code).cell_modulus(Some(100)).execute().output.unwrap(), b"56");
+        assert_eq!(run(&code).cell_modulus(Some(128)).constprop(true).execute().output.unwrap(), b"");
+
+        let report: RunReport = run(&code).cell_modulus(Some(100)).constprop(true).execute();
+        assert!(matches!(report.result, Err(RunError::CellModulus(100))));
+        let report: RunReport = run(&code).cell_modulus(Some(100)).single_pass(true).execute();
+        assert!(matches!(report.result, Err(RunError::Runtime(RuntimeError::InvalidCellModulus(100)))));
+    }
+
+    #[test]
+    fn cell_modulus_out_of_range_is_an_error_not_a_panic() {
+        for modulus in [0, 1, 257] {
+            for constprop in [false, true] {
+                let report: RunReport = run("+:").cell_modulus(Some(modulus)).constprop(constprop).execute();
+                assert!(matches!(report.result, Err(RunError::Runtime(RuntimeError::InvalidCellModulus(_)))), "{} with constprop {}", modulus, constprop);
+                assert_eq!(report.steps, 0);
+            }
+        }
+    }
+}
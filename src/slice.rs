@@ -0,0 +1,204 @@
+//! Dynamic slices: the instructions one output byte depends on, see the `slice` command.
+//!
+//! A [`Slicer`] follows one run and keeps a dependence node for every executed instruction that
+//! writes a cell, tests one (`[`, `]`) or outputs one. A shadow tape remembers which node last wrote
+//! each cell. A node depends on that last writer of the cell it reads (`+` and `-` read the cell they
+//! write, `,` and a folded `[-]` don't) and on the loop test that let its iteration run. The slice is
+//! made of the instructions of every node the node of the target output byte reaches.
+//!
+//! It is an approximation: the cell an instruction works on is taken as given, the pointer moves
+//! that chose it are not part of the slice, and neither is a loop that didn't run.
+
+use std::collections::HashMap;
+
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+use crate::parser::{Instruction, Program};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Node {
+    index: usize, // instruction index
+    data: Option<usize>, // last writer of the cell read
+    control: Option<usize> // loop test the iteration ran under
+}
+
+/// [`Observer`] recording the dependences of a run up to its output byte number `target` (0-based).
+#[derive(Debug, Clone, Default)]
+pub struct Slicer {
+    target: u64,
+    outputs: u64, // output bytes so far
+    nodes: Vec<Node>,
+    writers: HashMap<isize, usize>, // cell relative to the starting pointer -> node last writing it
+    loops: Vec<usize>, // node of the latest test of every loop being run, innermost last
+    found: Option<usize> // node of the target output byte
+}
+
+impl Slicer {
+    pub fn new(target: u64) -> Self {
+        Self { target, ..Slicer::default() }
+    }
+
+    /// Output bytes seen so far, at most one past the target.
+    pub fn outputs(&self) -> u64 {
+        self.outputs
+    }
+
+    /// Indices of the instructions the target output byte depends on, itself included, in program
+    /// order. A loop whose `[` or `]` is in the slice has both in it. `None` until the byte is written.
+    pub fn slice(&self, program: &Program) -> Option<Vec<usize>> {
+        let mut included: Vec<bool> = vec![false; program.len()];
+        let mut visited: Vec<bool> = vec![false; self.nodes.len()];
+        let mut pending: Vec<usize> = vec![self.found?];
+
+        while let Some(node) = pending.pop() {
+            if std::mem::replace(&mut visited[node], true) {
+                continue;
+            }
+            let Node { index, data, control } = self.nodes[node];
+            included[index] = true;
+            pending.extend(data.into_iter().chain(control));
+        }
+
+        for (index, instruction) in program.instructions().iter().enumerate() {
+            if included[index] && matches!(instruction, Instruction::Begin | Instruction::End) {
+                included[program.jumps()[index] - 1] = true;
+            }
+        }
+
+        Some((0..program.len()).filter(|&index| included[index]).collect())
+    }
+
+    fn push(&mut self, index: usize, data: Option<usize>) -> usize {
+        self.nodes.push(Node { index, data, control: self.loops.last().copied() });
+        self.nodes.len() - 1
+    }
+}
+
+impl Observer for Slicer {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if self.found.is_some() {
+            return;
+        }
+        let cell: isize = event.pointer as isize - interpreter.start_pointer() as isize;
+        let writer: Option<usize> = self.writers.get(&cell).copied();
+
+        match event.instruction {
+            Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) => {
+                let node: usize = self.push(event.index, writer);
+                self.writers.insert(cell, node);
+            },
+            Instruction::SetZero | Instruction::InputValue => {
+                let node: usize = self.push(event.index, None);
+                self.writers.insert(cell, node);
+            },
+            Instruction::OutputValue | Instruction::OutputDecimal => {
+                let bytes: u64 = if event.instruction == Instruction::OutputValue { 1 } else { event.before.to_string().len() as u64 };
+                if self.outputs + bytes > self.target {
+                    self.found = Some(self.push(event.index, writer));
+                }
+                self.outputs += bytes;
+            },
+            Instruction::Begin => {
+                let node: usize = self.push(event.index, writer);
+                if event.before != 0 {
+                    self.loops.push(node);
+                }
+            },
+            Instruction::End => {
+                let node: usize = self.push(event.index, writer);
+                self.loops.pop();
+                if event.before != 0 {
+                    self.loops.push(node);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Every source line holding a sliced position, numbered, with a `^` under each such position.
+/// Columns count bytes, a tab or any byte other than printable ASCII shows as a single character.
+pub fn render_slice(source: &[u8], positions: &[usize]) -> String {
+    let mut rendering: String = String::new();
+    let mut line_start: usize = 0;
+
+    for (number, line) in source.split(|&byte| byte == b'\n').enumerate() {
+        let line_end: usize = line_start + line.len();
+        let marked: Vec<usize> = positions.iter().filter(|&&position| (line_start..line_end).contains(&position)).map(|position| position - line_start).collect();
+        if !marked.is_empty() {
+            let text: String = line.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else if byte == b'\t' { ' ' } else { '?' }).collect();
+            let mut carets: Vec<u8> = vec![b' '; marked.last().map_or(0, |&column| column + 1)];
+            marked.iter().for_each(|&column| carets[column] = b'^');
+            rendering.push_str(&format!("{:>5} | {}\n      | {}\n", number + 1, text, String::from_utf8_lossy(&carets)));
+        }
+        line_start = line_end + 1;
+    }
+
+    rendering
+}
+
+/// Source text of the sliced instructions in program order, folded ones written out again. Without
+/// the instructions left out it generally computes something else, if it runs at all.
+pub fn sliced_source(program: &Program, slice: &[usize]) -> String {
+    slice.iter().map(|&index| program.instructions()[index].to_source()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_observed, ExecutionConfig};
+    use crate::parser::{compile, ParseOptions};
+
+    /// The slice of output byte `target` of `code` run on `input`, folded with `fold`.
+    fn slice_of(code: &str, input: &[u8], target: u64, fold: bool) -> Option<Vec<usize>> {
+        let program: Program = compile(code.as_bytes(), &ParseOptions { fold, ..ParseOptions::default() }, true).unwrap();
+        let mut slicer: Slicer = Slicer::new(target);
+        execute_observed(&program, &mut Interpreter::default(), &mut &input[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut slicer).unwrap();
+        slicer.slice(&program)
+    }
+
+    #[test]
+    fn each_output_depends_on_the_writes_to_its_own_cell() {
+        // 0 `+`  1 `>`  2 `+`  3 `+`  4 `<`  5 `.`  6 `>`  7 `.`
+        let code: &str = "+>++<.>.";
+        assert_eq!(slice_of(code, b"", 0, false), Some(vec![0, 5]));
+        assert_eq!(slice_of(code, b"", 1, false), Some(vec![2, 3, 7]));
+        assert_eq!(slice_of(code, b"", 2, false), None);
+    }
+
+    #[test]
+    fn inputs_and_clears_cut_the_dependence_on_earlier_writes() {
+        // 0-2 `+++`  3 `,`  4 `.`  5 `+`  6 `[`  7 `-`  8 `]`  9 `+`  10 `.`
+        let code: &str = "+++,.+[-]+.";
+        assert_eq!(slice_of(code, b"a", 0, false), Some(vec![3, 4]));
+        // unfolded, the clear is a loop whose test reads the cell: everything before it counts
+        assert_eq!(slice_of(code, b"a", 1, false), Some(vec![3, 5, 6, 7, 8, 9, 10]));
+        // folded it is a `SetZero`: 0 `Add(3)`  1 `,`  2 `.`  3 `+`  4 `[-]`  5 `+`  6 `.`
+        assert_eq!(slice_of(code, b"a", 1, true), Some(vec![4, 5, 6]));
+    }
+
+    #[test]
+    fn a_loop_carries_its_dependences_from_one_iteration_to_the_next() {
+        // 0 `,`  1 `[`  2 `-`  3 `>`  4 `+`  5 `<`  6 `]`  7 `>`  8 `.`
+        // The last `+` to cell 1 reads the `+` of the iteration before, and runs because the `]`
+        // before it saw the counter still non-zero, which the `-` and the `,` decided. The moves are
+        // never part of a slice.
+        let code: &str = ",[->+<]>.";
+        assert_eq!(slice_of(code, b"\x03", 0, false), Some(vec![0, 1, 2, 4, 6, 8]));
+        // a single iteration: the `+` ran under the `[`, the `]` ended the loop and isn't needed
+        // for the value, but as a loop bracket it comes with its `[`
+        assert_eq!(slice_of(code, b"\x01", 0, false), Some(vec![0, 1, 4, 6, 8]));
+    }
+
+    #[test]
+    fn a_loop_the_output_does_not_depend_on_is_left_out() {
+        // 0 `+`  1 `+`  2 `[`  3 `-`  4 `]`  5 `>`  6 `+`  7 `.`
+        assert_eq!(slice_of("++[-]>+.", b"", 0, false), Some(vec![6, 7]));
+        // `:` writes as many bytes as the value has digits, each of them is the same instruction
+        let program: Program = compile(b"++++++++++:+.", &ParseOptions { extensions: true, ..ParseOptions::default() }, true).unwrap();
+        for (target, last) in [(0, 10), (1, 10), (2, 12)] {
+            let mut slicer: Slicer = Slicer::new(target);
+            execute_observed(&program, &mut Interpreter::default(), &mut &b""[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut slicer).unwrap();
+            assert_eq!(slicer.slice(&program).unwrap().last(), Some(&last), "byte {}", target);
+        }
+    }
+}
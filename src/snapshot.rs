@@ -0,0 +1,262 @@
+//! Saved interpreter state, so a run stopped part way (see [`crate::Program::halt_at`]) can be resumed later.
+//!
+//! The file is a few `key value` header lines followed by the raw tape, `program` is the instruction
+//! count of the program (including `Halt`) the snapshot belongs to:
+//!
+//! ```text
+//! brainfck snapshot 2
+//! program 129
+//! mode fixed
+//! max_cells 30000
+//! pointer 15001
+//! start_pointer 15000
+//! sandbox 15000:15100
+//! ...
+//! tape 30000
+//! <30000 bytes>
+//! ```
+
+use std::fmt;
+
+use crate::interpreter::TapeMode;
+
+const MAGIC: &str = "brainfck snapshot 2";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub tape_mode: TapeMode,
+    pub max_cells: usize,
+    pub tape: Vec<u8>,
+    pub pointer: usize,
+    pub start_pointer: usize,
+    pub sandbox: Option<std::ops::Range<usize>>, // `sandbox none` in the file without one
+    pub instruction_index: usize,
+    pub steps: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotError {
+    pub message: String
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid snapshot: {}", self.message)
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+fn error(message: String) -> SnapshotError {
+    SnapshotError { message }
+}
+
+fn mode_name(mode: TapeMode) -> &'static str {
+    match mode {
+        TapeMode::Fixed => "fixed",
+        TapeMode::Growable => "right",
+        TapeMode::Bidirectional => "bidirectional"
+    }
+}
+
+impl Snapshot {
+    /// Serializes the snapshot, `program_len` is recorded so it can't be resumed with another program.
+    pub fn to_bytes(&self, program_len: usize) -> Vec<u8> {
+        let header: String = format!(
+            "{}\nprogram {}\nmode {}\nmax_cells {}\npointer {}\nstart_pointer {}\nsandbox {}\ninstruction {}\nsteps {}\ninput_bytes {}\noutput_bytes {}\ntape {}\n",
+            MAGIC,
+            program_len,
+            mode_name(self.tape_mode),
+            self.max_cells,
+            self.pointer,
+            self.start_pointer,
+            self.sandbox.as_ref().map_or("none".to_string(), |region| format!("{}:{}", region.start, region.end)),
+            self.instruction_index,
+            self.steps,
+            self.input_bytes,
+            self.output_bytes,
+            self.tape.len()
+        );
+
+        let mut bytes: Vec<u8> = header.into_bytes();
+        bytes.extend_from_slice(&self.tape);
+        bytes
+    }
+
+    /// Reads a snapshot written by [`Snapshot::to_bytes`] for a program of `program_len` instructions.
+    pub fn from_bytes(bytes: &[u8], program_len: usize) -> Result<Self, SnapshotError> {
+        let mut header: Header = Header { rest: bytes };
+        match header.line()? {
+            line if line == MAGIC => {},
+            line if line.starts_with("brainfck snapshot ") => return Err(error(format!("`{}` is an older format, take the snapshot again", line))),
+            _ => return Err(error("not a brainfck snapshot".to_string()))
+        }
+
+        let recorded_len: u64 = header.number("program")?;
+        if recorded_len != program_len as u64 {
+            return Err(error(format!("taken for a program of {} instructions, this one has {}", recorded_len, program_len)));
+        }
+
+        let tape_mode: TapeMode = match header.field("mode")?.as_str() {
+            "fixed" => TapeMode::Fixed,
+            "right" => TapeMode::Growable,
+            "bidirectional" => TapeMode::Bidirectional,
+            other => return Err(error(format!("unknown tape mode `{}`", other)))
+        };
+        let mut snapshot: Snapshot = Snapshot {
+            tape_mode,
+            max_cells: header.number("max_cells")? as usize,
+            tape: vec![],
+            pointer: header.number("pointer")? as usize,
+            start_pointer: header.number("start_pointer")? as usize,
+            sandbox: header.region("sandbox")?,
+            instruction_index: header.number("instruction")? as usize,
+            steps: header.number("steps")?,
+            input_bytes: header.number("input_bytes")?,
+            output_bytes: header.number("output_bytes")?
+        };
+        let tape_len: usize = header.number("tape")? as usize;
+
+        if header.rest.len() != tape_len {
+            return Err(error(format!("tape of {} cells announced, {} bytes found", tape_len, header.rest.len())));
+        }
+        if snapshot.pointer >= tape_len || snapshot.start_pointer >= tape_len || tape_len > snapshot.max_cells.max(1) {
+            return Err(error("pointer or tape size out of range".to_string()));
+        }
+        if snapshot.sandbox.as_ref().is_some_and(|region| tape_mode != TapeMode::Fixed || region.is_empty() || region.end > tape_len || !region.contains(&snapshot.pointer)) {
+            return Err(error("sandbox region out of range".to_string()));
+        }
+        if snapshot.instruction_index > program_len {
+            return Err(error(format!("instruction {} is past the end of the program", snapshot.instruction_index)));
+        }
+
+        snapshot.tape = header.rest.to_vec();
+        Ok(snapshot)
+    }
+}
+
+/// Cursor over the `key value` lines in front of the tape.
+struct Header<'a> {
+    rest: &'a [u8]
+}
+
+impl Header<'_> {
+    fn line(&mut self) -> Result<String, SnapshotError> {
+        let end: usize = self.rest.iter().position(|&byte| byte == b'\n').ok_or_else(|| error("truncated header".to_string()))?;
+        let line: String = String::from_utf8_lossy(&self.rest[..end]).into_owned();
+        self.rest = &self.rest[end + 1..];
+        Ok(line)
+    }
+
+    fn field(&mut self, key: &str) -> Result<String, SnapshotError> {
+        let line: String = self.line()?;
+        match line.split_once(' ') {
+            Some((found, value)) if found == key => Ok(value.to_string()),
+            _ => Err(error(format!("expected `{}`, found `{}`", key, line)))
+        }
+    }
+
+    fn number(&mut self, key: &str) -> Result<u64, SnapshotError> {
+        let value: String = self.field(key)?;
+        value.parse().map_err(|_| error(format!("`{}` is not a number: `{}`", key, value)))
+    }
+
+    /// `LO:HI` or `none`.
+    fn region(&mut self, key: &str) -> Result<Option<std::ops::Range<usize>>, SnapshotError> {
+        let value: String = self.field(key)?;
+        if value == "none" {
+            return Ok(None);
+        }
+        let bounds: Option<(usize, usize)> = value.split_once(':').and_then(|(low, high)| Some((low.parse().ok()?, high.parse().ok()?)));
+        bounds.map(|(low, high)| Some(low..high)).ok_or_else(|| error(format!("`{}` is not a range: `{}`", key, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_code, ExecutionConfig, HaltReason, Interpreter};
+    use crate::parser::{compile, ParseOptions, Program};
+
+    const CODE: &str = ",[.>,]<[+.<]";
+
+    fn program() -> Program {
+        compile(CODE.as_bytes(), &ParseOptions::default(), true).unwrap()
+    }
+
+    #[test]
+    fn a_run_stopped_in_a_loop_resumes_to_the_uninterrupted_end() {
+        let input: &[u8] = b"abcd";
+        let mut whole: Interpreter = Interpreter::default();
+        let mut expected: Vec<u8> = vec![];
+        execute_code(&program(), &mut whole, &mut &input[..], &mut expected, &ExecutionConfig::default()).unwrap();
+
+        // the `>` inside the first loop, reached once per input byte
+        let mut stopped: Program = program();
+        stopped.halt_at(3);
+        let mut first: Interpreter = Interpreter::default();
+        let mut output: Vec<u8> = vec![];
+        let summary = execute_code(&stopped, &mut first, &mut &input[..], &mut output, &ExecutionConfig::default()).unwrap();
+        assert_eq!((summary.halt, first.instruction_index(), output.as_slice()), (HaltReason::Stopped, 3, &b"a"[..]));
+
+        let bytes: Vec<u8> = first.snapshot().to_bytes(program().len());
+        let mut resumed: Interpreter = Interpreter::restore(&Snapshot::from_bytes(&bytes, program().len()).unwrap());
+        let mut rest: &[u8] = &input[resumed.input_bytes() as usize..];
+        execute_code(&program(), &mut resumed, &mut rest, &mut output, &ExecutionConfig::default()).unwrap();
+
+        assert_eq!(output, expected);
+        assert_eq!((resumed.tape_checksum(), resumed.steps(), resumed.pointer()), (whole.tape_checksum(), whole.steps(), whole.pointer()));
+        assert_eq!((resumed.input_bytes(), resumed.output_bytes()), (whole.input_bytes(), whole.output_bytes()));
+    }
+
+    #[test]
+    fn growing_tapes_round_trip() {
+        let mut interpreter: Interpreter = Interpreter::bidirectional(64);
+        execute_code(&program(), &mut interpreter, &mut &b"xyz"[..], &mut vec![], &ExecutionConfig::default()).unwrap();
+        let snapshot: Snapshot = interpreter.snapshot();
+        assert_eq!(snapshot.tape.len(), 8, "grown to the left of the start cell");
+        assert_eq!(Snapshot::from_bytes(&snapshot.to_bytes(program().len()), program().len()), Ok(snapshot));
+    }
+
+    #[test]
+    fn a_resumed_run_stays_in_its_sandbox() {
+        let code: &[u8] = b"+>>>>+.";
+        let program: Program = compile(code, &ParseOptions::default(), true).unwrap();
+        let mut sandboxed: Interpreter = Interpreter::fixed(16);
+        assert!(sandboxed.set_sandbox(8..10));
+        let mut stopped: Program = program.clone();
+        stopped.halt_at(2);
+        execute_code(&stopped, &mut sandboxed, &mut &b""[..], &mut vec![], &ExecutionConfig::default()).unwrap();
+
+        let snapshot: Snapshot = Snapshot::from_bytes(&sandboxed.snapshot().to_bytes(program.len()), program.len()).unwrap();
+        assert_eq!(snapshot.sandbox, Some(8..10));
+        let mut resumed: Interpreter = Interpreter::restore(&snapshot);
+        let mut output: Vec<u8> = vec![];
+        let result = execute_code(&program, &mut resumed, &mut &b""[..], &mut output, &ExecutionConfig::default());
+        assert!(matches!(result, Err(crate::interpreter::RuntimeError::SandboxViolation)), "{:?}", result);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn damaged_or_foreign_snapshots_are_refused() {
+        let bytes: Vec<u8> = Interpreter::fixed(8).snapshot().to_bytes(5);
+        let message = |bytes: &[u8], program_len: usize| Snapshot::from_bytes(bytes, program_len).unwrap_err().message;
+
+        assert_eq!(message(&bytes, 6), "taken for a program of 5 instructions, this one has 6");
+        assert_eq!(message(&bytes[..bytes.len() - 1], 5), "tape of 8 cells announced, 7 bytes found");
+        assert_eq!(message(b"brainfck snapshot 2\nprog", 5), "truncated header");
+        assert_eq!(message(b"brainfck snapshot 1\nprogram 5\n", 5), "`brainfck snapshot 1` is an older format, take the snapshot again");
+        assert_eq!(message(b"some other file\n", 5), "not a brainfck snapshot");
+
+        let text: String = String::from_utf8_lossy(&bytes).replace("mode fixed", "mode sideways");
+        assert_eq!(message(text.as_bytes(), 5), "unknown tape mode `sideways`");
+        let text: String = String::from_utf8_lossy(&bytes).replace("instruction 0", "instruction 6");
+        assert_eq!(message(text.as_bytes(), 5), "instruction 6 is past the end of the program");
+        let text: String = String::from_utf8_lossy(&bytes).replace("sandbox none", "sandbox 2:9");
+        assert_eq!(message(text.as_bytes(), 5), "sandbox region out of range");
+        let text: String = String::from_utf8_lossy(&bytes).replace("sandbox none", "sandbox 2-9");
+        assert_eq!(message(text.as_bytes(), 5), "`sandbox` is not a range: `2-9`");
+    }
+}
@@ -0,0 +1,126 @@
+/// 1-based `(line, column)` of the byte at `position`, columns count bytes.
+pub fn line_col(source: &[u8], position: usize) -> (usize, usize) {
+    let before: &[u8] = &source[..position.min(source.len())];
+    let line: usize = before.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    let line_start: usize = before.iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+
+    (line, position - line_start + 1)
+}
+
+/// Byte position of the 1-based `line` and byte `column`, the inverse of [`line_col`]. `None` if the
+/// line doesn't exist or is shorter than `column`.
+pub fn position_at(source: &[u8], line: usize, column: usize) -> Option<usize> {
+    let line_start: usize = match line {
+        0 => return None,
+        1 => 0,
+        _ => source.iter().enumerate().filter(|&(_, &byte)| byte == b'\n').nth(line - 2)?.0 + 1
+    };
+    let line_len: usize = source[line_start..].iter().position(|&byte| byte == b'\n').unwrap_or(source.len() - line_start);
+
+    (1..=line_len).contains(&column).then_some(line_start + column - 1)
+}
+
+/// Longest stretch of a source line shown by [`highlight_position`], in terminal columns.
+const HIGHLIGHT_WIDTH: usize = 80;
+
+/// Terminal columns taken by `c`: 0 for combining marks, joiners and control characters, 2 for wide
+/// (CJK, Hangul, fullwidth, emoji) ones, 1 otherwise. Coarse, but keeps carets under the right character.
+fn display_width(c: char) -> usize {
+    match c as u32 {
+        0x00..=0x1f | 0x7f..=0x9f => 0,
+        0x0300..=0x036f | 0x0483..=0x0489 | 0x0591..=0x05bd | 0x0610..=0x061a | 0x064b..=0x065f => 0,
+        0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x200b..=0x200f | 0x20d0..=0x20ff | 0xfe00..=0xfe0f | 0xfe20..=0xfe2f => 0,
+        0xe0100..=0xe01ef => 0,
+        0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xa000..=0xa4cf => 2,
+        0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6 => 2,
+        0x1f300..=0x1f64f | 0x1f900..=0x1f9ff | 0x1fa70..=0x1faff | 0x20000..=0x3fffd => 2,
+        _ => 1
+    }
+}
+
+/// The source line containing `position` with a caret under it, long lines are cut down to a window
+/// around the caret. Columns are counted in display width (tabs shown as single spaces), the window
+/// never splits a character. A line which isn't valid UTF-8 is shown byte by byte instead, with `?`
+/// for every non-ASCII byte.
+pub fn highlight_position(source: &[u8], position: usize) -> String {
+    let position: usize = position.min(source.len());
+    let line_start: usize = source[..position].iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+    let line_end: usize = source[position..].iter().position(|&byte| byte == b'\n').map_or(source.len(), |newline| position + newline);
+
+    // (byte offset, text, width) of every character of the line
+    let characters: Vec<(usize, String, usize)> = match std::str::from_utf8(&source[line_start..line_end]) {
+        Ok(line) => line.char_indices().map(|(offset, c)| {
+            let c: char = if c == '\t' { ' ' } else { c };
+            let width: usize = display_width(c);
+            (line_start + offset, if width == 0 && c.is_control() { String::new() } else { c.to_string() }, width)
+        }).collect(),
+        Err(_) => (line_start..line_end).map(|offset| {
+            let byte: u8 = source[offset];
+            let shown: char = if byte == b'\t' { ' ' } else if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '?' };
+            (offset, shown.to_string(), 1)
+        }).collect()
+    };
+
+    // the character holding `position`, or the one past the end of the line
+    let caret: usize = characters.iter().rposition(|&(offset, _, _)| offset <= position).unwrap_or(0);
+    let caret: usize = if position == line_end { characters.len() } else { caret };
+
+    let mut window_start: usize = caret;
+    let mut before: usize = 0;
+    while window_start > 0 && before + characters[window_start - 1].2 <= HIGHLIGHT_WIDTH / 2 {
+        window_start -= 1;
+        before += characters[window_start].2;
+    }
+    let mut window_end: usize = window_start;
+    let mut width: usize = 0;
+    while window_end < characters.len() && width + characters[window_end].2 <= HIGHLIGHT_WIDTH {
+        width += characters[window_end].2;
+        window_end += 1;
+    }
+
+    let text: String = characters[window_start..window_end].iter().map(|(_, shown, _)| shown.as_str()).collect();
+    format!("{}\n{}^", text, " ".repeat(before))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The highlight of the first `marker` in `source`.
+    fn highlight(source: &str, marker: char) -> String {
+        highlight_position(source.as_bytes(), source.find(marker).unwrap())
+    }
+
+    #[test]
+    fn carets_count_display_columns() {
+        assert_eq!(highlight("ab+cd", '+'), "ab+cd\n  ^");
+        assert_eq!(highlight("\t+", '+'), " +\n ^");
+        assert_eq!(highlight("😀😀+ smile", '+'), "😀😀+ smile\n    ^");
+        assert_eq!(highlight("漢字 [-] 한글", '['), "漢字 [-] 한글\n     ^");
+        assert_eq!(highlight("e\u{301}\u{200d}+", '+'), "e\u{301}\u{200d}+\n ^", "combining marks and joiners take no column");
+    }
+
+    #[test]
+    fn positions_inside_a_character_or_past_the_line() {
+        assert_eq!(highlight_position("😀+".as_bytes(), 2), "😀+\n^");
+        assert_eq!(highlight_position(b"ab\ncd", 2), "ab\n  ^");
+        assert_eq!(highlight_position("漢\n+".as_bytes(), 4), "+\n^");
+    }
+
+    #[test]
+    fn long_lines_are_cut_between_characters() {
+        let source: String = format!("{}+{}", "漢".repeat(100), "😀".repeat(100));
+        let highlighted: String = highlight(&source, '+');
+        let (text, caret): (&str, &str) = highlighted.split_once('\n').unwrap();
+        assert_eq!(text, format!("{}+{}", "漢".repeat(20), "😀".repeat(19)));
+        assert_eq!(caret, format!("{}^", " ".repeat(40)));
+    }
+
+    #[test]
+    fn invalid_utf8_is_shown_byte_by_byte() {
+        let source: &[u8] = b"\xe6\xbc\xff +.";
+        assert_eq!(highlight_position(source, 4), "??? +.\n    ^");
+        assert_eq!(highlight_position(b"ok\n\xff[\n\xe6\xbc\xa2]", 4), "?[\n ^", "only the broken line falls back");
+        assert_eq!(highlight_position(b"ok\n\xff[\n\xe6\xbc\xa2]", 9), "\u{6f22}]\n  ^");
+    }
+}
@@ -0,0 +1,193 @@
+//! Ownership tags on tape cells, to catch two routines disagreeing about which scratch cell is whose.
+//!
+//! Tags come from annotations in the source comments:
+//!
+//! - `@tag:NAME` hands the cell the next instruction operates on (the cell under the pointer when it
+//!   runs) to NAME
+//! - `@owner:NAME` marks the following code, up to the next `@owner:` or `@end`, as NAME's
+//!
+//! A cell-changing instruction in code owned by one name that writes a cell tagged with another name is
+//! reported, and so is retagging a cell with a different name. Code outside any `@owner:` region may
+//! write anything. Names are letters, digits and `_`,
+//! so annotations never contain brainfuck instructions.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+use crate::parser::{Instruction, Program};
+
+/// A tag or owner name as written in the source, with its byte position there.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub name: String,
+    pub position: usize
+}
+
+/// A write to a cell tagged with another name than the one owning the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub step: u64,
+    pub cell: isize, // relative to the starting pointer
+    pub tag: Annotation, // where the cell got its tag
+    pub writer: Annotation, // the owner annotation (or the retagging site) of the conflicting code
+    pub write_position: usize // source position of the conflicting instruction
+}
+
+fn writes_cell(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::IncrementValue | Instruction::DecrementValue | Instruction::Add(_) | Instruction::SetZero | Instruction::InputValue
+    )
+}
+
+/// `(keyword, name, position)` of every `@keyword:NAME` and `@end` in `source`.
+fn annotations(source: &[u8]) -> Vec<(&str, String, usize)> {
+    let mut found: Vec<(&str, String, usize)> = vec![];
+
+    for (position, _) in source.iter().enumerate().filter(|&(_, &byte)| byte == b'@') {
+        let rest: &[u8] = &source[position + 1..];
+        if rest.starts_with(b"end") && !rest.get(3).is_some_and(|byte| byte.is_ascii_alphanumeric() || *byte == b'_') {
+            found.push(("end", String::new(), position));
+            continue;
+        }
+        for keyword in ["tag", "owner"] {
+            let Some(rest) = rest.strip_prefix(keyword.as_bytes()).and_then(|rest| rest.strip_prefix(b":")) else { continue };
+            let length: usize = rest.iter().take_while(|byte| byte.is_ascii_alphanumeric() || **byte == b'_').count();
+            if length > 0 {
+                found.push((keyword, String::from_utf8_lossy(&rest[..length]).into_owned(), position));
+            }
+        }
+    }
+
+    found
+}
+
+/// [`Observer`] tracking the tags of the cells and collecting [`Conflict`]s.
+#[derive(Debug, Clone, Default)]
+pub struct TaintTracker {
+    tag_sites: HashMap<usize, Annotation>, // instruction index -> tag it applies
+    owners: Vec<Option<usize>>, // owning region of every instruction, index into `regions`
+    regions: Vec<Annotation>,
+    positions: Vec<usize>,
+    tags: HashMap<isize, Annotation>, // only cells that were tagged
+    reported: HashSet<(usize, isize)>, // (instruction, cell) pairs already reported
+    conflicts: Vec<Conflict>
+}
+
+impl TaintTracker {
+    /// Reads the annotations of `source`, which `program` was parsed from.
+    pub fn new(program: &Program, source: &[u8]) -> Self {
+        let mut tracker: TaintTracker = TaintTracker {
+            owners: vec![None; program.len()],
+            positions: program.positions().to_vec(),
+            ..TaintTracker::default()
+        };
+
+        let mut region_starts: Vec<(usize, Option<usize>)> = vec![]; // (first instruction, region)
+        for (keyword, name, position) in annotations(source) {
+            let Some(index) = program.index_at(position) else { continue };
+            match keyword {
+                "tag" => { tracker.tag_sites.insert(index, Annotation { name, position }); },
+                "owner" => {
+                    tracker.regions.push(Annotation { name, position });
+                    region_starts.push((index, Some(tracker.regions.len() - 1)));
+                },
+                _ => { region_starts.push((index, None)); }
+            }
+        }
+
+        let mut current: Option<usize> = None;
+        let mut starts = region_starts.into_iter().peekable();
+        for (index, owner) in tracker.owners.iter_mut().enumerate() {
+            while let Some(&(_, region)) = starts.peek().filter(|&&(start, _)| start <= index) {
+                current = region;
+                starts.next();
+            }
+            *owner = current;
+        }
+
+        tracker
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    fn report(&mut self, step: u64, index: usize, cell: isize, tag: Annotation, writer: Annotation) {
+        if self.reported.insert((index, cell)) {
+            self.conflicts.push(Conflict { step, cell, tag, writer, write_position: self.positions[index] });
+        }
+    }
+}
+
+impl Observer for TaintTracker {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        let cell: isize = event.pointer as isize - interpreter.start_pointer() as isize;
+
+        if let Some(site) = self.tag_sites.get(&event.index).cloned() {
+            match self.tags.get(&cell) {
+                Some(existing) if existing.name != site.name => {
+                    let existing: Annotation = existing.clone();
+                    self.report(event.step, event.index, cell, existing, site.clone());
+                },
+                _ => {}
+            }
+            self.tags.insert(cell, site);
+            return;
+        }
+
+        if !writes_cell(event.instruction) {
+            return;
+        }
+        let Some(region) = self.owners[event.index] else { return };
+        if let Some(tag) = self.tags.get(&cell).filter(|tag| tag.name != self.regions[region].name) {
+            let (tag, writer): (Annotation, Annotation) = (tag.clone(), self.regions[region].clone());
+            self.report(event.step, event.index, cell, tag, writer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{execute_observed, ExecutionConfig};
+    use crate::parser::{compile, ParseOptions};
+
+    fn conflicts(source: &str) -> Vec<Conflict> {
+        let program: Program = compile(source.as_bytes(), &ParseOptions::default(), true).unwrap();
+        let mut tracker: TaintTracker = TaintTracker::new(&program, source.as_bytes());
+        execute_observed(&program, &mut Interpreter::default(), &mut &b""[..], &mut std::io::sink(), &ExecutionConfig::default(), &mut tracker).unwrap();
+        tracker.conflicts().to_vec()
+    }
+
+    fn at(source: &str, text: &str) -> usize {
+        source.find(text).unwrap()
+    }
+
+    #[test]
+    fn a_routine_writing_the_scratch_cell_of_another_is_flagged() {
+        // both routines take the cell right of the start as their scratch cell
+        let source: &str = "@owner:sum >@tag:sum ++< @end\n@owner:copy >+ <@end\n";
+        assert_eq!(conflicts(source), [Conflict {
+            step: 6, // `>++<>` ran before it
+            cell: 1,
+            tag: Annotation { name: "sum".to_string(), position: at(source, "@tag:sum") },
+            writer: Annotation { name: "copy".to_string(), position: at(source, "@owner:copy") },
+            write_position: at(source, ">+ <") + 1
+        }]);
+    }
+
+    #[test]
+    fn routines_keeping_to_their_own_cells_are_clean() {
+        let source: &str = "@owner:sum >@tag:sum ++< @end\n@owner:copy >>@tag:copy + << @end\n+ unowned code may write anything >+<\n";
+        assert_eq!(conflicts(source), []);
+    }
+
+    #[test]
+    fn retagging_a_cell_under_another_name_is_flagged_once() {
+        let source: &str = "@tag:a + @tag:b - +[@tag:c -]";
+        let found: Vec<Conflict> = conflicts(source);
+        let names: Vec<(&str, &str)> = found.iter().map(|conflict| (conflict.tag.name.as_str(), conflict.writer.name.as_str())).collect();
+        assert_eq!(names, [("a", "b"), ("b", "c")]);
+    }
+}
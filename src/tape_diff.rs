@@ -0,0 +1,72 @@
+//! Per-step tape changes of a run, written as text for animations, see `--record-diffs`.
+//!
+//! The first line is `tape-diff 1` (format version), then one line per change, in step order:
+//!
+//! ```text
+//! STEP CELL VALUE    the step wrote VALUE (0-255) to CELL
+//! STEP @CELL         the step moved the pointer to CELL
+//! ```
+//!
+//! `STEP` counts executed instructions from 1, a run resumed from a snapshot continues its count.
+//! Cells are numbered relative to the starting pointer (negative left of it). Replaying starts from
+//! an all-zero tape with the pointer on cell 0, lines with step 0 set up the state a resumed run
+//! started from. Steps that change nothing (a loop test, output, `,` reading the value already
+//! there) have no line.
+
+use std::io::{self, Write};
+
+use crate::interpreter::{Interpreter, Observer, StepEvent};
+
+/// [`Observer`] writing the tape changes of a run to `writer`. The first write error stops the
+/// recording, [`TapeDiffRecorder::finish`] returns it.
+#[derive(Debug)]
+pub struct TapeDiffRecorder<W: Write> {
+    writer: W,
+    pointer: isize, // relative to the starting pointer
+    error: Option<io::Error>
+}
+
+impl<W: Write> TapeDiffRecorder<W> {
+    /// Writes the header and the part of `interpreter`'s state that differs from a fresh tape.
+    pub fn new(mut writer: W, interpreter: &Interpreter) -> io::Result<Self> {
+        let start: isize = interpreter.start_pointer() as isize;
+        writeln!(writer, "tape-diff 1")?;
+        for (index, &value) in interpreter.tape().iter().enumerate().filter(|&(_, &value)| value != 0) {
+            writeln!(writer, "0 {} {}", index as isize - start, value)?;
+        }
+        let pointer: isize = interpreter.pointer() as isize - start;
+        if pointer != 0 {
+            writeln!(writer, "0 @{}", pointer)?;
+        }
+        Ok(Self { writer, pointer, error: None })
+    }
+
+    /// Flushes the recording and hands the writer back, or the first error writing it.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.writer.flush().map(|_| self.writer)
+        }
+    }
+
+    fn record(&mut self, event: &StepEvent, interpreter: &Interpreter) -> io::Result<()> {
+        // no instruction both moves and writes, and a move growing the tape to the left shifts the
+        // cells `event` saw
+        let pointer: isize = interpreter.pointer() as isize - interpreter.start_pointer() as isize;
+        if pointer != self.pointer {
+            writeln!(self.writer, "{} @{}", event.step, pointer)?;
+            self.pointer = pointer;
+        } else if event.after != event.before {
+            writeln!(self.writer, "{} {} {}", event.step, pointer, event.after)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Observer for TapeDiffRecorder<W> {
+    fn on_step(&mut self, event: &StepEvent, interpreter: &Interpreter) {
+        if self.error.is_none() {
+            self.error = self.record(event, interpreter).err();
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::interpreter::{execute_code, EofBehavior, ExecutionConfig, Interpreter, RuntimeError};
+use crate::parser::{parse_bytes, ParseError, Program};
+
+/// Resource bounds for [`run_untrusted`], every one of them is enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_source_bytes: usize,
+    pub max_instructions: usize, // counted after parsing, comments don't count
+    pub max_steps: u64,
+    pub max_output_bytes: u64,
+    pub max_tape_cells: usize
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_source_bytes: 64 * 1024,
+            max_instructions: 64 * 1024,
+            max_steps: 10_000_000,
+            max_output_bytes: 64 * 1024,
+            max_tape_cells: 30000
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    SourceSize,
+    Instructions,
+    Steps,
+    Output,
+    TapeCells
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::SourceSize => write!(f, "source size"),
+            Limit::Instructions => write!(f, "instruction count"),
+            Limit::Steps => write!(f, "step count"),
+            Limit::Output => write!(f, "output size"),
+            Limit::TapeCells => write!(f, "tape cells")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Completed { output: Vec<u8> },
+    LimitExceeded(Limit),
+    ParseError(ParseError),
+    RuntimeError(RuntimeError)
+}
+
+/// Parses and runs `source` against `input` without touching stdin/stdout.
+///
+/// Always returns: every step is metered, the tape grows only up to `limits.max_tape_cells` and
+/// reading past the end of `input` leaves the current cell unchanged instead of blocking.
+pub fn run_untrusted(source: &[u8], input: &[u8], limits: Limits) -> Outcome {
+    if source.len() > limits.max_source_bytes {
+        return Outcome::LimitExceeded(Limit::SourceSize);
+    }
+
+    let mut program: Program = match parse_bytes(source) {
+        Ok(instructions) => instructions,
+        Err(err) => return Outcome::ParseError(err)
+    };
+    if program.len() > limits.max_instructions {
+        return Outcome::LimitExceeded(Limit::Instructions);
+    }
+    program.push_halt();
+
+    if limits.max_tape_cells == 0 {
+        return Outcome::LimitExceeded(Limit::TapeCells);
+    }
+
+    let config: ExecutionConfig = ExecutionConfig {
+        max_steps: Some(limits.max_steps),
+        gas: None,
+        max_output: Some(limits.max_output_bytes),
+        eof: EofBehavior::Unchanged,
+        cell_modulus: None,
+        loop_cell: None,
+        max_pointer_range: None,
+        control: None,
+        break_on_output: None,
+        halt_on_output: None,
+        emit_sentinel: false,
+        input_batch: None,
+        step_loops: false
+    };
+    let mut interpreter: Interpreter = Interpreter::growable(limits.max_tape_cells);
+    let mut input: &[u8] = input;
+    let mut output: Vec<u8> = vec![];
+
+    match execute_code(&program, &mut interpreter, &mut input, &mut output, &config) {
+        Ok(_) => Outcome::Completed { output },
+        Err(RuntimeError::StepLimitExceeded) => Outcome::LimitExceeded(Limit::Steps),
+        Err(RuntimeError::OutputLimitExceeded) => Outcome::LimitExceeded(Limit::Output),
+        Err(RuntimeError::TapeLimitExceeded) => Outcome::LimitExceeded(Limit::TapeCells),
+        Err(err) => Outcome::RuntimeError(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Runs `source` with the default limits, which bound any program to well under the 30 seconds
+    /// allowed here, even in a debug build.
+    fn run(source: &str, input: &[u8]) -> Outcome {
+        let started: Instant = Instant::now();
+        let outcome: Outcome = run_untrusted(source.as_bytes(), input, Limits::default());
+        assert!(started.elapsed() < Duration::from_secs(30), "{} took {:?}", source.escape_debug(), started.elapsed());
+        outcome
+    }
+
+    #[test]
+    fn a_well_behaved_program_completes() {
+        let outcome: Outcome = run("++++++++[>++++++++<-]>+.,.", b"!");
+        assert!(matches!(outcome, Outcome::Completed { output } if output == b"A!"));
+    }
+
+    #[test]
+    fn runaway_programs_hit_their_limit() {
+        assert!(matches!(run("+[]", b""), Outcome::LimitExceeded(Limit::Steps)));
+        assert!(matches!(run("+[.]", b""), Outcome::LimitExceeded(Limit::Output)));
+        assert!(matches!(run("+[>+]", b""), Outcome::LimitExceeded(Limit::TapeCells)));
+        // reading past the end leaves a non-zero cell alone rather than waiting for more
+        assert!(matches!(run(",[.,]", b"x"), Outcome::LimitExceeded(Limit::Output | Limit::Steps)));
+    }
+
+    #[test]
+    fn deep_nesting_parses_and_runs() {
+        let depth: usize = 30_000;
+        let source: String = format!("+{}-{}.", "[".repeat(depth), "]".repeat(depth));
+        assert!(matches!(run(&source, b""), Outcome::Completed { output } if output == [0]));
+
+        let unbalanced: String = "[".repeat(depth);
+        assert!(matches!(run(&unbalanced, b""), Outcome::ParseError(_)));
+    }
+
+    #[test]
+    fn oversized_programs_are_refused_before_running() {
+        let limits: Limits = Limits::default();
+        let comments: String = format!("{}+", " ".repeat(limits.max_source_bytes));
+        assert!(matches!(run(&comments, b""), Outcome::LimitExceeded(Limit::SourceSize)));
+
+        let few: Limits = Limits { max_instructions: 10, ..limits };
+        assert!(matches!(run_untrusted(b"+++++ +++++ comments don't count", b"", few), Outcome::Completed { .. }));
+        assert!(matches!(run_untrusted(&[b'+'; 11], b"", few), Outcome::LimitExceeded(Limit::Instructions)));
+        let zero_tape: Limits = Limits { max_tape_cells: 0, ..limits };
+        assert!(matches!(run_untrusted(b"+", b"", zero_tape), Outcome::LimitExceeded(Limit::TapeCells)));
+    }
+
+    #[test]
+    fn errors_other_than_limits_are_runtime_errors() {
+        assert!(matches!(run("<", b""), Outcome::RuntimeError(RuntimeError::PointerUnderflow)));
+    }
+}
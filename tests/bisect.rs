@@ -0,0 +1,84 @@
+//! Bisecting with the binary: `--stop-at` ends a run part way, `--save-snapshot` keeps its state and
+//! `--start-snapshot` resumes it, which has to end like the run that was never interrupted.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{binary, program, scratch, stderr_line};
+
+/// Echoes its input, then prints it backwards with every byte incremented.
+const CODE: &[u8] = b",[\n.>,\n]<[+.<]\n";
+const INPUT: &[u8] = b"abcd";
+
+#[test]
+fn a_run_stopped_in_a_loop_resumes_to_the_same_end() {
+    let path: PathBuf = program("bisect_loop.bf", CODE);
+    let whole: Output = binary(&["--tape-checksum"], &path, INPUT);
+    assert_eq!(whole.stdout, b"abcdedcb");
+
+    for stop in [&["--stop-at", "2:2"][..], &["--stop-at-instruction", "3"][..]] {
+        let snapshot: PathBuf = scratch(&format!("bisect_loop_{}.snapshot", stop[0].trim_start_matches('-')));
+        let snapshot: &str = snapshot.to_str().unwrap();
+        let first: Output = binary(&[stop, &["--save-snapshot", snapshot, "--tape-checksum"]].concat(), &path, INPUT);
+        // the first arrival at the `>`, right after echoing the first byte
+        assert_eq!(first.stdout, b"a", "{:?}", stop);
+        assert_eq!(stderr_line(&first, "stopped at"), "stopped at 2:2 (instruction 3) after 3 steps", "{:?}", stop);
+
+        let rest: Output = binary(&["--start-snapshot", snapshot, "--tape-checksum"], &path, INPUT);
+        assert_eq!([&first.stdout[..], &rest.stdout].concat(), whole.stdout, "{:?}", stop);
+        assert_eq!(stderr_line(&rest, "tape checksum"), stderr_line(&whole, "tape checksum"), "{:?}", stop);
+    }
+}
+
+#[test]
+fn stopping_at_every_instruction_resumes_to_the_same_end() {
+    let path: PathBuf = program("bisect_every.bf", CODE);
+    let whole: Output = binary(&["--tape-checksum"], &path, INPUT);
+    let snapshot: PathBuf = scratch("bisect_every.snapshot");
+    let snapshot: &str = snapshot.to_str().unwrap();
+
+    for index in 0..12 {
+        let index: String = index.to_string();
+        let first: Output = binary(&["--stop-at-instruction", &index, "--save-snapshot", snapshot], &path, INPUT);
+        let rest: Output = binary(&["--start-snapshot", snapshot, "--tape-checksum"], &path, INPUT);
+        assert_eq!([&first.stdout[..], &rest.stdout].concat(), whole.stdout, "stopped at instruction {}", index);
+        assert_eq!(stderr_line(&rest, "tape checksum"), stderr_line(&whole, "tape checksum"), "stopped at instruction {}", index);
+    }
+}
+
+#[test]
+fn a_snapshot_of_another_program_is_refused() {
+    let path: PathBuf = program("bisect_mine.bf", CODE);
+    let snapshot: PathBuf = scratch("bisect_mine.snapshot");
+    let snapshot: &str = snapshot.to_str().unwrap();
+    binary(&["--stop-at-instruction", "3", "--save-snapshot", snapshot], &path, INPUT);
+
+    let other: PathBuf = program("bisect_other.bf", b",[.,]");
+    let refused: Output = binary(&["--start-snapshot", snapshot], &other, INPUT);
+    assert_eq!(refused.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&refused.stderr).contains("taken for a program of 13 instructions, this one has 6"), "{:?}", refused);
+}
+
+#[test]
+fn a_resumed_run_keeps_the_sandbox_it_was_started_with() {
+    let path: PathBuf = program("bisect_sandbox.bf", b"+>>>>+.");
+    let sandbox: &[&str] = &["--sandbox-region", "15000:15002"];
+    let whole: Output = binary(sandbox, &path, b"");
+    assert_eq!(String::from_utf8_lossy(&whole.stdout).trim_end(), "Pointer left the sandbox region");
+
+    let snapshot: PathBuf = scratch("bisect_sandbox.snapshot");
+    let snapshot: &str = snapshot.to_str().unwrap();
+    let first: Output = binary(&[sandbox, &["--stop-at-instruction", "1", "--save-snapshot", snapshot]].concat(), &path, b"");
+    assert!(first.status.success(), "{}", String::from_utf8_lossy(&first.stderr));
+    let rest: Output = binary(&["--start-snapshot", snapshot], &path, b"");
+    assert_eq!(rest.stdout, whole.stdout);
+
+    let other: Output = binary(&["--start-snapshot", snapshot, "--sandbox-region", "15000:15010"], &path, b"");
+    assert_eq!(other.status.code(), Some(2));
+    assert_eq!(
+        stderr_line(&other, "--sandbox-region"),
+        "--sandbox-region 15000:15010 differs from the snapshot, which was taken with --sandbox-region 15000:15002"
+    );
+}
@@ -0,0 +1,72 @@
+//! A reader of stdout going away early (`| head`): by default the run ends quietly with status 141,
+//! `--on-broken-pipe error` reports it and exits with 1, `ignore` runs on to the end.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{closed_after, program, BINARY};
+
+/// Prints `\n` forever.
+const ENDLESS: &[u8] = b"++++++++++[.]";
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn a_closed_pipe_ends_the_run_with_141() {
+    let path: PathBuf = program("pipe_endless.bf", ENDLESS);
+    for args in [&[][..], &["--on-broken-pipe", "exit"][..], &["--single-pass"][..]] {
+        let (head, output) = closed_after(&[&["--no-config"], args, &[path.to_str().unwrap()]].concat(), vec![], 5);
+        assert_eq!(head, b"\n\n\n\n\n");
+        assert_eq!(output.status.code(), Some(141), "{:?}", args);
+        assert_eq!(stderr(&output), "", "{:?}", args);
+        assert!(output.stdout.is_empty());
+    }
+}
+
+#[test]
+fn the_error_mode_reports_the_closed_pipe() {
+    let path: PathBuf = program("pipe_endless_error.bf", ENDLESS);
+    let (_, output) = closed_after(&["--no-config", "--on-broken-pipe", "error", path.to_str().unwrap()], vec![], 5);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "Output I/O error: Broken pipe (os error 32)\n");
+
+    let echo: PathBuf = program("pipe_echo.bf", b",[.,]");
+    let (head, output) = closed_after(&["--no-config", "--on-broken-pipe", "error", "--filter=byte", echo.to_str().unwrap()], vec![b'y'; 1 << 20], 3);
+    assert_eq!(head, b"yyy");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Broken pipe"), "{}", stderr(&output));
+}
+
+#[test]
+fn the_ignore_mode_runs_to_the_end() {
+    // 520200 bytes, far more than a pipe buffers
+    let path: PathBuf = program("pipe_finite.bf", b"-[>-[........-]<-]");
+    let (_, output) = closed_after(&["--no-config", "--on-broken-pipe", "ignore", "--tape-checksum", path.to_str().unwrap()], vec![], 3);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stderr(&output).starts_with("tape checksum "), "{}", stderr(&output));
+}
+
+#[test]
+fn pipe_and_multi_stop_at_the_closed_pipe_too() {
+    let path: PathBuf = program("pipe_endless_stages.bf", ENDLESS);
+    let echo: PathBuf = program("pipe_echo_stage.bf", b",[.,]");
+    let (head, output) = closed_after(&["pipe", "--no-config", path.to_str().unwrap(), echo.to_str().unwrap()], vec![], 3);
+    assert_eq!((&head[..], output.status.code()), (&b"\n\n\n"[..], Some(141)));
+
+    let (head, output) = closed_after(&["multi", "--no-config", path.to_str().unwrap()], vec![], 3);
+    assert_eq!((head[0], output.status.code()), (b'[', Some(141)), "output lines tagged with the file name");
+}
+
+#[cfg(unix)]
+#[test]
+fn piped_into_head() {
+    let path: PathBuf = program("pipe_head.bf", ENDLESS);
+    let script: String = format!("{{ '{}' --no-config '{}'; echo \"status $?\" >&2; }} | head -c 4", BINARY, path.display());
+    let output: Output = std::process::Command::new("sh").arg("-c").arg(script).env_remove("BRAINFCK_OPTS").output().unwrap();
+    assert_eq!(output.stdout, b"\n\n\n\n");
+    assert_eq!(stderr(&output), "status 141\n");
+}
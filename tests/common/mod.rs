@@ -0,0 +1,82 @@
+//! Running the binary from the integration tests.
+
+#![allow(dead_code)] // every test crate uses a part of it
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+pub const BINARY: &str = env!("CARGO_BIN_EXE_bf_interpreter");
+
+pub fn sample(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("samples").join(name)
+}
+
+/// A path for the test to write, `name` has to be unique among the tests.
+pub fn scratch(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join(name)
+}
+
+/// `code` in a file of its own.
+pub fn program(name: &str, code: &[u8]) -> PathBuf {
+    let path: PathBuf = scratch(name);
+    std::fs::write(&path, code).unwrap();
+    path
+}
+
+/// The binary run on `path` with `args` and no defaults from a config file or the environment.
+pub fn binary(args: &[&str], path: &Path, stdin: &[u8]) -> Output {
+    let mut child = Command::new(BINARY)
+        .arg("--no-config")
+        .args(args)
+        .arg(path)
+        .env_remove("BRAINFCK_OPTS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // a program that stops reading early closes the pipe, that's not for this test to judge
+    let _ = child.stdin.take().unwrap().write_all(stdin);
+    child.wait_with_output().unwrap()
+}
+
+/// The binary run on `path` with `args`, the defaults of the config file next to it and `BRAINFCK_OPTS`
+/// set to `options` (unset without).
+pub fn configured(args: &[&str], path: &Path, options: Option<&str>) -> Output {
+    let mut command = Command::new(BINARY);
+    command.args(args).arg(path).stdin(Stdio::null());
+    match options {
+        Some(options) => command.env("BRAINFCK_OPTS", options),
+        None => command.env_remove("BRAINFCK_OPTS")
+    };
+    command.output().unwrap()
+}
+
+/// The binary run with exactly `args` and fed `stdin`, its stdout closed again after reading the first
+/// `bytes` of it, as `| head -c BYTES` does. Returns those bytes and how the run ended.
+pub fn closed_after(args: &[&str], stdin: Vec<u8>, bytes: usize) -> (Vec<u8>, Output) {
+    let mut child = Command::new(BINARY)
+        .args(args)
+        .env_remove("BRAINFCK_OPTS")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut writer = child.stdin.take().unwrap();
+    let feeding = std::thread::spawn(move || { let _ = writer.write_all(&stdin); });
+
+    let mut head: Vec<u8> = vec![0; bytes];
+    std::io::Read::read_exact(child.stdout.as_mut().unwrap(), &mut head).unwrap();
+    drop(child.stdout.take());
+    let output: Output = child.wait_with_output().unwrap();
+    feeding.join().unwrap();
+    (head, output)
+}
+
+/// The first line of the binary's stderr starting with `prefix`.
+pub fn stderr_line(output: &Output, prefix: &str) -> String {
+    let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+    stderr.lines().find(|line| line.starts_with(prefix)).unwrap_or_else(|| panic!("no `{}` in {:?}", prefix, stderr)).to_string()
+}
@@ -0,0 +1,102 @@
+//! Defaults from `.brainfck.toml` and `BRAINFCK_OPTS`: the file is overridden by the variable, both by
+//! the command line, and whatever either can't use is reported and skipped.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::{configured, sample, scratch};
+
+/// Hello world in `<name>/programs`, with `config` as the `.brainfck.toml` of `<name>`.
+fn project(name: &str, config: &str) -> PathBuf {
+    let root: PathBuf = scratch(name);
+    std::fs::create_dir_all(root.join("programs")).unwrap();
+    std::fs::write(root.join(".brainfck.toml"), config).unwrap();
+    std::fs::copy(sample("helloworld.bf"), root.join("programs").join("hello.bf")).unwrap();
+    root.join("programs").join("hello.bf")
+}
+
+fn config_file(program: &Path) -> String {
+    program.parent().unwrap().parent().unwrap().canonicalize().unwrap().join(".brainfck.toml").display().to_string()
+}
+
+/// `--print-config` as (setting, source) pairs.
+fn printed(output: &Output) -> Vec<(String, String)> {
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    String::from_utf8_lossy(&output.stdout).lines().map(|line| {
+        let (setting, source) = line.rsplit_once(" # ").unwrap();
+        (setting.trim_end().to_string(), source.to_string())
+    }).collect()
+}
+
+fn warnings(output: &Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stderr).lines().filter_map(|line| line.strip_prefix("warning: ")).map(str::to_string).collect()
+}
+
+#[test]
+fn the_command_line_beats_the_environment_beats_the_file() {
+    let program: PathBuf = project("config_precedence", "max_steps = 100\nsingle_pass = true\ntape_size = 500 # cells\n");
+    let file: String = config_file(&program);
+    let settings: Vec<(String, String)> = printed(&configured(&["--print-config", "--tape-size", "700"], &program, Some("--max-steps=200 --count-output")));
+    assert_eq!(settings, [
+        ("single-pass".to_string(), file.clone()),
+        ("tape-size = 700".to_string(), "command line".to_string()),
+        ("max-steps = 200".to_string(), "BRAINFCK_OPTS".to_string()),
+        ("count-output".to_string(), "BRAINFCK_OPTS".to_string())
+    ]);
+
+    // 100 steps are too few for hello world, 100000 aren't
+    let limited: Output = configured(&[], &program, None);
+    assert!(String::from_utf8_lossy(&limited.stdout).ends_with("Step limit exceeded\n"), "{:?}", limited);
+    let raised: Output = configured(&[], &program, Some("--max-steps 100000"));
+    assert_eq!(raised.stdout, b"Hello World!\n");
+    let lowered: Output = configured(&["--max-steps", "100"], &program, Some("--max-steps 100000"));
+    assert_eq!(lowered.stdout, limited.stdout);
+    let ignored: Output = configured(&["--no-config"], &program, Some("--max-steps 100"));
+    assert_eq!(ignored.stdout, b"Hello World!\n");
+}
+
+#[test]
+fn malformed_lines_are_skipped_with_a_warning() {
+    let program: PathBuf = project("config_malformed", "single_pass\n= 3\ntape_size = big\nconstprop = maybe\n\n  # only a comment\nlayout = \"cells # layout\"\nmax_steps = 5 # too few\n");
+    let file: String = config_file(&program);
+    let output: Output = configured(&["--print-config"], &program, None);
+    assert_eq!(warnings(&output), [
+        format!("{}, line 1: expected `key = value`, ignored", file),
+        format!("{}, line 2: unknown key ``, ignored", file),
+        format!("{}, line 3: `tape_size` expects a number or a quoted string, ignored", file),
+        format!("{}, line 4: `constprop` expects true or false, ignored", file)
+    ]);
+    let layout: String = Path::new(&file).parent().unwrap().join("cells # layout").display().to_string();
+    assert_eq!(printed(&output), [
+        ("max-steps = 5".to_string(), file.clone()),
+        (format!("layout = {}", layout), file.clone())
+    ], "a quoted `#` is no comment, a relative layout is next to the file");
+
+    let invalid: PathBuf = project("config_invalid", "tape_size = \"lots\"\n");
+    let refused: Output = configured(&[], &invalid, None);
+    assert_eq!(refused.status.code(), Some(2));
+    let stderr: String = String::from_utf8_lossy(&refused.stderr).into_owned();
+    assert!(stderr.starts_with(&format!("Invalid value `lots` for `--tape-size` (with the defaults from {})", config_file(&invalid))), "{}", stderr);
+}
+
+#[test]
+fn unknown_keys_and_flags_are_skipped_with_a_warning() {
+    let program: PathBuf = project("config_unknown", "cell_width = 16\ncolor = true\nsingle_pass = false\nconstprop = true\n");
+    let file: String = config_file(&program);
+    let output: Output = configured(&[], &program, Some("--stop-at 1:1 --tape-checksum stray"));
+    assert_eq!(warnings(&output), [
+        format!("{}, line 1: unknown key `cell_width`, ignored", file),
+        format!("{}, line 2: unknown key `color`, ignored", file),
+        "BRAINFCK_OPTS: `--stop-at` can't be given a default, ignored".to_string(),
+        "BRAINFCK_OPTS: `1:1` can't be given a default, ignored".to_string(),
+        "BRAINFCK_OPTS: `stray` can't be given a default, ignored".to_string()
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"Hello World!\n", "the run went on without them");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("tape checksum"), "the known flag was still taken");
+
+    let settings: Vec<(String, String)> = printed(&configured(&["--print-config"], &program, None));
+    assert_eq!(settings, [("constprop".to_string(), file)], "`false` sets nothing");
+}
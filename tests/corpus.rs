@@ -0,0 +1,88 @@
+//! Every built-in example runs to the end within a step limit and prints what it always printed, with
+//! any of the optimization passes or none.
+
+use brainfck::corpus::{self, Example};
+use brainfck::passes::{self, Pass};
+use brainfck::{execute_code, ExecutionConfig, HaltReason, InputSpec, Interpreter, OutputSpec, ParseOptions, Program, Run, RunReport, Source};
+
+/// Steps any example may take, the slowest (`mandelbrot`) needs about 85 million.
+const MAX_STEPS: u64 = 100_000_000;
+
+/// FNV-1a hash and length of the output expected from each example, with its sample input.
+const CHECKSUMS: &[(&str, u64, usize)] = &[
+    ("hello", 0x9b8d74ce8e354928, 13),
+    ("rot13", 0x6089e23b5f9294a0, 14),
+    ("bubblesort", 0x92c980bfea5ad276, 10),
+    ("squares", 0x979546edd2a47229, 460),
+    ("bitwidth", 0x4fe3f16f029c6f00, 17),
+    ("mandelbrot", 0x1eea91ab2e667d03, 198)
+];
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash: u64, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+fn run(example: &Example) -> Vec<u8> {
+    run_optimized(example, false, false)
+}
+
+/// [`run`] with `--single-pass` and `--constprop` as given.
+fn run_optimized(example: &Example, single_pass: bool, constprop: bool) -> Vec<u8> {
+    let report: RunReport = Run::new(Source::Text(example.source.to_string()))
+        .single_pass(single_pass)
+        .constprop(constprop)
+        .input(InputSpec::Bytes(example.input.as_bytes().to_vec()))
+        .output(OutputSpec::Capture)
+        .max_steps(Some(MAX_STEPS))
+        .execute();
+    assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)), "{} ended with {:?}", example.name, report.result);
+    report.output.unwrap()
+}
+
+#[test]
+fn every_example_has_a_checksum() {
+    let names: Vec<&str> = corpus::all().iter().map(|example| example.name).collect();
+    let checked: Vec<&str> = CHECKSUMS.iter().map(|&(name, _, _)| name).collect();
+    assert_eq!(names, checked);
+}
+
+#[test]
+fn examples_print_their_known_output() {
+    for &(name, checksum, length) in CHECKSUMS {
+        let output: Vec<u8> = run(corpus::find(name).unwrap());
+        assert_eq!((fnv1a(&output), output.len()), (checksum, length), "{}: {:?}", name, String::from_utf8_lossy(&output));
+    }
+}
+
+#[test]
+fn short_examples_print_what_they_say() {
+    assert_eq!(run(corpus::find("hello").unwrap()), b"Hello World!\n");
+    assert_eq!(run(corpus::find("rot13").unwrap()), b"Uryyb, Jbeyq!\n");
+    assert_eq!(run(corpus::find("bubblesort").unwrap()), b"\nabcfiknru");
+
+    let mandelbrot: Vec<u8> = run(corpus::find("mandelbrot").unwrap());
+    let rows: Vec<&[u8]> = mandelbrot.split(|&byte| byte == b'\n').filter(|row| !row.is_empty()).collect();
+    assert_eq!(rows.len(), 9);
+    assert!(rows.iter().all(|row| row.len() == 21));
+    assert_eq!(rows[4], b".##################%+", "the real axis runs through the set");
+}
+
+#[test]
+fn the_flags_build_what_the_pass_pipeline_builds() {
+    for example in corpus::all() {
+        let expected: Vec<u8> = run(example);
+        for (single_pass, constprop) in [(true, false), (false, true), (true, true)] {
+            let passes: Vec<Pass> = passes::PIPELINE.into_iter().filter(|&pass| if pass == Pass::Fold { single_pass } else { constprop }).collect();
+            let program: Program = passes::build(example.source.as_bytes(), &ParseOptions::default(), &passes).unwrap();
+            let mut output: Vec<u8> = vec![];
+            let config: ExecutionConfig = ExecutionConfig { max_steps: Some(MAX_STEPS), ..ExecutionConfig::default() };
+            let halt: Result<HaltReason, String> = execute_code(&program, &mut Interpreter::default(), &mut example.input.as_bytes(), &mut output, &config)
+                .map(|summary| summary.halt)
+                .map_err(|err| err.to_string());
+
+            assert_eq!(halt, Ok(HaltReason::EndOfProgram), "{} built with {:?}", example.name, passes);
+            assert_eq!(output, expected, "{} built with {:?}", example.name, passes);
+            assert_eq!(run_optimized(example, single_pass, constprop), expected, "{} with {:?}", example.name, passes);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Daniel Cristofani's dbfi under the conventions `--compat=dbfi` presets: a tape growing to the
+//! right, 8-bit wrapping cells and `,` leaving the cell alone at EOF, the program read from the input
+//! up to a `!`.
+
+use brainfck::{HaltReason, InputSpec, OutputSpec, Run, RunReport, Source, TapeMode};
+
+const DBFI: &str = include_str!("../samples/dbfi.bf");
+const HELLO: &str = include_str!("../samples/helloworld.bf");
+
+/// dbfi interpreting `input` (a program, `!` and that program's input), with at most `max_steps`.
+fn dbfi(input: Vec<u8>, max_steps: u64) -> RunReport {
+    Run::new(Source::Text(DBFI.to_string()))
+        .single_pass(true)
+        .tape_mode(TapeMode::Growable)
+        .max_steps(Some(max_steps))
+        .input(InputSpec::Bytes(input))
+        .output(OutputSpec::Capture)
+        .execute()
+}
+
+#[test]
+fn dbfi_runs_hello_world() {
+    let report: RunReport = dbfi(format!("{}!", HELLO.replace('!', "")).into_bytes(), 100_000_000);
+    assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)), "{:?}", report.result);
+    assert_eq!(report.output.unwrap(), b"Hello World!\n");
+}
+
+#[test]
+fn dbfi_passes_the_input_after_the_bang_on() {
+    let report: RunReport = dbfi(b",.>,.>,.!hi".to_vec(), 10_000_000);
+    assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)), "{:?}", report.result);
+    assert_eq!(report.output.unwrap(), b"hi\0", "the third `,` at EOF leaves its fresh cell at 0");
+}
+
+/// The classic nested test of `samples/dbfi_nested.spec`, about 14 billion steps: half a minute in a
+/// release build, so only with `cargo test --release -- --ignored`.
+#[test]
+#[ignore]
+fn dbfi_runs_itself_running_hello_world() {
+    let report: RunReport = dbfi(include_bytes!("../samples/dbfi_nested.in").to_vec(), 50_000_000_000);
+    assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)), "{:?}", report.result);
+    assert_eq!(report.output.unwrap(), b"Hello World!\n");
+}
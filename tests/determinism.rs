@@ -0,0 +1,22 @@
+//! `--verify-determinism` compares repeated runs, so it needs at least two of them.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{binary, program};
+
+#[test]
+fn fewer_than_two_runs_are_refused() {
+    let path: PathBuf = program("determinism_runs.bf", b",.,.");
+    for runs in ["0", "1"] {
+        let refused: Output = binary(&["--verify-determinism", runs, "--input-string", "ab"], &path, b"");
+        assert_eq!(refused.status.code(), Some(2), "{}", runs);
+        assert!(String::from_utf8_lossy(&refused.stderr).starts_with("`--verify-determinism` has to be at least 2, a single run has nothing to compare with\n"), "{}", runs);
+        assert!(refused.stdout.is_empty(), "{}", runs);
+    }
+
+    let compared: Output = binary(&["--verify-determinism", "2", "--input-string", "ab"], &path, b"");
+    assert_eq!((compared.status.code(), String::from_utf8_lossy(&compared.stdout).as_ref()), (Some(0), "2 run(s), all identical\n"));
+}
@@ -0,0 +1,173 @@
+//! The binary and `Run` agree: every scenario runs a program once with command-line flags and once
+//! with the facade's setters of the same name and compares what the two report.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use brainfck::{Compat, FilterMode, Gas, GasCosts, HaltReason, InputSpec, OutputSpec, Run, RunReport, Source, TapeMode};
+use common::{binary, program, sample, stderr_line};
+
+fn facade(path: &Path) -> Run {
+    Run::new(Source::Path(path.to_path_buf())).output(OutputSpec::Capture)
+}
+
+/// What the binary prints on stdout for the run of `report`: the output, then the error if any.
+fn stdout_of(report: &RunReport) -> Vec<u8> {
+    let mut stdout: Vec<u8> = report.output.clone().unwrap();
+    if let Err(err) = &report.result {
+        stdout.extend_from_slice(format!("{}\n", err).as_bytes());
+    }
+    stdout
+}
+
+#[test]
+fn plain_run() {
+    let path: PathBuf = sample("helloworld.bf");
+    let report: RunReport = facade(&path).execute();
+    let output: Output = binary(&[], &path, b"");
+    assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)));
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn folded_run_of_an_input_string() {
+    let path: PathBuf = sample("rot13.bf");
+    let report: RunReport = facade(&path).single_pass(true).constprop(true).input(InputSpec::Bytes(b"Hello, World!\n".to_vec())).execute();
+    let output: Output = binary(&["--single-pass", "--constprop", "--input-string", "Hello, World!\n"], &path, b"");
+    assert_eq!(report.output.as_deref(), Some(&b"Uryyb, Jbeyq!\n"[..]));
+    assert_eq!(output.stdout, stdout_of(&report));
+}
+
+#[test]
+fn stdin_is_the_input_of_both() {
+    let path: PathBuf = program("facade_echo.bf", b",[.[-],]");
+    let report: RunReport = facade(&path).input(InputSpec::Bytes(b"piped".to_vec())).execute();
+    let output: Output = binary(&[], &path, b"piped");
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(report.input_bytes, 5);
+}
+
+#[test]
+fn limits_end_both_runs_with_the_same_error() {
+    let path: PathBuf = sample("helloworld.bf");
+    let scenarios: [(&[&str], Run); 3] = [
+        (&["--max-steps", "100"], facade(&path).max_steps(Some(100))),
+        (&["--max-output", "5"], facade(&path).max_output(Some(5))),
+        (&["--tape-size", "2"], facade(&path).tape_size(Some(2)))
+    ];
+    for (args, run) in scenarios {
+        let report: RunReport = run.execute();
+        let output: Output = binary(args, &path, b"");
+        assert!(report.result.is_err(), "{:?}", args);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&stdout_of(&report)), "{:?}", args);
+    }
+}
+
+#[test]
+fn gas_used_is_what_the_binary_reports() {
+    let path: PathBuf = sample("helloworld.bf");
+    let costs: GasCosts = GasCosts { output: 3, ..GasCosts::default() };
+    let report: RunReport = facade(&path).gas(Some(Gas { limit: 400, costs })).execute();
+    let output: Output = binary(&["--gas-limit", "400", "--gas-cost", "output=3"], &path, b"");
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(stderr_line(&output, "gas used"), format!("gas used {} of 400, {} left", report.gas_used, 400 - report.gas_used));
+}
+
+#[test]
+fn compat_splits_the_source_at_the_bang() {
+    let path: PathBuf = program("facade_bang.bf", b",.>,.>,.!hi");
+    for (flag, compat) in [("--compat=dbfi", Compat::Dbfi), ("--compat=bff", Compat::Bff)] {
+        let report: RunReport = facade(&path).compat(Some(compat)).execute();
+        let output: Output = binary(&[flag, "--tape-checksum"], &path, b"");
+        assert_eq!(report.output.as_deref(), Some(&b"hi\0"[..]), "{}", flag);
+        assert_eq!(output.stdout, stdout_of(&report), "{}", flag);
+        assert_eq!(stderr_line(&output, "tape checksum"), format!("tape checksum {}", report.interpreter.tape_checksum()), "{}", flag);
+    }
+}
+
+#[test]
+fn dynamic_tape_ends_with_the_same_tape() {
+    let path: PathBuf = program("facade_left.bf", b"<<+>>>++<-");
+    let report: RunReport = facade(&path).tape_mode(TapeMode::Bidirectional).execute();
+    let output: Output = binary(&["--dynamic-tape=bidirectional", "--tape-checksum", "--pointer-range"], &path, b"");
+    let (lowest, highest) = report.interpreter.pointer_range();
+    assert_eq!(stderr_line(&output, "tape checksum"), format!("tape checksum {}", report.interpreter.tape_checksum()));
+    assert_eq!(stderr_line(&output, "pointer range"), format!("pointer range {}..={} ({} cells)", lowest, highest, highest - lowest + 1));
+}
+
+#[test]
+fn filter_runs_once_per_byte() {
+    let path: PathBuf = program("facade_succ.bf", b",+.");
+    let report: RunReport = facade(&path).filter(Some(FilterMode::Byte)).input(InputSpec::Bytes(b"HAL".to_vec())).execute();
+    let output: Output = binary(&["--filter=byte"], &path, b"HAL");
+    assert_eq!(report.output.as_deref(), Some(&b"IBM"[..]));
+    assert_eq!(output.stdout, stdout_of(&report));
+
+    let path: PathBuf = program("facade_stuck.bf", b",[+]");
+    let report: RunReport = facade(&path).filter(Some(FilterMode::Byte)).max_steps(Some(100)).input(InputSpec::Bytes(b"\0x".to_vec())).execute();
+    let output: Output = binary(&["--filter=byte", "--max-steps", "100"], &path, b"\0x");
+    let err: String = report.result.unwrap_err().to_string();
+    assert_eq!(err, "input byte 1: Step limit exceeded");
+    assert_eq!(String::from_utf8_lossy(&output.stderr).trim_end(), err);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn expect_matches_or_fails_both() {
+    let path: PathBuf = sample("helloworld.bf");
+    for (expected, matched, status) in [("Hello World!\n", true, 0), ("Hello", false, 1)] {
+        let report: RunReport = facade(&path).output(OutputSpec::Discard).expect(Some(expected.as_bytes().to_vec())).execute();
+        let output: Output = binary(&["--expect", expected], &path, b"");
+        assert_eq!(report.matched, Some(matched), "{:?}", expected);
+        assert_eq!(output.status.code(), Some(status), "{:?}", expected);
+        assert_eq!(output.stdout, report.output.unwrap(), "{:?}", expected);
+    }
+}
+
+#[test]
+fn random_input_reads_the_same_bytes() {
+    let path: PathBuf = program("facade_random.bf", b",[.,]");
+    let report: RunReport = facade(&path).input(InputSpec::Random(7)).max_steps(Some(10_000)).execute();
+    let output: Output = binary(&["--random-input", "7", "--max-steps", "10000"], &path, b"");
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(stderr_line(&output, "random input"), format!("random input seed 7, {} bytes read", report.input_bytes));
+}
+
+#[test]
+fn sentinel_halts_both() {
+    let path: PathBuf = sample("helloworld.bf");
+    let report: RunReport = facade(&path).halt_on_output(Some(b'o'), true).execute();
+    let output: Output = binary(&["--halt-on-output", "o", "--emit-sentinel", "--sentinel-exit-code", "3"], &path, b"");
+    assert!(matches!(report.result, Ok(HaltReason::Sentinel)));
+    assert_eq!(report.output.as_deref(), Some(&b"Hello"[..]));
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(stderr_line(&output, "halted by sentinel"), format!("halted by sentinel after {} steps", report.steps));
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn sentinel_suppressed_or_never_output() {
+    let path: PathBuf = sample("helloworld.bf");
+    let report: RunReport = facade(&path).halt_on_output(Some(b'o'), false).execute();
+    let output: Output = binary(&["--halt-on-output", "o", "--sentinel-exit-code", "3"], &path, b"");
+    assert!(matches!(report.result, Ok(HaltReason::Sentinel)));
+    assert_eq!(report.output.as_deref(), Some(&b"Hell"[..]));
+    assert_eq!(output.stdout, stdout_of(&report));
+    assert_eq!(output.status.code(), Some(3));
+
+    // `?` never appears: the run ends as it would without a sentinel, and so does the exit status
+    let whole: RunReport = facade(&path).execute();
+    for emit in [false, true] {
+        let report: RunReport = facade(&path).halt_on_output(Some(b'?'), emit).execute();
+        let flags: &[&str] = if emit { &["--halt-on-output", "?", "--emit-sentinel", "--sentinel-exit-code", "3"] } else { &["--halt-on-output", "?", "--sentinel-exit-code", "3"] };
+        let output: Output = binary(flags, &path, b"");
+        assert!(matches!(report.result, Ok(HaltReason::EndOfProgram)));
+        assert_eq!((report.output, report.steps), (whole.output.clone(), whole.steps));
+        assert_eq!(output.stdout, stdout_of(&whole));
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("sentinel"));
+        assert_eq!(output.status.code(), Some(0));
+    }
+}
@@ -0,0 +1,53 @@
+//! `golf` with the binary: its rewrites assume plain brainfuck, so options changing what the source
+//! means are refused rather than suggesting rewrites that change the program.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+use common::{configured, program, stderr_line};
+
+/// `golf` with `args` on `path`, the command has to come before `--no-config`.
+fn golf(args: &[&str], path: &Path) -> Output {
+    configured(&[&["golf", "--no-config"], args].concat(), path, None)
+}
+
+const REFUSED: &str = "`golf` rewrites plain brainfuck on 256-value cells, it can't be used with `--alt-brackets`, `--loop-cell` or `--cell-modulus`";
+
+#[test]
+fn plain_sources_get_their_suggestions() {
+    let path: PathBuf = program("golf_plain.bf", b"++[-]>[-].");
+    let output: Output = golf(&[], &path);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "1:3: \"[-]\" -> \"--\" saves 1 byte(s) (clear then set)\n".to_string()
+            + "1:7: \"[-]\" -> \"\" saves 3 byte(s) (redundant clear)\n"
+            + "2 suggestion(s), 4 byte(s) total\n"
+    );
+}
+
+#[test]
+fn options_changing_what_the_source_means_are_refused() {
+    let cases: [(&str, &[u8], &[&str]); 3] = [
+        // `(`/`)` is the loop, so `[-]` is two decrements of a cell holding 2
+        ("golf_alt.bf", b"++(>+<-)>[-].", &["--alt-brackets"]),
+        // the loops test cell 1, so `[-]` runs once and the cell ends at 255 rather than 0
+        ("golf_loop_cell.bf", b">+<[-]+.", &["--loop-cell", "1"]),
+        ("golf_modulus.bf", b"++++++++++++++++++++++++++++++++++++++++.", &["--cell-modulus", "7"])
+    ];
+    for (name, code, flags) in cases {
+        let path: PathBuf = program(name, code);
+        for apply in [&[][..], &["--apply"][..]] {
+            let output: Output = golf(&[apply, flags].concat(), &path);
+            assert_eq!(output.status.code(), Some(2), "{:?}", flags);
+            assert_eq!(stderr_line(&output, "`golf`"), REFUSED, "{:?}", flags);
+            assert!(output.stdout.is_empty(), "{:?}", flags);
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), code);
+    }
+
+    let path: PathBuf = program("golf_modulus_256.bf", b"[-]+.");
+    assert!(golf(&["--cell-modulus", "256"], &path).status.success());
+}
@@ -0,0 +1,33 @@
+//! `--layout` with the binary: the fields and the cells of `--dump-tape` are annotated with their names.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{binary, program};
+
+#[test]
+fn dumped_cells_are_annotated_with_their_fields() {
+    let layout: PathBuf = program("layout_fields.toml", b"counter = 0\nbuffer = [1, 3]\n");
+    let path: PathBuf = program("layout_fields.bf", b"+>++>+++");
+    let output: Output = binary(&["--layout", layout.to_str().unwrap(), "--dump-tape"], &path, b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "tape (pointer at 15002):\n".to_string()
+            + "  counter = 1 (cells 15000..15001)\n"
+            + "  buffer = [2, 3] (cells 15001..15003)\n"
+            + "  [ 15000]   1 counter\n"
+            + "  [ 15001]   2 buffer[0]\n"
+            + "  [ 15002]   3 buffer[1]\n"
+    );
+}
+
+#[test]
+fn a_range_too_wide_to_dump_is_refused() {
+    let layout: PathBuf = program("layout_huge.toml", b"huge = [0, 4000000000]\n");
+    let path: PathBuf = program("layout_huge.bf", b"+");
+    let output: Output = binary(&["--layout", layout.to_str().unwrap(), "--dump-tape"], &path, b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "layout line 1: range of `huge` is 4000000000 cells wide, at most 65536\n");
+}
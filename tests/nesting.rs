@@ -0,0 +1,68 @@
+//! A program nesting its loops half a million deep goes through the parser, every pass and analysis
+//! and the interpreter without overflowing the stack of a test thread, each walks the loops with an
+//! explicit stack of its own.
+
+use brainfck::{analysis, asm, constprop, golf, ir, lint, passes, slice, taint};
+use brainfck::{compile, execute_code, execute_observed, ExecutionConfig, HaltReason, Interpreter, ParseError, ParseOptions, Program, RunSummary};
+
+const DEPTH: usize = 500_000;
+
+/// Enters every loop of the nest, clears the cell in the innermost one and leaves them all, then
+/// prints a 1.
+fn nested() -> String {
+    format!("+{}-{}+.", "[".repeat(DEPTH), "]".repeat(DEPTH))
+}
+
+fn program(options: &ParseOptions) -> Program {
+    compile(nested().as_bytes(), options, true).unwrap()
+}
+
+#[test]
+fn nested_loops_parse_and_run_in_every_build() {
+    let code: String = nested();
+    for passes in [&[][..], &passes::PIPELINE[..1], &passes::PIPELINE[..]] {
+        let program: Program = passes::build(code.as_bytes(), &ParseOptions::default(), passes).unwrap();
+        let mut output: Vec<u8> = vec![];
+        let summary: RunSummary = execute_code(&program, &mut Interpreter::default(), &mut &b""[..], &mut output, &ExecutionConfig::default()).unwrap();
+        assert_eq!((summary.halt, &output[..]), (HaltReason::EndOfProgram, &[1][..]), "{:?}", passes);
+    }
+
+    let program: Program = program(&ParseOptions::default());
+    assert_eq!(program.to_source(), code);
+    assert_eq!(constprop::propagate(&program).len(), program.len(), "every loop of the nest is entered");
+}
+
+#[test]
+fn nested_loops_go_through_the_analyses() {
+    let program: Program = program(&ParseOptions { fold: true, ..ParseOptions::default() });
+    assert_eq!(analysis::analyze(&program).max_depth, DEPTH - 1, "the innermost `[-]` folded to a clear");
+    assert!(analysis::loop_excursions(&program).iter().filter(|excursion| excursion.is_some()).count() <= 1);
+    lint::lint(&program);
+    assert!(ir::emit_ir(&program, nested().as_bytes()).lines().count() >= 2 * DEPTH);
+
+    let assembled: Program = asm::assemble(&asm::disassemble(&program), true).unwrap();
+    assert!(assembled.instructions() == program.instructions(), "the listing assembles back into the program");
+
+    golf::suggest(nested().as_bytes());
+}
+
+#[test]
+fn nested_loops_can_be_observed() {
+    let code: String = nested();
+    let program: Program = program(&ParseOptions::default());
+
+    let mut slicer: slice::Slicer = slice::Slicer::new(0);
+    execute_observed(&program, &mut Interpreter::default(), &mut &b""[..], &mut vec![], &ExecutionConfig::default(), &mut slicer).unwrap();
+    let sliced: Vec<usize> = slicer.slice(&program).unwrap();
+    assert!(sliced.contains(&(program.len() - 2)), "the `.` itself");
+
+    let mut tracker: taint::TaintTracker = taint::TaintTracker::new(&program, code.as_bytes());
+    execute_observed(&program, &mut Interpreter::default(), &mut &b""[..], &mut vec![], &ExecutionConfig::default(), &mut tracker).unwrap();
+}
+
+#[test]
+fn the_nesting_limit_refuses_the_innermost_loop_too_deep() {
+    let refused: ParseError = compile(nested().as_bytes(), &ParseOptions { max_depth: Some(DEPTH - 1), ..ParseOptions::default() }, true).unwrap_err();
+    assert_eq!(refused, ParseError::NestingTooDeep { position: DEPTH, depth: DEPTH, limit: DEPTH - 1 });
+    assert!(compile(nested().as_bytes(), &ParseOptions { max_depth: Some(DEPTH), ..ParseOptions::default() }, true).is_ok());
+}
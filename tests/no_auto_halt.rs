@@ -0,0 +1,32 @@
+//! Without the closing `Halt` (`--no-auto-halt`) a program may end by jumping or running past its last
+//! instruction, or have no instructions at all; nothing reporting on a run may assume the `Halt`.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{binary, program};
+
+#[test]
+fn explaining_a_jump_to_the_end_of_the_program() {
+    let path: PathBuf = program("no_halt_skip.bf", b"[]");
+    let output: Output = binary(&["--no-auto-halt", "--explain", "--explain-delay", "0"], &path, b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "step 1: '[' — cell 15000 is 0, jumping past the loop to the end of the program\n");
+
+    // with the `Halt` the same jump lands on it
+    let output: Output = binary(&["--explain", "--explain-delay", "0"], &path, b"");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("step 1: '[' — cell 15000 is 0, jumping past the loop to instruction 2 (line 1)\n"));
+}
+
+#[test]
+fn stopping_in_a_program_without_instructions() {
+    let path: PathBuf = program("no_halt_comment.bf", b"only a comment\n");
+    let output: Output = binary(&["--no-auto-halt", "--stop-at-instruction", "0"], &path, b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "--stop-at-instruction 0 is past the end of the program, it has no instructions\n");
+
+    let output: Output = binary(&["--stop-at-instruction", "1"], &path, b"");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "--stop-at-instruction 1 is past the last instruction (0)\n", "the `Halt` is instruction 0");
+}
@@ -0,0 +1,36 @@
+//! The HTML report of a run whose title, source, settings and output are full of markup: all of it
+//! has to come out as text, compared against `snapshots/report_escaping.html` byte for byte.
+
+use brainfck::analysis::analyze;
+use brainfck::report::{escape_html, render_html, Profiler, RunReport};
+use brainfck::{compile, execute_observed, ExecutionConfig, Interpreter, ParseOptions, Program};
+
+/// Echoes its input, under a comment of HTML, quotes, a non-ASCII character and a byte which isn't UTF-8.
+const SOURCE: &[u8] = b"<!-- \"echo\" & 'repeat' \xc3\xa9 -->\n,[.,] </pre>\xff\n";
+const INPUT: &[u8] = b"</pre><script>alert(\"&amp;\")</script>\0";
+
+fn report() -> String {
+    let program: Program = compile(SOURCE, &ParseOptions::default(), true).unwrap();
+    let mut profiler: Profiler = Profiler::new(&program);
+    let mut interpreter: Interpreter = Interpreter::default();
+    let mut output: Vec<u8> = vec![];
+    let summary = execute_observed(&program, &mut interpreter, &mut &INPUT[..], &mut output, &ExecutionConfig::default(), &mut profiler).unwrap();
+    let config: Vec<(String, String)> = vec![("input-string".to_string(), "\"</td><td>\"".to_string()), ("<th>".to_string(), "&".to_string())];
+    let report: RunReport = RunReport::new("<title> & \"more\"", SOURCE, &program, analyze(&program), &profiler, &interpreter, summary.halt.to_string(), output, config);
+    render_html(&report)
+}
+
+#[test]
+fn everything_shown_is_escaped() {
+    assert_eq!(report(), include_str!("snapshots/report_escaping.html"));
+}
+
+#[test]
+fn only_the_report_makes_elements() {
+    let html: String = report();
+    assert!(!html.contains("<script"));
+    assert_eq!(html.matches("<pre>").count(), 2, "the source and the output");
+    assert_eq!(html.matches("</pre>").count(), 2);
+    assert_eq!(html.matches("<title>").count(), 1);
+    assert_eq!(escape_html("<a href=\"x\">&amp;</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;amp;&lt;/a&gt;");
+}
@@ -0,0 +1,59 @@
+//! `--start-at` begins a run part way into the program: only outside loops, only without the
+//! optimizations assuming a run from the first instruction, and a `--stop-at` still reports where it
+//! stopped.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::Output;
+
+use common::{binary, program, stderr_line};
+
+/// Outputs 255 when started at the `-`, nothing when started at the beginning.
+const CODE: &[u8] = b"+-[.+]";
+
+#[test]
+fn a_run_starts_mid_program() {
+    let path: PathBuf = program("start_mid.bf", CODE);
+    assert_eq!(binary(&[], &path, b"").stdout, b"");
+    let started: Output = binary(&["--start-at", "1", "--print-result"], &path, b"");
+    assert_eq!(started.stdout, [255]);
+    assert_eq!(stderr_line(&started, "pointer="), "pointer=0 cell=0");
+    assert_eq!(binary(&["--start-at", "2", "--single-pass"], &path, b"").stdout, b"", "a fresh cell skips the loop");
+    assert_eq!(binary(&["--start-at", "6"], &path, b"").status.code(), Some(0), "the end of the program ends the run at once");
+}
+
+#[test]
+fn a_start_inside_a_loop_or_past_the_end_is_refused() {
+    let path: PathBuf = program("start_inside.bf", CODE);
+    let inside: Output = binary(&["--start-at", "3"], &path, b"");
+    assert_eq!(inside.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&inside.stderr), "Instruction 3 is inside the loop opened by instruction 2, runs can only start outside loops\n");
+    assert!(inside.stdout.is_empty());
+
+    let past: Output = binary(&["--start-at", "8"], &path, b"");
+    assert_eq!(past.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&past.stderr), "Instruction 8 is past the end of the program (7 instructions)\n");
+}
+
+#[test]
+fn optimizations_assuming_a_start_at_zero_are_refused() {
+    let path: PathBuf = program("start_constprop.bf", CODE);
+    for flag in ["--constprop", "--bisect-passes"] {
+        let refused: Output = binary(&["--start-at", "1", flag], &path, b"");
+        assert_eq!(refused.status.code(), Some(2), "{}", flag);
+        assert!(String::from_utf8_lossy(&refused.stderr).starts_with("constant propagation assumes the run starts at the first instruction"), "{}", flag);
+    }
+}
+
+#[test]
+fn a_stop_after_a_start_reports_why_the_run_ended() {
+    let path: PathBuf = program("start_stop.bf", b"+++\n-.[-]\n+.");
+    let stopped: Output = binary(&["--start-at", "3", "--stop-at", "3:1"], &path, b"");
+    assert_eq!(stopped.stdout, [255]);
+    assert_eq!(stderr_line(&stopped, "stopped at"), "stopped at 3:1 (instruction 8) after 513 steps");
+
+    let ended: Output = binary(&["--start-at", "3"], &path, b"");
+    assert_eq!(ended.stdout, [255, 1]);
+    assert!(!String::from_utf8_lossy(&ended.stderr).contains("stopped at"), "the end of the program is no stop");
+}